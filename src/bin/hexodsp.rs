@@ -0,0 +1,328 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+//! Headless command line tool for HexoDSP patch files.
+//!
+//! It loads patches that were saved with [hexodsp::save_patch_to_file], prints
+//! a summary of them or renders them offline to a WAV file, optionally
+//! driven by a Standard MIDI File. The exit code is not 0 if a patch or
+//! one of it's samples could not be loaded, which makes it usable for
+//! checking patch files in CI.
+
+use hexodsp::matrix_repr::MatrixRepr;
+use hexodsp::midi_file::MidiFile;
+use hexodsp::offline_render::{RenderLength, WavFormat};
+use hexodsp::*;
+
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "\
+USAGE:
+    hexodsp info <PATCH>...
+    hexodsp render [OPTIONS] <PATCH> <OUTPUT.wav>
+
+COMMANDS:
+    info      Prints the nodes, edges and parameters of the patches.
+    render    Renders the patch offline to a stereo WAV file.
+
+OPTIONS:
+    --length <SECONDS>      Length of the rendering. Default is 5 seconds, or
+                            until silence after the end of the MIDI file.
+    --srate <HZ>            Sample rate to render at, default is 44100.
+    --midi <FILE.mid>       Feeds the events of a Standard MIDI File to the patch.
+    --tail <SECONDS>        Maximum time to render after the end of the MIDI file
+                            until the output is silent, default is 10 seconds.
+    --int16                 Writes 16 bit integer samples instead of 32 bit float.
+    --sample-dir <DIR>      Additional directory to search for the samples of the patch.
+                            Samples are also searched relative to the patch file.
+";
+
+/// The silence threshold used when rendering until silence after a MIDI file.
+const SILENCE_THRESHOLD: f32 = 0.0001;
+/// The output must be silent for this time to stop rendering after a MIDI file.
+const SILENCE_HOLD_MS: f32 = 500.0;
+
+#[derive(Debug, Default)]
+struct RenderOptions {
+    length: Option<f32>,
+    sample_rate: Option<f32>,
+    midi: Option<String>,
+    tail: Option<f32>,
+    int16: bool,
+    sample_dirs: Vec<PathBuf>,
+}
+
+enum CliError {
+    /// The command line was malformed, the usage is printed.
+    Usage(String),
+    Failed(String),
+}
+
+impl From<String> for CliError {
+    fn from(msg: String) -> Self {
+        CliError::Failed(msg)
+    }
+}
+
+struct LoadedPatch {
+    matrix: Matrix,
+    exec: NodeExecutor,
+    /// Samples and other problems that were reported while loading the patch.
+    errors: Vec<String>,
+}
+
+fn load_patch(path: &str, sample_dirs: &[PathBuf]) -> Result<LoadedPatch, String> {
+    let mut repr = MatrixRepr::read_from_file(path)
+        .map_err(|e| format!("Couldn't read patch '{}': {:?}", path, e))?;
+
+    let patch_dir = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+    let mut search_dirs = vec![patch_dir];
+    search_dirs.extend(sample_dirs.iter().map(|d| d.as_path()));
+
+    let mut errors: Vec<String> = repr
+        .resolve_sample_paths(&search_dirs[..])
+        .iter()
+        .map(|sample| format!("Sample not found: '{}'", sample))
+        .collect();
+
+    let w = repr.cells.iter().map(|c| c.x + 1).max().unwrap_or(1);
+    let h = repr.cells.iter().map(|c| c.y + 1).max().unwrap_or(1);
+
+    let (node_conf, exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, w, h);
+    matrix.from_repr(&repr).map_err(|e| format!("Couldn't load patch '{}': {:?}", path, e))?;
+
+    while let Some(err) = matrix.pop_error() {
+        errors.push(err.replace('\n', " "));
+    }
+
+    Ok(LoadedPatch { matrix, exec, errors })
+}
+
+fn format_param(param: ParamId, v: f32) -> String {
+    let mut buf: Vec<u8> = vec![];
+    match param.format(&mut buf, v) {
+        Some(Ok(_)) => String::from_utf8(buf).unwrap_or_default().trim().to_string(),
+        _ => format!("{:.3}", v),
+    }
+}
+
+fn format_atom(param: ParamId, at: &SAtom) -> String {
+    match at {
+        SAtom::Param(v) => format_param(param, *v),
+        SAtom::Setting(i) => format_param(param, *i as f32),
+        SAtom::AudioSample((path, _)) if path.is_empty() => "(no sample)".to_string(),
+        SAtom::AudioSample((path, _)) => format!("'{}'", path),
+        SAtom::Str(s) => format!("{:?}", s),
+        SAtom::MicroSample(v) => format!("{:?}", v),
+    }
+}
+
+fn print_summary(path: &str, patch: &LoadedPatch) {
+    let matrix = &patch.matrix;
+
+    let mut cells = vec![];
+    matrix.for_each(|x, y, cell| {
+        if cell.node_id() != NodeId::Nop {
+            cells.push((x, y, *cell));
+        }
+    });
+
+    println!("patch: {}", path);
+    println!("nodes: {}", cells.len());
+    for (x, y, cell) in cells.iter() {
+        let node_id = cell.node_id();
+        println!("    {} at ({}, {})", node_id, x, y);
+
+        let mut idx = 0;
+        while let Some(param) = node_id.param_by_idx(idx) {
+            idx += 1;
+
+            let at = if let Some(at) = matrix.get_param(&param) { at } else { continue };
+            let modamt = match matrix.get_param_modamt(&param) {
+                Some(modamt) => format!(" (modamt {:.3})", modamt),
+                None => "".to_string(),
+            };
+
+            println!("        {} = {}{}", param.name(), format_atom(param, &at), modamt);
+        }
+    }
+
+    let mut edges = vec![];
+    for (x, y, _) in cells.iter() {
+        for ((cell, dir, out_idx), (other, _, inp_idx, _)) in
+            matrix.get_connections(*x, *y).unwrap_or_default()
+        {
+            if !dir.is_output() {
+                continue;
+            }
+
+            let from = cell.node_id();
+            let to = other.node_id();
+            edges.push(format!(
+                "{}:{} -> {}:{}",
+                from,
+                from.out_name_by_idx(out_idx).unwrap_or("?"),
+                to,
+                to.inp_name_by_idx(inp_idx).unwrap_or("?"),
+            ));
+        }
+    }
+
+    println!("edges: {}", edges.len());
+    for edge in edges.iter() {
+        println!("    {}", edge);
+    }
+}
+
+fn cmd_info(paths: &[String]) -> Result<(), CliError> {
+    if paths.is_empty() {
+        return Err(CliError::Usage("No patch file given".to_string()));
+    }
+
+    let mut failed = 0;
+    for path in paths.iter() {
+        match load_patch(path, &[]) {
+            Ok(patch) => {
+                print_summary(path, &patch);
+                for err in patch.errors.iter() {
+                    eprintln!("{}: error: {}", path, err);
+                }
+                if !patch.errors.is_empty() {
+                    failed += 1;
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(CliError::Failed(format!("{} of {} patches had errors", failed, paths.len())));
+    }
+
+    Ok(())
+}
+
+fn cmd_render(patch_path: &str, out_path: &str, opts: &RenderOptions) -> Result<(), String> {
+    let patch = load_patch(patch_path, &opts.sample_dirs)?;
+    for err in patch.errors.iter() {
+        eprintln!("error: {}", err);
+    }
+    if !patch.errors.is_empty() {
+        return Err(format!("Patch '{}' could not be loaded completely", patch_path));
+    }
+
+    let mut renderer = OfflineRenderer::from_matrix(patch.matrix, patch.exec);
+    renderer.set_sample_rate(opts.sample_rate.unwrap_or(44100.0));
+    renderer.set_wav_format(if opts.int16 { WavFormat::Int16 } else { WavFormat::Float32 });
+
+    let mut length = RenderLength::Seconds(opts.length.unwrap_or(5.0));
+
+    if let Some(midi_path) = &opts.midi {
+        let smf = MidiFile::read_from_file(midi_path)
+            .map_err(|e| format!("Couldn't read MIDI file '{}': {:?}", midi_path, e))?;
+
+        for (secs, ev) in smf.timed_events() {
+            let frame = renderer.ms_to_frames((secs * 1000.0) as f32);
+            renderer.add_midi_event(frame, ev);
+        }
+
+        if opts.length.is_none() {
+            length = RenderLength::UntilSilence {
+                threshold: SILENCE_THRESHOLD,
+                hold_ms: SILENCE_HOLD_MS,
+                max_seconds: smf.duration_secs() as f32 + opts.tail.unwrap_or(10.0),
+            };
+        }
+    }
+
+    renderer.set_length(length);
+
+    let frames = renderer
+        .render_to_wav(out_path)
+        .map_err(|e| format!("Couldn't write '{}': {:?}", out_path, e))?;
+
+    println!(
+        "rendered {} frames ({:.3}s at {}Hz) to {}",
+        frames,
+        frames as f32 / renderer.sample_rate(),
+        renderer.sample_rate(),
+        out_path
+    );
+
+    Ok(())
+}
+
+fn parse_value<T: std::str::FromStr>(opt: &str, v: Option<String>) -> Result<T, CliError> {
+    let v = v.ok_or_else(|| CliError::Usage(format!("Missing value for {}", opt)))?;
+    v.parse().map_err(|_| CliError::Usage(format!("Invalid value for {}: '{}'", opt, v)))
+}
+
+fn parse_render_args(args: Vec<String>) -> Result<(RenderOptions, Vec<String>), CliError> {
+    let mut opts = RenderOptions::default();
+    let mut positional = vec![];
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--length" => opts.length = Some(parse_value(&arg, args.next())?),
+            "--srate" => opts.sample_rate = Some(parse_value(&arg, args.next())?),
+            "--midi" => opts.midi = Some(parse_value(&arg, args.next())?),
+            "--tail" => opts.tail = Some(parse_value(&arg, args.next())?),
+            "--int16" => opts.int16 = true,
+            "--sample-dir" => opts.sample_dirs.push(parse_value(&arg, args.next())?),
+            _ if arg.starts_with("--") => {
+                return Err(CliError::Usage(format!("Unknown option: {}", arg)))
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    Ok((opts, positional))
+}
+
+fn run(mut args: Vec<String>) -> Result<(), CliError> {
+    if args.is_empty() {
+        return Err(CliError::Usage("No command given".to_string()));
+    }
+
+    let cmd = args.remove(0);
+    match &cmd[..] {
+        "info" => cmd_info(&args),
+        "render" => {
+            let (opts, positional) = parse_render_args(args)?;
+            if positional.len() != 2 {
+                return Err(CliError::Usage(
+                    "render expects a patch and an output file".to_string(),
+                ));
+            }
+
+            Ok(cmd_render(&positional[0], &positional[1], &opts)?)
+        }
+        "help" | "--help" | "-h" => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(CliError::Usage(format!("Unknown command: {}", cmd))),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match run(args) {
+        Ok(()) => (),
+        Err(CliError::Usage(msg)) => {
+            eprintln!("hexodsp: {}\n\n{}", msg, USAGE);
+            std::process::exit(2);
+        }
+        Err(CliError::Failed(msg)) => {
+            eprintln!("hexodsp: {}", msg);
+            std::process::exit(1);
+        }
+    }
+}
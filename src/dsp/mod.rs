@@ -481,6 +481,50 @@ later!
 
 */
 
+/// Implements [DspNode::save_state] and [DspNode::load_state] for a node, whose whole
+/// struct is it's state. The sample rate that comes with the loaded state is overwritten
+/// by the [crate::nodes::NodeExecutor], which calls [DspNode::set_sample_rate] afterwards.
+///
+/// Without arguments [DspNode::copy_state] clones the whole node, which is only allowed
+/// if that does not allocate. Nodes with heap allocated parts list the fields to copy
+/// instead, which are copied with `clone_from` to reuse their allocations. Nodes that
+/// can't be copied without allocating pass `no_copy`.
+macro_rules! node_state_by_clone {
+    () => {
+        node_state_by_clone!(no_copy);
+
+        fn copy_state(&self, state: &mut NodeState) -> bool {
+            if let Some(slot) = state.get_mut::<Self>() {
+                slot.clone_from(self);
+                true
+            } else {
+                false
+            }
+        }
+    };
+    (no_copy) => {
+        fn save_state(&self) -> Option<NodeState> {
+            Some(NodeState::new(self.clone()))
+        }
+
+        fn load_state(&mut self, state: &mut NodeState) -> bool {
+            state.swap_into(self)
+        }
+    };
+    ($($field: ident),+) => {
+        node_state_by_clone!(no_copy);
+
+        fn copy_state(&self, state: &mut NodeState) -> bool {
+            if let Some(slot) = state.get_mut::<Self>() {
+                $(slot.$field.clone_from(&self.$field);)+
+                true
+            } else {
+                false
+            }
+        }
+    };
+}
+
 #[allow(non_upper_case_globals)]
 mod node_ad;
 #[allow(non_upper_case_globals)]
@@ -490,6 +534,8 @@ mod node_allp;
 #[allow(non_upper_case_globals)]
 mod node_amp;
 #[allow(non_upper_case_globals)]
+mod node_arp;
+#[allow(non_upper_case_globals)]
 mod node_biqfilt;
 #[allow(non_upper_case_globals)]
 mod node_bosc;
@@ -518,10 +564,18 @@ mod node_map;
 #[allow(non_upper_case_globals)]
 mod node_midicc;
 #[allow(non_upper_case_globals)]
+mod node_midiccout;
+#[allow(non_upper_case_globals)]
+mod node_midiclk;
+#[allow(non_upper_case_globals)]
+mod node_midiout;
+#[allow(non_upper_case_globals)]
 mod node_midip;
 #[allow(non_upper_case_globals)]
 mod node_mix3;
 #[allow(non_upper_case_globals)]
+mod node_mpep;
+#[allow(non_upper_case_globals)]
 mod node_mux9;
 #[allow(non_upper_case_globals)]
 mod node_noise;
@@ -606,6 +660,9 @@ pub use node_rust::{DynNode1x1Context, DynNodeBuffer, DynNodeHandle, DynamicNode
 use crate::fa_ad_mult;
 use crate::fa_adsr_mult;
 use crate::fa_amp_neg_att;
+use crate::fa_arp_latch;
+use crate::fa_arp_mode;
+use crate::fa_arp_oct;
 use crate::fa_biqfilt_ord;
 use crate::fa_biqfilt_type;
 use crate::fa_bosc_wtype;
@@ -614,15 +671,25 @@ use crate::fa_cqnt;
 use crate::fa_cqnt_omax;
 use crate::fa_cqnt_omin;
 use crate::fa_delay_mode;
+use crate::fa_delay_note;
 use crate::fa_fvafilt_lmode;
 use crate::fa_fvafilt_svf_mode;
 use crate::fa_fvafilt_type;
+use crate::fa_inp_bank;
 use crate::fa_map_clip;
 use crate::fa_midicc_cc;
+use crate::fa_midicc_mode;
+use crate::fa_midiclk_note;
+use crate::fa_midip_brange;
 use crate::fa_midip_chan;
 use crate::fa_midip_gmode;
+use crate::fa_midip_legato;
+use crate::fa_midip_prio;
+use crate::fa_midip_voice;
+use crate::fa_mpep_slot;
 use crate::fa_mux9_in_cnt;
 use crate::fa_noise_mode;
+use crate::fa_out_bank;
 use crate::fa_out_mono;
 use crate::fa_quant;
 use crate::fa_sampl_dclick;
@@ -634,6 +701,7 @@ use crate::fa_smap_clip;
 use crate::fa_smap_mode;
 use crate::fa_test_s;
 use crate::fa_tseq_cmode;
+use crate::fa_tslfo_sync;
 use crate::fa_vosc_ovrsmpl;
 use synfx_dsp::fa_distort;
 
@@ -641,6 +709,7 @@ use node_ad::Ad;
 use node_adsr::Adsr;
 use node_allp::AllP;
 use node_amp::Amp;
+use node_arp::Arp;
 use node_biqfilt::BiqFilt;
 use node_bosc::BOsc;
 use node_bowstri::BowStri;
@@ -661,8 +730,12 @@ use node_fvafilt::FVaFilt;
 use node_inp::Inp;
 use node_map::Map;
 use node_midicc::MidiCC;
+use node_midiccout::MidiCCOut;
+use node_midiclk::MidiClk;
+use node_midiout::MidiOut;
 use node_midip::MidiP;
 use node_mix3::Mix3;
+use node_mpep::MpeP;
 use node_mux9::Mux9;
 use node_noise::Noise;
 use node_out::Out;
@@ -688,6 +761,9 @@ pub const MAX_BLOCK_SIZE: usize = 128;
 /// currently executed node.
 /// This structure is created by the [crate::nodes::NodeExecutor] on the fly.
 pub struct NodeContext<'a> {
+    /// The [NodeId] of the node, for instance for emitting events with
+    /// [crate::nodes::NodeExecContext::emit_event].
+    pub node_id: NodeId,
     /// The bitmask that indicates which input ports are used/connected
     /// to some output.
     pub in_connected: u64,
@@ -710,6 +786,46 @@ pub trait GraphAtomData {
 
 pub type GraphFun = Box<dyn FnMut(&dyn GraphAtomData, bool, f32, f32) -> f32>;
 
+/// A snapshot of the internal state of a [DspNode], such as the phase of an oscillator,
+/// the stage of an envelope, the contents of a delay line or the position of a sequencer.
+/// See [DspNode::save_state] and [crate::nodes::NodeExecutor::save_node_states].
+pub struct NodeState(Box<dyn std::any::Any + Send>);
+
+impl NodeState {
+    /// Wraps the `state` of a node.
+    pub fn new<T: std::any::Any + Send>(state: T) -> Self {
+        Self(Box::new(state))
+    }
+
+    /// Returns the wrapped state, if it is of the type `T`.
+    pub fn get<T: std::any::Any>(&self) -> Option<&T> {
+        self.0.downcast_ref::<T>()
+    }
+
+    /// Returns the wrapped state mutably, if it is of the type `T`.
+    pub fn get_mut<T: std::any::Any>(&mut self) -> Option<&mut T> {
+        self.0.downcast_mut::<T>()
+    }
+
+    /// Swaps the wrapped state with `target`, without allocating or cloning.
+    /// Afterwards the wrapped state is the previous `target`. Returns `false` if
+    /// the wrapped state is not of the type `T`.
+    pub fn swap_into<T: std::any::Any>(&mut self, target: &mut T) -> bool {
+        if let Some(state) = self.get_mut::<T>() {
+            std::mem::swap(state, target);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl std::fmt::Debug for NodeState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NodeState")
+    }
+}
+
 /// This trait represents a DspNode for the [crate::matrix::Matrix]
 pub trait DspNode: std::fmt::Debug + Send {
     /// Updates the sample rate for the node.
@@ -718,6 +834,38 @@ pub trait DspNode: std::fmt::Debug + Send {
     /// Reset any internal state of the node.
     fn reset(&mut self);
 
+    /// Restarts the random number generators of the node with a seed derived
+    /// from the global random seed `seed`, see [node_random_seed].
+    /// Only nodes that generate random numbers need to implement this.
+    fn set_random_seed(&mut self, _seed: u64) {}
+
+    /// Takes a snapshot of the internal state of the node. Nodes without any
+    /// state worth saving return `None`, which is the default.
+    /// This allocates and is never called from the audio thread.
+    fn save_state(&self) -> Option<NodeState> {
+        None
+    }
+
+    /// Restores the internal state from a snapshot taken by [DspNode::save_state]
+    /// of the same kind of node, by swapping it with the current state. Afterwards
+    /// `state` holds the previous state of the node. This is called from the audio
+    /// thread and must not allocate. Returns `false` if the `state` does not belong
+    /// to this node. The state might have been saved at another sample rate, so
+    /// [DspNode::set_sample_rate] is called after a successful load.
+    fn load_state(&mut self, _state: &mut NodeState) -> bool {
+        false
+    }
+
+    /// Copies the internal state into `state`, which was taken by [DspNode::save_state]
+    /// of the same kind of node on the frontend thread. This is called from the audio
+    /// thread and must not allocate, see
+    /// [crate::nodes::NodeConfigurator::request_node_states]. Returns `false` if the
+    /// `state` does not belong to this node or if the state can't be copied without
+    /// allocating, which is the default.
+    fn copy_state(&self, _state: &mut NodeState) -> bool {
+        false
+    }
+
     /// The code DSP function.
     ///
     /// * `ctx` is the audio context, which informs the node about
@@ -1488,7 +1636,7 @@ macro_rules! node_list {
             tseq => TSeq UIType::Generic UICategory::Mod
                (0 clock n_id       d_id   r_id  f_def  stp_d  0.0, 1.0, 0.0)
                (1 trig  n_id       n_id   r_id  f_def  stp_d -1.0, 1.0, 0.0)
-               {2 0 cmode setting(1) mode fa_tseq_cmode 0  2}
+               {2 0 cmode setting(1) mode fa_tseq_cmode 0  3}
                [0 trk1]
                [1 trk2]
                [2 trk3]
@@ -1501,6 +1649,17 @@ macro_rules! node_list {
                [9  gat4]
                [10 gat5]
                [11 gat6],
+            arp => Arp UIType::Generic UICategory::Mod
+               (0 clock n_id       d_id   r_id  f_def  stp_d  0.0, 1.0, 0.0)
+               (1 trig  n_id       d_id   r_id  f_def  stp_d -1.0, 1.0, 0.0)
+               (2 glen  n_id       d_id   r_id  f_def  stp_d  0.0, 1.0, 0.5)
+               {3 0 chan  setting(0) mode fa_midip_chan 0 15}
+               {4 1 mode  setting(0) mode fa_arp_mode   0 4}
+               {5 2 oct   setting(0) mode fa_arp_oct    0 3}
+               {6 3 latch setting(0) mode fa_arp_latch  0 1}
+               [0 freq]
+               [1 gate]
+               [2 vel],
             code => Code UIType::Generic UICategory::Signal
                (0 in1   n_id       d_id   r_id  f_def  stp_d -1.0, 1.0, 0.0)
                (1 in2   n_id       d_id   r_id  f_def  stp_d -1.0, 1.0, 0.0)
@@ -1564,20 +1723,55 @@ macro_rules! node_list {
             midip => MidiP UIType::Generic UICategory::IOUtil
                (0 det   n_det      d_det r_det f_det   stp_f -0.2, 0.2, 0.0)
                (1 glen  n_lfot   d_lfot r_lfot f_lfoms stp_f 0.0, 1.0, 250.0)
-               {2 0 chan setting(0) mode fa_midip_chan 0 15}
-               {3 1 gmode setting(0) mode fa_midip_gmode 0 2}
+               (2 glide n_timz   d_timz r_tmz  f_ms    stp_m 0.0, 1.0, 0.0)
+               {3 0 chan setting(0) mode fa_midip_chan 0 15}
+               {4 1 gmode setting(0) mode fa_midip_gmode 0 2}
+               {5 2 voice setting(0) mode fa_midip_voice 0 16}
+               {6 3 brange setting(2) mode fa_midip_brange 0 24}
+               {7 4 prio setting(0) mode fa_midip_prio 0 2}
+               {8 5 legato setting(0) mode fa_midip_legato 0 1}
                [0 freq]
                [1 gate]
-               [2 vel],
+               [2 vel]
+               [3 bend]
+               [4 press],
+            mpep => MpeP UIType::Generic UICategory::IOUtil
+               (0 det   n_det      d_det r_det f_det   stp_f -0.2, 0.2, 0.0)
+               {1 0 slot   setting(0)  mode fa_mpep_slot    0 15}
+               {2 1 brange setting(48) mode fa_midip_brange 0 96}
+               {3 2 mrange setting(2)  mode fa_midip_brange 0 24}
+               [0 freq]
+               [1 gate]
+               [2 vel]
+               [3 press]
+               [4 slide],
             midicc => MidiCC UIType::Generic UICategory::IOUtil
                (0 slew  n_timz   d_timz r_tmz  f_ms  stp_m  0.0, 1.0, 0.0)
                {1 0 chan setting(0) mode fa_midip_chan 0 15}
                {2 1 cc1  setting(0) mode fa_midicc_cc 0 127}
                {3 2 cc2  setting(0) mode fa_midicc_cc 0 127}
                {4 3 cc3  setting(0) mode fa_midicc_cc 0 127}
+               {5 4 mode setting(0) mode fa_midicc_mode 0 3}
+               {6 5 pmsb setting(0) mode fa_midicc_cc 0 127}
                [0 sig1]
                [1 sig2]
                [2 sig3],
+            midiclk => MidiClk UIType::Generic UICategory::IOUtil
+               (0 slew  n_timz   d_timz r_tmz  f_ms  stp_m  0.0, 1.0, 0.0)
+               {1 0 note setting(12) mode fa_midiclk_note 0 15}
+               [0 clk]
+               [1 run]
+               [2 rst]
+               [3 bpm],
+            midiout => MidiOut UIType::Generic UICategory::IOUtil
+               (0 freq  n_pit      d_pit r_fq  f_freq  stp_d -1.0, 0.5647131, 440.0)
+               (1 gate  n_id       d_id  r_id  f_def   stp_d  0.0, 1.0, 0.0)
+               (2 vel   n_id       d_id  r_id  f_def   stp_d  0.0, 1.0, 0.8)
+               {3 0 chan setting(0) mode fa_midip_chan 0 15},
+            midiccout => MidiCCOut UIType::Generic UICategory::IOUtil
+               (0 inp   n_id       d_id  r_id  f_def   stp_d  0.0, 1.0, 0.0)
+               {1 0 chan setting(0) mode fa_midip_chan 0 15}
+               {2 1 cc   setting(0) mode fa_midicc_cc 0 127},
             exta => ExtA UIType::Generic UICategory::IOUtil
                (0 slew  n_timz   d_timz r_tmz  f_ms  stp_m  0.0, 1.0, 0.0)
                (1 atv1  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 1.0)
@@ -1628,6 +1822,7 @@ macro_rules! node_list {
                [2 sig3],
             inp => Inp UIType::Generic UICategory::IOUtil
                (0  vol  n_vol     d_vol  r_vol f_db   stp_v  0.0, 1.0, 1.0)
+               {1 0 bank setting(0) mode fa_inp_bank 0  7}
                [0 sig1]
                [1 sig2],
             out => Out UIType::Generic UICategory::IOUtil
@@ -1639,7 +1834,8 @@ macro_rules! node_list {
              // | | name constructor|     |     min max
              // | | |    |       def|ult_v|lue  |  /
              // | | |    |       |  |     |     |  |
-               {3 0 mono setting(0) mode fa_out_mono 0  1},
+               {3 0 mono setting(0) mode fa_out_mono 0  1}
+               {4 1 bank setting(0) mode fa_out_bank 0  7},
             fbwr => FbWr UIType::Generic UICategory::IOUtil
                (0  inp   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0),
             fbrd => FbRd UIType::Generic UICategory::IOUtil
@@ -1658,7 +1854,7 @@ macro_rules! node_list {
                (9  gain1 n_gain   d_gain r_gain f_db  stp_v 0.0, 1.0, 1.0)
                (10 gain2 n_gain   d_gain r_gain f_db  stp_v 0.0, 1.0, 1.0)
                (11 gain3 n_gain   d_gain r_gain f_db  stp_v 0.0, 1.0, 1.0)
-               {12 0 tsrc  setting(0) mode fa_scope_tsrc 0 2},
+               {12 0 tsrc  setting(0) mode fa_scope_tsrc 0 3},
             ad   => Ad   UIType::Generic UICategory::Mod
                (0  inp   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 1.0)
                (1  trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
@@ -1686,6 +1882,7 @@ macro_rules! node_list {
                 (0 time  n_lfot   d_lfot r_lfot f_lfot stp_f 0.0, 1.0, 1000.0)
                 (1 trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                 (2 rev   n_id      d_id  r_id   f_def stp_d  0.0, 1.0, 0.5)
+                {3 0 sync setting(0) mode fa_tslfo_sync 0 16}
                 [0 sig],
             rndwk => RndWk UIType::Generic UICategory::Mod
                 (0 trig  n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
@@ -1701,7 +1898,8 @@ macro_rules! node_list {
                (2  time  n_time   d_time r_tms  f_ms  stp_m  0.0, 1.0, 250.0)
                (3  fb    n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
                (4  mix   n_id      d_id  r_id   f_def stp_d  0.0, 1.0, 0.5)
-               {5 0 mode setting(0) mode fa_delay_mode 0 1}
+               {5 0 mode setting(0) mode fa_delay_mode 0 2}
+               {6 1 note setting(6) mode fa_delay_note 0 15}
                [0 sig],
             allp  => AllP UIType::Generic UICategory::Signal
                (0  inp   n_id      d_id  r_id   f_def stp_d -1.0, 1.0, 0.0)
@@ -1814,6 +2012,14 @@ impl UICategory {
     }
 }
 
+/// Derives the seed for the random number generator of the node `nid` from
+/// the global random seed, see [crate::NodeGlobalData::random_seed].
+/// Each node type uses it's own `salt`, so that different nodes with the
+/// same instance number don't generate the same random numbers.
+pub fn node_random_seed(global_seed: u64, salt: u64, nid: &NodeId) -> u64 {
+    global_seed.wrapping_add(salt.wrapping_mul(nid.instance() as u64 + 1))
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum RandNodeSelector {
     Any,
@@ -1838,8 +2044,16 @@ fn rand_node_satisfies_spec(nid: NodeId, sel: RandNodeSelector) -> bool {
     }
 }
 
+/// Returns `count` random [NodeId]s, drawn with a random seed from the current time.
+/// See [get_rand_node_id_seeded] and [crate::NodeConfigurator::rand_node_ids]
+/// for reproducible results.
 pub fn get_rand_node_id(count: usize, sel: RandNodeSelector) -> Vec<NodeId> {
-    let mut sm = synfx_dsp::SplitMix64::new_time_seed();
+    get_rand_node_id_seeded(count, sel, synfx_dsp::SplitMix64::new_time_seed().next_u64())
+}
+
+/// Like [get_rand_node_id], but the [NodeId]s are drawn with the given `seed`.
+pub fn get_rand_node_id_seeded(count: usize, sel: RandNodeSelector, seed: u64) -> Vec<NodeId> {
+    let mut sm = synfx_dsp::SplitMix64::new(seed);
     let mut out = vec![];
 
     let mut cnt = 0;
@@ -2722,6 +2936,25 @@ impl Node {
         }
     }
 
+    #[inline]
+    pub fn set_random_seed(&self, seed: u64) {
+        unsafe {
+            (*self.0.get()).set_random_seed(seed);
+        }
+    }
+
+    pub fn save_state(&self) -> Option<NodeState> {
+        unsafe { (*self.0.get()).save_state() }
+    }
+
+    pub fn load_state(&self, state: &mut NodeState) -> bool {
+        unsafe { (*self.0.get()).load_state(state) }
+    }
+
+    pub fn copy_state(&self, state: &mut NodeState) -> bool {
+        unsafe { (*self.0.get()).copy_state(state) }
+    }
+
     #[inline]
     pub fn process(
         &self,
@@ -3,8 +3,8 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    DspNode, GraphAtomData, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf,
-    SAtom,
+    DspNode, GraphAtomData, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState,
+    ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 use synfx_dsp::{sqrt4_to_pow4, EnvRetrigAD};
@@ -123,6 +123,8 @@ impl DspNode for Ad {
         self.env.reset();
     }
 
+    node_state_by_clone!();
+
     #[inline]
     fn process(
         &mut self,
@@ -3,10 +3,10 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    DspNode, GraphAtomData, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf,
-    SAtom,
+    DspNode, GraphAtomData, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState,
+    ProcBuf, SAtom,
 };
-use crate::nodes::{NodeAudioContext, NodeExecContext};
+use crate::nodes::{NodeAudioContext, NodeEvent, NodeExecContext};
 use synfx_dsp::{EnvADSRParams, EnvRetrigADSR};
 
 #[macro_export]
@@ -26,11 +26,13 @@ macro_rules! fa_adsr_mult {
 #[derive(Debug, Clone)]
 pub struct Adsr {
     env: EnvRetrigADSR,
+    /// Whether the `eoet` output is high, for emitting [NodeEvent::EnvEnd] once.
+    eoet_high: bool,
 }
 
 impl Adsr {
     pub fn new(_nid: &NodeId, _node_global: &NodeGlobalRef) -> Self {
-        Self { env: EnvRetrigADSR::new() }
+        Self { env: EnvRetrigADSR::new(), eoet_high: false }
     }
     pub const inp: &'static str =
         "Signal input. If you don't connect this, and set this to **1.0** \
@@ -135,14 +137,17 @@ impl DspNode for Adsr {
 
     fn reset(&mut self) {
         self.env.reset();
+        self.eoet_high = false;
     }
 
+    node_state_by_clone!();
+
     #[inline]
     fn process(
         &mut self,
         ctx: &mut dyn NodeAudioContext,
-        _ectx: &mut NodeExecContext,
-        _nctx: &NodeContext,
+        ectx: &mut NodeExecContext,
+        nctx: &NodeContext,
         atoms: &[SAtom],
         inputs: &[ProcBuf],
         outputs: &mut [ProcBuf],
@@ -187,6 +192,12 @@ impl DspNode for Adsr {
 
             let eoet = out::Adsr::eoet(outputs);
             eoet.write(frame, retrig_sig);
+
+            let eoet_high = retrig_sig > 0.5;
+            if eoet_high && !self.eoet_high {
+                ectx.emit_event(nctx.node_id, NodeEvent::EnvEnd);
+            }
+            self.eoet_high = eoet_high;
         }
 
         let last_frame = ctx.nframes() - 1;
@@ -3,7 +3,7 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf, SAtom,
+    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState, ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 use synfx_dsp::AllPass;
@@ -77,6 +77,8 @@ impl DspNode for AllP {
         self.allpass.reset();
     }
 
+    node_state_by_clone!(no_copy);
+
     #[inline]
     fn process(
         &mut self,
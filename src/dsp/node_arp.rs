@@ -0,0 +1,363 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{
+    at, denorm, inp, node_random_seed, out_idx, DspNode, GraphFun, LedPhaseVals, NodeContext,
+    NodeGlobalRef, NodeId, NodeState, ProcBuf, SAtom,
+};
+use crate::nodes::{HxMidiEvent, MidiEventPointer, NodeAudioContext, NodeExecContext};
+use synfx_dsp::{Rng, Trigger};
+
+/// The maximum number of notes the [Arp] steps through.
+const MAX_ARP_NOTES: usize = 16;
+
+const ARP_SEED_SALT: u64 = 0x4172_7000;
+
+#[macro_export]
+macro_rules! fa_arp_mode {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let s = match ($v.round() as usize) {
+            0 => "Up",
+            1 => "Down",
+            2 => "UpDown",
+            3 => "Random",
+            4 => "Played",
+            _ => "?",
+        };
+        write!($formatter, "{}", s)
+    }};
+}
+
+#[macro_export]
+macro_rules! fa_arp_oct {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let v = $v.round() as usize + 1;
+        if v == 1 {
+            write!($formatter, "1 Oct")
+        } else {
+            write!($formatter, "{} Octs", v)
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! fa_arp_latch {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let s = match ($v.round() as usize) {
+            0 => "Off",
+            1 => "On",
+            _ => "?",
+        };
+        write!($formatter, "{}", s)
+    }};
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ArpNote {
+    note: u8,
+    vel: f32,
+    /// False if the key was released, but the note is latched.
+    down: bool,
+}
+
+/// An arpeggiator, that steps through the held MIDI notes
+#[derive(Debug, Clone)]
+pub struct Arp {
+    nid: NodeId,
+    rng: Rng,
+    /// The notes in the order they were played.
+    notes: [ArpNote; MAX_ARP_NOTES],
+    notes_len: usize,
+    /// The next step of the arpeggio.
+    step: usize,
+    cur_note: i32,
+    cur_vel: f32,
+    gate_open: bool,
+    /// The gate is low for one sample, if a step follows an open gate.
+    gate_gap: bool,
+    since_step: usize,
+    since_clock: usize,
+    /// The measured length of a clock interval in samples, 0 if unknown.
+    clock_period: usize,
+    clock_trig: Trigger,
+    reset_trig: Trigger,
+}
+
+impl Arp {
+    pub fn new(nid: &NodeId, node_global: &NodeGlobalRef) -> Self {
+        let global_seed =
+            node_global.lock().map(|g| g.random_seed()).unwrap_or(crate::DEFAULT_RANDOM_SEED);
+
+        let mut rng = Rng::new();
+        rng.seed(node_random_seed(global_seed, ARP_SEED_SALT, nid));
+
+        Self {
+            nid: *nid,
+            rng,
+            notes: [ArpNote { note: 0, vel: 0.0, down: false }; MAX_ARP_NOTES],
+            notes_len: 0,
+            step: 0,
+            cur_note: 0,
+            cur_vel: 0.0,
+            gate_open: false,
+            gate_gap: false,
+            since_step: 0,
+            since_clock: usize::MAX,
+            clock_period: 0,
+            clock_trig: Trigger::new(),
+            reset_trig: Trigger::new(),
+        }
+    }
+
+    pub const clock: &'static str =
+        "Clock input, every trigger plays the next note of the arpeggio.";
+    pub const trig: &'static str = "Reset trigger, the next ~~clock~~ starts the arpeggio \
+        again with it's first note.";
+    pub const glen: &'static str = "Gate length\n\
+        The length of the ~~gate~~ relative to the interval of the ~~clock~~ triggers.";
+    pub const chan: &'static str = "MIDI Channel 0 to 15\n";
+    pub const mode: &'static str = "Arpeggio mode\n\
+        - **Up** from the lowest to the highest note\n\
+        - **Down** from the highest to the lowest note\n\
+        - **UpDown** up and down again, without repeating the highest and lowest note\n\
+        - **Random** a random note on every step\n\
+        - **Played** in the order the notes were played\n";
+    pub const oct: &'static str = "Octave range\n\
+        The number of octaves the held notes are repeated in.\n";
+    pub const latch: &'static str = "Latch\n\
+        If **On**, the notes keep playing after the keys are released, until \
+        new notes are played.\n";
+    pub const freq: &'static str = "The frequency of the current note.";
+    pub const gate: &'static str = "The gate of the current note.";
+    pub const vel: &'static str = "The velocity of the current note.";
+
+    pub const DESC: &'static str = "Arpeggiator\n\n\
+        This node steps through the held MIDI notes on every ~~clock~~ trigger. \
+        You get the frequency, gate and velocity of the note, like from the `MidiP` node.";
+    pub const HELP: &'static str = r#"Arpeggiator
+
+This node steps through the held MIDI notes of the channel ~~chan~~
+on every trigger at the ~~clock~~ input. The ~~freq~~, ~~gate~~ and ~~vel~~
+outputs are in the same format as the ones of the `MidiP` node, so you
+can connect them to the same oscillators and envelopes.
+
+The ~~mode~~ selects the order of the notes. The held notes are
+repeated in the octaves above, if the octave range ~~oct~~ is bigger
+than 1 octave. With ~~latch~~ the notes keep playing after the keys are
+released. Playing new notes after releasing all keys replaces them.
+The arpeggio stops when no notes are held anymore.
+
+The ~~gate~~ length ~~glen~~ is relative to the interval between the last
+two ~~clock~~ triggers. Until the interval is known, the gate stays open
+until the next ~~clock~~ trigger.
+
+For a clock, connect the `clk` output of a `MidiClk` node to follow an
+external MIDI clock, the gate of a `TSeq` track or a `TsLFO` with the
+`sync` setting.
+"#;
+
+    pub fn graph_fun() -> Option<GraphFun> {
+        None
+    }
+
+    fn note_on(&mut self, note: u8, vel: f32, latch: bool) {
+        if latch && !self.notes[..self.notes_len].iter().any(|n| n.down) {
+            self.notes_len = 0;
+        }
+
+        self.remove_note(note);
+
+        if self.notes_len == MAX_ARP_NOTES {
+            self.notes.copy_within(1.., 0);
+            self.notes_len -= 1;
+        }
+
+        self.notes[self.notes_len] = ArpNote { note, vel, down: true };
+        self.notes_len += 1;
+    }
+
+    fn note_off(&mut self, note: u8, latch: bool) {
+        if latch {
+            for n in self.notes[..self.notes_len].iter_mut() {
+                if n.note == note {
+                    n.down = false;
+                }
+            }
+        } else {
+            self.remove_note(note);
+        }
+    }
+
+    fn remove_note(&mut self, note: u8) {
+        if let Some(idx) = self.notes[..self.notes_len].iter().position(|n| n.note == note) {
+            self.notes.copy_within((idx + 1)..self.notes_len, idx);
+            self.notes_len -= 1;
+        }
+    }
+
+    /// Drops the latched notes, after the latch was switched off.
+    fn release_latched(&mut self) {
+        let mut len = 0;
+        for i in 0..self.notes_len {
+            if self.notes[i].down {
+                self.notes[len] = self.notes[i];
+                len += 1;
+            }
+        }
+        self.notes_len = len;
+    }
+
+    /// Returns the note and velocity of the next step of the arpeggio.
+    fn next_step(&mut self, mode: i64, octaves: usize) -> Option<(i32, f32)> {
+        if self.notes_len == 0 {
+            return None;
+        }
+
+        let mut sorted = [(0_u8, 0.0_f32); MAX_ARP_NOTES];
+        for (s, n) in sorted.iter_mut().zip(self.notes[..self.notes_len].iter()) {
+            *s = (n.note, n.vel);
+        }
+        let sorted = &mut sorted[..self.notes_len];
+        if mode != 4 {
+            sorted.sort_unstable_by_key(|(note, _)| *note);
+        }
+
+        let len = sorted.len() * octaves;
+        let pos = match mode {
+            1 => len - 1 - self.step % len,
+            2 => {
+                let period = (2 * len).saturating_sub(2).max(1);
+                let p = self.step % period;
+                if p < len {
+                    p
+                } else {
+                    period - p
+                }
+            }
+            3 => ((self.rng.next() * len as f32) as usize).min(len - 1),
+            _ => self.step % len,
+        };
+        self.step = self.step.wrapping_add(1);
+
+        let (note, vel) = sorted[pos % sorted.len()];
+        Some((note as i32 + 12 * (pos / sorted.len()) as i32, vel))
+    }
+}
+
+impl DspNode for Arp {
+    fn set_sample_rate(&mut self, _srate: f32) {}
+
+    fn reset(&mut self) {
+        self.notes_len = 0;
+        self.step = 0;
+        self.gate_open = false;
+        self.gate_gap = false;
+        self.since_step = 0;
+        self.since_clock = usize::MAX;
+        self.clock_period = 0;
+        self.clock_trig.reset();
+        self.reset_trig.reset();
+    }
+
+    node_state_by_clone!();
+
+    fn set_random_seed(&mut self, seed: u64) {
+        self.rng.seed(node_random_seed(seed, ARP_SEED_SALT, &self.nid));
+    }
+
+    #[inline]
+    fn process(
+        &mut self,
+        ctx: &mut dyn NodeAudioContext,
+        ectx: &mut NodeExecContext,
+        _nctx: &NodeContext,
+        atoms: &[SAtom],
+        inputs: &[ProcBuf],
+        outputs: &mut [ProcBuf],
+        ctx_vals: LedPhaseVals,
+    ) {
+        let clock = inp::Arp::clock(inputs);
+        let trig = inp::Arp::trig(inputs);
+        let glen = inp::Arp::glen(inputs);
+        let chan = at::Arp::chan(atoms);
+        let mode = at::Arp::mode(atoms).i();
+        let octaves = (at::Arp::oct(atoms).i().clamp(0, 3) + 1) as usize;
+        let latch = at::Arp::latch(atoms).i() == 1;
+        let out_i = out_idx::Arp::gate();
+        let (freq, r) = outputs.split_at_mut(out_i);
+        let (gate, vel) = r.split_at_mut(1);
+        let freq = &mut freq[0];
+        let gate = &mut gate[0];
+        let vel = &mut vel[0];
+
+        let arp_channel = (chan.i() as usize % 16) as u8;
+
+        if !latch {
+            self.release_latched();
+        }
+
+        let mut ptr = MidiEventPointer::new(&ectx.midi_notes[..]);
+
+        for frame in 0..ctx.nframes() {
+            while let Some(ev) = ptr.next_at(frame) {
+                match ev {
+                    HxMidiEvent::NoteOn { channel, note, vel } if channel == arp_channel => {
+                        self.note_on(note, vel, latch);
+                    }
+                    HxMidiEvent::NoteOff { channel, note } if channel == arp_channel => {
+                        self.note_off(note, latch);
+                    }
+                    _ => (),
+                }
+            }
+
+            if self.notes_len == 0 {
+                self.gate_open = false;
+                self.step = 0;
+            }
+
+            if self.reset_trig.check_trigger(denorm::Arp::trig(trig, frame)) {
+                self.step = 0;
+            }
+
+            if self.clock_trig.check_trigger(denorm::Arp::clock(clock, frame)) {
+                if self.since_clock != usize::MAX {
+                    self.clock_period = self.since_clock;
+                }
+                self.since_clock = 0;
+
+                if let Some((note, vel)) = self.next_step(mode, octaves) {
+                    self.gate_gap = self.gate_open;
+                    self.gate_open = true;
+                    self.since_step = 0;
+                    self.cur_note = note;
+                    self.cur_vel = vel;
+                }
+            }
+
+            let gate_len = denorm::Arp::glen(glen, frame).clamp(0.0, 1.0);
+            if self.clock_period > 0
+                && self.since_step as f32 >= gate_len * self.clock_period as f32
+            {
+                self.gate_open = false;
+            }
+
+            if self.gate_gap {
+                gate.write(frame, 0.0);
+                self.gate_gap = false;
+            } else {
+                gate.write(frame, if self.gate_open { 1.0 } else { 0.0 });
+            }
+
+            freq.write(frame, (self.cur_note as f32 - 69.0) / 120.0);
+            vel.write(frame, self.cur_vel);
+
+            self.since_step = self.since_step.saturating_add(1);
+            self.since_clock = self.since_clock.saturating_add(1);
+        }
+
+        let last_val = gate.read(ctx.nframes() - 1);
+        ctx_vals[0].set(last_val);
+    }
+}
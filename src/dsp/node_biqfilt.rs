@@ -3,7 +3,7 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf, SAtom,
+    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState, ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 use synfx_dsp::*;
@@ -83,12 +83,15 @@ treat it too rough. Please use other nodes like eg. `SFilter` for that.
 
 impl DspNode for BiqFilt {
     fn set_sample_rate(&mut self, srate: f32) {
+        // Keep the filter state if the sample rate stays, like after loading a saved state:
+        if srate != self.srate {
+            for b in &mut self.cascade {
+                b.reset();
+            }
+        }
+
         self.srate = srate;
         self.otype = 99; // cause recalculation of the filter
-
-        for b in &mut self.cascade {
-            b.reset();
-        }
     }
 
     fn reset(&mut self) {
@@ -97,6 +100,8 @@ impl DspNode for BiqFilt {
         }
     }
 
+    node_state_by_clone!(cascade, srate, ofreq, oq, ogain, otype);
+
     #[inline]
     fn process(
         &mut self,
@@ -4,7 +4,7 @@
 
 use crate::dsp::{
     denorm, denorm_offs, inp, out, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef,
-    NodeId, ProcBuf, SAtom,
+    NodeId, NodeState, ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 use synfx_dsp::{Biquad, DelayBuffer, FixedOnePole};
@@ -150,6 +150,8 @@ impl DspNode for BowStri {
         self.bstr.reset();
     }
 
+    node_state_by_clone!(no_copy);
+
     #[inline]
     fn process(
         &mut self,
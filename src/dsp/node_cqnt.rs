@@ -3,7 +3,7 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf, SAtom,
+    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState, ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 use synfx_dsp::{ChangeTrig, CtrlPitchQuantizer};
@@ -97,6 +97,10 @@ impl DspNode for CQnt {
         self.change_trig.reset();
     }
 
+    // Copying the quantizer would allocate it's keys, the copied state gets them
+    // recalculated from the atoms instead.
+    node_state_by_clone!(change_trig);
+
     #[inline]
     fn process(
         &mut self,
@@ -3,23 +3,35 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf, SAtom,
+    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState, ProcBuf, SAtom,
 };
-use crate::nodes::{NodeAudioContext, NodeExecContext};
+use crate::nodes::{host_note_div_beats, NodeAudioContext, NodeExecContext};
 use synfx_dsp::{crossfade, DelayBuffer, TriggerSampleClock};
 
+/// The maximum of the `time` parameter, which the [DelayBuffer] has room for.
+const MAX_DELAY_TIME_MS: f32 = 5000.0;
+
 #[macro_export]
 macro_rules! fa_delay_mode {
     ($formatter: expr, $v: expr, $denorm_v: expr) => {{
         let s = match ($v.round() as usize) {
             0 => "Time",
             1 => "Sync",
+            2 => "Host",
             _ => "?",
         };
         write!($formatter, "{}", s)
     }};
 }
 
+#[macro_export]
+macro_rules! fa_delay_note {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let s = $crate::nodes::HOST_NOTE_DIVS.get($v.round() as usize).map(|d| d.0).unwrap_or("?");
+        write!($formatter, "{}", s)
+    }};
+}
+
 /// A simple amplifier
 #[derive(Debug, Clone)]
 pub struct Delay {
@@ -44,7 +56,10 @@ impl Delay {
     pub const mode: &'static str = "Allows different operating modes of the delay. \
         **Time** is the default, and means that the ~~time~~ input \
         specifies the delay time. **Sync** will synchronize the delay time \
-        with the trigger signals on the ~~trig~~ input.";
+        with the trigger signals on the ~~trig~~ input. **Host** will set the delay time \
+        to the note length ~~note~~ at the tempo of the host, up to 5 seconds.";
+    pub const note: &'static str = "The note length of the delay time if ~~mode~~ is set \
+        to **Host**. A dot means dotted, a **T** means triplet.";
     pub const sig: &'static str = "The output of the dry/wet mix.";
 
     pub const DESC: &'static str = r#"Simple Delay Line
@@ -60,6 +75,9 @@ and a feedback ~~fb~~ parameter.
 
 Via the ~~mix~~ parameter you can mix in the input signal to the output.
 
+If you want the delay time to follow the tempo of your DAW, set ~~mode~~
+to **Host** and select the note length with ~~note~~.
+
 You can use this node to delay any kind of signal, from a simple control
 signal to an audio signal.
 
@@ -81,11 +99,13 @@ impl DspNode for Delay {
         self.clock.reset();
     }
 
+    node_state_by_clone!(no_copy);
+
     #[inline]
     fn process(
         &mut self,
         ctx: &mut dyn NodeAudioContext,
-        _ectx: &mut NodeExecContext,
+        ectx: &mut NodeExecContext,
         _nctx: &NodeContext,
         atoms: &[SAtom],
         inputs: &[ProcBuf],
@@ -97,6 +117,7 @@ impl DspNode for Delay {
         let buffer = &mut *self.buffer;
 
         let mode = at::Delay::mode(atoms);
+        let note = at::Delay::note(atoms);
         let inp = inp::Delay::inp(inputs);
         let trig = inp::Delay::trig(inputs);
         let time = inp::Delay::time(inputs);
@@ -112,6 +133,22 @@ impl DspNode for Delay {
 
                 buffer.feed(dry + out_sample * denorm::Delay::fb(fb, frame));
 
+                out.write(
+                    frame,
+                    crossfade(dry, out_sample, denorm::Delay::mix(mix, frame).clamp(0.0, 1.0)),
+                );
+            }
+        } else if mode.i() == 2 {
+            let time_ms = (ectx.transport.beats_to_ms(host_note_div_beats(note.i())) as f32)
+                .clamp(0.0, MAX_DELAY_TIME_MS);
+
+            for frame in 0..ctx.nframes() {
+                let dry = inp.read(frame);
+
+                let out_sample = buffer.cubic_interpolate_at(time_ms);
+
+                buffer.feed(dry + out_sample * denorm::Delay::fb(fb, frame));
+
                 out.write(
                     frame,
                     crossfade(dry, out_sample, denorm::Delay::mix(mix, frame).clamp(0.0, 1.0)),
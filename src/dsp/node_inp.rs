@@ -3,11 +3,19 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    denorm, inp, out_idx, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId,
+    at, denorm, inp, out_idx, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId,
     ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 
+#[macro_export]
+macro_rules! fa_inp_bank {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let bank = $v.round() as usize;
+        write!($formatter, "{}/{}", bank * 2 + 1, bank * 2 + 2)
+    }};
+}
+
 /// The (stereo) input port of the plugin
 #[derive(Debug, Clone)]
 pub struct Inp {}
@@ -21,8 +29,11 @@ impl Inp {
         "The volume of the two plugin input ports, applied to all channels. \
         Please note that this is a linear control, to prevent inaccuracies for **1.0**. \
         ";
-    pub const sig1: &'static str = "Audio input channel 1 (left)";
-    pub const sig2: &'static str = "Audio input channel 2 (right)";
+    pub const bank: &'static str =
+        "The pair of audio input channels that ~~sig1~~ and ~~sig2~~ receive. \
+        Channels that the audio driver does not provide are silent.\n";
+    pub const sig1: &'static str = "Audio input channel 1 (left) of the selected ~~bank~~";
+    pub const sig2: &'static str = "Audio input channel 2 (right) of the selected ~~bank~~";
 
     pub const DESC: &'static str = "Audio Input Port\n\n\
         This node gives you access to the two input ports of the HexoSynth plugin. \
@@ -34,6 +45,9 @@ This node gives you access to the two input ports of the HexoSynth plugin.
 You can build an effects plugin with this node and the `Out` node.
 Or a synthesizer that reacts to audio rate control signals on these two
 input ports.
+
+If the audio driver provides more than two input channels, you can
+select the pair of channels with the ~~bank~~ setting.
 "#;
 
     pub fn graph_fun() -> Option<GraphFun> {
@@ -49,9 +63,9 @@ impl DspNode for Inp {
     fn process(
         &mut self,
         ctx: &mut dyn NodeAudioContext,
-        _ectx: &mut NodeExecContext,
+        ectx: &mut NodeExecContext,
         _nctx: &NodeContext,
-        _atoms: &[SAtom],
+        atoms: &[SAtom],
         inputs: &[ProcBuf],
         outputs: &mut [ProcBuf],
         ctx_vals: LedPhaseVals,
@@ -63,10 +77,16 @@ impl DspNode for Inp {
         let sig1 = &mut sig1[0];
         let sig2 = &mut sig2[0];
 
+        let ch_l = at::Inp::bank(atoms).i() as usize * 2;
+        let ch_r = ch_l + 1;
+        let in_channels = ectx.in_channels;
+
         for frame in 0..ctx.nframes() {
             let vol = denorm::Inp::vol(vol, frame);
-            sig1.write(frame, vol * ctx.input(0, frame));
-            sig2.write(frame, vol * ctx.input(1, frame));
+            let l = if ch_l < in_channels { ctx.input(ch_l, frame) } else { 0.0 };
+            let r = if ch_r < in_channels { ctx.input(ch_r, frame) } else { 0.0 };
+            sig1.write(frame, vol * l);
+            sig2.write(frame, vol * r);
         }
 
         let last_val = sig1.read(ctx.nframes() - 1);
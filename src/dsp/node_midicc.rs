@@ -4,7 +4,7 @@
 
 use crate::dsp::{
     at, denorm, inp, out_idx, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId,
-    ProcBuf, SAtom,
+    NodeState, ProcBuf, SAtom,
 };
 use crate::nodes::{HxMidiEvent, MidiEventPointer, NodeAudioContext, NodeExecContext};
 use synfx_dsp::SlewValue;
@@ -16,6 +16,20 @@ macro_rules! fa_midicc_cc {
     }};
 }
 
+#[macro_export]
+macro_rules! fa_midicc_mode {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let s = match ($v.round() as usize) {
+            0 => "CC",
+            1 => "CC 14bit",
+            2 => "NRPN",
+            3 => "RPN",
+            _ => "?",
+        };
+        write!($formatter, "{}", s)
+    }};
+}
+
 #[derive(Debug, Clone)]
 pub struct MidiCC {
     cur_cc1: f32,
@@ -43,6 +57,13 @@ impl MidiCC {
     pub const cc1: &'static str = "MIDI selected CC 1";
     pub const cc2: &'static str = "MIDI selected CC 2";
     pub const cc3: &'static str = "MIDI selected CC 3";
+    pub const mode: &'static str = "Controller mode\n\
+        - **CC** 7-bit CC values\n\
+        - **CC 14bit** 14-bit values of the CCs 0 to 31, paired with the CCs 32 to 63\n\
+        - **NRPN** / **RPN** data entry values of the parameter numbers \
+        ~~pmsb~~ * 128 + ~~cc1~~, ~~cc2~~ and ~~cc3~~\n";
+    pub const pmsb: &'static str = "Parameter number MSB\n\
+        The MSB of the parameter numbers in the **NRPN** and **RPN** ~~mode~~.\n";
 
     pub const sig1: &'static str = "CC output channel 1";
     pub const sig2: &'static str = "CC output channel 2";
@@ -65,6 +86,17 @@ try to limit the speed of change with the ~~slew~~ limiter.
 
 If you need different ~~slew~~ values for the CCs, I recommend creating other
 `MidiCC` instances with different ~~slew~~ settings.
+
+7-bit steps can be audible on a filter cutoff. High resolution controllers
+send their values with 14 bits, which you can receive with the ~~mode~~ setting:
+
+- **CC 14bit** pairs the CCs 0 to 31 (MSB) with the CCs 32 to 63 (LSB).
+Set ~~cc1~~ to ~~cc3~~ to the MSB controller number.
+- **NRPN** and **RPN** receive the data entry value (CC 6 and 38) of
+the parameter number that was selected with the CCs 99/98 (NRPN) or
+101/100 (RPN). The parameter number is ~~pmsb~~ * 128 + ~~cc1~~, so for the
+NRPN with the MSB 1 and the LSB 2 set ~~pmsb~~ to 1 and ~~cc1~~ to 2.
+For example the RPN 0 is the pitch bend sensitivity.
 "#;
 
     pub fn graph_fun() -> Option<GraphFun> {
@@ -76,6 +108,8 @@ impl DspNode for MidiCC {
     fn set_sample_rate(&mut self, _srate: f32) {}
     fn reset(&mut self) {}
 
+    node_state_by_clone!();
+
     #[inline]
     fn process(
         &mut self,
@@ -92,6 +126,8 @@ impl DspNode for MidiCC {
         let cc1 = at::MidiCC::cc1(atoms);
         let cc2 = at::MidiCC::cc2(atoms);
         let cc3 = at::MidiCC::cc3(atoms);
+        let mode = at::MidiCC::mode(atoms);
+        let pmsb = at::MidiCC::pmsb(atoms);
         let sig2_i = out_idx::MidiCC::sig2();
         let (sig1, r) = outputs.split_at_mut(sig2_i);
         let (sig2, sig3) = r.split_at_mut(1);
@@ -100,9 +136,12 @@ impl DspNode for MidiCC {
         let sig3 = &mut sig3[0];
 
         let midicc_channel = (chan.i() as usize % 16) as u8;
-        let midicc_cc1 = (cc1.i() as usize % 128) as u8;
-        let midicc_cc2 = (cc2.i() as usize % 128) as u8;
-        let midicc_cc3 = (cc3.i() as usize % 128) as u8;
+        let midicc_mode = mode.i();
+        // In the NRPN and RPN mode, the CC settings are the LSB of the parameter number:
+        let msb = if midicc_mode >= 2 { (pmsb.i() as u16 % 128) << 7 } else { 0 };
+        let midicc_cc1 = msb | (cc1.i() as u16 % 128);
+        let midicc_cc2 = msb | (cc2.i() as u16 % 128);
+        let midicc_cc3 = msb | (cc3.i() as u16 % 128);
 
         let mut ptr = MidiEventPointer::new(&ectx.midi_ccs[..]);
 
@@ -112,21 +151,27 @@ impl DspNode for MidiCC {
             let slew_ms = denorm::MidiCC::slew(slew, frame);
 
             while let Some(ev) = ptr.next_at(frame) {
-                if let HxMidiEvent::CC { channel, cc, value } = ev {
-                    if channel != midicc_channel {
-                        continue;
-                    }
-
-                    if cc == midicc_cc1 {
-                        self.cur_cc1 = value;
-                        change = true;
-                    } else if cc == midicc_cc2 {
-                        self.cur_cc2 = value;
-                        change = true;
-                    } else if cc == midicc_cc3 {
-                        self.cur_cc3 = value;
-                        change = true;
-                    }
+                let (channel, cc, value) = match (midicc_mode, ev) {
+                    (0, HxMidiEvent::CC { channel, cc, value })
+                    | (1, HxMidiEvent::CC14 { channel, cc, value }) => (channel, cc as u16, value),
+                    (2, HxMidiEvent::NRPN { channel, param, value })
+                    | (3, HxMidiEvent::RPN { channel, param, value }) => (channel, param, value),
+                    _ => continue,
+                };
+
+                if channel != midicc_channel {
+                    continue;
+                }
+
+                if cc == midicc_cc1 {
+                    self.cur_cc1 = value;
+                    change = true;
+                } else if cc == midicc_cc2 {
+                    self.cur_cc2 = value;
+                    change = true;
+                } else if cc == midicc_cc3 {
+                    self.cur_cc3 = value;
+                    change = true;
                 }
             }
 
@@ -0,0 +1,110 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{
+    at, inp, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState,
+    ProcBuf, SAtom,
+};
+use crate::nodes::{HxTimedEvent, NodeAudioContext, NodeExecContext};
+
+/// The minimum time between two CC messages, so that fast changing signals
+/// don't flood the MIDI output.
+const MIDI_CC_OUT_INTERVAL_MS: f32 = 1.0;
+
+/// Sends a signal as MIDI CC value.
+#[derive(Debug, Clone)]
+pub struct MidiCCOut {
+    /// The channel, CC and 7 bit value of the last sent CC message.
+    last_sent: Option<(u8, u8, u8)>,
+    /// Samples since the last sent CC message.
+    since_send: usize,
+    interval: usize,
+}
+
+impl MidiCCOut {
+    pub fn new(_nid: &NodeId, _node_global: &NodeGlobalRef) -> Self {
+        Self {
+            last_sent: None,
+            since_send: usize::MAX,
+            interval: (44100.0 * MIDI_CC_OUT_INTERVAL_MS / 1000.0) as usize,
+        }
+    }
+
+    pub const inp: &'static str = "The CC value in the range 0.0 to 1.0";
+    pub const chan: &'static str = "MIDI Channel 0 to 15\n";
+    pub const cc: &'static str = "The sent MIDI CC";
+
+    pub const DESC: &'static str = "MIDI CC Output\n\n\
+        This node sends the ~~inp~~ signal as MIDI CC value out of the DSP graph, \
+        to control external synthesizers or other plugins.";
+    pub const HELP: &'static str = r#"MIDI CC Output
+
+This node sends the ~~inp~~ signal as MIDI CC value out of the DSP graph,
+to control external synthesizers or other plugins.
+
+The signal in the range 0.0 to 1.0 is mapped to the 7 bit CC value.
+A CC message is only sent if the 7 bit value changed, but at most
+once per millisecond, so that fast changing signals don't flood the MIDI
+output.
+
+The host application receives the MIDI events after the DSP graph
+was processed.
+"#;
+
+    pub fn graph_fun() -> Option<GraphFun> {
+        None
+    }
+}
+
+impl DspNode for MidiCCOut {
+    fn set_sample_rate(&mut self, srate: f32) {
+        self.interval = (srate * MIDI_CC_OUT_INTERVAL_MS / 1000.0) as usize;
+    }
+
+    fn reset(&mut self) {
+        self.last_sent = None;
+        self.since_send = usize::MAX;
+    }
+
+    node_state_by_clone!();
+
+    #[inline]
+    fn process(
+        &mut self,
+        ctx: &mut dyn NodeAudioContext,
+        ectx: &mut NodeExecContext,
+        _nctx: &NodeContext,
+        atoms: &[SAtom],
+        inputs: &[ProcBuf],
+        _outputs: &mut [ProcBuf],
+        ctx_vals: LedPhaseVals,
+    ) {
+        let inp = inp::MidiCCOut::inp(inputs);
+        let chan = at::MidiCCOut::chan(atoms);
+        let cc = at::MidiCCOut::cc(atoms);
+
+        let midi_channel = (chan.i() as usize % 16) as u8;
+        let midi_cc = (cc.i() as usize % 128) as u8;
+
+        for frame in 0..ctx.nframes() {
+            let value = (inp.read(frame).clamp(0.0, 1.0) * 127.0).round() as u8;
+            let msg = (midi_channel, midi_cc, value);
+
+            if self.last_sent != Some(msg) && self.since_send >= self.interval {
+                ectx.send_midi(HxTimedEvent::cc(
+                    frame,
+                    midi_channel,
+                    midi_cc,
+                    value as f32 / 127.0,
+                ));
+                self.last_sent = Some(msg);
+                self.since_send = 0;
+            }
+
+            self.since_send = self.since_send.saturating_add(1);
+        }
+
+        ctx_vals[0].set(inp.read(ctx.nframes() - 1));
+    }
+}
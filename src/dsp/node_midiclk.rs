@@ -0,0 +1,214 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{
+    at, denorm, inp, out_idx, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId,
+    NodeState, ProcBuf, SAtom,
+};
+use crate::nodes::{
+    host_note_div_beats, HxMidiEvent, MidiEventPointer, NodeAudioContext, NodeExecContext,
+};
+use synfx_dsp::{SlewValue, TrigSignal};
+
+/// The number of MIDI clock messages per quarter note.
+const MIDI_CLOCK_PPQ: usize = 24;
+
+/// If no clock arrives for this many seconds, the tempo measurement starts over.
+const MIDI_CLOCK_TIMEOUT: f32 = 1.0;
+
+#[macro_export]
+macro_rules! fa_midiclk_note {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let s = $crate::nodes::HOST_NOTE_DIVS.get($v.round() as usize).map(|d| d.0).unwrap_or("?");
+        write!($formatter, "{}", s)
+    }};
+}
+
+/// Follows the MIDI clock, start, stop, continue and song position messages.
+#[derive(Debug, Clone)]
+pub struct MidiClk {
+    srate: f32,
+    running: bool,
+    /// The number of clock messages since the start of the song.
+    tick: usize,
+    /// Samples since the last clock message, saturated if no clock was received yet.
+    since_clock: usize,
+    /// The lengths of the last [MIDI_CLOCK_PPQ] clock intervals in samples.
+    intervals: [usize; MIDI_CLOCK_PPQ],
+    interval_idx: usize,
+    interval_count: usize,
+    cur_bpm: f32,
+    clk_sig: TrigSignal,
+    rst_sig: TrigSignal,
+    slew_bpm: SlewValue<f32>,
+}
+
+impl MidiClk {
+    pub fn new(_nid: &NodeId, _node_global: &NodeGlobalRef) -> Self {
+        Self {
+            srate: 44100.0,
+            running: false,
+            tick: 0,
+            since_clock: usize::MAX,
+            intervals: [0; MIDI_CLOCK_PPQ],
+            interval_idx: 0,
+            interval_count: 0,
+            cur_bpm: 0.0,
+            clk_sig: TrigSignal::new(),
+            rst_sig: TrigSignal::new(),
+            slew_bpm: SlewValue::new(),
+        }
+    }
+
+    pub const slew: &'static str = "Slew limiter for the ~~bpm~~ output";
+    pub const note: &'static str = "Clock division\n\
+        The note length of one pulse at the ~~clk~~ output.\n";
+    pub const clk: &'static str = "Clock pulse, one trigger per ~~note~~ note length \
+        while the MIDI clock is running.";
+    pub const run: &'static str =
+        "Run gate, high between a MIDI start or continue and a MIDI stop.";
+    pub const rst: &'static str = "Reset trigger, sent on a MIDI start.";
+    pub const bpm: &'static str = "The tempo of the MIDI clock in BPM divided by 1000. \
+        120 BPM are output as 0.12.";
+
+    pub const DESC: &'static str = "MIDI Clock Input\n\n\
+        This node follows the MIDI clock of an external sequencer, drum machine or DAW. \
+        You get a clock pulse at a selectable note length, a run gate, a reset trigger \
+        and the tempo of the clock.";
+    pub const HELP: &'static str = r#"MIDI Clock Input
+
+This node follows the MIDI clock of an external sequencer, drum machine
+or DAW. It outputs a trigger on ~~clk~~ for every ~~note~~ note length
+while the clock is running. The first pulse comes with the first clock
+after a MIDI start. The ~~run~~ gate is high while the clock is running,
+and ~~rst~~ sends a trigger on MIDI start.
+The MIDI song position sets the position of the clock, so that
+~~clk~~ continues in sync after a MIDI continue.
+
+The tempo of the clock is measured over the last quarter note and
+output on ~~bpm~~ in BPM divided by 1000. Use ~~slew~~ to smooth
+the changes of the tempo.
+
+To let a `TSeq` follow external hardware, connect ~~clk~~ to it's
+`clock` input and set it's `cmode` to **RowT**. Connect ~~rst~~
+to it's `trig` input to restart the pattern on MIDI start.
+"#;
+
+    pub fn graph_fun() -> Option<GraphFun> {
+        None
+    }
+
+    fn clock(&mut self, div_ticks: usize) {
+        if self.since_clock > (self.srate * MIDI_CLOCK_TIMEOUT) as usize {
+            self.intervals = [0; MIDI_CLOCK_PPQ];
+            self.interval_idx = 0;
+            self.interval_count = 0;
+        } else {
+            self.intervals[self.interval_idx] = self.since_clock;
+            self.interval_idx = (self.interval_idx + 1) % MIDI_CLOCK_PPQ;
+            self.interval_count = (self.interval_count + 1).min(MIDI_CLOCK_PPQ);
+
+            // Until a full quarter note was measured, the average interval is extrapolated:
+            let sum = self.intervals.iter().sum::<usize>() as f32;
+            let sum = sum * (MIDI_CLOCK_PPQ as f32 / self.interval_count as f32);
+
+            if sum > 0.0 {
+                self.cur_bpm = (60.0 * self.srate) / sum;
+            }
+        }
+        self.since_clock = 0;
+
+        if self.running {
+            if self.tick.is_multiple_of(div_ticks) {
+                self.clk_sig.trigger();
+            }
+            self.tick += 1;
+        }
+    }
+}
+
+impl DspNode for MidiClk {
+    fn set_sample_rate(&mut self, srate: f32) {
+        self.srate = srate;
+        self.clk_sig.set_sample_rate(srate);
+        self.rst_sig.set_sample_rate(srate);
+    }
+
+    fn reset(&mut self) {
+        self.running = false;
+        self.tick = 0;
+        self.since_clock = usize::MAX;
+        self.intervals = [0; MIDI_CLOCK_PPQ];
+        self.interval_idx = 0;
+        self.interval_count = 0;
+        self.cur_bpm = 0.0;
+        self.clk_sig.reset();
+        self.rst_sig.reset();
+    }
+
+    node_state_by_clone!();
+
+    #[inline]
+    fn process(
+        &mut self,
+        ctx: &mut dyn NodeAudioContext,
+        ectx: &mut NodeExecContext,
+        _nctx: &NodeContext,
+        atoms: &[SAtom],
+        inputs: &[ProcBuf],
+        outputs: &mut [ProcBuf],
+        ctx_vals: LedPhaseVals,
+    ) {
+        let slew = inp::MidiClk::slew(inputs);
+        let note = at::MidiClk::note(atoms);
+        let run_i = out_idx::MidiClk::run();
+        let (clk, r) = outputs.split_at_mut(run_i);
+        let (run, r) = r.split_at_mut(1);
+        let (rst, bpm) = r.split_at_mut(1);
+        let clk = &mut clk[0];
+        let run = &mut run[0];
+        let rst = &mut rst[0];
+        let bpm = &mut bpm[0];
+
+        let div_ticks =
+            ((host_note_div_beats(note.i()) * MIDI_CLOCK_PPQ as f64).round() as usize).max(1);
+
+        let mut ptr = MidiEventPointer::new(&ectx.midi_realtime[..]);
+
+        for frame in 0..ctx.nframes() {
+            let slew_ms = denorm::MidiClk::slew(slew, frame);
+
+            while let Some(ev) = ptr.next_at(frame) {
+                match ev {
+                    HxMidiEvent::Clock => self.clock(div_ticks),
+                    HxMidiEvent::Start => {
+                        self.running = true;
+                        self.tick = 0;
+                        self.rst_sig.trigger();
+                    }
+                    HxMidiEvent::Continue => {
+                        self.running = true;
+                    }
+                    HxMidiEvent::Stop => {
+                        self.running = false;
+                    }
+                    HxMidiEvent::SongPosition { beats } => {
+                        // A MIDI beat is a sixteenth note:
+                        self.tick = beats as usize * (MIDI_CLOCK_PPQ / 4);
+                    }
+                    _ => (),
+                }
+            }
+
+            clk.write(frame, self.clk_sig.next());
+            run.write(frame, if self.running { 1.0 } else { 0.0 });
+            rst.write(frame, self.rst_sig.next());
+            bpm.write(frame, self.slew_bpm.next(self.cur_bpm / 1000.0, slew_ms));
+
+            self.since_clock = self.since_clock.saturating_add(1);
+        }
+
+        ctx_vals[0].set(if self.running { 1.0 } else { 0.0 });
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{
+    at, inp, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState,
+    ProcBuf, SAtom,
+};
+use crate::nodes::{HxTimedEvent, NodeAudioContext, NodeExecContext};
+
+/// Sends MIDI notes from a frequency, gate and velocity signal.
+#[derive(Debug, Clone)]
+pub struct MidiOut {
+    /// The channel and note of the currently playing note.
+    cur_note: Option<(u8, u8)>,
+    /// A note that was still playing when the node was reset, or whose note off
+    /// did not fit into the MIDI output. It's note off is sent with the next buffer period.
+    hanging_note: Option<(u8, u8)>,
+}
+
+impl MidiOut {
+    pub fn new(_nid: &NodeId, _node_global: &NodeGlobalRef) -> Self {
+        Self { cur_note: None, hanging_note: None }
+    }
+
+    pub const freq: &'static str = "The frequency of the note, it's rounded to the next MIDI note \
+        at the start of the ~~gate~~.";
+    pub const gate: &'static str = "The note is played while the gate is high (>= 0.5).";
+    pub const vel: &'static str = "The note velocity, sampled at the start of the ~~gate~~.";
+    pub const chan: &'static str = "MIDI Channel 0 to 15\n";
+
+    pub const DESC: &'static str = "MIDI Note Output\n\n\
+        This node sends MIDI notes out of the DSP graph, to drive external synthesizers \
+        or other plugins. The notes are made from a frequency, gate and velocity signal.";
+    pub const HELP: &'static str = r#"MIDI Note Output
+
+This node sends MIDI notes out of the DSP graph, to drive external
+synthesizers or other plugins from a `TSeq` pattern or a generative patch.
+
+A note on is sent when the ~~gate~~ goes high, and the note off
+when it goes low again. The ~~freq~~ and ~~vel~~ inputs are read at the
+start of the gate, the frequency is rounded to the next MIDI note.
+Changes of the ~~freq~~ while the gate is high don't start a new note.
+
+The host application receives the MIDI events after the DSP graph
+was processed.
+"#;
+
+    pub fn graph_fun() -> Option<GraphFun> {
+        None
+    }
+}
+
+impl DspNode for MidiOut {
+    fn set_sample_rate(&mut self, _srate: f32) {}
+
+    fn reset(&mut self) {
+        if let Some(note) = self.cur_note.take() {
+            self.hanging_note = Some(note);
+        }
+    }
+
+    node_state_by_clone!();
+
+    #[inline]
+    fn process(
+        &mut self,
+        ctx: &mut dyn NodeAudioContext,
+        ectx: &mut NodeExecContext,
+        _nctx: &NodeContext,
+        atoms: &[SAtom],
+        inputs: &[ProcBuf],
+        _outputs: &mut [ProcBuf],
+        ctx_vals: LedPhaseVals,
+    ) {
+        let freq = inp::MidiOut::freq(inputs);
+        let gate = inp::MidiOut::gate(inputs);
+        let vel = inp::MidiOut::vel(inputs);
+        let chan = at::MidiOut::chan(atoms);
+
+        let midi_channel = (chan.i() as usize % 16) as u8;
+
+        if let Some((channel, note)) = self.hanging_note {
+            if ectx.send_midi(HxTimedEvent::note_off(0, channel, note)) {
+                self.hanging_note = None;
+            }
+        }
+
+        for frame in 0..ctx.nframes() {
+            let gate_high = gate.read(frame) >= 0.5;
+
+            match self.cur_note {
+                // No new note is started before the note off of the hanging note was sent:
+                None if gate_high && self.hanging_note.is_none() => {
+                    // One semitone is 0.1 / 12.0 in the frequency signal:
+                    let note = (69.0 + freq.read(frame) * 120.0).round().clamp(0.0, 127.0) as u8;
+                    let vel = vel.read(frame).clamp(0.0, 1.0);
+
+                    // If the MIDI output is full, the note on is retried on the next frame:
+                    if ectx.send_midi(HxTimedEvent::note_on(frame, midi_channel, note, vel)) {
+                        self.cur_note = Some((midi_channel, note));
+                    }
+                }
+                Some((channel, note)) if !gate_high => {
+                    // If the MIDI output is full, the note off is sent with the next
+                    // buffer period:
+                    if !ectx.send_midi(HxTimedEvent::note_off(frame, channel, note)) {
+                        self.hanging_note = Some((channel, note));
+                    }
+                    self.cur_note = None;
+                }
+                _ => (),
+            }
+        }
+
+        ctx_vals[0].set(if self.cur_note.is_some() { 1.0 } else { 0.0 });
+    }
+}
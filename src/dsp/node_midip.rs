@@ -4,10 +4,13 @@
 
 use crate::dsp::{
     at, denorm, inp, out_idx, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId,
-    ProcBuf, SAtom,
+    NodeState, ProcBuf, SAtom,
 };
 use crate::nodes::{HxMidiEvent, MidiEventPointer, NodeAudioContext, NodeExecContext};
-use synfx_dsp::{GateSignal, TrigSignal};
+use synfx_dsp::{GateSignal, RampValue, TrigSignal};
+
+/// The maximum number of held notes that [MidiP] remembers for the note priority.
+const MAX_HELD_NOTES: usize = 16;
 
 #[macro_export]
 macro_rules! fa_midip_chan {
@@ -29,48 +32,198 @@ macro_rules! fa_midip_gmode {
     }};
 }
 
+#[macro_export]
+macro_rules! fa_midip_voice {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let v = $v.round() as usize;
+        if v == 0 {
+            write!($formatter, "Mono")
+        } else {
+            write!($formatter, "Voice {}", v)
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! fa_midip_brange {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let v = $v.round() as usize;
+        if v == 0 {
+            write!($formatter, "Off")
+        } else {
+            write!($formatter, "+/-{} st", v)
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! fa_midip_prio {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let s = match ($v.round() as usize) {
+            0 => "Last",
+            1 => "Low",
+            2 => "High",
+            _ => "?",
+        };
+        write!($formatter, "{}", s)
+    }};
+}
+
+#[macro_export]
+macro_rules! fa_midip_legato {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let s = match ($v.round() as usize) {
+            0 => "Retrig",
+            1 => "Legato",
+            _ => "?",
+        };
+        write!($formatter, "{}", s)
+    }};
+}
+
+#[inline]
+fn note_pitch(note: u8) -> f32 {
+    (note as f32 - 69.0) / 120.0
+}
+
 /// The (stereo) output port of the plugin
 #[derive(Debug, Clone)]
 pub struct MidiP {
+    /// The held notes with their velocity, the most recent note last.
+    held: [(u8, f32); MAX_HELD_NOTES],
+    held_len: usize,
+    pitch: RampValue<f32>,
+    /// Set after the first note, the first note doesn't glide.
+    has_note: bool,
     next_gate: i8,
     cur_note: u8,
     cur_gate: u8,
     cur_vel: f32,
+    cur_bend: f32,
+    cur_press: f32,
     trig_sig: TrigSignal,
     gate_sig: GateSignal,
 }
 
 impl MidiP {
     pub fn new(_nid: &NodeId, _node_global: &NodeGlobalRef) -> Self {
+        let mut pitch = RampValue::new();
+        pitch.set_target(note_pitch(0), 0.0);
+
         Self {
+            held: [(0, 0.0); MAX_HELD_NOTES],
+            held_len: 0,
+            pitch,
+            has_note: false,
             next_gate: 0,
             cur_note: 0,
             cur_gate: 0,
             cur_vel: 0.0,
+            cur_bend: 0.0,
+            cur_press: 0.0,
             trig_sig: TrigSignal::new(),
             gate_sig: GateSignal::new(),
         }
     }
 
+    fn push_note(&mut self, note: u8, vel: f32) {
+        self.remove_note(note);
+
+        if self.held_len == MAX_HELD_NOTES {
+            self.held.copy_within(1.., 0);
+            self.held_len -= 1;
+        }
+
+        self.held[self.held_len] = (note, vel);
+        self.held_len += 1;
+    }
+
+    /// Returns true if the `note` was held.
+    fn remove_note(&mut self, note: u8) -> bool {
+        if let Some(idx) = self.held[..self.held_len].iter().position(|(n, _)| *n == note) {
+            self.held.copy_within((idx + 1)..self.held_len, idx);
+            self.held_len -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Selects the note to play from the held notes by the priority `prio`.
+    fn select_note(&self, prio: i64) -> Option<(u8, f32)> {
+        let held = self.held[..self.held_len].iter().copied();
+        match prio {
+            1 => held.min_by_key(|(n, _)| *n),
+            2 => held.max_by_key(|(n, _)| *n),
+            _ => held.last(),
+        }
+    }
+
+    /// Switches to the `note`. The gate is restarted if `retrigger` is set, and
+    /// the `freq` output glides to the new note in `glide_ms` milliseconds.
+    fn play_note(&mut self, note: u8, vel: f32, retrigger: bool, glide_ms: f32) {
+        if retrigger {
+            if self.cur_gate > 0 {
+                self.next_gate = 1;
+                self.cur_gate = 0;
+            } else {
+                self.cur_gate = 1;
+            }
+            self.trig_sig.trigger();
+            self.gate_sig.trigger();
+        }
+
+        self.cur_note = note;
+        self.cur_vel = vel;
+        self.pitch.set_target(note_pitch(note), glide_ms);
+    }
+
     pub const chan: &'static str = "MIDI Channel 0 to 15\n";
     pub const gmode: &'static str = "MIDI gate mode.\n- **MIDI** gate same as MIDI input\n- **Trigger** output only triggers on ~~gate~~ output\n- **Gate Len** output gate with the length of the ~~glen~~ parameter\n";
     pub const glen: &'static str = "MIDI gate length\n\
         If ~~gmode~~ is set to **Gate Len** this controls and overrides the gate length on a MIDI \
         note event. **Trigger** will just send a short trigger when a note event is received. \
         **MIDI** means the gate reflects the note on/off duration.";
+    pub const voice: &'static str = "Polyphonic voice\n\
+        **Mono** receives all MIDI notes of the channel. A voice number receives only the notes \
+        that the voice allocation assigned to that voice.\n";
+    pub const brange: &'static str = "Pitch bend range\n\
+        The range in semitones up and down that the MIDI pitch bend \
+        applies to the ~~freq~~ output.\n";
+    pub const prio: &'static str = "Note priority\n\
+        Selects the note to play while multiple notes are held.\n\
+        - **Last** the most recently pressed note\n\
+        - **Low** the lowest held note\n\
+        - **High** the highest held note\n";
+    pub const legato: &'static str = "Legato mode\n\
+        - **Retrig** every note change restarts the ~~gate~~\n\
+        - **Legato** changing the note while another note is held keeps the ~~gate~~ open, \
+        and only these notes glide\n";
+    pub const glide: &'static str = "Portamento (glide) time\n\
+        The time the ~~freq~~ output takes to glide to the next note.";
     pub const det: &'static str = "Detune input pitch a bit";
-    pub const freq: &'static str = "MIDI note frequency, detuned by ~~det~~.";
+    pub const freq: &'static str =
+        "MIDI note frequency, detuned by ~~det~~, bent by the pitch bend and gliding by ~~glide~~.";
     pub const gate: &'static str = "MIDI note gate";
     pub const vel: &'static str = "MIDI note velocity";
+    pub const bend: &'static str = "MIDI pitch bend, in the range -1.0 to 1.0";
+    pub const press: &'static str = "MIDI channel pressure (aftertouch), or the polyphonic \
+        key pressure of the current note. In the range 0.0 to 1.0";
 
     pub const DESC: &'static str = "MIDI Pitch/Note Input\n\n\
         This node is an input of MIDI note events into the DSP graph. \
-        You get 3 outputs: frequency of the note, gate signal for the length of the note and the velocity.";
+        You get the frequency of the note, a gate signal for the length of the note, the velocity, \
+        the pitch bend and the pressure (aftertouch).";
     pub const HELP: &'static str = r#"MIDI Pitch/Note Input
 
 This node is an input of MIDI note events into the DSP graph.
-You get 3 outputs: frequency of the note, gate signal for the length of
-the note and the velocity.
+You get the frequency of the note, a gate signal for the length of
+the note, the velocity, the pitch bend and the pressure (aftertouch).
+
+The pitch bend is applied to the ~~freq~~ output with a range of
+~~brange~~ semitones up and down. The ~~press~~ output follows the
+channel pressure and the polyphonic key pressure of the current note,
+whichever was received last.
 
 You can modify the gate length using the ~~gmode~~ and ~~glen~~ settings.
 Setting ~~gmode~~ to **Trigger** allows you to get only a short trigger
@@ -78,6 +231,20 @@ signal, which might be helpful in some situations.
 The **Gate Len** setting allows you to overwrite the gate length with a
 custom and fixed gate length. However, if new note is played on this
 MIDI channel, the gate will restart after a very short pause.
+
+The node remembers the held notes. If the played note is released while
+other notes are still held, it returns to one of them, like a
+monophonic synthesizer. Which note is played is selected by the note
+priority ~~prio~~: the **Last** pressed, the **Low**est or the **High**est note.
+With ~~legato~~ set to **Legato**, note changes while a note is held don't
+restart the gate. The ~~glide~~ time makes the ~~freq~~ output slide to the
+next note. In **Legato** mode only the overlapping notes glide, a note
+played after all keys were released starts right at it's pitch.
+
+For polyphonic patches you can set ~~voice~~ to a voice number. The node
+then only receives the notes that the voice allocation of the DSP engine
+assigned to that voice. Place one MidiP node per voice, or let the
+`PolyVoices` API of HexoDSP replicate the voice sub-graph for you.
 "#;
 
     pub fn graph_fun() -> Option<GraphFun> {
@@ -88,14 +255,21 @@ MIDI channel, the gate will restart after a very short pause.
 #[allow(clippy::comparison_chain)]
 impl DspNode for MidiP {
     fn set_sample_rate(&mut self, srate: f32) {
+        self.pitch.set_sample_rate(srate);
         self.trig_sig.set_sample_rate(srate);
         self.gate_sig.set_sample_rate(srate);
     }
     fn reset(&mut self) {
         self.trig_sig.reset();
         self.gate_sig.reset();
+        self.cur_bend = 0.0;
+        self.cur_press = 0.0;
+        self.held_len = 0;
+        self.pitch.set_target(note_pitch(self.cur_note), 0.0);
     }
 
+    node_state_by_clone!();
+
     #[inline]
     fn process(
         &mut self,
@@ -109,23 +283,43 @@ impl DspNode for MidiP {
     ) {
         let det = inp::MidiP::det(inputs);
         let glen = inp::MidiP::glen(inputs);
+        let glide = inp::MidiP::glide(inputs);
         let chan = at::MidiP::chan(atoms);
         let gmode = at::MidiP::gmode(atoms);
+        let voice = at::MidiP::voice(atoms);
+        let brange = at::MidiP::brange(atoms);
+        let prio = at::MidiP::prio(atoms).i();
+        let legato_mode = at::MidiP::legato(atoms).i() == 1;
         let out_i = out_idx::MidiP::gate();
         let (freq, r) = outputs.split_at_mut(out_i);
-        let (gate, vel) = r.split_at_mut(1);
+        let (gate, r) = r.split_at_mut(1);
+        let (vel, r) = r.split_at_mut(1);
+        let (bend, press) = r.split_at_mut(1);
         let freq = &mut freq[0];
         let gate = &mut gate[0];
         let vel = &mut vel[0];
+        let bend = &mut bend[0];
+        let press = &mut press[0];
+
+        // One semitone is 0.1 / 12.0 in the frequency signal:
+        let bend_range = brange.i().clamp(0, 24) as f32 / 120.0;
 
         let midip_channel = (chan.i() as usize % 16) as u8;
 
-        let mut ptr = MidiEventPointer::new(&ectx.midi_notes[..]);
+        let voice = voice.i() as usize;
+        let notes = if voice > 0 && voice <= ectx.voice_notes.len() {
+            &ectx.voice_notes[voice - 1][..]
+        } else {
+            &ectx.midi_notes[..]
+        };
+
+        let mut ptr = MidiEventPointer::new(notes);
 
         let gmode = gmode.i();
 
         for frame in 0..ctx.nframes() {
             let gate_len = denorm::MidiP::glen(glen, frame);
+            let glide_ms = denorm::MidiP::glide(glide, frame);
 
             if self.next_gate > 0 {
                 self.cur_gate = 1;
@@ -141,26 +335,51 @@ impl DspNode for MidiP {
                             continue;
                         }
 
-                        if self.cur_gate > 0 {
-                            self.next_gate = 1;
-                            self.cur_gate = 0;
-                        } else {
-                            self.cur_gate = 1;
+                        let was_held = self.held_len > 0;
+                        self.push_note(note, vel);
+
+                        // A note that has no priority over the held notes is just remembered:
+                        if self.select_note(prio).map(|(n, _)| n) == Some(note) {
+                            let legato = legato_mode && was_held;
+                            let glide_ms = if self.has_note && (legato || !legato_mode) {
+                                glide_ms
+                            } else {
+                                0.0
+                            };
+                            self.has_note = true;
+                            self.play_note(note, vel, !legato, glide_ms);
                         }
-                        self.trig_sig.trigger();
-                        self.gate_sig.trigger();
-                        self.cur_note = note;
-                        self.cur_vel = vel;
                     }
                     HxMidiEvent::NoteOff { channel, note } => {
                         if channel != midip_channel {
                             continue;
                         }
 
-                        if self.cur_note == note {
-                            self.next_gate = -1;
+                        if !self.remove_note(note) {
+                            continue;
+                        }
+
+                        match self.select_note(prio) {
+                            None => {
+                                self.next_gate = -1;
+                            }
+                            Some((next_note, next_vel)) if next_note != self.cur_note => {
+                                self.play_note(next_note, next_vel, !legato_mode, glide_ms);
+                            }
+                            _ => (),
                         }
                     }
+                    HxMidiEvent::PitchBend { channel, value } if channel == midip_channel => {
+                        self.cur_bend = value;
+                    }
+                    HxMidiEvent::ChannelPressure { channel, value } if channel == midip_channel => {
+                        self.cur_press = value;
+                    }
+                    HxMidiEvent::PolyPressure { channel, note, value }
+                        if channel == midip_channel && note == self.cur_note =>
+                    {
+                        self.cur_press = value;
+                    }
                     _ => (),
                 }
             }
@@ -184,11 +403,13 @@ impl DspNode for MidiP {
                 }
             }
 
-            let note = (self.cur_note as f32 - 69.0) / 120.0;
-            let note = note + det.read(frame);
+            let note = self.pitch.next();
+            let note = note + det.read(frame) + self.cur_bend * bend_range;
             //d// println!("FRAME: {} => gate={}, freq={}, next_gate={}", frame, self.cur_gate, note, self.next_gate);
             freq.write(frame, note);
             vel.write(frame, self.cur_vel as f32);
+            bend.write(frame, self.cur_bend);
+            press.write(frame, self.cur_press);
         }
 
         let last_val = gate.read(ctx.nframes() - 1);
@@ -0,0 +1,198 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{
+    at, inp, out_idx, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId,
+    NodeState, ProcBuf, SAtom,
+};
+use crate::nodes::{HxMidiEvent, MidiEventPointer, NodeAudioContext, NodeExecContext};
+use crate::nodes::{MAX_MPE_SLOTS, MPE_SLIDE_CC};
+
+#[macro_export]
+macro_rules! fa_mpep_slot {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        write!($formatter, "Slot {}", $v.round() as usize + 1)
+    }};
+}
+
+/// Follows one note of the MPE zone.
+#[derive(Debug, Clone)]
+pub struct MpeP {
+    next_gate: i8,
+    cur_note: u8,
+    cur_gate: u8,
+    cur_vel: f32,
+    note_bend: f32,
+    master_bend: f32,
+    cur_press: f32,
+    cur_slide: f32,
+}
+
+impl MpeP {
+    pub fn new(_nid: &NodeId, _node_global: &NodeGlobalRef) -> Self {
+        Self {
+            next_gate: 0,
+            cur_note: 0,
+            cur_gate: 0,
+            cur_vel: 0.0,
+            note_bend: 0.0,
+            master_bend: 0.0,
+            cur_press: 0.0,
+            cur_slide: 0.0,
+        }
+    }
+
+    pub const det: &'static str = "Detune input pitch a bit";
+    pub const slot: &'static str = "The MPE note slot this node follows\n\
+        A new note takes the first free slot and keeps it until it's released.\n";
+    pub const brange: &'static str = "Per note pitch bend range\n\
+        The range of the pitch bend on the member channel of the note. \
+        MPE controllers use +/-48 semitones by default.\n";
+    pub const mrange: &'static str = "Master pitch bend range\n\
+        The range of the zone wide pitch bend on the master channel.\n";
+    pub const freq: &'static str =
+        "MPE note frequency, detuned by ~~det~~ and bent by the per note and master pitch bend.";
+    pub const gate: &'static str = "MPE note gate";
+    pub const vel: &'static str = "MPE note velocity";
+    pub const press: &'static str = "Per note pressure (aftertouch), in the range 0.0 to 1.0";
+    pub const slide: &'static str = "Per note slide (CC74, Y axis), in the range 0.0 to 1.0";
+
+    pub const DESC: &'static str = "MPE Pitch/Note Input\n\n\
+        This node follows one note of an MPE (MIDI Polyphonic Expression) controller. \
+        You get the frequency, gate and velocity of the note, with it's own pitch bend, \
+        pressure and slide.";
+    pub const HELP: &'static str = r#"MPE Pitch/Note Input
+
+This node follows one note of an MPE (MIDI Polyphonic Expression)
+controller. MPE controllers play each note on it's own MIDI channel,
+so every note has it's own pitch bend, pressure and slide.
+
+The MPE zone has to be configured with `set_mpe_zone()` on the
+`NodeConfigurator`, `Matrix` or `SynthConstructor`, otherwise this node
+receives no notes.
+
+Each new note of the zone takes the first free slot and keeps it until
+it's released. Place one `MpeP` per ~~slot~~ and give each a voice
+sub-graph to build an MPE voice bank. With only one note held, it's
+always in the first slot.
+
+The ~~freq~~ output is bent by the per note pitch bend with the
+~~brange~~ and by the zone wide pitch bend of the master channel with
+the ~~mrange~~. The ~~press~~ output follows the channel pressure of the
+note and ~~slide~~ the CC74 of the note.
+"#;
+
+    pub fn graph_fun() -> Option<GraphFun> {
+        None
+    }
+}
+
+impl DspNode for MpeP {
+    fn set_sample_rate(&mut self, _srate: f32) {}
+
+    fn reset(&mut self) {
+        self.next_gate = 0;
+        self.cur_gate = 0;
+        self.note_bend = 0.0;
+        self.master_bend = 0.0;
+        self.cur_press = 0.0;
+        self.cur_slide = 0.0;
+    }
+
+    node_state_by_clone!();
+
+    #[inline]
+    fn process(
+        &mut self,
+        ctx: &mut dyn NodeAudioContext,
+        ectx: &mut NodeExecContext,
+        _nctx: &NodeContext,
+        atoms: &[SAtom],
+        inputs: &[ProcBuf],
+        outputs: &mut [ProcBuf],
+        ctx_vals: LedPhaseVals,
+    ) {
+        let det = inp::MpeP::det(inputs);
+        let slot = at::MpeP::slot(atoms);
+        let brange = at::MpeP::brange(atoms);
+        let mrange = at::MpeP::mrange(atoms);
+        let out_i = out_idx::MpeP::gate();
+        let (freq, r) = outputs.split_at_mut(out_i);
+        let (gate, r) = r.split_at_mut(1);
+        let (vel, r) = r.split_at_mut(1);
+        let (press, slide) = r.split_at_mut(1);
+        let freq = &mut freq[0];
+        let gate = &mut gate[0];
+        let vel = &mut vel[0];
+        let press = &mut press[0];
+        let slide = &mut slide[0];
+
+        // One semitone is 0.1 / 12.0 in the frequency signal:
+        let note_bend_range = brange.i().clamp(0, 96) as f32 / 120.0;
+        let master_bend_range = mrange.i().clamp(0, 24) as f32 / 120.0;
+
+        let master = ectx.mpe_alloc.zone().map(|z| z.master);
+        let slot = (slot.i() as usize).min(MAX_MPE_SLOTS - 1);
+        let mut ptr = MidiEventPointer::new(&ectx.mpe_notes[slot][..]);
+
+        for frame in 0..ctx.nframes() {
+            if self.next_gate > 0 {
+                self.cur_gate = 1;
+            } else if self.next_gate < 0 {
+                self.cur_gate = 0;
+            }
+            self.next_gate = 0;
+
+            while let Some(ev) = ptr.next_at(frame) {
+                match ev {
+                    HxMidiEvent::NoteOn { note, vel, .. } => {
+                        if self.cur_gate > 0 {
+                            self.next_gate = 1;
+                            self.cur_gate = 0;
+                        } else {
+                            self.cur_gate = 1;
+                        }
+                        self.cur_note = note;
+                        self.cur_vel = vel;
+                    }
+                    HxMidiEvent::NoteOff { note, .. } => {
+                        if self.cur_note == note {
+                            self.next_gate = -1;
+                        }
+                    }
+                    HxMidiEvent::PitchBend { channel, value } => {
+                        if Some(channel) == master {
+                            self.master_bend = value;
+                        } else {
+                            self.note_bend = value;
+                        }
+                    }
+                    HxMidiEvent::ChannelPressure { value, .. }
+                    | HxMidiEvent::PolyPressure { value, .. } => {
+                        self.cur_press = value;
+                    }
+                    HxMidiEvent::CC { cc: MPE_SLIDE_CC, value, .. } => {
+                        self.cur_slide = value;
+                    }
+                    _ => (),
+                }
+            }
+
+            gate.write(frame, if self.cur_gate > 0 { 1.0 } else { 0.0 });
+
+            let note = (self.cur_note as f32 - 69.0) / 120.0;
+            let note = note
+                + det.read(frame)
+                + self.note_bend * note_bend_range
+                + self.master_bend * master_bend_range;
+            freq.write(frame, note);
+            vel.write(frame, self.cur_vel);
+            press.write(frame, self.cur_press);
+            slide.write(frame, self.cur_slide);
+        }
+
+        let last_val = gate.read(ctx.nframes() - 1);
+        ctx_vals[0].set(last_val);
+    }
+}
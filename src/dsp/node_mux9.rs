@@ -3,7 +3,7 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf, SAtom,
+    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState, ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 use synfx_dsp::Trigger;
@@ -123,6 +123,8 @@ impl DspNode for Mux9 {
     fn set_sample_rate(&mut self, _srate: f32) {}
     fn reset(&mut self) {}
 
+    node_state_by_clone!();
+
     #[inline]
     fn process(
         &mut self,
@@ -3,7 +3,8 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf, SAtom,
+    node_random_seed, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId,
+    NodeState, ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 use synfx_dsp::Rng;
@@ -23,16 +24,23 @@ macro_rules! fa_noise_mode {
 /// A simple noise generator
 #[derive(Debug, Clone)]
 pub struct Noise {
+    nid: NodeId,
     seed: u64,
     rng: Rng,
 }
 
+const NOISE_SEED_SALT: u64 = 0x131415;
+
 impl Noise {
-    pub fn new(nid: &NodeId, _node_global: &NodeGlobalRef) -> Self {
+    pub fn new(nid: &NodeId, node_global: &NodeGlobalRef) -> Self {
+        let global_seed =
+            node_global.lock().map(|g| g.random_seed()).unwrap_or(crate::DEFAULT_RANDOM_SEED);
+        let seed = node_random_seed(global_seed, NOISE_SEED_SALT, nid);
+
         let mut rng = Rng::new();
-        rng.seed((0x193a67f4a8a6d769_u64).wrapping_add(0x131415 * (nid.instance() as u64 + 1)));
+        rng.seed(seed);
 
-        Self { seed: nid.instance() as u64, rng }
+        Self { nid: *nid, seed, rng }
     }
 
     pub const atv: &'static str = "Attenuverter input, to attenuate or invert \
@@ -76,7 +84,14 @@ impl DspNode for Noise {
     fn set_sample_rate(&mut self, _srate: f32) {}
 
     fn reset(&mut self) {
-        self.rng.seed((0x193a67f4a8a6d769_u64).wrapping_add(0x131415 * (self.seed + 1)));
+        self.rng.seed(self.seed);
+    }
+
+    node_state_by_clone!();
+
+    fn set_random_seed(&mut self, seed: u64) {
+        self.seed = node_random_seed(seed, NOISE_SEED_SALT, &self.nid);
+        self.rng.seed(self.seed);
     }
 
     #[inline]
@@ -20,6 +20,14 @@ macro_rules! fa_out_mono {
     }};
 }
 
+#[macro_export]
+macro_rules! fa_out_bank {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let bank = $v.round() as usize;
+        write!($formatter, "{}/{}", bank * 2 + 1, bank * 2 + 2)
+    }};
+}
+
 /// The (stereo) output port of the plugin
 #[derive(Debug, Clone)]
 pub struct Out {
@@ -36,12 +44,15 @@ impl Out {
 
     pub const mono: &'static str =
         "If set to **Mono**, ~~ch1~~ will be sent to both output channels.\n(UI only)";
+    pub const bank: &'static str =
+        "The pair of audio output channels ~~ch1~~ and ~~ch2~~ are sent to. \
+        Channels that the audio driver does not provide are not written.\n";
     pub const vol: &'static str =
         "The main volume of the synthesizer output, applied to all channels. \
         Please note that this is a linear control, to prevent inaccuracies for **1.0**. \
         ";
-    pub const ch1: &'static str = "Audio channel 1 (left)";
-    pub const ch2: &'static str = "Audio channel 2 (right)";
+    pub const ch1: &'static str = "Audio channel 1 (left) of the selected ~~bank~~";
+    pub const ch2: &'static str = "Audio channel 2 (right) of the selected ~~bank~~";
 
     pub const DESC: &'static str = "Audio Output Port\n\n\
         This output port node allows you to send audio signals \
@@ -52,6 +63,11 @@ This output port node allows you to send audio signals to audio devices
 or tracks in your DAW. If you need a stereo output but only have a mono
 signal you can use the ~~mono~~ setting to duplicate the signal on the ~~ch1~~
 input to the second channel ~~ch2~~.
+
+If the audio driver provides more than two output channels, you can
+select the pair of channels with the ~~bank~~ setting. Use multiple
+`Out` nodes with different banks to route surround channels or multitrack
+stems out of one graph.
 "#;
 
     pub fn graph_fun() -> Option<GraphFun> {
@@ -59,6 +75,17 @@ input to the second channel ~~ch2~~.
     }
 }
 
+/// Limits the sample `v` to `-limit..=limit` if the output guard is enabled.
+/// Non finite samples are silenced then.
+#[inline]
+fn guard_sample(v: f32, limit: Option<f32>) -> f32 {
+    match limit {
+        Some(limit) if v.is_finite() => v.max(-limit).min(limit),
+        Some(_) => 0.0,
+        None => v,
+    }
+}
+
 impl DspNode for Out {
     fn set_sample_rate(&mut self, _srate: f32) {}
     fn reset(&mut self) {}
@@ -67,7 +94,7 @@ impl DspNode for Out {
     fn process(
         &mut self,
         ctx: &mut dyn NodeAudioContext,
-        _ectx: &mut NodeExecContext,
+        ectx: &mut NodeExecContext,
         _nctx: &NodeContext,
         atoms: &[SAtom],
         inputs: &[ProcBuf],
@@ -77,19 +104,26 @@ impl DspNode for Out {
         let in1 = inp::Out::ch1(inputs);
         let vol = inp::Out::vol(inputs);
 
-        if at::Out::mono(atoms).i() > 0 {
+        let ch_l = at::Out::bank(atoms).i() as usize * 2;
+        let ch_r = ch_l + 1;
+        let out_channels = ectx.out_channels;
+
+        let in2 = if at::Out::mono(atoms).i() > 0 { in1 } else { inp::Out::ch2(inputs) };
+
+        // The output guard of the executor hard limits the output:
+        let limit = ectx.output_guard;
+
+        if ch_l < out_channels {
             for frame in 0..ctx.nframes() {
                 let vol = denorm::Out::vol(vol, frame);
-                ctx.output(0, frame, vol * in1.read(frame));
-                ctx.output(1, frame, vol * in1.read(frame));
+                ctx.output(ch_l, frame, guard_sample(vol * in1.read(frame), limit));
             }
-        } else {
-            let in2 = inp::Out::ch2(inputs);
+        }
 
+        if ch_r < out_channels {
             for frame in 0..ctx.nframes() {
                 let vol = denorm::Out::vol(vol, frame);
-                ctx.output(0, frame, vol * in1.read(frame));
-                ctx.output(1, frame, vol * in2.read(frame));
+                ctx.output(ch_r, frame, guard_sample(vol * in2.read(frame), limit));
             }
         }
 
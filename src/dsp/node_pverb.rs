@@ -3,7 +3,8 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    denorm, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf, SAtom,
+    denorm, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState,
+    ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 use synfx_dsp::{crossfade, DattorroReverb, DattorroReverbParams};
@@ -180,6 +181,8 @@ impl DspNode for PVerb {
         self.verb.reset();
     }
 
+    node_state_by_clone!(no_copy);
+
     #[inline]
     fn process(
         &mut self,
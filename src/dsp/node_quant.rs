@@ -3,7 +3,7 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf, SAtom,
+    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState, ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 use synfx_dsp::{ChangeTrig, Quantizer};
@@ -68,6 +68,8 @@ impl DspNode for Quant {
         self.change_trig.reset();
     }
 
+    node_state_by_clone!(quant, change_trig);
+
     #[inline]
     fn process(
         &mut self,
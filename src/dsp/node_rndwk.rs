@@ -3,7 +3,8 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf, SAtom,
+    node_random_seed, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId,
+    NodeState, ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 use synfx_dsp::{Rng, SlewValue, Trigger};
@@ -11,18 +12,24 @@ use synfx_dsp::{Rng, SlewValue, Trigger};
 /// A triggered random walker
 #[derive(Debug, Clone)]
 pub struct RndWk {
+    nid: NodeId,
     rng: Rng,
     slew_val: SlewValue<f64>,
     trig: Trigger,
     target: f64,
 }
 
+const RNDWK_SEED_SALT: u64 = 0x262829;
+
 impl RndWk {
-    pub fn new(nid: &NodeId, _node_global: &NodeGlobalRef) -> Self {
+    pub fn new(nid: &NodeId, node_global: &NodeGlobalRef) -> Self {
+        let global_seed =
+            node_global.lock().map(|g| g.random_seed()).unwrap_or(crate::DEFAULT_RANDOM_SEED);
+
         let mut rng = Rng::new();
-        rng.seed((0x193a67f4a8a6d769_u64).wrapping_add(0x262829 * (nid.instance() as u64 + 1)));
+        rng.seed(node_random_seed(global_seed, RNDWK_SEED_SALT, nid));
 
-        Self { rng, trig: Trigger::new(), slew_val: SlewValue::new(), target: 0.0 }
+        Self { nid: *nid, rng, trig: Trigger::new(), slew_val: SlewValue::new(), target: 0.0 }
     }
 
     pub const trig: &'static str = "This trigger generates a new random number within \
@@ -85,6 +92,12 @@ impl DspNode for RndWk {
         self.target = 0.0;
     }
 
+    node_state_by_clone!();
+
+    fn set_random_seed(&mut self, seed: u64) {
+        self.rng.seed(node_random_seed(seed, RNDWK_SEED_SALT, &self.nid));
+    }
+
     #[inline]
     fn process(
         &mut self,
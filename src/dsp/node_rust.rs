@@ -215,7 +215,9 @@ See also [crate::SynthConstructor] and [crate::DynamicNode1x1].
 impl DspNode for Rust1x1 {
     fn set_sample_rate(&mut self, _srate: f32) {}
 
-    fn reset(&mut self) {}
+    fn reset(&mut self) {
+        self.buffer.access().reset();
+    }
 
     #[inline]
     fn process(
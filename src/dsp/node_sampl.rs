@@ -2,9 +2,9 @@
 // This file is a part of HexoDSP. Released under GPL-3.0-or-later.
 // See README.md and COPYING for details.
 
-use crate::dsp::{at, denorm, denorm_offs, inp, out, GraphFun, NodeGlobalRef}; //, inp, denorm, denorm_v, inp_dir, at};
+use crate::dsp::{at, denorm, denorm_offs, inp, out, GraphFun, NodeGlobalRef, NodeState}; //, inp, denorm, denorm_v, inp_dir, at};
 use crate::dsp::{DspNode, LedPhaseVals, NodeContext, NodeId, ProcBuf, SAtom};
-use crate::nodes::{NodeAudioContext, NodeExecContext};
+use crate::nodes::{NodeAudioContext, NodeEvent, NodeExecContext};
 use synfx_dsp::{cubic_interpolate, Trigger};
 
 #[macro_export]
@@ -170,7 +170,7 @@ impl Sampl {
         do_loop: bool,
         declick: bool,
         reverse: bool,
-    ) {
+    ) -> bool {
         let freq = inp::Sampl::freq(inputs);
         let trig = inp::Sampl::trig(inputs);
         let offs = inp::Sampl::offs(inputs);
@@ -187,6 +187,7 @@ impl Sampl {
         let ramp_inc = 1000.0 / ramp_time;
 
         let mut is_playing = self.is_playing;
+        let mut reached_end = false;
 
         if do_loop {
             is_playing = true;
@@ -263,9 +264,13 @@ impl Sampl {
                 self.last_sample = s;
                 out.write(frame, s);
 
-                if !do_loop && prev_phase > self.phase {
-                    // played past end => stop playing.
-                    is_playing = false;
+                if prev_phase > self.phase {
+                    reached_end = true;
+
+                    if !do_loop {
+                        // played past end => stop playing.
+                        is_playing = false;
+                    }
                 }
 
                 s
@@ -286,6 +291,8 @@ impl Sampl {
         }
 
         self.is_playing = is_playing;
+
+        reached_end
     }
 }
 
@@ -297,12 +304,14 @@ impl DspNode for Sampl {
         self.trig.reset();
     }
 
+    node_state_by_clone!();
+
     #[inline]
     fn process(
         &mut self,
         ctx: &mut dyn NodeAudioContext,
-        _ectx: &mut NodeExecContext,
-        _nctx: &NodeContext,
+        ectx: &mut NodeExecContext,
+        nctx: &NodeContext,
         atoms: &[SAtom],
         inputs: &[ProcBuf],
         outputs: &mut [ProcBuf],
@@ -324,7 +333,7 @@ impl DspNode for Sampl {
                 return;
             }
 
-            self.play(
+            let reached_end = self.play(
                 inputs,
                 ctx.nframes(),
                 &sample_data[..],
@@ -333,6 +342,10 @@ impl DspNode for Sampl {
                 dclick.i() == 1,
                 dir.i() == 1,
             );
+
+            if reached_end {
+                ectx.emit_event(nctx.node_id, NodeEvent::SampleEnd);
+            }
         } else {
             for frame in 0..ctx.nframes() {
                 out.write(frame, 0.0);
@@ -12,7 +12,7 @@ use crate::dsp::{
     DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf, SAtom,
 };
 use crate::nodes::SCOPE_SAMPLES;
-use crate::nodes::{NodeAudioContext, NodeExecContext};
+use crate::nodes::{NodeAudioContext, NodeEvent, NodeExecContext};
 use crate::ScopeHandle;
 use std::sync::Arc;
 use synfx_dsp::CustomTrigger;
@@ -24,6 +24,7 @@ macro_rules! fa_scope_tsrc {
             0 => "Off",
             1 => "Intern",
             2 => "Extern",
+            3 => "Host",
             _ => "?",
         };
         write!($formatter, "{}", s)
@@ -76,7 +77,8 @@ impl Scope {
     pub const tsrc: &'static str =
         "Triggering allows you to capture fast signals or pinning fast waveforms into the scope \
         view for better inspection. You can let the scope freeze and manually recapture \
-        waveforms by setting ~~tsrc~~ to **Extern** and hitting the ~~trig~~ button manually.";
+        waveforms by setting ~~tsrc~~ to **Extern** and hitting the ~~trig~~ button manually. \
+        **Host** triggers at the start of every bar of the host song position.";
     pub const DESC: &'static str = r#"Signal Oscilloscope Probe
 
 This is a signal oscilloscope probe node, you can capture up to 3 signals.
@@ -99,6 +101,10 @@ You can also route in an external trigger to capture signals with the ~~trig~~
 input and ~~tsrc~~ set to **Extern**. Of course you can also hit the ~~trig~~ button
 manually to recapture a waveform.
 
+With ~~tsrc~~ set to **Host** the recording restarts at the beginning of
+every bar while the host (your DAW) is playing. Combine this with a ~~time~~
+of one bar length to watch tempo synchronized modulation.
+
 The inputs ~~off1~~, ~~off2~~ and ~~off3~~ define a vertical offset of the signal
 waveform in the scope view. Use ~~gain1~~, ~~gain2~~ and ~~gain3~~ for scaling
 the input signals up/down.
@@ -120,7 +126,7 @@ impl DspNode for Scope {
     fn process(
         &mut self,
         ctx: &mut dyn NodeAudioContext,
-        _ectx: &mut NodeExecContext,
+        ectx: &mut NodeExecContext,
         nctx: &NodeContext,
         atoms: &[SAtom],
         inputs: &[ProcBuf],
@@ -165,6 +171,10 @@ impl DspNode for Scope {
 
         let trigger_input = if tsrc.i() == 2 { trig } else { in1 };
         let trigger_disabled = tsrc.i() == 0;
+        let host_trigger = tsrc.i() == 3;
+        let srate = self.srate_ms * 1000.0;
+        let transport = &ectx.transport;
+        let bar_beats = transport.beats_per_bar();
 
         self.handle.set_threshold(if trigger_disabled { None } else { Some(threshold) });
 
@@ -182,11 +192,21 @@ impl DspNode for Scope {
                     self.idx = self.idx.saturating_add(copy_count);
                 }
 
-                if self.idx >= SCOPE_SAMPLES
-                    && (trigger_disabled || self.trig.check_trigger(trigger_input.read(frame)))
-                {
-                    self.frame_time = 0.0;
-                    self.idx = 0;
+                if self.idx >= SCOPE_SAMPLES {
+                    let triggered = if host_trigger {
+                        transport.starts_at(frame, srate, bar_beats)
+                    } else {
+                        trigger_disabled || self.trig.check_trigger(trigger_input.read(frame))
+                    };
+
+                    if triggered {
+                        self.frame_time = 0.0;
+                        self.idx = 0;
+
+                        if !trigger_disabled {
+                            ectx.emit_event(nctx.node_id, NodeEvent::ScopeTrigger);
+                        }
+                    }
                 }
             }
         } else {
@@ -214,10 +234,17 @@ impl DspNode for Scope {
                 }
 
                 if self.idx >= SCOPE_SAMPLES {
-                    if self.trig.check_trigger(trigger_input.read(frame)) {
+                    let triggered = if host_trigger {
+                        transport.starts_at(frame, srate, bar_beats)
+                    } else {
+                        self.trig.check_trigger(trigger_input.read(frame))
+                    };
+
+                    if triggered {
                         *cur_mm = [(-99999.0, 99999.0); 3];
                         self.frame_time = 0.0;
                         self.idx = 0;
+                        ectx.emit_event(nctx.node_id, NodeEvent::ScopeTrigger);
                     } else if trigger_disabled {
                         *cur_mm = [(-99999.0, 99999.0); 3];
                         self.idx = 0;
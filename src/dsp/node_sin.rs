@@ -4,7 +4,7 @@
 
 use crate::dsp::{
     denorm, denorm_offs, inp, out, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef,
-    NodeId, ProcBuf, SAtom,
+    NodeId, NodeState, ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 use synfx_dsp::fast_sin;
@@ -77,6 +77,8 @@ impl DspNode for Sin {
         self.phase = self.init_phase;
     }
 
+    node_state_by_clone!();
+
     #[inline]
     fn process(
         &mut self,
@@ -2,11 +2,12 @@
 // This file is a part of HexoDSP. Released under GPL-3.0-or-later.
 // See README.md and COPYING for details.
 
-use crate::dsp::tracker::TrackerBackend;
+use crate::dsp::tracker::{SeqRandomState, TrackerBackend};
 use crate::dsp::{
-    DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf, SAtom,
+    node_random_seed, DspNode, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId,
+    NodeState, ProcBuf, SAtom,
 };
-use crate::nodes::{NodeAudioContext, NodeExecContext};
+use crate::nodes::{NodeAudioContext, NodeEvent, NodeExecContext};
 use synfx_dsp::{Trigger, TriggerPhaseClock};
 
 use crate::dsp::MAX_BLOCK_SIZE;
@@ -18,21 +19,25 @@ macro_rules! fa_tseq_cmode {
             0 => "RowT",
             1 => "PatT",
             2 => "Phase",
+            3 => "Host",
             _ => "?",
         };
         write!($formatter, "{}", s)
     }};
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TSeqTime {
     clock: TriggerPhaseClock,
     trigger: Trigger,
+    /// The current row, for emitting [NodeEvent::TSeqRow].
+    row: Option<usize>,
 }
 
 /// A tracker based sequencer
 #[derive(Debug)]
 pub struct TSeq {
+    nid: NodeId,
     backend: Option<Box<TrackerBackend>>,
     srate: f64,
     time: Box<TSeqTime>,
@@ -41,25 +46,47 @@ pub struct TSeq {
 impl Clone for TSeq {
     fn clone(&self) -> Self {
         Self {
+            nid: self.nid,
             backend: None,
             srate: self.srate,
-            time: Box::new(TSeqTime { clock: TriggerPhaseClock::new(), trigger: Trigger::new() }),
+            time: Box::new(TSeqTime {
+                clock: TriggerPhaseClock::new(),
+                trigger: Trigger::new(),
+                row: None,
+            }),
         }
     }
 }
 
+/// The play position and gate randomness of a [TSeq], see [DspNode::save_state].
+/// The pattern data is not part of it, it's owned by the frontend.
+#[derive(Debug, Clone)]
+struct TSeqState {
+    time: TSeqTime,
+    random: Option<SeqRandomState>,
+}
+
+const TSEQ_SEED_SALT: u64 = 0x91234;
+
 impl TSeq {
     pub fn new(nid: &NodeId, node_global: &NodeGlobalRef) -> Self {
         let backend = if let Ok(mut handle) = node_global.lock() {
-            Some(Box::new(handle.get_tracker_backend(nid.instance() as usize)))
+            let mut backend = Box::new(handle.get_tracker_backend(nid.instance() as usize));
+            backend.reseed(node_random_seed(handle.random_seed(), TSEQ_SEED_SALT, nid));
+            Some(backend)
         } else {
             None
         };
 
         Self {
+            nid: *nid,
             backend,
             srate: 48000.0,
-            time: Box::new(TSeqTime { clock: TriggerPhaseClock::new(), trigger: Trigger::new() }),
+            time: Box::new(TSeqTime {
+                clock: TriggerPhaseClock::new(),
+                trigger: Trigger::new(),
+                row: None,
+            }),
         }
     }
 
@@ -69,6 +96,7 @@ impl TSeq {
              - **RowT**: Trigger = advance row\n\
              - **PatT**: Trigger = pattern rate\n\
              - **Phase**: Phase to pattern index\n\
+             - **Host**: Follow host song position, 4 rows per beat\n\
          \n";
     pub const trk1: &'static str = "Track 1 signal output";
     pub const trk2: &'static str = "Track 2 signal output";
@@ -101,6 +129,10 @@ With that the phase (**0..1**) signal on the ~~clock~~ input determines the
 exact play head position in the pattern. With this you just need to
 synchronize the phase generators for different sequencers.
 
+If you set ~~cmode~~ to **Host**, the sequencer follows the song position
+of the host (your DAW) and ignores the ~~clock~~ input. Each row is
+one 1/16 note long, so 4 rows make up one beat.
+
 For an idea how to chain multiple tracker sequencers, see the next page.
 
 This tracker provides 6 columns that each can have one of the following
@@ -182,14 +214,53 @@ impl DspNode for TSeq {
         self.backend = None;
         self.time.clock.reset();
         self.time.trigger.reset();
+        self.time.row = None;
+    }
+
+    fn set_random_seed(&mut self, seed: u64) {
+        if let Some(backend) = &mut self.backend {
+            backend.reseed(node_random_seed(seed, TSEQ_SEED_SALT, &self.nid));
+        }
+    }
+
+    fn save_state(&self) -> Option<NodeState> {
+        Some(NodeState::new(TSeqState {
+            time: (*self.time).clone(),
+            random: self.backend.as_ref().map(|backend| backend.random_state()),
+        }))
+    }
+
+    fn copy_state(&self, state: &mut NodeState) -> bool {
+        if let Some(state) = state.get_mut::<TSeqState>() {
+            state.time.clone_from(&self.time);
+            state.random = self.backend.as_ref().map(|backend| backend.random_state());
+            true
+        } else {
+            false
+        }
+    }
+
+    fn load_state(&mut self, state: &mut NodeState) -> bool {
+        if let Some(state) = state.get_mut::<TSeqState>() {
+            std::mem::swap(&mut *self.time, &mut state.time);
+
+            if let (Some(backend), Some(random)) = (&mut self.backend, state.random) {
+                state.random = Some(backend.random_state());
+                backend.set_random_state(random);
+            }
+
+            true
+        } else {
+            false
+        }
     }
 
     #[inline]
     fn process(
         &mut self,
         ctx: &mut dyn NodeAudioContext,
-        _ectx: &mut NodeExecContext,
-        _nctx: &NodeContext,
+        ectx: &mut NodeExecContext,
+        nctx: &NodeContext,
         atoms: &[SAtom],
         inputs: &[ProcBuf],
         outputs: &mut [ProcBuf],
@@ -224,10 +295,18 @@ impl DspNode for TSeq {
             let phase = match cmode {
                 0 => time.clock.next_phase(plen, clock.read(frame)) / plen,
                 1 => time.clock.next_phase(1.0, clock.read(frame)),
+                3 => ((ectx.transport.beat_at(frame, self.srate as f32) * 4.0) / plen)
+                    .rem_euclid(1.0),
                 _ => (clock.read(frame).abs() as f64).fract(),
             };
 
             *phase_out = phase as f32;
+
+            let row = ((phase * plen) as usize).min(plen as usize - 1);
+            if time.row != Some(row) {
+                time.row = Some(row);
+                ectx.emit_event(nctx.node_id, NodeEvent::TSeqRow { row });
+            }
         }
 
         let mut col_out: [f32; MAX_BLOCK_SIZE] = [0.0; MAX_BLOCK_SIZE];
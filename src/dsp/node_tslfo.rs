@@ -3,21 +3,44 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    DspNode, GraphAtomData, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf,
-    SAtom,
+    DspNode, GraphAtomData, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState,
+    ProcBuf, SAtom,
 };
-use crate::nodes::{NodeAudioContext, NodeExecContext};
+use crate::nodes::{host_note_div_beats, NodeAudioContext, NodeExecContext};
 use synfx_dsp::{TriSawLFO, Trigger};
 
+#[macro_export]
+macro_rules! fa_tslfo_sync {
+    ($formatter: expr, $v: expr, $denorm_v: expr) => {{
+        let s = match ($v.round() as usize) {
+            0 => "Off",
+            v => $crate::nodes::HOST_NOTE_DIVS.get(v - 1).map(|d| d.0).unwrap_or("?"),
+        };
+        write!($formatter, "{}", s)
+    }};
+}
+
+/// Calculates the TriSaw waveform at the given `phase` (0..1) with the reverse point `rev`.
+#[inline]
+fn tri_saw_at(phase: f64, rev: f64) -> f64 {
+    let rev = rev.clamp(0.0001, 0.999);
+    if phase < rev {
+        phase / rev
+    } else {
+        (1.0 - phase) / (1.0 - rev)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TsLFO {
     lfo: Box<TriSawLFO<f64>>,
     trig: Trigger,
+    srate: f32,
 }
 
 impl TsLFO {
     pub fn new(_nid: &NodeId, _node_global: &NodeGlobalRef) -> Self {
-        Self { lfo: Box::new(TriSawLFO::new()), trig: Trigger::new() }
+        Self { lfo: Box::new(TriSawLFO::new()), trig: Trigger::new(), srate: 44100.0 }
     }
 
     pub const time: &'static str = "The frequency or period time of the LFO, goes all the \
@@ -29,6 +52,9 @@ impl TsLFO {
         be (almost) a (reversed) saw tooth. Node: A perfect sawtooth can not be \
         achieved with this oscillator, as there will always be a minimal \
         rise/fall time.";
+    pub const sync: &'static str = "Synchronizes the LFO to the song position of the host. \
+        If not **Off**, one LFO period lasts the selected note length and ~~time~~ \
+        and ~~trig~~ are ignored.";
     pub const sig: &'static str = "The LFO output.";
     pub const DESC: &'static str = r#"TriSaw LFO
 
@@ -42,6 +68,10 @@ triangular to sawtooth waveforms using the ~~rev~~ parameter.
 
 Using the ~~trig~~ input you can reset the LFO phase, which allows to use it
 kind of like an envelope.
+
+With the ~~sync~~ setting the LFO follows the song position of the host
+(your DAW). The LFO phase is then locked to the beats, so that the LFO
+stays in time even when you jump around in your song.
 "#;
 
     pub fn graph_fun() -> Option<GraphFun> {
@@ -66,6 +96,7 @@ kind of like an envelope.
 
 impl DspNode for TsLFO {
     fn set_sample_rate(&mut self, srate: f32) {
+        self.srate = srate;
         self.lfo.set_sample_rate(srate as f64);
     }
 
@@ -74,24 +105,41 @@ impl DspNode for TsLFO {
         self.trig.reset();
     }
 
+    node_state_by_clone!(lfo, trig, srate);
+
     #[inline]
     fn process(
         &mut self,
         ctx: &mut dyn NodeAudioContext,
-        _ectx: &mut NodeExecContext,
+        ectx: &mut NodeExecContext,
         _nctx: &NodeContext,
-        _atoms: &[SAtom],
+        atoms: &[SAtom],
         inputs: &[ProcBuf],
         outputs: &mut [ProcBuf],
         ctx_vals: LedPhaseVals,
     ) {
-        use crate::dsp::{denorm, inp, out};
+        use crate::dsp::{at, denorm, inp, out};
 
         let time = inp::TsLFO::time(inputs);
         let trig = inp::TsLFO::trig(inputs);
         let rev = inp::TsLFO::rev(inputs);
+        let sync = at::TsLFO::sync(atoms);
         let out = out::TsLFO::sig(outputs);
 
+        if sync.i() > 0 {
+            let div_beats = host_note_div_beats(sync.i() - 1);
+            let transport = &ectx.transport;
+
+            for frame in 0..ctx.nframes() {
+                let phase = (transport.beat_at(frame, self.srate) / div_beats).rem_euclid(1.0);
+                let rev = denorm::TsLFO::rev(rev, frame) as f64;
+                out.write(frame, tri_saw_at(phase, rev) as f32);
+            }
+
+            ctx_vals[0].set(out.read(ctx.nframes() - 1));
+            return;
+        }
+
         let lfo = &mut *self.lfo;
 
         for frame in 0..ctx.nframes() {
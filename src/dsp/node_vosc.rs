@@ -3,8 +3,8 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{
-    DspNode, GraphAtomData, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, ProcBuf,
-    SAtom,
+    DspNode, GraphAtomData, GraphFun, LedPhaseVals, NodeContext, NodeGlobalRef, NodeId, NodeState,
+    ProcBuf, SAtom,
 };
 use crate::nodes::{NodeAudioContext, NodeExecContext};
 use synfx_dsp::{apply_distortion, Oversampling, VPSOscillator};
@@ -121,6 +121,8 @@ impl DspNode for VOsc {
         self.osc.reset();
     }
 
+    node_state_by_clone!(israte, osc, oversampling);
+
     #[inline]
     fn process(
         &mut self,
@@ -22,7 +22,7 @@ pub enum PatternColType {
 }
 
 pub use pattern::{PatternData, UIPatternModel};
-pub use sequencer::PatternSequencer;
+pub use sequencer::{PatternSequencer, SeqRandomState};
 
 #[derive(Debug, Clone, Copy)]
 pub enum PatternUpdateMsg {
@@ -143,6 +143,19 @@ impl TrackerBackend {
         }
     }
 
+    /// Restarts the random number generator of the sequencer with `seed`.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seq.reseed(seed);
+    }
+
+    pub fn random_state(&self) -> SeqRandomState {
+        self.seq.random_state()
+    }
+
+    pub fn set_random_state(&mut self, state: SeqRandomState) {
+        self.seq.set_random_state(state);
+    }
+
     pub fn pattern_len(&self) -> usize {
         self.seq.rows()
     }
@@ -6,6 +6,10 @@ use super::MAX_COLS;
 use super::MAX_PATTERN_LEN;
 use synfx_dsp::SplitMix64;
 
+/// The random number generator and the random values of the current rows
+/// of a [PatternSequencer], see [PatternSequencer::random_state].
+pub type SeqRandomState = (SplitMix64, [(usize, f64); MAX_COLS]);
+
 pub struct PatternSequencer {
     rows: usize,
     data: Vec<Vec<(f32, u8)>>,
@@ -56,6 +60,24 @@ impl PatternSequencer {
         }
     }
 
+    /// Restarts the random number generator for the gate probabilities with `seed`.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = SplitMix64::new(seed);
+        self.rand_vals = [(99999, 0.0); MAX_COLS];
+    }
+
+    /// Returns the state of the random number generator for the gate probabilities.
+    pub fn random_state(&self) -> SeqRandomState {
+        (self.rng, self.rand_vals)
+    }
+
+    /// Restores the state of the random number generator saved by
+    /// [PatternSequencer::random_state].
+    pub fn set_random_state(&mut self, state: SeqRandomState) {
+        self.rng = state.0;
+        self.rand_vals = state.1;
+    }
+
     pub fn set_rows(&mut self, rows: usize) {
         self.rows = rows;
     }
@@ -8,12 +8,17 @@ use crate::wblockdsp::*;
 use crate::{ScopeHandle, SharedFeedback, SharedFeedbackReader, SharedFeedbackWriter};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use synfx_dsp::SplitMix64;
 #[cfg(feature = "synfx-dsp-jit")]
 use synfx_dsp_jit::engine::{CodeEngine, CodeEngineBackend};
 
 /// Reference to a [crate::NodeGlobalData] instance.
 pub type NodeGlobalRef = Arc<Mutex<NodeGlobalData>>;
 
+/// The global random seed of a new [crate::NodeGlobalData] instance.
+/// See also [crate::NodeConfigurator::set_random_seed].
+pub const DEFAULT_RANDOM_SEED: u64 = 0x193a67f4a8a6d769;
+
 /// This structure holds any global state that may be shared among
 /// [crate::dsp::DspNode] instances.
 ///
@@ -43,6 +48,11 @@ pub struct NodeGlobalData {
     /// Holds the communication handles to send [crate::dsp::DynamicNode1x1] instances
     /// to their corresponding `Rust1x1` DSP nodes.
     dyn_nodes1x1: HashMap<usize, DynNodeHandle<Box<dyn DynamicNode1x1>>>,
+    /// The seed that the random number generators of all nodes derive their
+    /// seed from, see [crate::dsp::node_random_seed].
+    random_seed: u64,
+    /// Random number generator for the frontend, eg. for [crate::NodeConfigurator::rand_node_ids].
+    frontend_rng: SplitMix64,
 }
 
 impl NodeGlobalData {
@@ -56,9 +66,30 @@ impl NodeGlobalData {
             #[cfg(feature = "synfx-dsp-jit")]
             block_functions: HashMap::new(),
             dyn_nodes1x1: HashMap::new(),
+            random_seed: DEFAULT_RANDOM_SEED,
+            frontend_rng: SplitMix64::new(DEFAULT_RANDOM_SEED),
         }))
     }
 
+    /// Returns the global random seed. Nodes derive the seed of their random
+    /// number generators from this with [crate::dsp::node_random_seed].
+    pub fn random_seed(&self) -> u64 {
+        self.random_seed
+    }
+
+    /// Sets the global random seed and restarts the random number generator of the frontend.
+    /// Use [crate::NodeConfigurator::set_random_seed] to also reseed the running nodes.
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.random_seed = seed;
+        self.frontend_rng = SplitMix64::new(seed);
+    }
+
+    /// Returns the next random number of the frontend random number generator,
+    /// which is derived from the global random seed.
+    pub fn next_frontend_random(&mut self) -> u64 {
+        self.frontend_rng.next_u64()
+    }
+
     pub fn get_scope_handle(&mut self, scope: usize) -> Arc<ScopeHandle> {
         if let Some(handle) = self.scopes.get(&scope) {
             return handle.clone();
@@ -61,6 +61,7 @@ For a comprehensive list checkout the
 | Ctrl    | CQnt        | Control signal pitch quantizer |
 | Ctrl    | Quant       | Pitch signal quantizer |
 | Mod     | TSeq        | Tracker/pattern sequencer |
+| Mod     | Arp         | Arpeggiator, driven by the held MIDI notes |
 | Mod     | Ad          | Attack-Decay (AD) envelope |
 | Mod     | Adsr        | Attack-Decay-Sustain-Release (ADSR) envelope |
 | Mod     | TsLFO       | Tri/Saw waveform low frequency oscillator (LFO) |
@@ -69,6 +70,10 @@ For a comprehensive list checkout the
 | IO Util | Scope       | Oscilloscope for up to 3 channels |
 | IO Util | MidiP       | MIDI Pitch/Note input from plugin host, DAW or hardware |
 | IO Util | MidiCC      | MIDI CC input from plugin host, DAW or hardware |
+| IO Util | MpeP        | MPE note input with per note pitch bend, pressure and slide |
+| IO Util | MidiClk     | MIDI clock input with clock divider, run gate and tempo |
+| IO Util | MidiOut     | MIDI Note output to plugin host, DAW or hardware |
+| IO Util | MidiCCOut   | MIDI CC output to plugin host, DAW or hardware |
 | IO Util | ExtA - ExtF | Access to plugin parameter sets A to F |
 
 ## API Examples
@@ -230,6 +235,21 @@ These might work on Debian too:
     sudo apt install libjack0 libjack-dev
 ```
 
+## Running the Command Line Tool:
+
+The `hexodsp` binary loads patch files without any audio driver. It prints a summary
+of the nodes, edges and parameters of a patch, or renders it offline to a WAV file,
+optionally driven by a Standard MIDI File:
+
+```text
+    cargo run --release -- info my_patch.hxy
+    cargo run --release -- render --length 10 --srate 48000 my_patch.hxy out.wav
+    cargo run --release -- render --midi song.mid my_patch.hxy out.wav
+```
+
+The exit code is not 0 if a patch or one of it's samples could not be loaded,
+which is useful for checking patch files in CI.
+
 ## Running the Automated Testsuite:
 
 There exists an automate test suite for the DSP and backend code:
@@ -358,8 +378,12 @@ mod global;
 pub mod log;
 pub mod matrix;
 pub mod matrix_repr;
+pub mod midi_file;
+pub mod midi_player;
+pub mod midi_recorder;
 pub mod monitor;
 pub mod nodes;
+pub mod offline_render;
 pub mod sample_lib;
 pub mod scope_handle;
 pub mod shared_feedback;
@@ -371,7 +395,7 @@ pub use cell_dir::CellDir;
 pub use chain_builder::MatrixCellChain;
 pub use dsp::{DynNode1x1Context, DynamicNode1x1};
 pub use dsp::{NodeId, NodeInfo, ParamId, SAtom};
-pub use global::{NodeGlobalData, NodeGlobalRef};
+pub use global::{NodeGlobalData, NodeGlobalRef, DEFAULT_RANDOM_SEED};
 pub use log::log;
 pub use matrix::{Cell, Matrix};
 pub use matrix_repr::load_patch_from_file;
@@ -379,6 +403,7 @@ pub use matrix_repr::load_patch_from_mem;
 pub use matrix_repr::save_patch_to_file;
 pub use matrix_repr::save_patch_to_mem;
 pub use nodes::{new_node_engine, HxMidiEvent, NodeConfigurator, NodeExecutor};
+pub use offline_render::OfflineRenderer;
 pub use sample_lib::{SampleLibrary, SampleLoadError};
 pub use scope_handle::ScopeHandle;
 pub use shared_feedback::*;
@@ -398,12 +423,18 @@ impl<'a, 'b, 'c, 'd> nodes::NodeAudioContext for Context<'a, 'b, 'c, 'd> {
 
     #[inline]
     fn output(&mut self, channel: usize, frame: usize, v: f32) {
-        self.output[channel][frame] = v;
+        if let Some(out) = self.output.get_mut(channel) {
+            out[frame] = v;
+        }
     }
 
     #[inline]
     fn input(&mut self, channel: usize, frame: usize) -> f32 {
-        self.input[channel][frame]
+        if let Some(inp) = self.input.get(channel) {
+            inp[frame]
+        } else {
+            0.0
+        }
     }
 }
 
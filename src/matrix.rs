@@ -3,14 +3,19 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::tracker::PatternData;
-use crate::dsp::{NodeId, NodeInfo, ParamId, SAtom};
+use crate::dsp::{NodeId, NodeInfo, ParamId, RandNodeSelector, SAtom};
 use crate::matrix_repr::*;
+use crate::midi_recorder::MidiRecorder;
 pub use crate::monitor::MON_SIG_CNT;
 pub use crate::nodes::MinMaxMonitorSamples;
-use crate::nodes::{GraphEvent, HxMidiEvent, NodeConfigurator, NodeGraphOrdering, NodeProg};
+use crate::nodes::{
+    GraphEvent, HxMidiEvent, MidiMapping, MpeZone, NodeConfigurator, NodeEvent, NodeGraphOrdering,
+    NodeProg, NodeStates, SignalFault, VoiceStealing,
+};
 use crate::wblockdsp::{BlkJITCompileError, BlockFun, BlockFunSnapshot};
 pub use crate::CellDir;
 use crate::ScopeHandle;
+use crate::DEFAULT_RANDOM_SEED;
 
 use std::collections::{HashMap, HashSet};
 
@@ -469,6 +474,15 @@ pub trait MatrixObserver {
     fn update_all(&self);
     /// Called when a MIDI event was received.
     fn midi_event(&self, midi_ev: HxMidiEvent);
+    /// Called when the output guard detected a [SignalFault] in the outputs
+    /// of a node and reset it. See also [Matrix::set_output_guard].
+    fn node_fault(&self, _node_id: NodeId, _fault: SignalFault) {}
+    /// Called when a node emitted a [NodeEvent], like a `TSeq` that advanced
+    /// to the next row. See also [crate::nodes::NodeExecContext::emit_event].
+    fn node_event(&self, _node_id: NodeId, _ev: NodeEvent) {}
+    /// Called with the snapshot of the node states requested by
+    /// [Matrix::request_node_states].
+    fn node_states(&self, _states: NodeStates) {}
 }
 
 pub struct Matrix {
@@ -519,6 +533,10 @@ pub struct Matrix {
 
     /// A trait object that tracks changed on the [Matrix].
     observer: Option<Arc<dyn MatrixObserver>>,
+
+    /// Receives the recorded MIDI events in [Matrix::handle_graph_events],
+    /// see [Matrix::start_midi_recording].
+    midi_recorder: Option<MidiRecorder>,
 }
 
 unsafe impl Send for Matrix {}
@@ -537,6 +555,7 @@ impl Matrix {
             assigned_inputs: HashSet::new(),
             properties: HashMap::new(),
             observer: None,
+            midi_recorder: None,
             config,
             w,
             h,
@@ -569,6 +588,122 @@ impl Matrix {
         self.config.led_value_for(node_id)
     }
 
+    /// Sets the time in milliseconds the audio output is crossfaded when
+    /// the matrix is synchronized to the DSP thread. `0.0` disables the crossfade.
+    /// See also [NodeConfigurator::set_prog_crossfade].
+    pub fn set_prog_crossfade(&mut self, time_ms: f32) {
+        self.config.set_prog_crossfade(time_ms);
+    }
+
+    /// Sets the number of worker threads that execute independent nodes in parallel.
+    /// See also [NodeConfigurator::set_worker_threads].
+    pub fn set_worker_threads(&mut self, threads: usize) {
+        self.config.set_worker_threads(threads);
+    }
+
+    /// Sets the oversampling factor of a node and synchronizes the matrix.
+    /// Unsupported factors are ignored. See also [NodeConfigurator::set_oversampling].
+    pub fn set_oversampling(&mut self, node_id: NodeId, factor: usize) -> Result<(), MatrixError> {
+        if self.config.set_oversampling(node_id, factor) {
+            // XXX: Like set_param_modamt(), there is no structural change:
+            let obs = self.observer.take();
+            let ret = self.sync();
+            self.observer = obs;
+            ret
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the oversampling factor of a node, see [Matrix::set_oversampling].
+    pub fn oversampling(&self, node_id: &NodeId) -> usize {
+        self.config.oversampling(node_id)
+    }
+
+    /// Sets the global random seed and restarts the random number generators of all
+    /// nodes. The seed is saved with the [MatrixRepr].
+    /// See also [NodeConfigurator::set_random_seed].
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.config.set_random_seed(seed);
+        self.gen_counter += 1;
+    }
+
+    /// Returns the global random seed, see [Matrix::set_random_seed].
+    pub fn random_seed(&self) -> u64 {
+        self.config.random_seed()
+    }
+
+    /// Maps a MIDI CC to a parameter or setting, the mapping is saved with the
+    /// [MatrixRepr]. Returns false if the parameter can't be mapped.
+    /// The frontend parameter values are updated by [Matrix::handle_graph_events].
+    /// See also [NodeConfigurator::set_midi_mapping].
+    pub fn set_midi_mapping(&mut self, mapping: MidiMapping) -> bool {
+        self.config.set_midi_mapping(mapping)
+    }
+
+    /// Removes the MIDI CC mapping of the parameter.
+    pub fn remove_midi_mapping(&mut self, param: &ParamId) {
+        self.config.remove_midi_mapping(param);
+    }
+
+    /// Removes all MIDI CC mappings.
+    pub fn clear_midi_mappings(&mut self) {
+        self.config.clear_midi_mappings();
+    }
+
+    /// Returns the current MIDI CC mappings, see [Matrix::set_midi_mapping].
+    pub fn midi_mappings(&self) -> &[MidiMapping] {
+        self.config.midi_mappings()
+    }
+
+    /// Maps the next CC that is handled by [Matrix::handle_graph_events] to the parameter.
+    /// See also [NodeConfigurator::set_midi_learn].
+    pub fn set_midi_learn(&mut self, param: Option<ParamId>) {
+        self.config.set_midi_learn(param);
+    }
+
+    /// Returns the parameter that waits for a CC, see [Matrix::set_midi_learn].
+    pub fn midi_learn(&self) -> Option<ParamId> {
+        self.config.midi_learn()
+    }
+
+    /// Requests a snapshot of the internal state of the running nodes, which is passed
+    /// to [MatrixObserver::node_states] by [Matrix::handle_graph_events].
+    /// See also [NodeConfigurator::request_node_states].
+    pub fn request_node_states(&mut self) {
+        self.config.request_node_states();
+    }
+
+    /// Restores a snapshot of the node states in the running nodes.
+    /// See also [NodeConfigurator::load_node_states].
+    pub fn load_node_states(&mut self, states: NodeStates) {
+        self.config.load_node_states(states);
+    }
+
+    /// Returns `count` random [NodeId]s, drawn from a random number generator that
+    /// is derived from the global random seed. See also [NodeConfigurator::rand_node_ids].
+    pub fn rand_node_ids(&mut self, count: usize, sel: RandNodeSelector) -> Vec<NodeId> {
+        self.config.rand_node_ids(count, sel)
+    }
+
+    /// Enables the output guard against NaN and runaway signals, with a hard
+    /// limit for the `Out` nodes. See also [NodeConfigurator::set_output_guard].
+    pub fn set_output_guard(&mut self, limit: Option<f32>) {
+        self.config.set_output_guard(limit);
+    }
+
+    /// Enables or disables the per node CPU profiling.
+    /// See also [NodeConfigurator::set_cpu_profiling].
+    pub fn set_cpu_profiling(&mut self, enabled: bool) {
+        self.config.set_cpu_profiling(enabled);
+    }
+
+    /// Returns the CPU load of the given node as fraction of the DSP budget.
+    /// See also [NodeConfigurator::node_cpu_load].
+    pub fn node_cpu_load(&self, node_id: &NodeId) -> f32 {
+        self.config.node_cpu_load(node_id)
+    }
+
     pub fn update_filters(&mut self) {
         self.config.update_filters();
     }
@@ -807,6 +942,7 @@ impl Matrix {
         self.assigned_inputs.clear();
         self.saved_matrix = None;
         self.properties.clear();
+        self.config.clear_midi_mappings();
 
         self.config.delete_nodes();
         self.monitor_cell(Cell::empty(NodeId::Nop));
@@ -901,7 +1037,20 @@ impl Matrix {
 
         let properties = self.properties.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
 
-        MatrixRepr { cells, params, atoms, patterns, block_funs, properties, version: 2 }
+        let random_seed = self.random_seed();
+        let random_seed = if random_seed == DEFAULT_RANDOM_SEED { None } else { Some(random_seed) };
+
+        MatrixRepr {
+            cells,
+            params,
+            atoms,
+            patterns,
+            block_funs,
+            properties,
+            random_seed,
+            midi_map: self.config.midi_mappings().to_vec(),
+            version: 2,
+        }
     }
 
     /// Loads the matrix from a previously my [Matrix::to_repr]
@@ -914,6 +1063,9 @@ impl Matrix {
 
         let normalize_params = repr.version > 1;
 
+        // Before the nodes are created, so they are seeded properly:
+        self.set_random_seed(repr.random_seed.unwrap_or(DEFAULT_RANDOM_SEED));
+
         self.config.load_dumped_param_values(&repr.params[..], &repr.atoms[..], normalize_params);
 
         for (key, val) in repr.properties.iter() {
@@ -943,6 +1095,10 @@ impl Matrix {
 
         let ret = self.sync();
 
+        for mapping in repr.midi_map.iter() {
+            self.config.set_midi_mapping(*mapping);
+        }
+
         if let Some(obs) = &self.observer {
             obs.update_all();
         }
@@ -1047,6 +1203,17 @@ impl Matrix {
         }
     }
 
+    /// Like [Matrix::set_param], but applies the change at the sample `frame` of the
+    /// audio clock, optionally with a linear ramp over `ramp` frames.
+    /// See also [NodeConfigurator::set_param_at] and [Matrix::audio_clock].
+    pub fn set_param_at(&mut self, param: ParamId, at: SAtom, frame: u64, ramp: usize) {
+        self.config.set_param_at(param, at, frame, ramp);
+        self.gen_counter += 1;
+        if let Some(obs) = &self.observer {
+            obs.update_param(&param);
+        }
+    }
+
     /// Retrieve the modulation amount of the input parameter.
     pub fn get_param_modamt(&self, param: &ParamId) -> Option<f32> {
         self.config.get_param_modamt(param)
@@ -1373,7 +1540,8 @@ impl Matrix {
         let mut prog = self.config.rebuild_node_ports();
 
         for node_id in ordered_nodes.iter() {
-            self.config.add_prog_node(&mut prog, node_id);
+            let level = self.graph_ordering.node_level(node_id).unwrap_or(0);
+            self.config.add_prog_node_with_level(&mut prog, node_id, level);
         }
 
         for edge in self.edges.iter() {
@@ -1455,6 +1623,10 @@ impl Matrix {
     /// eg. with [Matrix::place] and want to publish the
     /// changes to the DSP thread.
     ///
+    /// Only the changes since the last sync are applied to the
+    /// running DSP graph, so the nodes that were not touched keep their
+    /// state. See also [NodeConfigurator::upload_prog].
+    ///
     /// This method might return an error, for instance if the
     /// DSP graph topology contains cycles or has other errors.
     ///
@@ -1518,6 +1690,73 @@ impl Matrix {
         self.config.inject_midi_event(midi_ev);
     }
 
+    /// Schedules a [HxMidiEvent] at the sample `frame` of the audio clock.
+    /// See also [NodeConfigurator::schedule_midi_event].
+    pub fn schedule_midi_event(&mut self, frame: u64, midi_ev: HxMidiEvent) -> bool {
+        self.config.schedule_midi_event(frame, midi_ev)
+    }
+
+    /// Drops the scheduled MIDI events that are not yet due.
+    /// See also [NodeConfigurator::clear_scheduled_midi].
+    pub fn clear_scheduled_midi(&mut self) {
+        self.config.clear_scheduled_midi();
+    }
+
+    /// Enables or disables the MIDI recording of the DSP backend.
+    /// See also [NodeConfigurator::set_midi_recording] and [Matrix::start_midi_recording].
+    pub fn set_midi_recording(&mut self, enabled: bool) {
+        self.config.set_midi_recording(enabled);
+    }
+
+    /// Starts the `recorder` and keeps it, to pass the recorded MIDI events from
+    /// [Matrix::handle_graph_events] to it. A previously running recording is dropped.
+    /// See also [MidiRecorder].
+    pub fn start_midi_recording(&mut self, mut recorder: MidiRecorder) {
+        recorder.start(&mut self.config);
+        self.midi_recorder = Some(recorder);
+    }
+
+    /// Handles the pending graph events and returns the stopped [MidiRecorder],
+    /// if a recording was started with [Matrix::start_midi_recording].
+    pub fn stop_midi_recording(&mut self) -> Option<MidiRecorder> {
+        self.handle_graph_events();
+
+        let mut recorder = self.midi_recorder.take()?;
+        recorder.stop(&mut self.config);
+        Some(recorder)
+    }
+
+    /// Returns the [MidiRecorder] that was started with [Matrix::start_midi_recording].
+    pub fn midi_recorder(&self) -> Option<&MidiRecorder> {
+        self.midi_recorder.as_ref()
+    }
+
+    /// Returns the number of sample frames the DSP backend has processed so far.
+    /// See also [NodeConfigurator::audio_clock].
+    pub fn audio_clock(&self) -> u64 {
+        self.config.audio_clock()
+    }
+
+    /// Returns the sample rate of the DSP backend.
+    pub fn sample_rate(&self) -> f32 {
+        self.config.sample_rate()
+    }
+
+    /// Configures the voice allocation of the incoming MIDI notes.
+    /// Place one `MidiP` node per voice and set it's `voice` setting to the voice
+    /// number, to build a polyphonic patch.
+    ///
+    /// See also [NodeConfigurator::set_voice_allocation].
+    pub fn set_voice_allocation(&mut self, voices: usize, stealing: VoiceStealing) {
+        self.config.set_voice_allocation(voices, stealing);
+    }
+
+    /// Configures the MPE zone of the incoming MIDI. Place one `MpeP` node per slot
+    /// to build an MPE voice bank. See also [NodeConfigurator::set_mpe_zone].
+    pub fn set_mpe_zone(&mut self, zone: Option<MpeZone>) {
+        self.config.set_mpe_zone(zone);
+    }
+
     /// Handles events from the DSP graph. Such as MIDI events for MIDI learn
     /// functionality! Call this regularily (every UI frame) if you want to
     /// have MIDI learn to work and receive events such as MIDI events via the [MatrixObserver].
@@ -1525,10 +1764,35 @@ impl Matrix {
         while let Some(event) = self.config.next_event() {
             match event {
                 GraphEvent::MIDI(midi_ev) => {
+                    let changed = self.config.update_midi_mapped_params(&midi_ev);
+
                     if let Some(obs) = &self.observer {
+                        for param in changed.iter() {
+                            obs.update_param(param);
+                        }
                         obs.midi_event(midi_ev);
                     }
                 }
+                GraphEvent::NodeFault(node_id, fault) => {
+                    if let Some(obs) = &self.observer {
+                        obs.node_fault(node_id, fault);
+                    }
+                }
+                GraphEvent::Node(node_id, ev) => {
+                    if let Some(obs) = &self.observer {
+                        obs.node_event(node_id, ev);
+                    }
+                }
+                GraphEvent::NodeStates(states) => {
+                    if let Some(obs) = &self.observer {
+                        obs.node_states(states);
+                    }
+                }
+                GraphEvent::RecordedMIDI(frame, midi_ev) => {
+                    if let Some(recorder) = &mut self.midi_recorder {
+                        recorder.record(frame, midi_ev);
+                    }
+                }
             }
         }
     }
@@ -1688,7 +1952,7 @@ mod tests {
         assert_eq!(prog.prog[0].to_string(), "Op(i=0 out=(0-1|1) in=(0-3|0) at=(0-0) mod=(0-0))");
         assert_eq!(
             prog.prog[1].to_string(),
-            "Op(i=1 out=(1-1|0) in=(3-6|1) at=(0-1) mod=(0-0) cpy=(o0 => i3))"
+            "Op(i=1 out=(1-1|0) in=(3-6|1) at=(0-2) mod=(0-0) cpy=(o0 => i3))"
         );
     }
 
@@ -1721,7 +1985,7 @@ mod tests {
         assert_eq!(prog.prog[0].to_string(), "Op(i=2 out=(2-3|1) in=(6-9|0) at=(0-0) mod=(0-0))");
         assert_eq!(
             prog.prog[1].to_string(),
-            "Op(i=3 out=(3-3|0) in=(9-12|1) at=(0-1) mod=(0-0) cpy=(o2 => i9))"
+            "Op(i=3 out=(3-3|0) in=(9-12|1) at=(0-2) mod=(0-0) cpy=(o2 => i9))"
         );
     }
 
@@ -3,6 +3,7 @@
 // See README.md and COPYING for details.
 
 use crate::dsp::{NodeId, ParamId, SAtom};
+use crate::nodes::{MidiMapCurve, MidiMapping};
 use crate::wblockdsp::BlockFunSnapshot;
 use serde_json::{json, Value};
 
@@ -189,6 +190,11 @@ pub struct MatrixRepr {
     pub patterns: Vec<Option<PatternRepr>>,
     pub properties: Vec<(String, SAtom)>,
     pub block_funs: Vec<Option<BlockFunSnapshot>>,
+    /// The global random seed, `None` stands for [crate::DEFAULT_RANDOM_SEED].
+    /// See also [crate::Matrix::set_random_seed].
+    pub random_seed: Option<u64>,
+    /// The MIDI CC mappings, see [crate::Matrix::set_midi_mapping].
+    pub midi_map: Vec<MidiMapping>,
     pub version: i64,
 }
 
@@ -293,7 +299,17 @@ impl MatrixRepr {
         let properties = vec![];
         let block_funs = vec![];
 
-        Self { cells, params, atoms, patterns, block_funs, properties, version: 2 }
+        Self {
+            cells,
+            params,
+            atoms,
+            patterns,
+            block_funs,
+            properties,
+            random_seed: None,
+            midi_map: vec![],
+            version: 2,
+        }
     }
 
     pub fn write_to_mem(&mut self) -> Vec<u8> {
@@ -338,6 +354,40 @@ impl MatrixRepr {
         MatrixRepr::deserialize(s)
     }
 
+    /// Rewrites the paths of the audio samples in the patch, that can't be found
+    /// relative to the current working directory. They are searched relative to
+    /// each of the `search_dirs`, usually the directory of the patch file.
+    ///
+    /// Returns the sample paths that could not be found anywhere.
+    pub fn resolve_sample_paths(&mut self, search_dirs: &[&std::path::Path]) -> Vec<String> {
+        let mut missing = vec![];
+
+        for (_, atom) in self.atoms.iter_mut() {
+            let path = if let SAtom::AudioSample((path, _)) = atom {
+                path.clone()
+            } else {
+                continue;
+            };
+
+            if path.is_empty() || std::path::Path::new(&path).exists() {
+                continue;
+            }
+
+            let found = search_dirs
+                .iter()
+                .map(|dir| dir.join(&path))
+                .find(|candidate| candidate.exists())
+                .and_then(|candidate| candidate.to_str().map(|s| s.to_string()));
+
+            match found {
+                Some(resolved) => *atom = SAtom::audio_unloaded(&resolved),
+                None => missing.push(path),
+            }
+        }
+
+        missing
+    }
+
     pub fn deserialize(s: &str) -> Result<MatrixRepr, MatrixDeserError> {
         let v: Value = serde_json::from_str(s)?;
 
@@ -423,6 +473,36 @@ impl MatrixRepr {
             }
         }
 
+        m.random_seed = v["random_seed"].as_u64();
+
+        let midi_map = &v["midi_map"];
+        if let Value::Array(midi_map) = midi_map {
+            for v in midi_map.iter() {
+                let node_id = deserialize_node_id(v, 2, 3)?;
+                let param_id = node_id.inp_param(v[4].as_str().unwrap_or(""));
+
+                if let Some(param_id) = param_id {
+                    let curve = MidiMapCurve::from_name(v[7].as_str().unwrap_or(""))
+                        .unwrap_or(MidiMapCurve::Lin);
+
+                    m.midi_map.push(
+                        MidiMapping::new(
+                            v[0].as_u64().unwrap_or(0) as u8,
+                            v[1].as_u64().unwrap_or(0) as u8,
+                            param_id,
+                        )
+                        .range(
+                            v[5].as_f64().unwrap_or(0.0) as f32,
+                            v[6].as_f64().unwrap_or(1.0) as f32,
+                        )
+                        .curve(curve),
+                    );
+                } else {
+                    return Err(MatrixDeserError::UnknownParamId(v.to_string()));
+                }
+            }
+        }
+
         Ok(m)
     }
 
@@ -502,6 +582,30 @@ impl MatrixRepr {
 
         v["block_funs"] = block_funs;
 
+        if let Some(seed) = self.random_seed {
+            v["random_seed"] = json!(seed);
+        }
+
+        if !self.midi_map.is_empty() {
+            let mut midi_map = json!([]);
+            if let Value::Array(midi_map) = &mut midi_map {
+                for m in self.midi_map.iter() {
+                    midi_map.push(json!([
+                        m.channel,
+                        m.cc,
+                        m.param.node_id().name(),
+                        m.param.node_id().instance(),
+                        m.param.name(),
+                        m.min,
+                        m.max,
+                        m.curve.name(),
+                    ]));
+                }
+            }
+
+            v["midi_map"] = midi_map;
+        }
+
         v.to_string()
     }
 }
@@ -581,7 +685,7 @@ mod tests {
         let s = mr.serialize();
 
         assert_eq!(s,
-            "{\"VERSION\":2,\"atoms\":[[\"out\",0,\"mono\",[\"i\",0]]],\"block_funs\":[null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null],\"cells\":[[\"sin\",2,0,0,[-1,-1,-1],[-1,\"sig\",-1]],[\"out\",0,1,0,[-1,\"ch1\",-1],[-1,-1,-1]]],\"params\":[[\"out\",0,\"ch1\",0.0],[\"out\",0,\"ch2\",0.0],[\"sin\",0,\"det\",0.0],[\"sin\",1,\"det\",0.0],[\"sin\",2,\"det\",0.0],[\"sin\",0,\"freq\",440.0],[\"sin\",1,\"freq\",440.0],[\"sin\",2,\"freq\",220.0],[\"sin\",0,\"pm\",0.0],[\"sin\",1,\"pm\",0.0],[\"sin\",2,\"pm\",0.0],[\"out\",0,\"vol\",0.9999997615814209]],\"patterns\":[null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null],\"props\":[]}");
+            "{\"VERSION\":2,\"atoms\":[[\"out\",0,\"bank\",[\"i\",0]],[\"out\",0,\"mono\",[\"i\",0]]],\"block_funs\":[null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null],\"cells\":[[\"sin\",2,0,0,[-1,-1,-1],[-1,\"sig\",-1]],[\"out\",0,1,0,[-1,\"ch1\",-1],[-1,-1,-1]]],\"params\":[[\"out\",0,\"ch1\",0.0],[\"out\",0,\"ch2\",0.0],[\"sin\",0,\"det\",0.0],[\"sin\",1,\"det\",0.0],[\"sin\",2,\"det\",0.0],[\"sin\",0,\"freq\",440.0],[\"sin\",1,\"freq\",440.0],[\"sin\",2,\"freq\",220.0],[\"sin\",0,\"pm\",0.0],[\"sin\",1,\"pm\",0.0],[\"sin\",2,\"pm\",0.0],[\"out\",0,\"vol\",0.9999997615814209]],\"patterns\":[null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null,null],\"props\":[]}");
         let mut mr2 = MatrixRepr::deserialize(&s).unwrap();
 
         let s2 = mr2.serialize();
@@ -0,0 +1,490 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+/*! Reading of Standard MIDI Files (SMF).
+
+[MidiFile] parses format 0 and 1 files with a ticks per quarter note time base.
+The channel messages that [HxMidiEvent] can represent are kept, together with
+the tempo changes. [MidiFile::to_bytes] and [MidiFile::write_to_file] write
+the file back, for instance after recording it with the
+[crate::midi_recorder::MidiRecorder]. [MidiFile::timed_events] merges all tracks and converts the
+ticks to seconds using the tempo map, which is what the [crate::OfflineRenderer]
+needs to render a patch against a MIDI file.
+
+```
+use hexodsp::midi_file::MidiFile;
+use hexodsp::HxMidiEvent;
+
+let smf = MidiFile::parse(&[
+    b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96,
+    b'M', b'T', b'r', b'k', 0, 0, 0, 12,
+    0x00, 0x90, 60, 127,   // Note on at tick 0
+    0x60, 0x80, 60, 0,     // Note off one quarter note later
+    0x00, 0xFF, 0x2F, 0x00,
+]).unwrap();
+
+let events = smf.timed_events();
+assert_eq!(events.len(), 2);
+// The default tempo is 120 BPM:
+assert_eq!(events[1].0, 0.5);
+assert!(matches!(events[1].1, HxMidiEvent::NoteOff { channel: 0, note: 60 }));
+```
+*/
+
+use crate::nodes::HxMidiEvent;
+
+/// The tempo of a MIDI file until the first tempo change, in microseconds per quarter note.
+pub const SMF_DEFAULT_TEMPO: u32 = 500000;
+
+/// Returned by [MidiFile::parse] and [MidiFile::read_from_file].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MidiFileError {
+    IO(String),
+    InvalidHeader,
+    UnsupportedFormat(u16),
+    SmpteTiming,
+    /// The track with the given index contains a malformed event.
+    InvalidTrack(usize),
+    UnexpectedEnd,
+}
+
+/// An event in a track of a [MidiFile].
+#[derive(Debug, Clone, Copy)]
+pub enum SmfEvent {
+    Midi(HxMidiEvent),
+    /// A tempo change in microseconds per quarter note.
+    Tempo(u32),
+}
+
+/// An [SmfEvent] at an absolute tick position in it's track.
+#[derive(Debug, Clone, Copy)]
+pub struct SmfTrackEvent {
+    pub tick: u64,
+    pub event: SmfEvent,
+}
+
+/// A parsed Standard MIDI File.
+#[derive(Debug, Clone)]
+pub struct MidiFile {
+    /// The SMF format, 0 or 1.
+    pub format: u16,
+    pub ticks_per_quarter: u16,
+    /// The events of each track, sorted by tick.
+    pub tracks: Vec<Vec<SmfTrackEvent>>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn u8(&mut self) -> Result<u8, MidiFileError> {
+        let b = *self.data.get(self.pos).ok_or(MidiFileError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], MidiFileError> {
+        if self.pos + len > self.data.len() {
+            return Err(MidiFileError::UnexpectedEnd);
+        }
+
+        let bytes = &self.data[self.pos..(self.pos + len)];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, MidiFileError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, MidiFileError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Reads a variable length quantity, which has at most 4 bytes.
+    fn vlq(&mut self) -> Result<u32, MidiFileError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let b = self.u8()?;
+            value = (value << 7) | (b & 0x7F) as u32;
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(MidiFileError::UnexpectedEnd)
+    }
+}
+
+fn parse_track(data: &[u8], track_idx: usize) -> Result<Vec<SmfTrackEvent>, MidiFileError> {
+    let mut rd = Reader::new(data);
+    let mut events = vec![];
+    let mut tick: u64 = 0;
+    let mut running_status: Option<u8> = None;
+
+    while !rd.at_end() {
+        tick += rd.vlq()? as u64;
+
+        let mut status = rd.u8()?;
+        let first_data = if status < 0x80 {
+            let data = status;
+            status = running_status.ok_or(MidiFileError::InvalidTrack(track_idx))?;
+            Some(data)
+        } else {
+            None
+        };
+
+        match status {
+            0xFF => {
+                let typ = rd.u8()?;
+                let len = rd.vlq()? as usize;
+                let data = rd.bytes(len)?;
+
+                match typ {
+                    0x2F => break,
+                    0x51 if len == 3 => {
+                        let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                        events.push(SmfTrackEvent { tick, event: SmfEvent::Tempo(tempo) });
+                    }
+                    _ => (),
+                }
+            }
+            0xF0 | 0xF7 => {
+                // System exclusive messages are skipped:
+                let len = rd.vlq()? as usize;
+                rd.bytes(len)?;
+                running_status = None;
+            }
+            0x80..=0xEF => {
+                running_status = Some(status);
+
+                let channel = status & 0x0F;
+                let d1 = match first_data {
+                    Some(d) => d,
+                    None => rd.u8()?,
+                };
+                // Program change and channel pressure only have one data byte:
+                let d2 = match status & 0xF0 {
+                    0xC0 | 0xD0 => 0,
+                    _ => rd.u8()?,
+                };
+
+                let ev = match status & 0xF0 {
+                    0x80 => Some(HxMidiEvent::NoteOff { channel, note: d1 }),
+                    0x90 if d2 == 0 => Some(HxMidiEvent::NoteOff { channel, note: d1 }),
+                    0x90 => Some(HxMidiEvent::NoteOn { channel, note: d1, vel: d2 as f32 / 127.0 }),
+                    0xA0 => Some(HxMidiEvent::PolyPressure {
+                        channel,
+                        note: d1,
+                        value: d2 as f32 / 127.0,
+                    }),
+                    0xB0 => Some(HxMidiEvent::CC { channel, cc: d1, value: d2 as f32 / 127.0 }),
+                    0xC0 => Some(HxMidiEvent::ProgramChange { channel, program: d1 }),
+                    0xD0 => {
+                        Some(HxMidiEvent::ChannelPressure { channel, value: d1 as f32 / 127.0 })
+                    }
+                    0xE0 => Some(HxMidiEvent::pitch_bend(channel, (d2 as u16) << 7 | d1 as u16)),
+                    _ => None,
+                };
+
+                if let Some(ev) = ev {
+                    events.push(SmfTrackEvent { tick, event: SmfEvent::Midi(ev) });
+                }
+            }
+            _ => return Err(MidiFileError::InvalidTrack(track_idx)),
+        }
+    }
+
+    Ok(events)
+}
+
+fn write_vlq(data: &mut Vec<u8>, value: u32) {
+    // Only 28 bits fit into the 4 bytes of a variable length quantity:
+    let value = value.min(0x0FFF_FFFF);
+
+    let mut shift = 21;
+    while shift > 0 && (value >> shift) == 0 {
+        shift -= 7;
+    }
+
+    while shift > 0 {
+        data.push(((value >> shift) & 0x7F) as u8 | 0x80);
+        shift -= 7;
+    }
+    data.push((value & 0x7F) as u8);
+}
+
+/// Converts a value in the range 0.0 to 1.0 to a 7-bit MIDI data byte.
+fn data_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 127.0).round() as u8
+}
+
+/// Writes a sequence of CC messages, the ones after the first with a delta time of 0.
+fn write_ccs(data: &mut Vec<u8>, channel: u8, ccs: &[(u8, u8)]) {
+    for (i, (cc, value)) in ccs.iter().enumerate() {
+        if i > 0 {
+            data.push(0x00);
+        }
+        data.extend_from_slice(&[0xB0 | (channel & 0x0F), *cc, *value]);
+    }
+}
+
+/// Writes the channel message of `ev`. The high resolution controller events
+/// are written as their sequence of CC messages. The system realtime events
+/// can't be stored in a MIDI file and are skipped.
+fn write_midi_event(data: &mut Vec<u8>, ev: HxMidiEvent) {
+    match ev {
+        HxMidiEvent::NoteOn { channel, note, vel } => {
+            // A velocity of 0 would be read as note off:
+            data.extend_from_slice(&[0x90 | (channel & 0x0F), note & 0x7F, data_byte(vel).max(1)]);
+        }
+        HxMidiEvent::NoteOff { channel, note } => {
+            data.extend_from_slice(&[0x80 | (channel & 0x0F), note & 0x7F, 0]);
+        }
+        HxMidiEvent::PolyPressure { channel, note, value } => {
+            data.extend_from_slice(&[0xA0 | (channel & 0x0F), note & 0x7F, data_byte(value)]);
+        }
+        HxMidiEvent::CC { channel, cc, value } => {
+            data.extend_from_slice(&[0xB0 | (channel & 0x0F), cc & 0x7F, data_byte(value)]);
+        }
+        HxMidiEvent::ProgramChange { channel, program } => {
+            data.extend_from_slice(&[0xC0 | (channel & 0x0F), program & 0x7F]);
+        }
+        HxMidiEvent::ChannelPressure { channel, value } => {
+            data.extend_from_slice(&[0xD0 | (channel & 0x0F), data_byte(value)]);
+        }
+        HxMidiEvent::PitchBend { channel, value } => {
+            let value = value.clamp(-1.0, 1.0);
+            let bend = if value < 0.0 { value * 8192.0 } else { value * 8191.0 };
+            let bend = (bend.round() + 8192.0) as u16;
+            data.extend_from_slice(&[
+                0xE0 | (channel & 0x0F),
+                (bend & 0x7F) as u8,
+                ((bend >> 7) & 0x7F) as u8,
+            ]);
+        }
+        HxMidiEvent::CC14 { channel, cc, value } => {
+            let v = (value.clamp(0.0, 1.0) * 16383.0).round() as u16;
+            let cc = cc & 0x1F;
+            write_ccs(data, channel, &[(cc, (v >> 7) as u8), (cc + 32, (v & 0x7F) as u8)]);
+        }
+        HxMidiEvent::NRPN { channel, param, value }
+        | HxMidiEvent::RPN { channel, param, value } => {
+            let (cc_msb, cc_lsb) =
+                if let HxMidiEvent::NRPN { .. } = ev { (99, 98) } else { (101, 100) };
+            let v = (value.clamp(0.0, 1.0) * 16383.0).round() as u16;
+            write_ccs(
+                data,
+                channel,
+                &[
+                    (cc_msb, ((param >> 7) & 0x7F) as u8),
+                    (cc_lsb, (param & 0x7F) as u8),
+                    (6, (v >> 7) as u8),
+                    (38, (v & 0x7F) as u8),
+                ],
+            );
+        }
+        HxMidiEvent::Clock
+        | HxMidiEvent::Start
+        | HxMidiEvent::Stop
+        | HxMidiEvent::Continue
+        | HxMidiEvent::SongPosition { .. } => {
+            // Keep the delta time of the skipped event with an empty text meta event:
+            data.extend_from_slice(&[0xFF, 0x01, 0x00]);
+        }
+    }
+}
+
+impl MidiFile {
+    /// Parses a Standard MIDI File from memory.
+    pub fn parse(data: &[u8]) -> Result<Self, MidiFileError> {
+        let mut rd = Reader::new(data);
+
+        if rd.bytes(4)? != b"MThd" {
+            return Err(MidiFileError::InvalidHeader);
+        }
+
+        let header_len = rd.u32()? as usize;
+        if header_len < 6 {
+            return Err(MidiFileError::InvalidHeader);
+        }
+
+        let format = rd.u16()?;
+        let ntracks = rd.u16()? as usize;
+        let division = rd.u16()?;
+        rd.bytes(header_len - 6)?;
+
+        if format > 1 {
+            return Err(MidiFileError::UnsupportedFormat(format));
+        }
+        if division & 0x8000 != 0 {
+            return Err(MidiFileError::SmpteTiming);
+        }
+
+        let mut tracks = vec![];
+        while tracks.len() < ntracks && !rd.at_end() {
+            let chunk_type = rd.bytes(4)?;
+            let len = rd.u32()? as usize;
+            let chunk = rd.bytes(len)?;
+
+            // Unknown chunk types must be ignored:
+            if chunk_type == b"MTrk" {
+                tracks.push(parse_track(chunk, tracks.len())?);
+            }
+        }
+
+        Ok(Self { format, ticks_per_quarter: division.max(1), tracks })
+    }
+
+    /// Reads and parses a Standard MIDI File from `path`.
+    pub fn read_from_file(path: &str) -> Result<Self, MidiFileError> {
+        let data = std::fs::read(path).map_err(|e| MidiFileError::IO(format!("{}", e)))?;
+        Self::parse(&data)
+    }
+
+    /// Serializes the file as Standard MIDI File. Each track is terminated by an
+    /// end of track event after it's last event.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = b"MThd".to_vec();
+        data.extend_from_slice(&6u32.to_be_bytes());
+        data.extend_from_slice(&self.format.to_be_bytes());
+        data.extend_from_slice(&(self.tracks.len() as u16).to_be_bytes());
+        data.extend_from_slice(&self.ticks_per_quarter.to_be_bytes());
+
+        for track in self.tracks.iter() {
+            let mut chunk = vec![];
+            let mut last_tick = 0;
+
+            for ev in track.iter() {
+                write_vlq(&mut chunk, ev.tick.saturating_sub(last_tick) as u32);
+                last_tick = last_tick.max(ev.tick);
+
+                match ev.event {
+                    SmfEvent::Midi(midi_ev) => write_midi_event(&mut chunk, midi_ev),
+                    SmfEvent::Tempo(tempo) => {
+                        let t = tempo.to_be_bytes();
+                        chunk.extend_from_slice(&[0xFF, 0x51, 0x03, t[1], t[2], t[3]]);
+                    }
+                }
+            }
+
+            chunk.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+
+            data.extend_from_slice(b"MTrk");
+            data.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            data.extend_from_slice(&chunk[..]);
+        }
+
+        data
+    }
+
+    /// Writes the file as Standard MIDI File to `path`, see [MidiFile::to_bytes].
+    pub fn write_to_file(&self, path: &str) -> Result<(), MidiFileError> {
+        std::fs::write(path, self.to_bytes()).map_err(|e| MidiFileError::IO(format!("{}", e)))
+    }
+
+    /// Returns the events of all tracks, sorted by tick. Events at the same
+    /// tick keep the order of their tracks.
+    pub fn merged_events(&self) -> Vec<SmfTrackEvent> {
+        let mut events: Vec<SmfTrackEvent> = self.tracks.iter().flatten().copied().collect();
+        events.sort_by_key(|ev| ev.tick);
+        events
+    }
+
+    /// Returns the MIDI events of all tracks with their time in seconds,
+    /// calculated from the tempo changes in the file.
+    pub fn timed_events(&self) -> Vec<(f64, HxMidiEvent)> {
+        let mut ret = vec![];
+        self.for_each_in_seconds(|secs, ev| {
+            if let SmfEvent::Midi(ev) = ev {
+                ret.push((secs, ev));
+            }
+        });
+        ret
+    }
+
+    /// Returns the time of the last event in seconds.
+    pub fn duration_secs(&self) -> f64 {
+        let mut duration = 0.0;
+        self.for_each_in_seconds(|secs, _| duration = secs);
+        duration
+    }
+
+    /// Converts a tick position to seconds, using the tempo changes in the file.
+    pub fn ticks_to_secs(&self, tick: u64) -> f64 {
+        let mut tempo = SMF_DEFAULT_TEMPO;
+        let mut last_tick = 0;
+        let mut last_secs = 0.0;
+
+        for ev in self.merged_events() {
+            if ev.tick > tick {
+                break;
+            }
+
+            if let SmfEvent::Tempo(t) = ev.event {
+                last_secs += self.ticks_len_secs(ev.tick - last_tick, tempo);
+                last_tick = ev.tick;
+                tempo = t;
+            }
+        }
+
+        last_secs + self.ticks_len_secs(tick - last_tick, tempo)
+    }
+
+    /// Converts a time in seconds to the tick position, using the tempo changes in the file.
+    pub fn secs_to_ticks(&self, secs: f64) -> u64 {
+        let mut tempo = SMF_DEFAULT_TEMPO;
+        let mut last_tick = 0;
+        let mut last_secs = 0.0;
+
+        for ev in self.merged_events() {
+            if let SmfEvent::Tempo(t) = ev.event {
+                let ev_secs = last_secs + self.ticks_len_secs(ev.tick - last_tick, tempo);
+                if ev_secs > secs {
+                    break;
+                }
+
+                last_secs = ev_secs;
+                last_tick = ev.tick;
+                tempo = t;
+            }
+        }
+
+        let tick_len = self.ticks_len_secs(1, tempo);
+        last_tick + ((secs - last_secs).max(0.0) / tick_len).round() as u64
+    }
+
+    fn ticks_len_secs(&self, ticks: u64, tempo: u32) -> f64 {
+        (ticks as f64 * tempo as f64) / (self.ticks_per_quarter as f64 * 1000000.0)
+    }
+
+    fn for_each_in_seconds<F: FnMut(f64, SmfEvent)>(&self, mut f: F) {
+        let mut tempo = SMF_DEFAULT_TEMPO;
+        let mut last_tick = 0;
+        let mut last_secs = 0.0;
+
+        for ev in self.merged_events() {
+            last_secs += self.ticks_len_secs(ev.tick - last_tick, tempo);
+            last_tick = ev.tick;
+
+            if let SmfEvent::Tempo(t) = ev.event {
+                tempo = t;
+            }
+
+            f(last_secs, ev.event);
+        }
+    }
+}
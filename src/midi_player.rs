@@ -0,0 +1,354 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+/*! Realtime playback of Standard MIDI Files into the DSP graph.
+
+The [MidiPlayer] runs on the frontend thread. It schedules the events of a
+[MidiFile] a bit ahead of the audio clock of the [crate::nodes::NodeExecutor],
+which feeds them into the graph at their exact sample frame, like the events
+passed to [crate::nodes::NodeExecutor::feed_midi_events_from]. This way patches
+can be auditioned against fixed MIDI material without a DAW.
+
+Call [MidiPlayer::update] regularly, for instance once per GUI frame. The
+events are scheduled [MidiPlayer::set_lookahead] seconds ahead, so the update
+interval must be shorter than that.
+
+```
+use hexodsp::*;
+use hexodsp::midi_file::MidiFile;
+use hexodsp::midi_player::MidiPlayer;
+
+let (node_conf, mut node_exec) = new_node_engine();
+let mut matrix = Matrix::new(node_conf, 3, 3);
+
+let smf = MidiFile::parse(&[
+    b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96,
+    b'M', b'T', b'r', b'k', 0, 0, 0, 12,
+    0x00, 0x90, 60, 127,
+    0x60, 0x80, 60, 0,
+    0x00, 0xFF, 0x2F, 0x00,
+]).unwrap();
+
+let mut player = MidiPlayer::new(&smf);
+player.set_loop(Some((0.0, 0.5)));
+player.play();
+
+for _ in 0..10 {
+    player.update(&mut matrix);
+    node_exec.test_run(0.05, false, &[]);
+}
+
+assert!(player.is_playing());
+assert!(player.position(&matrix) < 0.5);
+```
+*/
+
+use crate::midi_file::MidiFile;
+use crate::nodes::HxMidiEvent;
+use crate::{Matrix, NodeConfigurator};
+
+/// The maximum number of events [MidiPlayer::update] schedules per call,
+/// so that the message queue to the audio thread does not overflow.
+const MAX_EVENTS_PER_UPDATE: usize = 256;
+
+/// The shortest loop [MidiPlayer::set_loop] accepts, in seconds.
+const MIN_LOOP_LEN: f64 = 0.001;
+
+/// The interface the [MidiPlayer] schedules the MIDI events with, and the
+/// [crate::midi_recorder::MidiRecorder] enables the recording with.
+/// Implemented by [NodeConfigurator] and [Matrix].
+pub trait MidiScheduler {
+    /// See [NodeConfigurator::audio_clock].
+    fn audio_clock(&self) -> u64;
+    /// See [NodeConfigurator::sample_rate].
+    fn sample_rate(&self) -> f32;
+    /// See [NodeConfigurator::schedule_midi_event].
+    fn schedule_midi_event(&mut self, frame: u64, midi_ev: HxMidiEvent) -> bool;
+    /// See [NodeConfigurator::clear_scheduled_midi].
+    fn clear_scheduled_midi(&mut self);
+    /// See [NodeConfigurator::set_midi_recording].
+    fn set_midi_recording(&mut self, enabled: bool);
+}
+
+impl MidiScheduler for NodeConfigurator {
+    fn audio_clock(&self) -> u64 {
+        NodeConfigurator::audio_clock(self)
+    }
+
+    fn sample_rate(&self) -> f32 {
+        NodeConfigurator::sample_rate(self)
+    }
+
+    fn schedule_midi_event(&mut self, frame: u64, midi_ev: HxMidiEvent) -> bool {
+        NodeConfigurator::schedule_midi_event(self, frame, midi_ev)
+    }
+
+    fn clear_scheduled_midi(&mut self) {
+        NodeConfigurator::clear_scheduled_midi(self);
+    }
+
+    fn set_midi_recording(&mut self, enabled: bool) {
+        NodeConfigurator::set_midi_recording(self, enabled);
+    }
+}
+
+impl MidiScheduler for Matrix {
+    fn audio_clock(&self) -> u64 {
+        Matrix::audio_clock(self)
+    }
+
+    fn sample_rate(&self) -> f32 {
+        Matrix::sample_rate(self)
+    }
+
+    fn schedule_midi_event(&mut self, frame: u64, midi_ev: HxMidiEvent) -> bool {
+        Matrix::schedule_midi_event(self, frame, midi_ev)
+    }
+
+    fn clear_scheduled_midi(&mut self) {
+        Matrix::clear_scheduled_midi(self);
+    }
+
+    fn set_midi_recording(&mut self, enabled: bool) {
+        Matrix::set_midi_recording(self, enabled);
+    }
+}
+
+/// Plays a [MidiFile] into the DSP graph, see the [crate::midi_player] module documentation.
+#[derive(Debug, Clone)]
+pub struct MidiPlayer {
+    smf: MidiFile,
+    /// The MIDI events with their song position in seconds.
+    events: Vec<(f64, HxMidiEvent)>,
+    duration: f64,
+    playing: bool,
+    /// The song position while stopped, and the start position of the playback.
+    position: f64,
+    /// The audio clock frame the song position is played at, set by the first
+    /// [MidiPlayer::update] after starting the playback.
+    anchor: Option<(u64, f64)>,
+    /// The index of the next event to schedule.
+    next_idx: usize,
+    loop_range: Option<(f64, f64)>,
+    lookahead: f64,
+    /// The channel and note of the scheduled notes that are not yet released.
+    held_notes: Vec<(u8, u8)>,
+}
+
+impl MidiPlayer {
+    /// Creates a player for the `smf`. The tempo changes of the file are applied
+    /// to all tracks, for format 0 and format 1 files.
+    pub fn new(smf: &MidiFile) -> Self {
+        let events = smf.timed_events();
+
+        Self {
+            smf: smf.clone(),
+            duration: smf.duration_secs(),
+            events,
+            playing: false,
+            position: 0.0,
+            anchor: None,
+            next_idx: 0,
+            loop_range: None,
+            lookahead: 0.1,
+            held_notes: vec![],
+        }
+    }
+
+    /// Returns the played [MidiFile].
+    pub fn midi_file(&self) -> &MidiFile {
+        &self.smf
+    }
+
+    /// Returns the length of the MIDI file in seconds.
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    /// Sets how far ahead of the audio clock the events are scheduled, in seconds.
+    /// [MidiPlayer::update] needs to be called more often than this. Default is 0.1.
+    pub fn set_lookahead(&mut self, secs: f64) {
+        self.lookahead = secs.max(0.0);
+    }
+
+    /// Loops the song between the `start` and `end` position in seconds.
+    /// The held notes are released at the end of the loop. `None` disables looping.
+    /// Loops shorter than a millisecond are ignored.
+    ///
+    /// The loop takes effect for the events that are not yet scheduled.
+    pub fn set_loop(&mut self, range: Option<(f64, f64)>) {
+        self.loop_range = range.filter(|(start, end)| *end - *start >= MIN_LOOP_LEN);
+    }
+
+    /// Returns the loop range, see [MidiPlayer::set_loop].
+    pub fn loop_range(&self) -> Option<(f64, f64)> {
+        self.loop_range
+    }
+
+    /// Returns true while the song is playing. The playback stops by itself at
+    /// the end of the song, unless it's looped.
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Starts the playback at the current position.
+    pub fn play(&mut self) {
+        if !self.playing {
+            self.playing = true;
+            self.anchor = None;
+        }
+    }
+
+    /// Stops the playback and releases the held notes. The position is kept,
+    /// so that [MidiPlayer::play] continues where the song was stopped.
+    pub fn stop<S: MidiScheduler>(&mut self, sched: &mut S) {
+        if self.playing {
+            self.position = self.position(sched);
+            self.seek_idx(self.position);
+            self.release_notes(sched);
+            self.playing = false;
+            self.anchor = None;
+        }
+    }
+
+    /// Moves the song position to `secs`. During playback the held notes are
+    /// released and the playback continues at the new position.
+    pub fn seek<S: MidiScheduler>(&mut self, secs: f64, sched: &mut S) {
+        self.position = secs.clamp(0.0, self.duration);
+        self.seek_idx(self.position);
+
+        if self.playing {
+            self.release_notes(sched);
+            self.anchor = None;
+        }
+    }
+
+    /// Like [MidiPlayer::seek], but the position is given in ticks of the MIDI file.
+    pub fn seek_ticks<S: MidiScheduler>(&mut self, tick: u64, sched: &mut S) {
+        let secs = self.smf.ticks_to_secs(tick);
+        self.seek(secs, sched);
+    }
+
+    /// Returns the song position in seconds that is currently played by the audio thread.
+    pub fn position<S: MidiScheduler>(&self, sched: &S) -> f64 {
+        match (self.playing, self.anchor) {
+            (true, Some((a_frame, a_secs))) => {
+                let srate = sched.sample_rate() as f64;
+                let clock = sched.audio_clock();
+
+                if clock >= a_frame {
+                    (a_secs + (clock - a_frame) as f64 / srate).min(self.duration)
+                } else if let Some((_, end)) = self.loop_range {
+                    // The loop was already scheduled to start over:
+                    end - (a_frame - clock) as f64 / srate
+                } else {
+                    a_secs
+                }
+            }
+            _ => self.position,
+        }
+    }
+
+    /// Returns the song position in ticks of the MIDI file, see [MidiPlayer::position].
+    pub fn position_ticks<S: MidiScheduler>(&self, sched: &S) -> u64 {
+        self.smf.secs_to_ticks(self.position(sched))
+    }
+
+    /// Schedules the events up to the lookahead time, see [MidiPlayer::set_lookahead].
+    /// Call this regularly during playback.
+    pub fn update<S: MidiScheduler>(&mut self, sched: &mut S) {
+        if !self.playing {
+            return;
+        }
+
+        let srate = sched.sample_rate() as f64;
+        let clock = sched.audio_clock();
+        let (mut a_frame, mut a_secs) = *self.anchor.get_or_insert((clock, self.position));
+        let horizon = clock + (self.lookahead * srate).round() as u64;
+
+        let frame_at = |a_frame: u64, a_secs: f64, secs: f64| -> u64 {
+            a_frame + ((secs - a_secs).max(0.0) * srate).round() as u64
+        };
+
+        let mut budget = MAX_EVENTS_PER_UPDATE;
+
+        loop {
+            // A loop only applies if the playback started before it's end:
+            let loop_range = self.loop_range.filter(|(_, end)| a_secs < *end);
+            let end_secs = loop_range.map(|(_, end)| end).unwrap_or(self.duration);
+
+            while let Some((secs, ev)) = self.events.get(self.next_idx).copied() {
+                if loop_range.is_some() && secs >= end_secs {
+                    break;
+                }
+
+                let frame = frame_at(a_frame, a_secs, secs);
+                if frame >= horizon || budget == 0 {
+                    return;
+                }
+
+                if !sched.schedule_midi_event(frame, ev) {
+                    return;
+                }
+
+                budget -= 1;
+                self.track_note(ev);
+                self.next_idx += 1;
+            }
+
+            let end_frame = frame_at(a_frame, a_secs, end_secs);
+
+            if let Some((start, _)) = loop_range {
+                if end_frame >= horizon {
+                    return;
+                }
+
+                for (channel, note) in self.held_notes.drain(..) {
+                    sched.schedule_midi_event(end_frame, HxMidiEvent::NoteOff { channel, note });
+                }
+
+                a_frame = end_frame;
+                a_secs = start;
+                self.anchor = Some((a_frame, a_secs));
+                self.seek_idx(start);
+            } else {
+                if end_frame <= clock {
+                    self.playing = false;
+                    self.anchor = None;
+                    self.position = self.duration;
+                }
+
+                return;
+            }
+        }
+    }
+
+    fn seek_idx(&mut self, secs: f64) {
+        self.next_idx = self.events.partition_point(|(t, _)| *t < secs);
+    }
+
+    fn track_note(&mut self, ev: HxMidiEvent) {
+        match ev {
+            HxMidiEvent::NoteOn { channel, note, .. } => {
+                if !self.held_notes.contains(&(channel, note)) {
+                    self.held_notes.push((channel, note));
+                }
+            }
+            HxMidiEvent::NoteOff { channel, note } => {
+                self.held_notes.retain(|n| *n != (channel, note));
+            }
+            _ => (),
+        }
+    }
+
+    /// Drops the scheduled events and releases the held notes right away.
+    fn release_notes<S: MidiScheduler>(&mut self, sched: &mut S) {
+        sched.clear_scheduled_midi();
+
+        let clock = sched.audio_clock();
+        for (channel, note) in self.held_notes.drain(..) {
+            sched.schedule_midi_event(clock, HxMidiEvent::NoteOff { channel, note });
+        }
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+/*! Recording of the incoming MIDI events to a Standard MIDI File.
+
+The [MidiRecorder] runs on the frontend thread. While it records, the
+[crate::nodes::NodeExecutor] reports every MIDI event it receives as
+[GraphEvent::RecordedMIDI], with the sample frame of the audio clock it was
+played at. The recorder converts the frames to ticks, so that the performance
+can be written with [MidiFile::write_to_file] and played back through a changed
+patch with the [crate::midi_player::MidiPlayer] or the [crate::OfflineRenderer].
+
+If you use a [crate::Matrix], let it own the recorder with
+[crate::Matrix::start_midi_recording], because [crate::Matrix::handle_graph_events]
+consumes the [GraphEvent]s. Otherwise pass the events of
+[crate::NodeConfigurator::next_event] to [MidiRecorder::handle_event].
+
+```
+use hexodsp::*;
+use hexodsp::midi_recorder::MidiRecorder;
+
+let (node_conf, mut node_exec) = new_node_engine();
+let mut matrix = Matrix::new(node_conf, 3, 3);
+
+matrix.start_midi_recording(MidiRecorder::new(120.0, 480));
+node_exec.test_run(0.1, false, &[]);
+
+matrix.inject_midi_event(HxMidiEvent::NoteOn { channel: 0, note: 60, vel: 1.0 });
+node_exec.test_run(0.5, false, &[]);
+matrix.handle_graph_events();
+
+let smf = matrix.stop_midi_recording().unwrap().midi_file();
+let events = smf.timed_events();
+assert!(matches!(events[0].1, HxMidiEvent::NoteOn { note: 60, .. }));
+// The note is still held and released at the end of the recording:
+assert!(matches!(events[1].1, HxMidiEvent::NoteOff { note: 60, .. }));
+```
+*/
+
+use crate::midi_file::{MidiFile, SmfEvent, SmfTrackEvent};
+use crate::midi_player::MidiScheduler;
+use crate::nodes::{GraphEvent, HxMidiEvent};
+
+/// Records the MIDI events of the DSP graph, see the [crate::midi_recorder] module documentation.
+#[derive(Debug, Clone)]
+pub struct MidiRecorder {
+    ticks_per_quarter: u16,
+    /// The tempo of the recording in microseconds per quarter note.
+    tempo: u32,
+    sample_rate: f64,
+    /// The audio clock frame of tick 0.
+    start_frame: u64,
+    /// The audio clock frame the recording was stopped at.
+    end_frame: Option<u64>,
+    recording: bool,
+    events: Vec<(u64, HxMidiEvent)>,
+}
+
+impl MidiRecorder {
+    /// Creates a recorder, that writes the events with the tempo `bpm`
+    /// and a resolution of `ticks_per_quarter`.
+    pub fn new(bpm: f64, ticks_per_quarter: u16) -> Self {
+        Self {
+            ticks_per_quarter: ticks_per_quarter.max(1),
+            tempo: (60000000.0 / bpm.max(1.0)).round() as u32,
+            sample_rate: 44100.0,
+            start_frame: 0,
+            end_frame: None,
+            recording: false,
+            events: vec![],
+        }
+    }
+
+    /// Returns true while recording.
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Drops the previously recorded events and starts the recording at the current
+    /// frame of the audio clock.
+    pub fn start<S: MidiScheduler>(&mut self, sched: &mut S) {
+        self.events.clear();
+        self.sample_rate = sched.sample_rate() as f64;
+        self.start_frame = sched.audio_clock();
+        self.end_frame = None;
+        self.recording = true;
+        sched.set_midi_recording(true);
+    }
+
+    /// Stops the recording at the current frame of the audio clock. Events
+    /// before that frame, that are still in the event queue, are still recorded
+    /// by [MidiRecorder::handle_event].
+    pub fn stop<S: MidiScheduler>(&mut self, sched: &mut S) {
+        if self.recording {
+            sched.set_midi_recording(false);
+            self.end_frame = Some(sched.audio_clock());
+            self.recording = false;
+        }
+    }
+
+    /// Records the [GraphEvent::RecordedMIDI] events, other events are ignored.
+    pub fn handle_event(&mut self, ev: &GraphEvent) {
+        if let GraphEvent::RecordedMIDI(frame, midi_ev) = ev {
+            self.record(*frame, *midi_ev);
+        }
+    }
+
+    /// Records the `midi_ev` at the `frame` of the audio clock.
+    pub fn record(&mut self, frame: u64, midi_ev: HxMidiEvent) {
+        if frame < self.start_frame || self.end_frame.map(|end| frame >= end).unwrap_or(false) {
+            return;
+        }
+
+        let idx = self.events.partition_point(|(f, _)| *f <= frame);
+        self.events.insert(idx, (frame, midi_ev));
+    }
+
+    /// Returns the number of recorded events.
+    pub fn event_count(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Converts a frame of the audio clock to the tick of the recording.
+    pub fn frame_to_tick(&self, frame: u64) -> u64 {
+        let secs = frame.saturating_sub(self.start_frame) as f64 / self.sample_rate;
+        let ticks_per_sec = (self.ticks_per_quarter as f64 * 1000000.0) / self.tempo as f64;
+        (secs * ticks_per_sec).round() as u64
+    }
+
+    /// Returns the recording as format 0 [MidiFile]. Notes that are still held
+    /// are released at the end of the recording, or after the last event while
+    /// still recording.
+    pub fn midi_file(&self) -> MidiFile {
+        let mut track = vec![SmfTrackEvent { tick: 0, event: SmfEvent::Tempo(self.tempo) }];
+        let mut held_notes: Vec<(u8, u8)> = vec![];
+
+        for (frame, midi_ev) in self.events.iter() {
+            match *midi_ev {
+                HxMidiEvent::NoteOn { channel, note, .. } => {
+                    if !held_notes.contains(&(channel, note)) {
+                        held_notes.push((channel, note));
+                    }
+                }
+                HxMidiEvent::NoteOff { channel, note } => {
+                    held_notes.retain(|n| *n != (channel, note));
+                }
+                _ => (),
+            }
+
+            track.push(SmfTrackEvent {
+                tick: self.frame_to_tick(*frame),
+                event: SmfEvent::Midi(*midi_ev),
+            });
+        }
+
+        let last_tick = track.last().map(|ev| ev.tick).unwrap_or(0);
+        let end_tick = self.end_frame.map(|end| self.frame_to_tick(end)).unwrap_or(0);
+        let end_tick = end_tick.max(last_tick);
+
+        for (channel, note) in held_notes {
+            track.push(SmfTrackEvent {
+                tick: end_tick,
+                event: SmfEvent::Midi(HxMidiEvent::NoteOff { channel, note }),
+            });
+        }
+
+        MidiFile { format: 0, ticks_per_quarter: self.ticks_per_quarter, tracks: vec![track] }
+    }
+}
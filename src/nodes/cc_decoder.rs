@@ -0,0 +1,218 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use super::HxMidiEvent;
+
+const CC_DATA_ENTRY_MSB: u8 = 6;
+const CC_DATA_ENTRY_LSB: u8 = 38;
+const CC_NRPN_LSB: u8 = 98;
+const CC_NRPN_MSB: u8 = 99;
+const CC_RPN_LSB: u8 = 100;
+const CC_RPN_MSB: u8 = 101;
+
+/// The RPN that deselects the current parameter, so that following data
+/// entry messages are ignored.
+const RPN_NULL: u16 = 0x3FFF;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ParamKind {
+    None,
+    Nrpn,
+    Rpn,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ChannelState {
+    /// The last MSB of the controllers 0 to 31.
+    msb: [u8; 32],
+    nrpn: u16,
+    rpn: u16,
+    /// The kind of parameter number that was selected last.
+    selected: ParamKind,
+    /// The 14-bit data entry value of the selected parameter.
+    data: u16,
+}
+
+impl ChannelState {
+    fn new() -> Self {
+        Self { msb: [0; 32], nrpn: 0, rpn: 0, selected: ParamKind::None, data: 0 }
+    }
+
+    fn param_event(&self, channel: u8) -> Option<HxMidiEvent> {
+        let value = self.data as f32 / 16383.0;
+        match self.selected {
+            ParamKind::None => None,
+            ParamKind::Nrpn => Some(HxMidiEvent::NRPN { channel, param: self.nrpn, value }),
+            ParamKind::Rpn => Some(HxMidiEvent::RPN { channel, param: self.rpn, value }),
+        }
+    }
+}
+
+fn set_msb(param: u16, msb: u8) -> u16 {
+    (param & 0x7F) | ((msb as u16) << 7)
+}
+
+fn set_lsb(param: u16, lsb: u8) -> u16 {
+    (param & !0x7F) | lsb as u16
+}
+
+/// Decodes the high resolution controllers from the 7-bit [HxMidiEvent::CC] events.
+///
+/// This is used by the [crate::nodes::NodeExecutor] to add the decoded events to
+/// [crate::nodes::NodeExecContext::midi_ccs], from where the `MidiCC` node picks
+/// them up, depending on it's `mode` setting:
+///
+/// - The controllers 0 to 31 are paired with their LSB controllers 32 to 63 to
+///   [HxMidiEvent::CC14] events. The MSB resets the LSB to 0, so controllers that only
+///   send the MSB work too.
+/// - The NRPN (99/98) and RPN (101/100) parameter numbers select the parameter, which
+///   the data entry controllers 6 (MSB) and 38 (LSB) set as [HxMidiEvent::NRPN] or
+///   [HxMidiEvent::RPN] event. While a parameter is selected, the controllers 6 and 38
+///   are not paired to a [HxMidiEvent::CC14]. The RPN null (127/127) deselects it.
+///
+/// The data increment and decrement controllers are not supported.
+#[derive(Debug, Clone)]
+pub struct MidiCCDecoder {
+    channels: [ChannelState; 16],
+}
+
+impl MidiCCDecoder {
+    pub fn new() -> Self {
+        Self { channels: [ChannelState::new(); 16] }
+    }
+
+    /// Forgets the received controller values and the selected parameters.
+    pub fn reset(&mut self) {
+        self.channels = [ChannelState::new(); 16];
+    }
+
+    /// Feeds the next event to the decoder. Returns the decoded high resolution
+    /// event, if `ev` is a CC event that completes one.
+    pub fn decode(&mut self, ev: HxMidiEvent) -> Option<HxMidiEvent> {
+        let (channel, cc, value) = match ev {
+            HxMidiEvent::CC { channel, cc, value } => (channel, cc, value),
+            _ => return None,
+        };
+
+        let st = &mut self.channels[(channel & 0x0F) as usize];
+        let v = (value.clamp(0.0, 1.0) * 127.0).round() as u8;
+
+        match cc {
+            CC_NRPN_MSB => {
+                st.nrpn = set_msb(st.nrpn, v);
+                st.selected = ParamKind::Nrpn;
+                st.data = 0;
+                None
+            }
+            CC_NRPN_LSB => {
+                st.nrpn = set_lsb(st.nrpn, v);
+                st.selected = ParamKind::Nrpn;
+                st.data = 0;
+                None
+            }
+            CC_RPN_MSB | CC_RPN_LSB => {
+                st.rpn = if cc == CC_RPN_MSB { set_msb(st.rpn, v) } else { set_lsb(st.rpn, v) };
+                st.selected = if st.rpn == RPN_NULL { ParamKind::None } else { ParamKind::Rpn };
+                st.data = 0;
+                None
+            }
+            CC_DATA_ENTRY_MSB if st.selected != ParamKind::None => {
+                st.data = (v as u16) << 7;
+                st.param_event(channel)
+            }
+            CC_DATA_ENTRY_LSB if st.selected != ParamKind::None => {
+                st.data = set_lsb(st.data, v);
+                st.param_event(channel)
+            }
+            0..=31 => {
+                st.msb[cc as usize] = v;
+                Some(HxMidiEvent::CC14 { channel, cc, value: ((v as u16) << 7) as f32 / 16383.0 })
+            }
+            32..=63 => {
+                let msb = st.msb[(cc - 32) as usize] as u16;
+                let value = ((msb << 7) | v as u16) as f32 / 16383.0;
+                Some(HxMidiEvent::CC14 { channel, cc: cc - 32, value })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Default for MidiCCDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cc(cc: u8, v: u8) -> HxMidiEvent {
+        HxMidiEvent::CC { channel: 2, cc, value: v as f32 / 127.0 }
+    }
+
+    fn dump(ev: Option<HxMidiEvent>) -> String {
+        match ev {
+            Some(HxMidiEvent::CC14 { channel, cc, value }) => {
+                format!("cc14:{}:{}={}", channel, cc, (value * 16383.0).round())
+            }
+            Some(HxMidiEvent::NRPN { channel, param, value }) => {
+                format!("nrpn:{}:{}={}", channel, param, (value * 16383.0).round())
+            }
+            Some(HxMidiEvent::RPN { channel, param, value }) => {
+                format!("rpn:{}:{}={}", channel, param, (value * 16383.0).round())
+            }
+            _ => "-".to_string(),
+        }
+    }
+
+    #[test]
+    fn check_cc_decoder_14bit() {
+        let mut dec = MidiCCDecoder::new();
+
+        assert_eq!(dump(dec.decode(cc(1, 64))), "cc14:2:1=8192");
+        assert_eq!(dump(dec.decode(cc(33, 1))), "cc14:2:1=8193");
+        assert_eq!(dump(dec.decode(cc(33, 127))), "cc14:2:1=8319");
+        assert_eq!(dump(dec.decode(cc(1, 127))), "cc14:2:1=16256");
+        assert_eq!(dump(dec.decode(cc(33, 127))), "cc14:2:1=16383");
+        assert_eq!(dump(dec.decode(cc(74, 10))), "-");
+
+        // The MSB is kept per channel:
+        let ev = HxMidiEvent::CC { channel: 3, cc: 33, value: 1.0 };
+        assert_eq!(dump(dec.decode(ev)), "cc14:3:1=127");
+        assert_eq!(dump(dec.decode(HxMidiEvent::ProgramChange { channel: 2, program: 1 })), "-");
+    }
+
+    #[test]
+    fn check_cc_decoder_nrpn_rpn() {
+        let mut dec = MidiCCDecoder::new();
+
+        // Without a selected parameter, the data entry is a normal 14-bit CC:
+        assert_eq!(dump(dec.decode(cc(6, 1))), "cc14:2:6=128");
+
+        assert_eq!(dump(dec.decode(cc(99, 1))), "-");
+        assert_eq!(dump(dec.decode(cc(98, 2))), "-");
+        assert_eq!(dump(dec.decode(cc(6, 64))), "nrpn:2:130=8192");
+        assert_eq!(dump(dec.decode(cc(38, 3))), "nrpn:2:130=8195");
+        assert_eq!(dump(dec.decode(cc(38, 4))), "nrpn:2:130=8196");
+
+        // Pitch bend sensitivity of 12 semitones:
+        assert_eq!(dump(dec.decode(cc(101, 0))), "-");
+        assert_eq!(dump(dec.decode(cc(100, 0))), "-");
+        assert_eq!(dump(dec.decode(cc(6, 12))), "rpn:2:0=1536");
+
+        // RPN null deselects the parameter:
+        assert_eq!(dump(dec.decode(cc(101, 127))), "-");
+        assert_eq!(dump(dec.decode(cc(100, 127))), "-");
+        assert_eq!(dump(dec.decode(cc(6, 12))), "cc14:2:6=1536");
+
+        // Selecting the NRPN LSB keeps the MSB:
+        assert_eq!(dump(dec.decode(cc(98, 5))), "-");
+        assert_eq!(dump(dec.decode(cc(6, 1))), "nrpn:2:133=128");
+
+        dec.reset();
+        assert_eq!(dump(dec.decode(cc(6, 1))), "cc14:2:6=128");
+    }
+}
@@ -26,6 +26,10 @@ impl HxTimedEvent {
         self.kind
     }
 
+    pub fn timing(&self) -> usize {
+        self.timing
+    }
+
     pub fn cc(timing: usize, channel: u8, cc: u8, value: f32) -> Self {
         Self { timing, kind: HxMidiEvent::CC { channel, cc, value } }
     }
@@ -37,6 +41,55 @@ impl HxTimedEvent {
     pub fn note_off(timing: usize, channel: u8, note: u8) -> Self {
         Self { timing, kind: HxMidiEvent::NoteOff { channel, note } }
     }
+
+    /// Pitch bend event from the 14-bit value of the MIDI message, `0x2000` is the center.
+    pub fn pitch_bend(timing: usize, channel: u8, bend: u16) -> Self {
+        Self { timing, kind: HxMidiEvent::pitch_bend(channel, bend) }
+    }
+
+    pub fn channel_pressure(timing: usize, channel: u8, value: f32) -> Self {
+        Self { timing, kind: HxMidiEvent::ChannelPressure { channel, value } }
+    }
+
+    pub fn poly_pressure(timing: usize, channel: u8, note: u8, value: f32) -> Self {
+        Self { timing, kind: HxMidiEvent::PolyPressure { channel, note, value } }
+    }
+
+    pub fn program_change(timing: usize, channel: u8, program: u8) -> Self {
+        Self { timing, kind: HxMidiEvent::ProgramChange { channel, program } }
+    }
+
+    pub fn cc14(timing: usize, channel: u8, cc: u8, value: f32) -> Self {
+        Self { timing, kind: HxMidiEvent::CC14 { channel, cc, value } }
+    }
+
+    pub fn nrpn(timing: usize, channel: u8, param: u16, value: f32) -> Self {
+        Self { timing, kind: HxMidiEvent::NRPN { channel, param, value } }
+    }
+
+    pub fn rpn(timing: usize, channel: u8, param: u16, value: f32) -> Self {
+        Self { timing, kind: HxMidiEvent::RPN { channel, param, value } }
+    }
+
+    pub fn clock(timing: usize) -> Self {
+        Self { timing, kind: HxMidiEvent::Clock }
+    }
+
+    pub fn start(timing: usize) -> Self {
+        Self { timing, kind: HxMidiEvent::Start }
+    }
+
+    pub fn stop(timing: usize) -> Self {
+        Self { timing, kind: HxMidiEvent::Stop }
+    }
+
+    pub fn cont(timing: usize) -> Self {
+        Self { timing, kind: HxMidiEvent::Continue }
+    }
+
+    pub fn song_position(timing: usize, beats: u16) -> Self {
+        Self { timing, kind: HxMidiEvent::SongPosition { beats } }
+    }
 }
 
 pub struct MidiEventPointer<'a> {
@@ -59,11 +112,110 @@ impl<'a> MidiEventPointer<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum HxMidiEvent {
-    NoteOn { channel: u8, note: u8, vel: f32 },
-    NoteOff { channel: u8, note: u8 },
-    CC { channel: u8, cc: u8, value: f32 },
+    NoteOn {
+        channel: u8,
+        note: u8,
+        vel: f32,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+    },
+    CC {
+        channel: u8,
+        cc: u8,
+        value: f32,
+    },
+    /// The pitch bend `value` is in the range -1.0 to 1.0, see also [HxMidiEvent::pitch_bend].
+    PitchBend {
+        channel: u8,
+        value: f32,
+    },
+    /// Channel pressure (aftertouch), the `value` is in the range 0.0 to 1.0.
+    ChannelPressure {
+        channel: u8,
+        value: f32,
+    },
+    /// Polyphonic key pressure of a single `note`, the `value` is in the range 0.0 to 1.0.
+    PolyPressure {
+        channel: u8,
+        note: u8,
+        value: f32,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    /// A 14-bit controller value of the MSB controller `cc` (0 to 31) and it's
+    /// LSB controller `cc + 32`. The `value` is in the range 0.0 to 1.0.
+    /// Decoded from the [HxMidiEvent::CC] events by the [crate::nodes::MidiCCDecoder].
+    CC14 {
+        channel: u8,
+        cc: u8,
+        value: f32,
+    },
+    /// The 14-bit data entry `value` (0.0 to 1.0) of the non-registered parameter
+    /// number `param` (0 to 16383). Decoded by the [crate::nodes::MidiCCDecoder].
+    NRPN {
+        channel: u8,
+        param: u16,
+        value: f32,
+    },
+    /// The 14-bit data entry `value` (0.0 to 1.0) of the registered parameter
+    /// number `param`, for instance 0 for the pitch bend sensitivity.
+    /// Decoded by the [crate::nodes::MidiCCDecoder].
+    RPN {
+        channel: u8,
+        param: u16,
+        value: f32,
+    },
+    /// MIDI timing clock, sent 24 times per quarter note.
+    Clock,
+    Start,
+    Stop,
+    Continue,
+    /// Song position pointer, in MIDI beats (sixteenth notes) since the start of the song.
+    SongPosition {
+        beats: u16,
+    },
+}
+
+impl HxMidiEvent {
+    /// Creates a [HxMidiEvent::PitchBend] from the 14-bit value of the MIDI message.
+    /// `0x2000` is the center, `0x0` the lowest and `0x3FFF` the highest bend.
+    pub fn pitch_bend(channel: u8, bend: u16) -> Self {
+        let bend = bend.min(0x3FFF) as f32 - 8192.0;
+        let value = if bend < 0.0 { bend / 8192.0 } else { bend / 8191.0 };
+        HxMidiEvent::PitchBend { channel, value }
+    }
+
+    /// Returns true for the events that go into [crate::nodes::NodeExecContext::midi_notes]:
+    /// Notes and the pitch bend and pressure events that modulate them.
+    pub fn is_note_event(&self) -> bool {
+        matches!(
+            self,
+            HxMidiEvent::NoteOn { .. }
+                | HxMidiEvent::NoteOff { .. }
+                | HxMidiEvent::PitchBend { .. }
+                | HxMidiEvent::ChannelPressure { .. }
+                | HxMidiEvent::PolyPressure { .. }
+        )
+    }
+
+    /// Returns true for the system realtime and song position events, that go
+    /// into [crate::nodes::NodeExecContext::midi_realtime].
+    pub fn is_realtime(&self) -> bool {
+        matches!(
+            self,
+            HxMidiEvent::Clock
+                | HxMidiEvent::Start
+                | HxMidiEvent::Stop
+                | HxMidiEvent::Continue
+                | HxMidiEvent::SongPosition { .. }
+        )
+    }
 }
 
 pub struct EventWindowing {
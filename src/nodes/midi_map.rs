@@ -0,0 +1,126 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{ParamId, SAtom};
+
+/// The response curve of a [MidiMapping], applied to the CC value before
+/// it's mapped to the range of the parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiMapCurve {
+    Lin,
+    /// Finer control of the lower part of the range.
+    Exp,
+    /// Finer control of the upper part of the range.
+    Log,
+}
+
+impl MidiMapCurve {
+    #[inline]
+    pub fn apply(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        match self {
+            MidiMapCurve::Lin => x,
+            MidiMapCurve::Exp => x * x,
+            MidiMapCurve::Log => 1.0 - (1.0 - x) * (1.0 - x),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MidiMapCurve::Lin => "lin",
+            MidiMapCurve::Exp => "exp",
+            MidiMapCurve::Log => "log",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "lin" => Some(MidiMapCurve::Lin),
+            "exp" => Some(MidiMapCurve::Exp),
+            "log" => Some(MidiMapCurve::Log),
+            _ => None,
+        }
+    }
+}
+
+/// Binds a MIDI CC on a channel to a parameter or setting of a node. The
+/// [crate::nodes::NodeExecutor] applies the incoming CC values directly to the
+/// parameter, without any `MidiCC` node in the graph.
+/// See [crate::nodes::NodeConfigurator::set_midi_mapping].
+///
+/// For parameter inputs `min` and `max` are normalized parameter values.
+/// For settings they are setting values, and the mapped value is rounded
+/// to the next setting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MidiMapping {
+    pub channel: u8,
+    pub cc: u8,
+    pub param: ParamId,
+    /// The parameter value at CC value 0.
+    pub min: f32,
+    /// The parameter value at CC value 127.
+    pub max: f32,
+    pub curve: MidiMapCurve,
+}
+
+impl MidiMapping {
+    /// Creates a linear mapping over the whole range of the parameter or setting.
+    pub fn new(channel: u8, cc: u8, param: ParamId) -> Self {
+        let (min, max) = if param.is_atom() {
+            param.setting_min_max().map(|(min, max)| (min as f32, max as f32)).unwrap_or((0.0, 0.0))
+        } else {
+            param.param_min_max().map(|(range, _)| range).unwrap_or((0.0, 1.0))
+        };
+
+        Self { channel, cc, param, min, max, curve: MidiMapCurve::Lin }
+    }
+
+    pub fn range(mut self, min: f32, max: f32) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    pub fn curve(mut self, curve: MidiMapCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Returns true if the mapping can be applied to the parameter. Only parameter
+    /// inputs and settings can be mapped, but no samples or strings.
+    pub fn is_mappable(param: &ParamId) -> bool {
+        !param.is_atom() || matches!(param.as_atom_def(), SAtom::Setting(_))
+    }
+
+    /// Maps the CC `value` in the range 0.0 to 1.0 to the parameter value.
+    #[inline]
+    pub fn map_value(&self, value: f32) -> f32 {
+        self.min + (self.max - self.min) * self.curve.apply(value)
+    }
+
+    /// Returns the [SAtom] for the CC `value`, a setting for settings and a
+    /// normalized parameter value for parameter inputs.
+    pub fn map_atom(&self, value: f32) -> SAtom {
+        if self.param.is_atom() {
+            SAtom::setting(self.map_value(value).round() as i64)
+        } else {
+            SAtom::param(self.map_value(value))
+        }
+    }
+}
+
+/// Where the [crate::nodes::NodeExecutor] applies a [MidiMapping].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum MidiMapTarget {
+    Param(usize),
+    Atom(usize),
+}
+
+/// A [MidiMapping] with the input or atom index it refers to in the
+/// current [crate::nodes::NodeProg].
+#[derive(Debug, Clone, Copy)]
+pub struct ResolvedMidiMapping {
+    pub(crate) mapping: MidiMapping,
+    pub(crate) target: MidiMapTarget,
+}
@@ -6,26 +6,55 @@ pub const SCOPE_SAMPLES: usize = 512;
 pub const MAX_DSP_NODE_INPUTS: usize = 32;
 pub const MAX_SMOOTHERS: usize = 36 + 4; // 6 * 6 modulator inputs + 4 UI Knobs
 pub const MAX_INJ_MIDI_EVENTS: usize = 64;
+pub const MAX_TIMED_PARAM_UPDATES: usize = 256;
+pub const MAX_SCHEDULED_MIDI_EVENTS: usize = 1024;
+pub const MAX_AUDIO_CHANNELS: usize = 16;
+/// The absolute signal level at which the output guard considers a node
+/// output as runaway, see [NodeConfigurator::set_output_guard].
+pub const GUARD_RUNAWAY_LEVEL: f32 = 1000.0;
 
+mod cc_decoder;
 mod drop_thread;
 mod feedback_filter;
 mod midi;
+mod midi_map;
+mod mpe;
 mod node_conf;
+mod node_events;
 mod node_exec;
 mod node_graph_ordering;
 mod node_prog;
+mod node_states;
+mod oversampling;
+mod poly_voices;
+mod prog_crossfade;
+mod prog_diff;
+mod transport;
 pub mod visual_sampling_filter;
+mod voice_alloc;
+mod worker_pool;
 
 pub(crate) use visual_sampling_filter::*;
 
+pub use cc_decoder::MidiCCDecoder;
 pub use feedback_filter::*;
 pub use midi::{EventWindowing, HxMidiEvent, HxTimedEvent, MidiEventPointer};
+pub use midi_map::{MidiMapCurve, MidiMapping, ResolvedMidiMapping};
+pub use mpe::{MpeAllocator, MpeZone, MAX_MPE_SLOTS, MPE_SLIDE_CC};
 pub use node_conf::*;
+pub use node_events::{NodeEvent, MAX_NODE_EVENTS_PER_BLOCK};
 pub use node_exec::*;
 pub use node_graph_ordering::NodeGraphOrdering;
 pub use node_prog::*;
+pub use node_states::NodeStates;
+pub use oversampling::{OversamplingGroup, OVERSAMPLING_FACTORS};
+pub use poly_voices::PolyVoices;
+pub use prog_diff::NodeProgDiff;
+pub use transport::{host_note_div_beats, HostTransport, HOST_NOTE_DIVS};
+pub use voice_alloc::{VoiceAllocator, VoiceStealing, MAX_VOICES};
+pub use worker_pool::{WorkerPool, MAX_WORKER_THREADS};
 
-use crate::dsp::{Node, SAtom};
+use crate::dsp::{Node, NodeId, SAtom};
 pub use crate::monitor::MinMaxMonitorSamples;
 use crate::monitor::MON_SIG_CNT;
 
@@ -34,7 +63,13 @@ use crate::monitor::MON_SIG_CNT;
 pub(crate) enum DropMsg {
     Node { node: Node },
     Prog { prog: NodeProg },
+    ProgDiff { diff: NodeProgDiff },
+    NodeStates { states: NodeStates },
+    MidiMap { map: Vec<ResolvedMidiMapping> },
     Atom { atom: SAtom },
+    WorkerPool { pool: WorkerPool },
+    SharedOps { ops: Vec<Option<usize>> },
+    Nodes { nodes: Vec<Node> },
 }
 
 /// Messages for updating the [NodeExecutor] thread.
@@ -45,10 +80,18 @@ pub enum GraphMessage {
     NewProg {
         prog: NodeProg,
         copy_old_out: bool,
+        /// Maps the ops of the previous program to the ops of `prog` for the
+        /// crossfade, see [NodeConfigurator::set_prog_crossfade].
+        shared_ops: Vec<Option<usize>>,
     },
     Clear {
         prog: NodeProg,
     },
+    /// Applies the changes of a new [NodeProg] to the running one, see
+    /// [NodeConfigurator::upload_prog].
+    ProgDiff {
+        diff: NodeProgDiff,
+    },
 
     // XXX: Parameter updates used to be separate from the graph update, but this
     // became a race condition and I had to revert this premature optimization.
@@ -60,6 +103,21 @@ pub enum GraphMessage {
         input_idx: usize,
         value: f32,
     },
+    /// An atom update that is applied at the start of the buffer period
+    /// that contains the `frame`, see [NodeConfigurator::set_param_at].
+    TimedAtomUpdate {
+        at_idx: usize,
+        value: SAtom,
+        frame: u64,
+    },
+    /// A parameter update that is applied at the exact `frame`, optionally
+    /// with a linear ramp of `ramp` frames, see [NodeConfigurator::set_param_at].
+    TimedParamUpdate {
+        input_idx: usize,
+        value: f32,
+        frame: u64,
+        ramp: usize,
+    },
     ModamtUpdate {
         mod_idx: usize,
         modamt: f32,
@@ -67,20 +125,144 @@ pub enum GraphMessage {
     InjectMidi {
         midi_ev: HxMidiEvent,
     },
+    /// A MIDI event that is fed into the graph at the sample `frame` of the
+    /// audio clock, see [NodeConfigurator::schedule_midi_event].
+    ScheduleMidi {
+        midi_ev: HxMidiEvent,
+        frame: u64,
+    },
+    /// Drops the MIDI events that were scheduled with [GraphMessage::ScheduleMidi]
+    /// and are not yet due.
+    ClearScheduledMidi,
+    /// Enables or disables sending the MIDI events as [GraphEvent::RecordedMIDI].
+    SetMidiRecording {
+        enabled: bool,
+    },
     /// Sets the buffer indices to monitor with the FeedbackProcessor.
     SetMonitor {
         bufs: [usize; MON_SIG_CNT],
     },
+    /// Configures the [VoiceAllocator] of the [NodeExecutor].
+    SetVoiceAlloc {
+        voices: usize,
+        stealing: VoiceStealing,
+    },
+    /// Configures the [MpeZone] of the [MpeAllocator] of the [NodeExecutor].
+    SetMpeZone {
+        zone: Option<MpeZone>,
+    },
+    /// Sets the crossfade time for swapping the [NodeProg] on [GraphMessage::NewProg].
+    SetProgCrossfade {
+        time_ms: f32,
+    },
+    /// Replaces the [WorkerPool] for executing the [NodeProg] in parallel,
+    /// `None` switches back to the single threaded execution.
+    SetWorkerPool {
+        pool: Option<WorkerPool>,
+    },
+    /// Enables or disables measuring the CPU time of each node.
+    SetCpuProfiling {
+        enabled: bool,
+    },
+    /// Enables the output guard with the given hard limit for the `Out` nodes,
+    /// `None` disables it.
+    SetOutputGuard {
+        limit: Option<f32>,
+    },
+    /// Restarts the random number generators of all nodes with the new global `seed`.
+    SetRandomSeed {
+        seed: u64,
+        /// All node instances of the [NodeConfigurator], including the ones
+        /// that are not part of the running program.
+        nodes: Vec<Node>,
+    },
+    /// Copies the node states into the preallocated `states`, which are sent back
+    /// as [GraphEvent::NodeStates]. See [NodeExecutor::copy_node_states].
+    SaveNodeStates {
+        states: NodeStates,
+    },
+    /// Restores the node states, see [NodeExecutor::load_node_states].
+    LoadNodeStates {
+        states: NodeStates,
+    },
+    /// Replaces the MIDI CC mappings, see [NodeConfigurator::set_midi_mapping].
+    SetMidiMap {
+        map: Vec<ResolvedMidiMapping>,
+    },
+}
+
+/// The kind of misbehaviour the output guard detected in a node output,
+/// see [NodeConfigurator::set_output_guard].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalFault {
+    /// The output contained a NaN or an infinite value.
+    NotFinite,
+    /// The output exceeded [GUARD_RUNAWAY_LEVEL].
+    Runaway,
 }
 
 /// Message from the DSP graph/backend to the frontend. Such as MIDI events
 /// for MIDI learn for instance.
 pub enum GraphEvent {
     MIDI(HxMidiEvent),
+    /// The output guard detected a [SignalFault] in an output of the node
+    /// and has reset it.
+    NodeFault(NodeId, SignalFault),
+    /// An event emitted by a node, see [NodeExecContext::emit_event].
+    Node(NodeId, NodeEvent),
+    /// The node states requested by [NodeConfigurator::request_node_states].
+    NodeStates(NodeStates),
+    /// A MIDI event that reached the [NodeExecutor], with the sample frame of the
+    /// audio clock it was played at. Only sent while the MIDI recording is enabled,
+    /// see [NodeConfigurator::set_midi_recording].
+    RecordedMIDI(u64, HxMidiEvent),
 }
 
 pub const UNUSED_MONITOR_IDX: usize = 99999;
 
+/// Returns true if the node reads or writes the audio driver buffers via the
+/// [NodeAudioContext]. These nodes have to be processed on the audio thread
+/// at the sample rate of the audio driver.
+pub(crate) fn uses_audio_driver(node_id: NodeId) -> bool {
+    matches!(node_id, NodeId::Out(_) | NodeId::Inp(_))
+}
+
+/// Returns true if the node reads the MIDI events or the transport position of the
+/// [NodeExecContext], or sends MIDI events. Their frame offsets refer to the sample
+/// rate of the audio driver, so these nodes are never oversampled.
+pub(crate) fn uses_host_events(node_id: NodeId) -> bool {
+    matches!(
+        node_id,
+        NodeId::MidiP(_)
+            | NodeId::MpeP(_)
+            | NodeId::MidiCC(_)
+            | NodeId::MidiClk(_)
+            | NodeId::MidiOut(_)
+            | NodeId::MidiCCOut(_)
+            | NodeId::Arp(_)
+            | NodeId::TSeq(_)
+            | NodeId::TsLFO(_)
+            | NodeId::Scope(_)
+    )
+}
+
+/// Returns true if the node needs the [NodeExecContext] of the audio thread, because it
+/// reads the MIDI events or the external parameters, or sends MIDI events. The
+/// [WorkerPool] threads process all other nodes with their own context, which only
+/// mirrors the channel counts, the transport and the output guard.
+pub(crate) fn uses_exec_context(node_id: NodeId) -> bool {
+    uses_host_events(node_id)
+        || matches!(
+            node_id,
+            NodeId::ExtA(_)
+                | NodeId::ExtB(_)
+                | NodeId::ExtC(_)
+                | NodeId::ExtD(_)
+                | NodeId::ExtE(_)
+                | NodeId::ExtF(_)
+        )
+}
+
 /// Creates a [NodeConfigurator] and a [NodeExecutor] which are interconnected
 /// by ring buffers.
 pub fn new_node_engine() -> (NodeConfigurator, NodeExecutor) {
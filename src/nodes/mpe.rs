@@ -0,0 +1,360 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use super::{HxMidiEvent, HxTimedEvent};
+
+/// The maximum number of concurrent MPE notes the [MpeAllocator] tracks.
+pub const MAX_MPE_SLOTS: usize = 16;
+
+/// The CC that MPE controllers send the per note slide (Y axis) on.
+pub const MPE_SLIDE_CC: u8 = 74;
+
+/// An MPE (MIDI Polyphonic Expression) zone. Each note of the zone is played on it's
+/// own member channel, with it's own pitch bend, pressure and slide. The master
+/// channel carries the zone wide pitch bend.
+///
+/// Channels are counted from 0, so the lower zone has the master channel 0 (MIDI channel 1)
+/// and the upper zone the master channel 15 (MIDI channel 16).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MpeZone {
+    pub master: u8,
+    /// The number of member channels, following the master channel in the lower zone,
+    /// and preceding it in the upper zone.
+    pub members: u8,
+}
+
+impl MpeZone {
+    /// The lower zone with the master channel 0 and the member channels from 1 on.
+    pub fn lower(members: u8) -> Self {
+        Self { master: 0, members: members.clamp(1, 15) }
+    }
+
+    /// The upper zone with the master channel 15 and the member channels from 14 down.
+    pub fn upper(members: u8) -> Self {
+        Self { master: 15, members: members.clamp(1, 15) }
+    }
+
+    pub fn is_master(&self, channel: u8) -> bool {
+        channel == self.master
+    }
+
+    pub fn is_member(&self, channel: u8) -> bool {
+        if self.master == 0 {
+            channel >= 1 && channel <= self.members
+        } else {
+            channel < self.master && channel >= self.master.saturating_sub(self.members)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MpeSlot {
+    channel: u8,
+    note: u8,
+    active: bool,
+}
+
+/// The last per note expression values that were received on a member channel.
+#[derive(Debug, Clone, Copy)]
+struct MemberChannel {
+    bend: f32,
+    press: f32,
+    slide: f32,
+}
+
+impl MemberChannel {
+    fn new() -> Self {
+        Self { bend: 0.0, press: 0.0, slide: 0.0 }
+    }
+}
+
+/// Tracks the active notes of an [MpeZone] and assigns them to slots.
+///
+/// This is used by the [crate::nodes::NodeExecutor] to distribute the MPE events
+/// of a buffer period into [crate::nodes::NodeExecContext::mpe_notes], from where the
+/// `MpeP` nodes with a `slot` setting pick them up.
+///
+/// A new note takes the first free slot and keeps it until it's released. The slot gets
+/// the events of the member channel of the note and the pitch bend of the master channel.
+/// MPE controllers send the initial pitch bend, pressure and slide of a note before
+/// it's note on, these are passed to the slot right before the note on event.
+#[derive(Debug, Clone)]
+pub struct MpeAllocator {
+    zone: Option<MpeZone>,
+    slots: [MpeSlot; MAX_MPE_SLOTS],
+    channels: [MemberChannel; 16],
+}
+
+impl MpeAllocator {
+    pub fn new() -> Self {
+        Self {
+            zone: None,
+            slots: [MpeSlot { channel: 0, note: 0, active: false }; MAX_MPE_SLOTS],
+            channels: [MemberChannel::new(); 16],
+        }
+    }
+
+    /// Sets the MPE zone, `None` disables MPE. This also resets the allocation state.
+    pub fn set_zone(&mut self, zone: Option<MpeZone>) {
+        self.zone = zone;
+        self.reset();
+    }
+
+    pub fn zone(&self) -> Option<MpeZone> {
+        self.zone
+    }
+
+    /// Forgets about all currently playing notes.
+    pub fn reset(&mut self) {
+        self.slots = [MpeSlot { channel: 0, note: 0, active: false }; MAX_MPE_SLOTS];
+        self.channels = [MemberChannel::new(); 16];
+    }
+
+    /// Returns the note that is currently playing in the given slot, if any.
+    pub fn slot_note(&self, slot: usize) -> Option<(u8, u8)> {
+        let s = self.slots.get(slot)?;
+        if s.active {
+            Some((s.channel, s.note))
+        } else {
+            None
+        }
+    }
+
+    /// Distributes the MPE events in `notes` and `ccs` to the per slot event lists
+    /// in `slot_events`. Both lists need to be sorted by their timing. The slot lists
+    /// are cleared first. Events are not pushed beyond the capacity of the lists, so that
+    /// this does not allocate on the audio thread.
+    pub fn distribute(
+        &mut self,
+        notes: &[HxTimedEvent],
+        ccs: &[HxTimedEvent],
+        slot_events: &mut [Vec<HxTimedEvent>],
+    ) {
+        for evs in slot_events.iter_mut() {
+            evs.clear();
+        }
+
+        let zone = if let Some(zone) = self.zone { zone } else { return };
+
+        let (mut ni, mut ci) = (0, 0);
+        loop {
+            // Merge the lists, CCs first, because the initial slide precedes the note on:
+            let ev = match (notes.get(ni), ccs.get(ci)) {
+                (Some(n), Some(c)) if c.timing() <= n.timing() => {
+                    ci += 1;
+                    *c
+                }
+                (Some(n), _) => {
+                    ni += 1;
+                    *n
+                }
+                (None, Some(c)) => {
+                    ci += 1;
+                    *c
+                }
+                (None, None) => break,
+            };
+
+            self.distribute_event(&zone, ev, slot_events);
+        }
+    }
+
+    fn distribute_event(
+        &mut self,
+        zone: &MpeZone,
+        ev: HxTimedEvent,
+        slot_events: &mut [Vec<HxTimedEvent>],
+    ) {
+        let push = |evs: &mut Vec<HxTimedEvent>, ev: HxTimedEvent| {
+            if evs.len() < evs.capacity() {
+                evs.push(ev);
+            }
+        };
+
+        let kind = ev.kind();
+        let channel = match kind {
+            HxMidiEvent::NoteOn { channel, .. }
+            | HxMidiEvent::NoteOff { channel, .. }
+            | HxMidiEvent::CC { channel, .. }
+            | HxMidiEvent::PitchBend { channel, .. }
+            | HxMidiEvent::ChannelPressure { channel, .. }
+            | HxMidiEvent::PolyPressure { channel, .. } => channel,
+            _ => return,
+        };
+
+        if zone.is_master(channel) {
+            // The zone wide pitch bend goes to all slots:
+            if let HxMidiEvent::PitchBend { .. } = kind {
+                for evs in slot_events.iter_mut().take(MAX_MPE_SLOTS) {
+                    push(evs, ev);
+                }
+            }
+            return;
+        }
+
+        if !zone.is_member(channel) {
+            return;
+        }
+
+        let chan_state = &mut self.channels[channel as usize % 16];
+
+        match kind {
+            HxMidiEvent::NoteOn { note, .. } => {
+                let slot = self
+                    .slots
+                    .iter()
+                    .position(|s| s.active && s.channel == channel && s.note == note)
+                    .or_else(|| self.slots.iter().position(|s| !s.active));
+
+                if let Some(slot) = slot {
+                    self.slots[slot] = MpeSlot { channel, note, active: true };
+
+                    if let Some(evs) = slot_events.get_mut(slot) {
+                        let t = ev.timing();
+                        push(
+                            evs,
+                            HxTimedEvent::new_timed(
+                                t,
+                                HxMidiEvent::PitchBend { channel, value: chan_state.bend },
+                            ),
+                        );
+                        push(evs, HxTimedEvent::channel_pressure(t, channel, chan_state.press));
+                        push(evs, HxTimedEvent::cc(t, channel, MPE_SLIDE_CC, chan_state.slide));
+                        push(evs, ev);
+                    }
+                }
+            }
+            HxMidiEvent::NoteOff { note, .. } => {
+                let slot = self
+                    .slots
+                    .iter()
+                    .position(|s| s.active && s.channel == channel && s.note == note);
+
+                if let Some(slot) = slot {
+                    self.slots[slot].active = false;
+
+                    if let Some(evs) = slot_events.get_mut(slot) {
+                        push(evs, ev);
+                    }
+                }
+            }
+            HxMidiEvent::PolyPressure { note, .. } => {
+                let slot = self
+                    .slots
+                    .iter()
+                    .position(|s| s.active && s.channel == channel && s.note == note);
+
+                if let Some(evs) = slot.and_then(|slot| slot_events.get_mut(slot)) {
+                    push(evs, ev);
+                }
+            }
+            HxMidiEvent::PitchBend { value, .. }
+            | HxMidiEvent::ChannelPressure { value, .. }
+            | HxMidiEvent::CC { cc: MPE_SLIDE_CC, value, .. } => {
+                match kind {
+                    HxMidiEvent::PitchBend { .. } => chan_state.bend = value,
+                    HxMidiEvent::ChannelPressure { .. } => chan_state.press = value,
+                    _ => chan_state.slide = value,
+                }
+
+                for (slot, evs) in self.slots.iter().zip(slot_events.iter_mut()) {
+                    if slot.active && slot.channel == channel {
+                        push(evs, ev);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Default for MpeAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_mpe_zone_channels() {
+        let lower = MpeZone::lower(3);
+        assert!(lower.is_master(0));
+        assert!(!lower.is_member(0));
+        assert!(lower.is_member(1));
+        assert!(lower.is_member(3));
+        assert!(!lower.is_member(4));
+
+        let upper = MpeZone::upper(15);
+        assert!(upper.is_master(15));
+        assert!(!upper.is_member(15));
+        assert!(upper.is_member(14));
+        assert!(upper.is_member(0));
+    }
+
+    #[test]
+    fn check_mpe_alloc_distribute() {
+        let mut ma = MpeAllocator::new();
+
+        let mut slot_events = vec![];
+        slot_events.resize_with(MAX_MPE_SLOTS, || Vec::with_capacity(16));
+
+        let notes = [
+            HxTimedEvent::pitch_bend(1, 1, 0x3000),
+            HxTimedEvent::note_on(1, 1, 60, 1.0),
+            HxTimedEvent::note_on(2, 2, 64, 0.5),
+            HxTimedEvent::pitch_bend(3, 2, 0x2000),
+            HxTimedEvent::pitch_bend(4, 0, 0x2000),
+            HxTimedEvent::note_off(5, 1, 60),
+            HxTimedEvent::note_on(6, 3, 67, 0.5),
+        ];
+        let ccs = [HxTimedEvent::cc(1, 1, MPE_SLIDE_CC, 0.25), HxTimedEvent::cc(4, 2, 1, 0.5)];
+
+        // Without a zone nothing is distributed:
+        ma.distribute(&notes[..], &ccs[..], &mut slot_events[..]);
+        assert!(slot_events.iter().all(|evs| evs.is_empty()));
+
+        ma.set_zone(Some(MpeZone::lower(15)));
+        ma.distribute(&notes[..], &ccs[..], &mut slot_events[..]);
+
+        let dump = |evs: &Vec<HxTimedEvent>| {
+            evs.iter()
+                .map(|ev| match ev.kind() {
+                    HxMidiEvent::NoteOn { note, .. } => format!("{}:on{}", ev.timing(), note),
+                    HxMidiEvent::NoteOff { note, .. } => format!("{}:off{}", ev.timing(), note),
+                    HxMidiEvent::PitchBend { channel, value } => {
+                        format!("{}:bend{}={:.2}", ev.timing(), channel, value)
+                    }
+                    HxMidiEvent::ChannelPressure { value, .. } => {
+                        format!("{}:press={:.2}", ev.timing(), value)
+                    }
+                    HxMidiEvent::CC { value, .. } => format!("{}:slide={:.2}", ev.timing(), value),
+                    _ => "?".to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+
+        assert_eq!(
+            dump(&slot_events[0]),
+            "1:bend1=0.50 1:press=0.00 1:slide=0.25 1:on60 4:bend0=0.00 5:off60 \
+             6:bend3=0.00 6:press=0.00 6:slide=0.00 6:on67"
+        );
+        assert_eq!(
+            dump(&slot_events[1]),
+            "2:bend2=0.00 2:press=0.00 2:slide=0.00 2:on64 3:bend2=0.00 4:bend0=0.00"
+        );
+        assert_eq!(dump(&slot_events[2]), "4:bend0=0.00");
+        assert_eq!(ma.slot_note(0), Some((3, 67)));
+        assert_eq!(ma.slot_note(1), Some((2, 64)));
+        assert_eq!(ma.slot_note(2), None);
+
+        // The first free slot is taken by the next note:
+        let notes = [HxTimedEvent::note_off(0, 3, 67), HxTimedEvent::note_on(1, 4, 72, 1.0)];
+        ma.distribute(&notes[..], &[], &mut slot_events[..]);
+        assert_eq!(ma.slot_note(0), Some((4, 72)));
+    }
+}
@@ -2,11 +2,17 @@
 // This file is a part of HexoDSP. Released under GPL-3.0-or-later.
 // See README.md and COPYING for details.
 
+use super::midi_map::MidiMapTarget;
+use super::prog_crossfade;
+use super::prog_diff::ProgShape;
 use super::{
-    FeedbackFilter, GraphEvent, GraphMessage, HxMidiEvent, NodeOp, NodeProg, MAX_DSP_NODE_INPUTS,
-    UNUSED_MONITOR_IDX,
+    FeedbackFilter, GraphEvent, GraphMessage, HxMidiEvent, MidiMapping, MpeZone, NodeOp, NodeProg,
+    NodeStates, OversamplingGroup, ResolvedMidiMapping, VoiceStealing, WorkerPool,
+    MAX_DSP_NODE_INPUTS, OVERSAMPLING_FACTORS, UNUSED_MONITOR_IDX,
+};
+use crate::dsp::{
+    get_rand_node_id_seeded, node_factory, Node, NodeId, NodeInfo, ParamId, RandNodeSelector, SAtom,
 };
-use crate::dsp::{node_factory, Node, NodeId, NodeInfo, ParamId, SAtom};
 use crate::monitor::{new_monitor_processor, MinMaxMonitorSamples, Monitor, MON_SIG_CNT};
 use crate::nodes::drop_thread::DropThread;
 use crate::SampleLibrary;
@@ -14,6 +20,7 @@ use crate::{NodeGlobalData, NodeGlobalRef};
 
 use ringbuf::{Consumer, Producer, RingBuffer};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use synfx_dsp::AtomicFloat;
@@ -83,6 +90,8 @@ impl NodeInstance {
             out_connected: 0x0,
             in_connected: 0x0,
             inputs: vec![],
+            level: None,
+            fault: None,
         }
     }
 
@@ -240,6 +249,8 @@ pub struct NodeConfigurator {
     atoms: std::collections::HashMap<ParamId, NodeInputAtom>,
     /// Stores the most recently set atoms
     atom_values: std::collections::HashMap<ParamId, SAtom>,
+    /// Stores the oversampling factors of the nodes, see [NodeConfigurator::set_oversampling].
+    node_oversampling: std::collections::HashMap<NodeId, usize>,
 
     /// Holds a copy of the most recently updated output port feedback
     /// values. Update this by calling [NodeConfigurator::update_output_feedback].
@@ -248,6 +259,18 @@ pub struct NodeConfigurator {
     /// Holds the channel to the backend that sends output port feedback.
     /// This is queried by [NodeConfigurator::update_output_feedback].
     output_fb_cons: Option<Output<Vec<f32>>>,
+
+    /// The shape of the most recently uploaded [NodeProg], to upload only
+    /// the changes of the next one. See [NodeConfigurator::upload_prog].
+    uploaded_shape: Option<ProgShape>,
+    /// The crossfade time set with [NodeConfigurator::set_prog_crossfade].
+    prog_crossfade_ms: f32,
+
+    /// The MIDI CC mappings, see [NodeConfigurator::set_midi_mapping].
+    midi_map: Vec<MidiMapping>,
+    /// The parameter that is mapped to the next incoming CC,
+    /// see [NodeConfigurator::set_midi_learn].
+    midi_learn: Option<ParamId>,
 }
 
 pub(crate) struct SharedNodeConf {
@@ -264,6 +287,10 @@ pub(crate) struct SharedNodeConf {
     pub(crate) drop_thread: DropThread,
     /// Sample rate of the backend
     pub(crate) sample_rate: Arc<AtomicFloat>,
+    /// Holds the CPU load of the nodes, if CPU profiling is enabled.
+    pub(crate) node_cpu_load: Vec<Arc<AtomicFloat>>,
+    /// The number of sample frames the backend has processed.
+    pub(crate) audio_clock: Arc<AtomicU64>,
 }
 
 use super::node_exec::SharedNodeExec;
@@ -293,6 +320,13 @@ impl SharedNodeConf {
 
         let sample_rate = Arc::new(AtomicFloat::new(44100.0));
 
+        let mut node_cpu_load = Vec::new();
+        node_cpu_load
+            .resize_with(NODE_COMMUNICATION_BUFFER_SIZE / 2, || Arc::new(AtomicFloat::new(0.0)));
+        let exec_node_cpu_load = node_cpu_load.clone();
+
+        let audio_clock = Arc::new(AtomicU64::new(0));
+
         (
             Self {
                 node_ctx_values,
@@ -301,6 +335,8 @@ impl SharedNodeConf {
                 monitor,
                 drop_thread,
                 sample_rate: sample_rate.clone(),
+                node_cpu_load,
+                audio_clock: audio_clock.clone(),
             },
             SharedNodeExec {
                 node_ctx_values: exec_node_ctx_vals,
@@ -309,6 +345,8 @@ impl SharedNodeConf {
                 graph_event_prod: rb_ev_prod,
                 monitor_backend,
                 sample_rate,
+                node_cpu_load: exec_node_cpu_load,
+                audio_clock,
             },
         )
     }
@@ -337,7 +375,12 @@ impl NodeConfigurator {
                 param_modamt: std::collections::HashMap::new(),
                 atoms: std::collections::HashMap::new(),
                 atom_values: std::collections::HashMap::new(),
+                node_oversampling: std::collections::HashMap::new(),
                 node2idx: HashMap::new(),
+                uploaded_shape: None,
+                prog_crossfade_ms: 0.0,
+                midi_map: vec![],
+                midi_learn: None,
             },
             shared_exec,
         )
@@ -465,6 +508,26 @@ impl NodeConfigurator {
     /// before calling this. If no graph or the corresponding parameter is not active yet,
     /// then the value will be remembered until [NodeConfigurator::rebuild_node_ports] is called.
     pub fn set_param(&mut self, param: ParamId, at: SAtom) {
+        self.set_param_update(param, at, None);
+    }
+
+    /// Like [NodeConfigurator::set_param], but the parameter change is applied by the
+    /// [NodeExecutor] at the sample `frame` of the audio clock, see
+    /// [NodeConfigurator::audio_clock], like [NodeConfigurator::schedule_midi_event].
+    /// Frames that already passed are applied at the start of the next buffer period.
+    ///
+    /// If `ramp` is not `0`, the parameter ramps linearly to the new value over
+    /// `ramp` frames starting at `frame`. Otherwise it jumps to the new value without
+    /// the usual parameter smoothing. This makes automation reproducible, especially
+    /// for offline rendering.
+    ///
+    /// Atoms can't change in the middle of a buffer period, they are set at the start
+    /// of the buffer period that contains the `frame`.
+    pub fn set_param_at(&mut self, param: ParamId, at: SAtom, frame: u64, ramp: usize) {
+        self.set_param_update(param, at, Some((frame, ramp)));
+    }
+
+    fn set_param_update(&mut self, param: ParamId, at: SAtom, timed: Option<(u64, usize)>) {
         if param.is_atom() {
             let at = if let SAtom::AudioSample((path, None)) = at.clone() {
                 if !path.is_empty() {
@@ -492,10 +555,12 @@ impl NodeConfigurator {
                 nparam.value = at.clone();
 
                 let at_idx = nparam.at_idx;
-                let _ = self
-                    .shared
-                    .graph_update_prod
-                    .push(GraphMessage::AtomUpdate { at_idx, value: at });
+                let msg = if let Some((frame, _)) = timed {
+                    GraphMessage::TimedAtomUpdate { at_idx, value: at, frame }
+                } else {
+                    GraphMessage::AtomUpdate { at_idx, value: at }
+                };
+                let _ = self.shared.graph_update_prod.push(msg);
             }
         } else {
             self.param_values.insert(param, at.f());
@@ -505,10 +570,12 @@ impl NodeConfigurator {
                 nparam.value = value;
 
                 let input_idx = nparam.input_idx;
-                let _ = self
-                    .shared
-                    .graph_update_prod
-                    .push(GraphMessage::ParamUpdate { input_idx, value });
+                let msg = if let Some((frame, ramp)) = timed {
+                    GraphMessage::TimedParamUpdate { input_idx, value, frame, ramp }
+                } else {
+                    GraphMessage::ParamUpdate { input_idx, value }
+                };
+                let _ = self.shared.graph_update_prod.push(msg);
             }
         }
     }
@@ -597,6 +664,174 @@ impl NodeConfigurator {
         }
     }
 
+    /// Sets the number of worker threads that process independent nodes of the DSP
+    /// graph in parallel to the audio thread. `0` disables the parallel execution,
+    /// which is the default.
+    ///
+    /// This spawns the threads of a new [WorkerPool], so don't call it too often.
+    /// The threads take over the priority of the audio thread, and if they miss
+    /// their deadline, the audio thread processes the rest of the buffer period alone.
+    /// See [WorkerPool] for the details.
+    ///
+    /// Only programs that were built with [NodeConfigurator::add_prog_node_with_level],
+    /// like the ones of [crate::Matrix] and [crate::SynthConstructor], are executed
+    /// in parallel. Whether this pays off depends
+    /// on the patch: It needs several expensive independent signal paths.
+    pub fn set_worker_threads(&mut self, threads: usize) {
+        let pool = if threads > 0 {
+            Some(WorkerPool::new(threads, self.shared.node_cpu_load.len()))
+        } else {
+            None
+        };
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetWorkerPool { pool });
+    }
+
+    /// Sets the oversampling factor of a node, one of [OVERSAMPLING_FACTORS].
+    /// Returns false and ignores the call for any other factor.
+    ///
+    /// Nodes that directly follow each other in the [NodeProg] and have the same factor
+    /// are run together as one [OversamplingGroup] at `factor` times the sample rate.
+    /// The signals entering the group are upsampled and the signals leaving it are
+    /// downsampled with a lowpass filter. This reduces aliasing of nonlinear nodes,
+    /// like distortions or oscillators with hard sync. The `Out` and `Inp` nodes
+    /// always run at the normal sample rate.
+    ///
+    /// MIDI events and the transport position are not converted to the higher rate,
+    /// so the nodes that use them, like `MidiP`, `MidiOut`, `TSeq` or `TsLFO`, also
+    /// always run at the normal sample rate.
+    ///
+    /// The factor takes effect with the next [NodeConfigurator::upload_prog].
+    pub fn set_oversampling(&mut self, node_id: NodeId, factor: usize) -> bool {
+        if !OVERSAMPLING_FACTORS.contains(&factor) {
+            return false;
+        }
+
+        if factor == 1 {
+            self.node_oversampling.remove(&node_id);
+        } else {
+            self.node_oversampling.insert(node_id, factor);
+        }
+
+        true
+    }
+
+    /// Returns the oversampling factor of a node, see [NodeConfigurator::set_oversampling].
+    pub fn oversampling(&self, node_id: &NodeId) -> usize {
+        self.node_oversampling.get(node_id).copied().unwrap_or(1)
+    }
+
+    /// Enables or disables the measurement of the CPU time spent in each node
+    /// on the audio thread. Profiling adds a small overhead, so it's disabled
+    /// by default. Disabling it resets all loads to `0.0`.
+    ///
+    /// See also [NodeConfigurator::node_cpu_load].
+    pub fn set_cpu_profiling(&mut self, enabled: bool) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetCpuProfiling { enabled });
+    }
+
+    /// Sets the global random seed, from which the random number generators of all
+    /// nodes derive their seed, see [crate::dsp::node_random_seed]. The random number
+    /// generators of the running nodes are restarted. Setting the same seed again
+    /// restarts them from the same state, so rendering a patch after setting
+    /// it's seed is reproducible. The default is [crate::DEFAULT_RANDOM_SEED].
+    pub fn set_random_seed(&mut self, seed: u64) {
+        if let Ok(mut node_global) = self.node_global.lock() {
+            node_global.set_random_seed(seed);
+        }
+
+        // The nodes that are not part of the running program are reseeded by the
+        // executor too, so that they don't race with the audio thread.
+        let nodes = self.nodes.iter().map(|(_, _, node)| node.clone()).collect();
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetRandomSeed { seed, nodes });
+    }
+
+    /// Requests a snapshot of the internal state of the running nodes, like oscillator
+    /// phases, envelopes and sequencer positions. The snapshot is sent back as
+    /// [GraphEvent::NodeStates], retrieve it with [NodeConfigurator::next_event].
+    ///
+    /// The states are allocated here and only copied on the audio thread, see
+    /// [crate::nodes::NodeExecutor::copy_node_states]. The nodes with delay lines,
+    /// `Delay`, `AllP`, `Comb`, `PVerb` and `BowStri`, can't be copied in time and
+    /// are not part of the snapshot. Take it with
+    /// [crate::nodes::NodeExecutor::save_node_states] to get them too.
+    pub fn request_node_states(&mut self) {
+        // Fresh nodes provide the preallocated states. They get their own
+        // NodeGlobalData, so that the running nodes keep their tracker backends:
+        let node_global = NodeGlobalData::new_ref();
+        let mut states = NodeStates::new();
+
+        for op in self.uploaded_shape.iter().flat_map(|shape| shape.ops()) {
+            let node_id = op.node.id();
+
+            if let Some((node, _info)) = node_factory(node_id, &node_global) {
+                if let Some(mut state) = node.save_state() {
+                    if node.copy_state(&mut state) {
+                        states.insert(node_id, state);
+                    }
+                }
+            }
+        }
+
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SaveNodeStates { states });
+    }
+
+    /// Restores a snapshot of the node states received by
+    /// [NodeConfigurator::request_node_states] or taken by
+    /// [crate::nodes::NodeExecutor::save_node_states] in the running nodes.
+    /// The replaced states are sent to the drop thread, so the audio thread
+    /// doesn't free them.
+    pub fn load_node_states(&mut self, states: NodeStates) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::LoadNodeStates { states });
+    }
+
+    /// Returns the global random seed, see [NodeConfigurator::set_random_seed].
+    pub fn random_seed(&self) -> u64 {
+        self.node_global.lock().map(|g| g.random_seed()).unwrap_or(crate::DEFAULT_RANDOM_SEED)
+    }
+
+    /// Returns `count` random [NodeId]s. Unlike [crate::dsp::get_rand_node_id] the
+    /// random numbers are derived from the global random seed, so the sequence of
+    /// returned [NodeId]s repeats after [NodeConfigurator::set_random_seed].
+    pub fn rand_node_ids(&mut self, count: usize, sel: RandNodeSelector) -> Vec<NodeId> {
+        let seed = self
+            .node_global
+            .lock()
+            .map(|mut g| g.next_frontend_random())
+            .unwrap_or(crate::DEFAULT_RANDOM_SEED);
+
+        get_rand_node_id_seeded(count, sel, seed)
+    }
+
+    /// Enables the output guard of the [crate::nodes::NodeExecutor], which protects
+    /// the audio driver from blown up signals. `None` disables it, which is the default.
+    ///
+    /// After each node is processed, the guard checks it's outputs for NaN, infinite
+    /// and runaway values above [crate::nodes::GUARD_RUNAWAY_LEVEL]. If it finds any, the node is reset
+    /// via [crate::dsp::DspNode::reset], it's outputs are silenced for the current buffer
+    /// period and a [GraphEvent::NodeFault] is sent to the frontend. Denormal values are
+    /// flushed to zero. On top of that, the `Out` nodes hard limit their output to
+    /// `-limit..=limit` and silence non finite samples. A negative `limit` is used as
+    /// positive value, a NaN `limit` means there is no hard limit.
+    pub fn set_output_guard(&mut self, limit: Option<f32>) {
+        let limit = limit.map(|l| if l.is_nan() { f32::INFINITY } else { l.abs() });
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetOutputGuard { limit });
+    }
+
+    /// Returns the CPU load of the given node as fraction of the real time
+    /// that is available for processing the audio. A value of `0.1` means,
+    /// that the node takes 10% of the DSP budget. The value is aggregated
+    /// over a window of a quarter second.
+    ///
+    /// Returns `0.0` if CPU profiling is not enabled with
+    /// [NodeConfigurator::set_cpu_profiling].
+    pub fn node_cpu_load(&self, ni: &NodeId) -> f32 {
+        if let Some(idx) = self.unique_index_for(ni) {
+            self.shared.node_cpu_load.get(idx).map(|l| l.get()).unwrap_or(0.0)
+        } else {
+            0.0
+        }
+    }
+
     /// Triggers recalculation of the filtered values from the
     /// current LED values and output feedback.
     ///
@@ -715,6 +950,8 @@ impl NodeConfigurator {
         self.param_modamt.clear();
         self.atoms.clear();
         self.atom_values.clear();
+        self.node_oversampling.clear();
+        self.uploaded_shape = None;
 
         let _ = self.shared.graph_update_prod.push(GraphMessage::Clear { prog: NodeProg::empty() });
     }
@@ -879,6 +1116,24 @@ impl NodeConfigurator {
         }
     }
 
+    /// Like [NodeConfigurator::add_prog_node], but also stores the dependency `level`
+    /// of the node, as calculated by [crate::nodes::NodeGraphOrdering::node_level].
+    /// Nodes with the same level that are added right after each other don't depend on
+    /// each other and may be executed in parallel, see [NodeConfigurator::set_worker_threads].
+    pub fn add_prog_node_with_level(
+        &mut self,
+        prog: &mut NodeProg,
+        node_id: &NodeId,
+        level: usize,
+    ) {
+        if let Some((_node_info, Some(node_instance), _)) = self.node_by_id_mut(node_id) {
+            node_instance.mark_used();
+            let mut op = node_instance.as_op();
+            op.level = Some(level);
+            prog.append_op(op);
+        }
+    }
+
     /// Adds an adjacent output connection to the given node input.
     /// Will either create a new [NodeOp] in the [NodeProg] or append to an
     /// existing one. This means the order you set the to be executed node
@@ -926,6 +1181,13 @@ impl NodeConfigurator {
     /// It must not be set when a completely new set of node instances
     /// was created, for instance when a completely new patch was loaded.
     ///
+    /// If `copy_old_out` is set, only the differences to the previously uploaded
+    /// program are sent as [crate::nodes::NodeProgDiff]. The ops, buffers and
+    /// oversampling filters of the parts of the graph that did not change keep
+    /// their state, and nothing is sent at all if the program did not change.
+    /// The whole program is only replaced if a crossfade is configured with
+    /// [NodeConfigurator::set_prog_crossfade].
+    ///
     /// Here is an example on how to use the [NodeConfigurator]
     /// directly to setup and upload a [NodeProg]:
     ///
@@ -966,9 +1228,70 @@ impl NodeConfigurator {
             prog.atoms_mut()[param.at_idx] = param.value.clone();
         }
 
-        self.output_fb_cons = prog.take_feedback_consumer();
+        prog.ovs_groups =
+            OversamplingGroup::collect_groups(&prog, &|node_id| self.oversampling(&node_id));
+
+        let prev_shape = self.uploaded_shape.replace(ProgShape::new(&prog, self.mod_inputs()));
+        let upload_diff = copy_old_out && self.prog_crossfade_ms <= 0.0;
+
+        if let Some(prev_shape) = prev_shape.as_ref().filter(|_| upload_diff) {
+            let shape = self.uploaded_shape.as_ref().unwrap();
+
+            if let Some(mut diff) = prev_shape.diff(shape, prog) {
+                if let Some(buffers) = diff.buffers.as_mut() {
+                    buffers.initialize_input_buffers();
+                    self.output_fb_cons = buffers.take_feedback_consumer();
+                }
+
+                let _ = self.shared.graph_update_prod.push(GraphMessage::ProgDiff { diff });
+            }
+        } else {
+            self.output_fb_cons = prog.take_feedback_consumer();
+
+            // The crossfade needs to know which nodes the old and new program share:
+            let shared_ops = match prev_shape {
+                Some(prev_shape) if self.prog_crossfade_ms > 0.0 => {
+                    prog_crossfade::shared_ops(prev_shape.ops(), &prog)
+                }
+                _ => vec![],
+            };
+
+            let _ = self.shared.graph_update_prod.push(GraphMessage::NewProg {
+                prog,
+                copy_old_out,
+                shared_ops,
+            });
+        }
+
+        // The input and atom indices of the mapped parameters might have changed:
+        if !self.midi_map.is_empty() {
+            self.upload_midi_map();
+        }
+    }
+
+    /// Returns the input index of each [crate::nodes::ModOp] of the current [NodeProg].
+    fn mod_inputs(&self) -> Vec<usize> {
+        let mut mods: Vec<(usize, usize)> = self
+            .params
+            .values()
+            .filter_map(|param| param.modamt.map(|(mod_idx, _)| (mod_idx, param.input_idx)))
+            .collect();
+        mods.sort_unstable();
+        mods.into_iter().map(|(_, input_idx)| input_idx).collect()
+    }
 
-        let _ = self.shared.graph_update_prod.push(GraphMessage::NewProg { prog, copy_old_out });
+    /// Sets the time in milliseconds the audio output is crossfaded from the old to the
+    /// new [NodeProg] when a program is uploaded with [NodeConfigurator::upload_prog].
+    /// This prevents clicks when cells are re-routed while audio is playing.
+    ///
+    /// During the crossfade both programs are executed. Nodes that are part of both programs
+    /// are only executed once, so mostly the signal paths that were changed are blended.
+    ///
+    /// The default is `0.0`, which applies only the changes of the new program
+    /// instantly, see [NodeConfigurator::upload_prog].
+    pub fn set_prog_crossfade(&mut self, time_ms: f32) {
+        self.prog_crossfade_ms = time_ms;
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetProgCrossfade { time_ms });
     }
 
     /// Retrieves the feedback value for a specific output port of the
@@ -1013,6 +1336,182 @@ impl NodeConfigurator {
         let _ = self.shared.graph_update_prod.push(GraphMessage::InjectMidi { midi_ev });
     }
 
+    /// Schedules a [HxMidiEvent] to be fed into the DSP graph at the sample `frame`
+    /// of the audio clock, see [NodeConfigurator::audio_clock]. The event arrives
+    /// like the events passed to [crate::nodes::NodeExecutor::feed_midi_events_from],
+    /// frames that already passed are played at the start of the next buffer period.
+    ///
+    /// At most [crate::nodes::MAX_SCHEDULED_MIDI_EVENTS] events can be pending.
+    /// Returns false if the event could not be sent to the audio thread,
+    /// because the message queue is full.
+    pub fn schedule_midi_event(&mut self, frame: u64, midi_ev: HxMidiEvent) -> bool {
+        self.shared.graph_update_prod.push(GraphMessage::ScheduleMidi { midi_ev, frame }).is_ok()
+    }
+
+    /// Drops all scheduled MIDI events that are not yet due.
+    /// See also [NodeConfigurator::schedule_midi_event].
+    pub fn clear_scheduled_midi(&mut self) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::ClearScheduledMidi);
+    }
+
+    /// Enables or disables the MIDI recording. While enabled, the [crate::nodes::NodeExecutor]
+    /// sends every MIDI event it receives, except the system realtime events, also as
+    /// [GraphEvent::RecordedMIDI] with the frame of the audio clock it is played at.
+    /// See also [crate::midi_recorder::MidiRecorder].
+    pub fn set_midi_recording(&mut self, enabled: bool) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetMidiRecording { enabled });
+    }
+
+    /// Returns the number of sample frames the [crate::nodes::NodeExecutor] has processed
+    /// so far. Use this together with [NodeConfigurator::sample_rate] to schedule
+    /// MIDI events in sync with the audio with [NodeConfigurator::schedule_midi_event].
+    pub fn audio_clock(&self) -> u64 {
+        self.shared.audio_clock.load(Ordering::Relaxed)
+    }
+
+    /// Returns the sample rate of the [crate::nodes::NodeExecutor].
+    pub fn sample_rate(&self) -> f32 {
+        self.shared.sample_rate.get()
+    }
+
+    /// Configures the voice allocation on the audio thread. Incoming MIDI notes are
+    /// distributed to `voices` voices, which can be picked up by the `MidiP` nodes
+    /// with their `voice` setting. If all voices are busy, `stealing` determines
+    /// which voice is taken away for a new note.
+    ///
+    /// See also [crate::nodes::PolyVoices] for replicating a voice sub-graph.
+    pub fn set_voice_allocation(&mut self, voices: usize, stealing: VoiceStealing) {
+        let _ =
+            self.shared.graph_update_prod.push(GraphMessage::SetVoiceAlloc { voices, stealing });
+    }
+
+    /// Configures the MPE zone of the incoming MIDI on the audio thread. The notes of
+    /// the zone are assigned to slots, which can be picked up by the `MpeP` nodes with
+    /// their `slot` setting. `None` disables the MPE processing.
+    pub fn set_mpe_zone(&mut self, zone: Option<MpeZone>) {
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetMpeZone { zone });
+    }
+
+    /// Maps a MIDI CC to a parameter or setting. The [crate::nodes::NodeExecutor] applies
+    /// the CC values directly to the parameter, without a `MidiCC` node or any edges in the
+    /// graph. A parameter can only be mapped to one CC, an existing mapping of the parameter
+    /// is replaced. Returns false if the parameter can't be mapped,
+    /// see [MidiMapping::is_mappable].
+    ///
+    /// The audio thread does not report the new parameter values back. To keep the parameter
+    /// values of the frontend up to date, pass the [GraphEvent::MIDI] events to
+    /// [NodeConfigurator::update_midi_mapped_params]. [crate::Matrix::handle_graph_events]
+    /// does that for you.
+    pub fn set_midi_mapping(&mut self, mapping: MidiMapping) -> bool {
+        if !MidiMapping::is_mappable(&mapping.param) {
+            return false;
+        }
+
+        if let Some(m) = self.midi_map.iter_mut().find(|m| m.param == mapping.param) {
+            *m = mapping;
+        } else {
+            self.midi_map.push(mapping);
+        }
+
+        self.upload_midi_map();
+        true
+    }
+
+    /// Removes the MIDI CC mapping of the parameter.
+    pub fn remove_midi_mapping(&mut self, param: &ParamId) {
+        self.midi_map.retain(|m| m.param != *param);
+        self.upload_midi_map();
+    }
+
+    /// Removes all MIDI CC mappings.
+    pub fn clear_midi_mappings(&mut self) {
+        self.midi_map.clear();
+        self.midi_learn = None;
+        self.upload_midi_map();
+    }
+
+    /// Returns the current MIDI CC mappings, see [NodeConfigurator::set_midi_mapping].
+    pub fn midi_mappings(&self) -> &[MidiMapping] {
+        &self.midi_map[..]
+    }
+
+    /// Maps the next CC that arrives at [NodeConfigurator::update_midi_mapped_params]
+    /// to the parameter, over the whole range of the parameter. `None` cancels the
+    /// MIDI learn.
+    pub fn set_midi_learn(&mut self, param: Option<ParamId>) {
+        self.midi_learn = param.filter(MidiMapping::is_mappable);
+    }
+
+    /// Returns the parameter that waits for a CC, see [NodeConfigurator::set_midi_learn].
+    pub fn midi_learn(&self) -> Option<ParamId> {
+        self.midi_learn
+    }
+
+    /// Updates the frontend values of the parameters, that the [crate::nodes::NodeExecutor]
+    /// changed due to the MIDI CC mappings. Also completes a pending MIDI learn.
+    /// Call this for each [GraphEvent::MIDI] event.
+    /// Returns the parameters that were changed or learned.
+    pub fn update_midi_mapped_params(&mut self, midi_ev: &HxMidiEvent) -> Vec<ParamId> {
+        let mut changed = vec![];
+
+        if let HxMidiEvent::CC { channel, cc, value } = *midi_ev {
+            if let Some(param) = self.midi_learn.take() {
+                // The audio thread did not know the mapping yet, so send the learned value:
+                let mapping = MidiMapping::new(channel, cc, param);
+                if self.set_midi_mapping(mapping) {
+                    self.set_param(param, mapping.map_atom(value));
+                }
+            }
+
+            for mapping in self.midi_map.iter() {
+                if mapping.channel != channel || mapping.cc != cc {
+                    continue;
+                }
+
+                // The value is already set on the audio thread, so don't send it:
+                if mapping.param.is_atom() {
+                    let at = mapping.map_atom(value);
+                    if let Some(atom) = self.atoms.get_mut(&mapping.param) {
+                        atom.value = at.clone();
+                    }
+                    self.atom_values.insert(mapping.param, at);
+                } else {
+                    let v = mapping.map_value(value);
+                    if let Some(param) = self.params.get_mut(&mapping.param) {
+                        param.value = v;
+                    }
+                    self.param_values.insert(mapping.param, v);
+                }
+
+                if !changed.contains(&mapping.param) {
+                    changed.push(mapping.param);
+                }
+            }
+        }
+
+        changed
+    }
+
+    /// Sends the MIDI CC mappings with the input and atom indices of the current
+    /// parameters to the [crate::nodes::NodeExecutor].
+    fn upload_midi_map(&mut self) {
+        let map = self
+            .midi_map
+            .iter()
+            .filter_map(|mapping| {
+                let target = if mapping.param.is_atom() {
+                    MidiMapTarget::Atom(self.atoms.get(&mapping.param)?.at_idx)
+                } else {
+                    MidiMapTarget::Param(self.params.get(&mapping.param)?.input_idx)
+                };
+
+                Some(ResolvedMidiMapping { mapping: *mapping, target })
+            })
+            .collect();
+
+        let _ = self.shared.graph_update_prod.push(GraphMessage::SetMidiMap { map });
+    }
+
     /// Returns the next [GraphEvent] from the DSP/audio/backend thread.
     pub fn next_event(&mut self) -> Option<GraphEvent> {
         self.shared.graph_event_cons.pop()
@@ -0,0 +1,80 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The maximum number of [NodeEvent] that can be emitted per buffer period.
+/// Further events are dropped.
+pub const MAX_NODE_EVENTS_PER_BLOCK: usize = 256;
+
+/// An event that a node emits while it's processed, to inform the frontend.
+/// They are delivered as [crate::nodes::GraphEvent::Node].
+///
+/// See also [crate::nodes::NodeExecContext::emit_event].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeEvent {
+    /// The `TSeq` node advanced to a new row of it's pattern.
+    TSeqRow { row: usize },
+    /// The `Sampl` node played past the end of the sample, also emitted
+    /// each time a looping sample wraps around.
+    SampleEnd,
+    /// The `Scope` node was triggered and started a new capture.
+    ScopeTrigger,
+    /// The envelope of the `Adsr` node has finished, at the same time it's
+    /// `eoet` output triggers.
+    EnvEnd,
+}
+
+/// A fixed size list of the events the nodes emit in the current buffer
+/// period, such as [NodeEvent] or MIDI output events.
+///
+/// The nodes of one level might be processed in parallel by the
+/// [crate::nodes::WorkerPool], so the slots are claimed atomically
+/// and emitting an event does not need a mutable reference.
+pub(crate) struct EventQueue<T: Copy> {
+    events: Box<[UnsafeCell<T>]>,
+    len: AtomicUsize,
+}
+
+// Each slot is only written by the thread that claimed it, and only
+// read on the audio thread after all nodes have been processed.
+unsafe impl<T: Copy + Send> Sync for EventQueue<T> {}
+unsafe impl<T: Copy + Send> Send for EventQueue<T> {}
+
+impl<T: Copy> EventQueue<T> {
+    /// Creates a queue for `capacity` events, the slots are initialized with `empty`.
+    pub(crate) fn new(capacity: usize, empty: T) -> Self {
+        let events =
+            (0..capacity).map(|_| UnsafeCell::new(empty)).collect::<Vec<_>>().into_boxed_slice();
+
+        Self { events, len: AtomicUsize::new(0) }
+    }
+
+    /// Adds an event, returns false if the queue is full.
+    #[inline]
+    pub(crate) fn push(&self, ev: T) -> bool {
+        let idx = self.len.fetch_add(1, Ordering::Relaxed);
+        if idx >= self.events.len() {
+            return false;
+        }
+
+        unsafe {
+            *self.events[idx].get() = ev;
+        }
+
+        true
+    }
+
+    /// Passes all events to `f` and clears the queue.
+    pub(crate) fn drain<F: FnMut(T)>(&mut self, mut f: F) {
+        let len = (*self.len.get_mut()).min(self.events.len());
+
+        for ev in self.events[0..len].iter_mut() {
+            f(*ev.get_mut());
+        }
+
+        *self.len.get_mut() = 0;
+    }
+}
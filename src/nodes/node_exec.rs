@@ -2,11 +2,19 @@
 // This file is a part of HexoDSP. Released under GPL-3.0-or-later.
 // See README.md and COPYING for details.
 
+use super::midi_map::MidiMapTarget;
+use super::node_events::EventQueue;
+use super::oversampling::process_group;
+use super::prog_crossfade::{CaptureContext, ProgCrossfade};
+use super::worker_pool::WorkerPool;
 use super::{
-    DropMsg, EventWindowing, GraphEvent, GraphMessage, HxMidiEvent, HxTimedEvent, NodeProg,
-    MAX_INJ_MIDI_EVENTS, MAX_SMOOTHERS, UNUSED_MONITOR_IDX,
+    DropMsg, EventWindowing, GraphEvent, GraphMessage, HostTransport, HxMidiEvent, HxTimedEvent,
+    MidiCCDecoder, MpeAllocator, NodeEvent, NodeProg, NodeStates, OpSlot, ResolvedMidiMapping,
+    SignalFault, VoiceAllocator, GUARD_RUNAWAY_LEVEL, MAX_AUDIO_CHANNELS, MAX_INJ_MIDI_EVENTS,
+    MAX_MPE_SLOTS, MAX_NODE_EVENTS_PER_BLOCK, MAX_SCHEDULED_MIDI_EVENTS, MAX_SMOOTHERS,
+    MAX_TIMED_PARAM_UPDATES, MAX_VOICES, UNUSED_MONITOR_IDX,
 };
-use crate::dsp::{Node, NodeContext, MAX_BLOCK_SIZE};
+use crate::dsp::{Node, NodeContext, NodeId, SAtom, MAX_BLOCK_SIZE};
 use crate::monitor::{MonitorBackend, MON_SIG_CNT};
 use crate::util::Smoother;
 use synfx_dsp::AtomicFloat;
@@ -15,6 +23,7 @@ use crate::log;
 use std::io::Write;
 
 use ringbuf::{Consumer, Producer};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 //use core::arch::x86_64::{
@@ -26,6 +35,8 @@ use std::sync::Arc;
 
 pub const MAX_MIDI_NOTES_PER_BLOCK: usize = 512;
 pub const MAX_MIDI_CC_PER_BLOCK: usize = 1024;
+pub const MAX_MIDI_REALTIME_PER_BLOCK: usize = 256;
+pub const MAX_MIDI_OUT_PER_BLOCK: usize = 256;
 
 /// Holds the complete allocation of nodes and
 /// the program. New Nodes or the program is
@@ -42,6 +53,9 @@ pub struct NodeExecutor {
     /// these will refresh the input buffers:
     pub(crate) target_refresh: Vec<(usize, f32)>,
 
+    /// Contains the pending timed parameter and atom updates, sorted by frame.
+    timed_updates: Vec<TimedUpdate>,
+
     /// Contains the to be executed nodes and output operations.
     /// Is copied from the input ringbuffer when a corresponding
     /// message arrives.
@@ -62,8 +76,117 @@ pub struct NodeExecutor {
     /// A small buffer for injected [HxMidiEvent]
     injected_midi: Vec<HxMidiEvent>,
 
+    /// The MIDI events scheduled by the frontend, sorted by their frame of the audio clock.
+    /// See [crate::nodes::NodeConfigurator::schedule_midi_event].
+    scheduled_midi: Vec<(u64, HxMidiEvent)>,
+
+    /// Set if [NodeExecutor::feed_midi_events_from] was called for the next
+    /// buffer period.
+    midi_fed: bool,
+
+    /// The number of sample frames processed so far.
+    audio_clock: u64,
+
+    /// If set, the MIDI events are also sent to the frontend as [GraphEvent::RecordedMIDI].
+    midi_recording: bool,
+
+    /// The MIDI events the nodes sent in the last buffer period, sorted by frame.
+    midi_output: Vec<HxTimedEvent>,
+
+    /// The MIDI CC mappings, see [crate::nodes::NodeConfigurator::set_midi_mapping].
+    midi_map: Vec<ResolvedMidiMapping>,
+
     /// A flag to remember if we already initialized the logger on the audio thread.
     dsp_log_init: bool,
+
+    /// Measures the time spent in each node, if enabled.
+    cpu_profiler: CpuProfiler,
+
+    /// Holds the previous [NodeProg] while it is faded out after a program swap.
+    xfade: ProgCrossfade,
+
+    /// Processes independent nodes in parallel, if set.
+    workers: Option<WorkerPool>,
+
+    /// The oversampling factor each node currently has it's sample rate
+    /// set up for, indexed by the node index.
+    node_ovs: Vec<usize>,
+}
+
+/// A parameter or atom update that is waiting for it's frame,
+/// see [crate::nodes::NodeConfigurator::set_param_at].
+#[derive(Debug)]
+struct TimedUpdate {
+    /// The frame offset relative to the start of the current buffer period.
+    frame: usize,
+    kind: TimedUpdateKind,
+}
+
+#[derive(Debug)]
+enum TimedUpdateKind {
+    Param { input_idx: usize, value: f32, ramp: usize },
+    Atom { at_idx: usize, value: SAtom },
+}
+
+/// The length of the window the per node CPU time is aggregated over, in seconds.
+const CPU_PROFILE_WINDOW: f64 = 0.25;
+
+/// Aggregates the time spent in each [crate::nodes::NodeOp] over a window of
+/// [CPU_PROFILE_WINDOW] seconds and publishes the load via [SharedNodeExec::node_cpu_load].
+pub(crate) struct CpuProfiler {
+    pub(crate) enabled: bool,
+    /// Accumulated processing time in seconds, indexed by the unique node index.
+    node_time: Vec<f64>,
+    /// Number of frames processed in the current window.
+    window_frames: usize,
+}
+
+impl CpuProfiler {
+    pub(crate) fn new(node_count: usize) -> Self {
+        Self { enabled: false, node_time: vec![0.0; node_count], window_frames: 0 }
+    }
+
+    fn reset(&mut self, node_cpu_load: &[Arc<AtomicFloat>]) {
+        for t in self.node_time.iter_mut() {
+            *t = 0.0;
+        }
+        self.window_frames = 0;
+
+        for load in node_cpu_load.iter() {
+            load.set(0.0);
+        }
+    }
+
+    #[inline]
+    fn add_time(&mut self, idx: usize, time: f64) {
+        if let Some(t) = self.node_time.get_mut(idx) {
+            *t += time;
+        }
+    }
+
+    /// Adds the time measured by `other`, which is reset. Used for
+    /// collecting the time measured by the [WorkerPool] threads.
+    pub(crate) fn merge_from(&mut self, other: &mut CpuProfiler) {
+        for (t, other_t) in self.node_time.iter_mut().zip(other.node_time.iter_mut()) {
+            *t += *other_t;
+            *other_t = 0.0;
+        }
+    }
+
+    fn end_block(&mut self, nframes: usize, sample_rate: f32, node_cpu_load: &[Arc<AtomicFloat>]) {
+        self.window_frames += nframes;
+
+        let window_len = self.window_frames as f64 / sample_rate as f64;
+        if window_len < CPU_PROFILE_WINDOW {
+            return;
+        }
+
+        for (t, load) in self.node_time.iter_mut().zip(node_cpu_load.iter()) {
+            load.set((*t / window_len) as f32);
+            *t = 0.0;
+        }
+        self.window_frames = 0;
+    }
 }
 
 /// Contains anything that connects the [NodeExecutor] with the frontend part.
@@ -84,6 +207,12 @@ pub(crate) struct SharedNodeExec {
     pub(crate) monitor_backend: MonitorBackend,
     /// The current sample rate of the backend
     pub(crate) sample_rate: Arc<AtomicFloat>,
+    /// The CPU load of each node, as fraction of the real time
+    /// available for processing the audio. Indexed by the unique node index.
+    /// Only updated if CPU profiling is enabled.
+    pub(crate) node_cpu_load: Vec<Arc<AtomicFloat>>,
+    /// The number of sample frames the backend has processed.
+    pub(crate) audio_clock: Arc<AtomicU64>,
 }
 
 /// Contains audio driver context informations. Such as the number
@@ -154,24 +283,378 @@ pub trait ExternalParams: Send + Sync {
 /// thread to send MIDI and provide external parameters.
 pub struct NodeExecContext {
     /// List of current MIDI note events that were passed into HexoDSP in this buffer period.
+    /// Also contains the pitch bend, channel pressure and polyphonic key pressure events,
+    /// see [HxMidiEvent::is_note_event].
     pub midi_notes: Vec<HxTimedEvent>,
-    /// List of current MIDI CC events that were passed into HexoDSP in this buffer period.
+    /// List of current MIDI CC and program change events that were passed into HexoDSP
+    /// in this buffer period. Also contains the [HxMidiEvent::CC14], [HxMidiEvent::NRPN]
+    /// and [HxMidiEvent::RPN] events decoded by [NodeExecContext::cc_decoder].
     pub midi_ccs: Vec<HxTimedEvent>,
+    /// List of current MIDI clock, start, stop, continue and song position events
+    /// that were passed into HexoDSP in this buffer period.
+    pub midi_realtime: Vec<HxTimedEvent>,
+    /// The MIDI note events of this buffer period, distributed to the voices
+    /// by [NodeExecContext::voice_alloc]. There are always [MAX_VOICES] lists.
+    pub voice_notes: Vec<Vec<HxTimedEvent>>,
+    /// Allocates the note events in [NodeExecContext::midi_notes] to voices.
+    pub voice_alloc: VoiceAllocator,
+    /// The MPE events of this buffer period, distributed to the slots by
+    /// [NodeExecContext::mpe_alloc]. There are always [MAX_MPE_SLOTS] lists.
+    pub mpe_notes: Vec<Vec<HxTimedEvent>>,
+    /// Allocates the notes of the MPE zone to slots.
+    pub mpe_alloc: MpeAllocator,
+    /// Decodes the 14-bit CC pairs and NRPN/RPN sequences in [NodeExecContext::midi_ccs].
+    pub cc_decoder: MidiCCDecoder,
+    /// The undecoded CC events while [NodeExecContext::decode_midi_ccs] runs.
+    midi_ccs_raw: Vec<HxTimedEvent>,
     /// Handle to the external parameters, external meaning parameters that come in via eg. the
     /// plugin API or are provided elsewhere on the audio thread.
     pub ext_param: Option<Arc<dyn ExternalParams>>,
+    /// The number of audio input channels the audio driver provides.
+    /// See also [NodeExecutor::set_channel_count].
+    pub in_channels: usize,
+    /// The number of audio output channels the audio driver provides.
+    /// See also [NodeExecutor::set_channel_count].
+    pub out_channels: usize,
+    /// The transport and tempo information of the host.
+    /// See also [NodeExecutor::set_transport].
+    pub transport: HostTransport,
+    /// The hard limit for the `Out` nodes, if the output guard is enabled.
+    /// See also [crate::nodes::NodeConfigurator::set_output_guard].
+    pub output_guard: Option<f32>,
+    /// The events emitted by the nodes in the current buffer period.
+    node_events: EventQueue<(NodeId, NodeEvent)>,
+    /// The MIDI events sent by the nodes in the current buffer period.
+    midi_out: EventQueue<HxTimedEvent>,
 }
 
 impl NodeExecContext {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         let midi_notes = Vec::with_capacity(MAX_MIDI_NOTES_PER_BLOCK);
         let midi_ccs = Vec::with_capacity(MAX_MIDI_CC_PER_BLOCK);
-        Self { midi_notes, midi_ccs, ext_param: None }
+        let midi_ccs_raw = Vec::with_capacity(MAX_MIDI_CC_PER_BLOCK);
+        let midi_realtime = Vec::with_capacity(MAX_MIDI_REALTIME_PER_BLOCK);
+        let mut voice_notes = Vec::with_capacity(MAX_VOICES);
+        voice_notes.resize_with(MAX_VOICES, || Vec::with_capacity(MAX_MIDI_NOTES_PER_BLOCK));
+        let mut mpe_notes = Vec::with_capacity(MAX_MPE_SLOTS);
+        mpe_notes.resize_with(MAX_MPE_SLOTS, || Vec::with_capacity(MAX_MIDI_NOTES_PER_BLOCK));
+        Self {
+            midi_notes,
+            midi_ccs,
+            midi_realtime,
+            voice_notes,
+            voice_alloc: VoiceAllocator::new(),
+            mpe_notes,
+            mpe_alloc: MpeAllocator::new(),
+            cc_decoder: MidiCCDecoder::new(),
+            midi_ccs_raw,
+            ext_param: None,
+            in_channels: 2,
+            out_channels: 2,
+            transport: HostTransport::new(),
+            output_guard: None,
+            node_events: EventQueue::new(
+                MAX_NODE_EVENTS_PER_BLOCK,
+                (NodeId::Nop, NodeEvent::SampleEnd),
+            ),
+            midi_out: EventQueue::new(MAX_MIDI_OUT_PER_BLOCK, HxTimedEvent::note_off(0, 0, 0)),
+        }
     }
 
     fn set_sample_rate(&mut self, _srate: f32) {}
 
-    fn clear(&mut self) {}
+    /// Copies the state of the host, that nodes without [crate::nodes::uses_exec_context]
+    /// read, from the `main` context of the audio thread. Used for the contexts of the
+    /// [WorkerPool] threads.
+    pub(crate) fn mirror_host_state(&mut self, main: &NodeExecContext) {
+        self.in_channels = main.in_channels;
+        self.out_channels = main.out_channels;
+        self.transport = main.transport;
+        self.output_guard = main.output_guard;
+    }
+
+    /// Moves the events emitted with [NodeExecContext::emit_event] into the `main` context.
+    pub(crate) fn forward_node_events(&mut self, main: &mut NodeExecContext) {
+        self.node_events.drain(|ev| {
+            main.node_events.push(ev);
+        });
+    }
+
+    fn clear(&mut self) {
+        self.voice_alloc.reset();
+        self.mpe_alloc.reset();
+        self.cc_decoder.reset();
+    }
+
+    /// Emits a [NodeEvent] for the frontend, where it arrives as [GraphEvent::Node].
+    /// Pass [NodeContext::node_id] as `node_id`. At most [crate::nodes::MAX_NODE_EVENTS_PER_BLOCK]
+    /// events are delivered per buffer period, the rest is dropped and false is returned.
+    #[inline]
+    pub fn emit_event(&self, node_id: NodeId, ev: NodeEvent) -> bool {
+        self.node_events.push((node_id, ev))
+    }
+
+    /// Sends a MIDI event out of the DSP graph, the `ev` timing is the frame
+    /// in the current buffer period. The host reads the events with
+    /// [NodeExecutor::midi_output] after [NodeExecutor::process].
+    /// At most [MAX_MIDI_OUT_PER_BLOCK] events are sent per buffer period,
+    /// the rest is dropped and false is returned.
+    #[inline]
+    pub fn send_midi(&self, ev: HxTimedEvent) -> bool {
+        self.midi_out.push(ev)
+    }
+
+    /// Puts the event into the list for it's kind: [NodeExecContext::midi_notes],
+    /// [NodeExecContext::midi_ccs] or [NodeExecContext::midi_realtime].
+    fn push_midi_event(&mut self, ev: HxTimedEvent) {
+        let kind = ev.kind();
+        if kind.is_note_event() {
+            self.midi_notes.push(ev);
+        } else if kind.is_realtime() {
+            self.midi_realtime.push(ev);
+        } else {
+            self.midi_ccs.push(ev);
+        }
+    }
+
+    /// Like [NodeExecContext::push_midi_event], but keeps the list sorted by timing.
+    /// Events on the same frame keep their order. The event is dropped if the list is full.
+    fn insert_midi_event(&mut self, ev: HxTimedEvent) {
+        let kind = ev.kind();
+        let list = if kind.is_note_event() {
+            &mut self.midi_notes
+        } else if kind.is_realtime() {
+            &mut self.midi_realtime
+        } else {
+            &mut self.midi_ccs
+        };
+
+        if list.len() < list.capacity() {
+            let idx = list.partition_point(|e| e.timing() <= ev.timing());
+            list.insert(idx, ev);
+        }
+    }
+
+    /// Distributes the current [NodeExecContext::midi_notes] to the
+    /// [NodeExecContext::voice_notes].
+    pub fn distribute_voice_notes(&mut self) {
+        self.voice_alloc.distribute(&self.midi_notes[..], &mut self.voice_notes[..]);
+    }
+
+    /// Distributes the MPE events of the current [NodeExecContext::midi_notes] and
+    /// [NodeExecContext::midi_ccs] to the [NodeExecContext::mpe_notes].
+    pub fn distribute_mpe_notes(&mut self) {
+        self.mpe_alloc.distribute(
+            &self.midi_notes[..],
+            &self.midi_ccs[..],
+            &mut self.mpe_notes[..],
+        );
+    }
+
+    /// Adds the high resolution controller events, that the [NodeExecContext::cc_decoder]
+    /// decodes from the CC events in [NodeExecContext::midi_ccs], right after the CC
+    /// event that completed them. Events that don't fit into the list are dropped.
+    pub fn decode_midi_ccs(&mut self) {
+        std::mem::swap(&mut self.midi_ccs, &mut self.midi_ccs_raw);
+        self.midi_ccs.clear();
+
+        for ev in self.midi_ccs_raw.iter() {
+            if self.midi_ccs.len() < self.midi_ccs.capacity() {
+                self.midi_ccs.push(*ev);
+            }
+
+            if let Some(decoded) = self.cc_decoder.decode(ev.kind()) {
+                if self.midi_ccs.len() < self.midi_ccs.capacity() {
+                    self.midi_ccs.push(HxTimedEvent::new_timed(ev.timing(), decoded));
+                }
+            }
+        }
+    }
+}
+
+/// Executes the [NodeOp]s of the `prog` for one buffer period.
+///
+/// If `shared` is given, the `prog` is a program that is faded out after a program swap.
+/// For the nodes that are also executed by the new program, the outputs are copied from it
+/// instead of processing the node a second time. The `Out` nodes don't have any state,
+/// so they are processed by both programs. An [crate::nodes::OversamplingGroup] that
+/// shares any node with the new program is not processed at all, only the outputs of the
+/// shared nodes are copied.
+fn process_ops(
+    prog: &mut NodeProg,
+    shared: Option<(&NodeProg, &[Option<usize>])>,
+    ctx: &mut dyn NodeAudioContext,
+    exec_ctx: &mut NodeExecContext,
+    ctx_vals: &[Arc<AtomicFloat>],
+    profiler: &mut CpuProfiler,
+) {
+    let nframes = ctx.nframes();
+    let mut op_idx = 0;
+
+    while op_idx < prog.prog.len() {
+        if let Some(g_idx) = prog.ovs_group_of(op_idx) {
+            let (start, end) = prog.ovs_groups[g_idx].ops();
+
+            let shared_group = shared.filter(|(_, shared_ops)| {
+                (start..end).any(|i| matches!(shared_ops.get(i), Some(Some(_))))
+            });
+
+            if let Some((new_prog, shared_ops)) = shared_group {
+                for (i, op) in prog.prog.iter().enumerate().take(end).skip(start) {
+                    let out = op.out_idxlen;
+                    let new_out = shared_ops
+                        .get(i)
+                        .copied()
+                        .flatten()
+                        .map(|new_i| new_prog.prog[new_i].out_idxlen);
+
+                    for out_idx in out.0..out.1 {
+                        let mut buf = prog.host_out_buf(out_idx);
+                        if let Some(new_out) = new_out {
+                            let new_buf = new_prog.host_out_buf(new_out.0 + out_idx - out.0);
+                            buf.write_from(new_buf.slice(nframes));
+                        } else {
+                            buf.fill(0.0);
+                        }
+                    }
+                }
+            } else {
+                process_group(prog, g_idx, ctx, exec_ctx, ctx_vals, profiler);
+            }
+
+            op_idx = end;
+            continue;
+        }
+
+        if let Some((new_prog, shared_ops)) = shared {
+            if let Some(Some(new_op_idx)) = shared_ops.get(op_idx) {
+                let op = &prog.prog[op_idx];
+                if !matches!(op.node.id(), NodeId::Out(_)) {
+                    let out = op.out_idxlen;
+                    let new_out = new_prog.prog[*new_op_idx].out_idxlen;
+                    for (old_pb, new_out_idx) in
+                        prog.out[out.0..out.1].iter_mut().zip(new_out.0..new_out.1)
+                    {
+                        old_pb.write_from(new_prog.host_out_buf(new_out_idx).slice(nframes));
+                    }
+                    op_idx += 1;
+                    continue;
+                }
+            }
+        }
+
+        process_op(prog.op_slot(op_idx), ctx, exec_ctx, ctx_vals, profiler);
+        op_idx += 1;
+    }
+}
+
+/// Processes the [crate::nodes::NodeOp] at `op_idx` of the `prog`. If it's the first op of
+/// an [crate::nodes::OversamplingGroup], the whole group is processed.
+#[inline]
+pub(crate) fn process_step(
+    prog: &mut NodeProg,
+    op_idx: usize,
+    ctx: &mut dyn NodeAudioContext,
+    exec_ctx: &mut NodeExecContext,
+    ctx_vals: &[Arc<AtomicFloat>],
+    profiler: &mut CpuProfiler,
+) {
+    match prog.ovs_group_of(op_idx) {
+        Some(g_idx) => process_group(prog, g_idx, ctx, exec_ctx, ctx_vals, profiler),
+        None => process_op(prog.op_slot(op_idx), ctx, exec_ctx, ctx_vals, profiler),
+    }
+}
+
+/// Processes the [crate::nodes::NodeOp] of the `slot`.
+#[inline]
+pub(crate) fn process_op(
+    slot: OpSlot,
+    ctx: &mut dyn NodeAudioContext,
+    exec_ctx: &mut NodeExecContext,
+    ctx_vals: &[Arc<AtomicFloat>],
+    profiler: &mut CpuProfiler,
+) {
+    let nframes = ctx.nframes();
+
+    for modop in slot.modops.iter_mut() {
+        modop.process(nframes);
+    }
+
+    process_op_node(slot, ctx, exec_ctx, ctx_vals, profiler);
+}
+
+/// Processes only the node of the [crate::nodes::NodeOp] of the `slot`, without
+/// it's modulators.
+#[inline]
+pub(crate) fn process_op_node(
+    slot: OpSlot,
+    ctx: &mut dyn NodeAudioContext,
+    exec_ctx: &mut NodeExecContext,
+    ctx_vals: &[Arc<AtomicFloat>],
+    profiler: &mut CpuProfiler,
+) {
+    let op = &*slot.op;
+    let ctx_idx = op.idx as usize * 2;
+
+    let op_start = if profiler.enabled { Some(std::time::Instant::now()) } else { None };
+
+    op.node.process(
+        ctx,
+        exec_ctx,
+        &NodeContext {
+            node_id: op.node.id(),
+            out_connected: op.out_connected,
+            in_connected: op.in_connected,
+            params: slot.params,
+        },
+        slot.atoms,
+        slot.inputs,
+        slot.outputs,
+        &ctx_vals[ctx_idx..ctx_idx + 2],
+    );
+
+    if let Some(op_start) = op_start {
+        profiler.add_time(op.idx as usize, op_start.elapsed().as_secs_f64());
+    }
+
+    if exec_ctx.output_guard.is_some() {
+        guard_outputs(slot, ctx.nframes());
+    }
+}
+
+/// Checks the outputs of the [NodeOp] of the `slot` for NaN, infinite and runaway
+/// values and flushes denormals to zero. A misbehaving node is reset, it's outputs
+/// are silenced and the fault is recorded in [NodeOp::fault] for the frontend.
+#[inline]
+fn guard_outputs(slot: OpSlot, nframes: usize) {
+    let mut fault = None;
+    for buf in slot.outputs.iter_mut() {
+        for v in buf.slice_mut(nframes).iter_mut() {
+            if !v.is_finite() {
+                fault = Some(SignalFault::NotFinite);
+                break;
+            } else if v.abs() > GUARD_RUNAWAY_LEVEL {
+                fault = Some(SignalFault::Runaway);
+                break;
+            } else if v.is_subnormal() {
+                *v = 0.0;
+            }
+        }
+
+        if fault.is_some() {
+            break;
+        }
+    }
+
+    if let Some(fault) = fault {
+        slot.op.node.reset();
+
+        for buf in slot.outputs.iter_mut() {
+            buf.fill(0.0);
+        }
+
+        slot.op.fault = Some(fault);
+    }
 }
 
 impl NodeExecutor {
@@ -179,18 +662,32 @@ impl NodeExecutor {
         let mut smoothers = Vec::new();
         smoothers.resize_with(MAX_SMOOTHERS, || (0, Smoother::new()));
 
-        let target_refresh = Vec::with_capacity(MAX_SMOOTHERS);
+        // Timed updates refresh their input buffers too:
+        let target_refresh = Vec::with_capacity(MAX_SMOOTHERS + MAX_TIMED_PARAM_UPDATES);
+        let timed_updates = Vec::with_capacity(MAX_TIMED_PARAM_UPDATES);
         let injected_midi = Vec::with_capacity(MAX_INJ_MIDI_EVENTS);
+        let cpu_profiler = CpuProfiler::new(shared.node_cpu_load.len());
 
         NodeExecutor {
             smoothers,
             target_refresh,
+            timed_updates,
             sample_rate: 44100.0,
             prog: NodeProg::empty(),
             monitor_signal_cur_inp_indices: [UNUSED_MONITOR_IDX; MON_SIG_CNT],
             exec_ctx: NodeExecContext::new(),
             dsp_log_init: false,
             injected_midi,
+            scheduled_midi: Vec::with_capacity(MAX_SCHEDULED_MIDI_EVENTS),
+            midi_fed: false,
+            audio_clock: 0,
+            midi_recording: false,
+            midi_output: Vec::with_capacity(MAX_MIDI_OUT_PER_BLOCK),
+            midi_map: vec![],
+            cpu_profiler,
+            xfade: ProgCrossfade::new(),
+            workers: None,
+            node_ovs: vec![1; 256],
             shared,
         }
     }
@@ -212,10 +709,21 @@ impl NodeExecutor {
                         let _ = write!(w, "[dbg] Cleared graph ({} nodes)", self.prog.prog.len());
                     });
 
+                    if let Some(old_prog) = self.xfade.stop() {
+                        let _ = self.shared.graph_drop_prod.push(DropMsg::Prog { prog: old_prog });
+                    }
+
                     let prev_prog = std::mem::replace(&mut self.prog, prog);
                     let _ = self.shared.graph_drop_prod.push(DropMsg::Prog { prog: prev_prog });
+
+                    // The nodes are created anew at the normal sample rate:
+                    self.node_ovs.fill(1);
+
+                    if let Some(workers) = self.workers.as_mut() {
+                        workers.update_schedule(&self.prog);
+                    }
                 }
-                GraphMessage::NewProg { prog, copy_old_out } => {
+                GraphMessage::NewProg { prog, copy_old_out, mut shared_ops } => {
                     let mut prev_prog = std::mem::replace(&mut self.prog, prog);
 
                     //                    unsafe {
@@ -273,8 +781,23 @@ impl NodeExecutor {
                     }
 
                     self.prog.assign_outputs();
+                    self.update_oversampling();
 
-                    let _ = self.shared.graph_drop_prod.push(DropMsg::Prog { prog: prev_prog });
+                    if let Some(workers) = self.workers.as_mut() {
+                        workers.update_schedule(&self.prog);
+                    }
+
+                    if let Some(drop_prog) =
+                        self.xfade.start(prev_prog, &mut shared_ops, self.sample_rate)
+                    {
+                        let _ = self.shared.graph_drop_prod.push(DropMsg::Prog { prog: drop_prog });
+                    }
+                    if shared_ops.capacity() > 0 {
+                        let _ = self
+                            .shared
+                            .graph_drop_prod
+                            .push(DropMsg::SharedOps { ops: shared_ops });
+                    }
 
                     log(|w| {
                         let _ = write!(
@@ -284,26 +807,147 @@ impl NodeExecutor {
                         );
                     });
                 }
-                GraphMessage::AtomUpdate { at_idx, value } => {
-                    let prog = &mut self.prog;
-                    let garbage = std::mem::replace(&mut prog.atoms[at_idx], value);
+                GraphMessage::ProgDiff { mut diff } => {
+                    // A running crossfade refers to the op indices of the current program:
+                    if let Some(old_prog) = self.xfade.stop() {
+                        let _ = self.shared.graph_drop_prod.push(DropMsg::Prog { prog: old_prog });
+                    }
+
+                    self.prog.unlock_buffers();
+
+                    if let Some(buffers) = diff.buffers.as_mut() {
+                        self.prog.swap_buffers(buffers);
+                    }
 
-                    let _ = self.shared.graph_drop_prod.push(DropMsg::Atom { atom: garbage });
+                    std::mem::swap(&mut self.prog.prog, &mut diff.ops);
+
+                    for (group, keep) in diff.ovs_groups.iter_mut().zip(diff.ovs_keep.iter()) {
+                        if let Some(old_idx) = keep {
+                            group.take_state(&mut self.prog.ovs_groups[*old_idx]);
+                        }
+                    }
+                    std::mem::swap(&mut self.prog.ovs_groups, &mut diff.ovs_groups);
+
+                    self.prog.assign_outputs();
+                    self.update_oversampling();
+
+                    if let Some(workers) = self.workers.as_mut() {
+                        workers.update_schedule(&self.prog);
+                    }
+
+                    log(|w| {
+                        let _ = write!(
+                            w,
+                            "[dbg] Updated graph (changed={} removed={} node count={})",
+                            diff.changed_ops,
+                            diff.removed_ops,
+                            self.prog.prog.len()
+                        );
+                    });
+
+                    let _ = self.shared.graph_drop_prod.push(DropMsg::ProgDiff { diff });
+                }
+                GraphMessage::AtomUpdate { at_idx, value } => {
+                    self.set_atom(at_idx, value);
                 }
                 GraphMessage::ParamUpdate { input_idx, value } => {
                     self.set_param(input_idx, value);
                 }
+                GraphMessage::TimedAtomUpdate { at_idx, value, frame } => {
+                    self.push_timed_update(TimedUpdate {
+                        frame: frame.saturating_sub(self.audio_clock) as usize,
+                        kind: TimedUpdateKind::Atom { at_idx, value },
+                    });
+                }
+                GraphMessage::TimedParamUpdate { input_idx, value, frame, ramp } => {
+                    self.push_timed_update(TimedUpdate {
+                        frame: frame.saturating_sub(self.audio_clock) as usize,
+                        kind: TimedUpdateKind::Param { input_idx, value, ramp },
+                    });
+                }
                 GraphMessage::ModamtUpdate { mod_idx, modamt } => {
                     self.set_modamt(mod_idx, modamt);
                 }
                 GraphMessage::SetMonitor { bufs } => {
                     self.monitor_signal_cur_inp_indices = bufs;
                 }
+                GraphMessage::SetVoiceAlloc { voices, stealing } => {
+                    self.exec_ctx.voice_alloc.configure(voices, stealing);
+                }
+                GraphMessage::SetMpeZone { zone } => {
+                    self.exec_ctx.mpe_alloc.set_zone(zone);
+                }
+                GraphMessage::SetProgCrossfade { time_ms } => {
+                    self.xfade.set_time_ms(time_ms);
+                }
+                GraphMessage::SetWorkerPool { pool } => {
+                    let prev_pool = std::mem::replace(&mut self.workers, pool);
+
+                    if let Some(workers) = self.workers.as_mut() {
+                        workers.update_schedule(&self.prog);
+                    }
+
+                    if let Some(pool) = prev_pool {
+                        let _ = self.shared.graph_drop_prod.push(DropMsg::WorkerPool { pool });
+                    }
+                }
+                GraphMessage::SetOutputGuard { limit } => {
+                    self.exec_ctx.output_guard = limit;
+                }
+                GraphMessage::SetRandomSeed { seed, nodes } => {
+                    for op in self.prog.prog.iter() {
+                        op.node.set_random_seed(seed);
+                    }
+                    if let Some(old_prog) = &self.xfade.old_prog {
+                        for op in old_prog.prog.iter() {
+                            op.node.set_random_seed(seed);
+                        }
+                    }
+                    for node in nodes.iter() {
+                        node.set_random_seed(seed);
+                    }
+
+                    let _ = self.shared.graph_drop_prod.push(DropMsg::Nodes { nodes });
+                }
+                GraphMessage::SetMidiMap { map } => {
+                    let map = std::mem::replace(&mut self.midi_map, map);
+                    let _ = self.shared.graph_drop_prod.push(DropMsg::MidiMap { map });
+                }
+                GraphMessage::SaveNodeStates { mut states } => {
+                    self.copy_node_states(&mut states);
+
+                    // Don't free the states here, if the frontend does not keep up:
+                    if let Err(GraphEvent::NodeStates(states)) =
+                        self.shared.graph_event_prod.push(GraphEvent::NodeStates(states))
+                    {
+                        let _ = self.shared.graph_drop_prod.push(DropMsg::NodeStates { states });
+                    }
+                }
+                GraphMessage::LoadNodeStates { mut states } => {
+                    self.load_node_states(&mut states);
+                    let _ = self.shared.graph_drop_prod.push(DropMsg::NodeStates { states });
+                }
+                GraphMessage::SetCpuProfiling { enabled } => {
+                    self.cpu_profiler.reset(&self.shared.node_cpu_load[..]);
+                    self.cpu_profiler.enabled = enabled;
+                }
                 GraphMessage::InjectMidi { midi_ev } => {
                     if self.injected_midi.len() < MAX_INJ_MIDI_EVENTS {
                         self.injected_midi.push(midi_ev);
                     }
                 }
+                GraphMessage::ScheduleMidi { midi_ev, frame } => {
+                    if self.scheduled_midi.len() < MAX_SCHEDULED_MIDI_EVENTS {
+                        let idx = self.scheduled_midi.partition_point(|(f, _)| *f <= frame);
+                        self.scheduled_midi.insert(idx, (frame, midi_ev));
+                    }
+                }
+                GraphMessage::ClearScheduledMidi => {
+                    self.scheduled_midi.clear();
+                }
+                GraphMessage::SetMidiRecording { enabled } => {
+                    self.midi_recording = enabled;
+                }
             }
         }
     }
@@ -312,47 +956,79 @@ impl NodeExecutor {
         self.exec_ctx.ext_param = Some(ext_param);
     }
 
+    /// Sets the number of audio input and output channels the audio driver provides
+    /// in the [NodeAudioContext]. The counts are clamped to `1..=MAX_AUDIO_CHANNELS`.
+    /// The default is 2 inputs and 2 outputs. The `Inp` and `Out` nodes select a pair
+    /// of these channels with their `bank` setting.
+    pub fn set_channel_count(&mut self, inputs: usize, outputs: usize) {
+        self.exec_ctx.in_channels = inputs.clamp(1, MAX_AUDIO_CHANNELS);
+        self.exec_ctx.out_channels = outputs.clamp(1, MAX_AUDIO_CHANNELS);
+    }
+
+    /// Returns the number of audio input channels. See also [NodeExecutor::set_channel_count].
+    pub fn input_channels(&self) -> usize {
+        self.exec_ctx.in_channels
+    }
+
+    /// Returns the number of audio output channels. See also [NodeExecutor::set_channel_count].
+    pub fn output_channels(&self) -> usize {
+        self.exec_ctx.out_channels
+    }
+
+    /// Sets the transport and tempo information of the host, call this before
+    /// [NodeExecutor::process]. Nodes like `TsLFO`, `TSeq`, `Delay` and `Scope` can
+    /// synchronize to it.
+    ///
+    /// The song position is advanced by the [NodeExecutor] after each buffer period
+    /// while the transport is playing, so you only need to call this when the
+    /// host transport changes.
+    pub fn set_transport(&mut self, transport: HostTransport) {
+        self.exec_ctx.transport = transport;
+    }
+
+    /// Returns the current transport and tempo information.
+    pub fn transport(&self) -> &HostTransport {
+        &self.exec_ctx.transport
+    }
+
     pub fn set_sample_rate(&mut self, sample_rate: f32) {
         self.sample_rate = sample_rate;
         self.shared.sample_rate.set(sample_rate);
         self.exec_ctx.set_sample_rate(sample_rate);
 
-        for op in self.prog.prog.iter() {
-            op.node.set_sample_rate(sample_rate);
+        for (op_idx, op) in self.prog.prog.iter().enumerate() {
+            op.node.set_sample_rate(sample_rate * self.prog.oversampling_at(op_idx) as f32);
         }
+        self.prog.set_ovs_sample_rate(sample_rate);
 
         for sm in self.smoothers.iter_mut() {
             sm.1.set_sample_rate(sample_rate);
         }
     }
 
-    #[inline]
-    pub fn feed_midi_events_from<F: FnMut() -> Option<HxTimedEvent>>(&mut self, mut f: F) {
-        self.exec_ctx.midi_notes.clear();
-        self.exec_ctx.midi_ccs.clear();
+    /// Sets up the sample rate of the nodes whose oversampling factor
+    /// changed with the new [NodeProg].
+    fn update_oversampling(&mut self) {
+        self.prog.set_ovs_sample_rate(self.sample_rate);
 
-        if self.injected_midi.len() > 0 {
-            for ev in self.injected_midi.iter().rev() {
-                let ev = HxTimedEvent::new_timed(0, *ev);
-                if ev.is_cc() {
-                    self.exec_ctx.midi_ccs.push(ev);
-                } else {
-                    self.exec_ctx.midi_notes.push(ev);
-                }
-                let _ = self.shared.graph_event_prod.push(GraphEvent::MIDI(ev.kind()));
-            }
+        for (op_idx, op) in self.prog.prog.iter().enumerate() {
+            let factor = self.prog.oversampling_at(op_idx);
+            let node_factor = &mut self.node_ovs[op.idx as usize];
 
-            self.injected_midi.clear();
+            if *node_factor != factor {
+                *node_factor = factor;
+                op.node.set_sample_rate(self.sample_rate * factor as f32);
+            }
         }
+    }
 
-        while let Some(ev) = f() {
-            if ev.is_cc() {
-                self.exec_ctx.midi_ccs.push(ev);
-            } else {
-                self.exec_ctx.midi_notes.push(ev);
-            }
+    #[inline]
+    pub fn feed_midi_events_from<F: FnMut() -> Option<HxTimedEvent>>(&mut self, mut f: F) {
+        self.start_midi_feed();
 
-            let _ = self.shared.graph_event_prod.push(GraphEvent::MIDI(ev.kind()));
+        while let Some(ev) = f() {
+            self.exec_ctx.push_midi_event(ev);
+            self.report_midi_event(ev);
 
             if self.exec_ctx.midi_ccs.len() == MAX_MIDI_CC_PER_BLOCK {
                 break;
@@ -360,6 +1036,104 @@ impl NodeExecutor {
             if self.exec_ctx.midi_notes.len() == MAX_MIDI_NOTES_PER_BLOCK {
                 break;
             }
+            if self.exec_ctx.midi_realtime.len() == MAX_MIDI_REALTIME_PER_BLOCK {
+                break;
+            }
+        }
+
+        self.midi_fed = true;
+    }
+
+    /// Clears the MIDI events of the previous buffer period and adds the injected
+    /// MIDI events, see [crate::nodes::NodeConfigurator::inject_midi_event].
+    fn start_midi_feed(&mut self) {
+        self.exec_ctx.midi_notes.clear();
+        self.exec_ctx.midi_ccs.clear();
+        self.exec_ctx.midi_realtime.clear();
+
+        while let Some(ev) = self.injected_midi.pop() {
+            let ev = HxTimedEvent::new_timed(0, ev);
+            self.exec_ctx.push_midi_event(ev);
+            self.report_midi_event(ev);
+        }
+    }
+
+    /// Sends a MIDI event of the next buffer period to the frontend as [GraphEvent::MIDI],
+    /// and as [GraphEvent::RecordedMIDI] if the MIDI recording is enabled.
+    fn report_midi_event(&mut self, ev: HxTimedEvent) {
+        let kind = ev.kind();
+
+        // The MIDI clock is not passed on, to not flood the frontend with events:
+        if !matches!(kind, HxMidiEvent::Clock) {
+            let _ = self.shared.graph_event_prod.push(GraphEvent::MIDI(kind));
+        }
+
+        // Realtime events can't be stored in a Standard MIDI File:
+        if self.midi_recording && !kind.is_realtime() {
+            let frame = self.audio_clock + ev.timing() as u64;
+            let _ = self.shared.graph_event_prod.push(GraphEvent::RecordedMIDI(frame, kind));
+        }
+    }
+
+    /// Returns the number of sample frames processed so far. This is the clock the
+    /// frames of [crate::nodes::NodeConfigurator::schedule_midi_event] refer to.
+    pub fn audio_clock(&self) -> u64 {
+        self.audio_clock
+    }
+
+    /// Adds the scheduled MIDI events that are due in the next `nframes` to the
+    /// events of this buffer period, and distributes them to the voices.
+    /// If no MIDI events were fed for this buffer period, the ones of the previous
+    /// buffer period are cleared, so they don't trigger the nodes again.
+    fn finish_midi_feed(&mut self, nframes: usize) {
+        if !self.midi_fed {
+            self.start_midi_feed();
+        }
+        self.midi_fed = false;
+
+        let block_end = self.audio_clock + nframes as u64;
+        let due = self.scheduled_midi.partition_point(|(frame, _)| *frame < block_end);
+
+        for i in 0..due {
+            let (frame, ev) = self.scheduled_midi[i];
+            // Events that are already late are played at the start of the buffer period:
+            let ev = HxTimedEvent::new_timed(frame.saturating_sub(self.audio_clock) as usize, ev);
+            self.exec_ctx.insert_midi_event(ev);
+            self.report_midi_event(ev);
+        }
+        self.scheduled_midi.drain(0..due);
+
+        self.exec_ctx.decode_midi_ccs();
+        self.exec_ctx.distribute_voice_notes();
+        self.exec_ctx.distribute_mpe_notes();
+        self.apply_midi_map();
+    }
+
+    /// Applies the CC events of this buffer period to the parameters they are
+    /// mapped to, see [crate::nodes::NodeConfigurator::set_midi_mapping].
+    fn apply_midi_map(&mut self) {
+        if self.midi_map.is_empty() {
+            return;
+        }
+
+        for i in 0..self.exec_ctx.midi_ccs.len() {
+            if let HxMidiEvent::CC { channel, cc, value } = self.exec_ctx.midi_ccs[i].kind() {
+                for j in 0..self.midi_map.len() {
+                    let ResolvedMidiMapping { mapping, target } = self.midi_map[j];
+                    if mapping.channel != channel || mapping.cc != cc {
+                        continue;
+                    }
+
+                    match target {
+                        MidiMapTarget::Param(input_idx) => {
+                            self.set_param(input_idx, mapping.map_value(value));
+                        }
+                        MidiMapTarget::Atom(at_idx) => {
+                            self.set_atom(at_idx, mapping.map_atom(value));
+                        }
+                    }
+                }
+            }
         }
     }
 
@@ -368,6 +1142,65 @@ impl NodeExecutor {
         &self.prog
     }
 
+    /// Takes a snapshot of the internal state of the nodes in the current [NodeProg],
+    /// see [crate::dsp::DspNode::save_state]. Call this in between two calls to
+    /// [NodeExecutor::process]. The snapshot can be restored with
+    /// [NodeExecutor::load_node_states], also into another [NodeExecutor] that runs the
+    /// same patch.
+    ///
+    /// Saving the states clones the nodes and allocates memory, don't call this
+    /// from a realtime audio callback. Use [NodeExecutor::copy_node_states] there.
+    pub fn save_node_states(&self) -> NodeStates {
+        let mut states = NodeStates::new();
+
+        for op in self.prog.prog.iter() {
+            if let Some(state) = op.node.save_state() {
+                states.insert(op.node.id(), state);
+            }
+        }
+
+        states
+    }
+
+    /// Like [NodeExecutor::save_node_states], but copies the states into `states`,
+    /// which were preallocated for the nodes of the current [NodeProg] on another
+    /// thread, see [crate::dsp::DspNode::copy_state]. This does not allocate, so it
+    /// can be called from the audio thread. Returns the number of copied nodes.
+    pub fn copy_node_states(&self, states: &mut NodeStates) -> usize {
+        let mut copied = 0;
+
+        for op in self.prog.prog.iter() {
+            if let Some(state) = states.get_mut(&op.node.id()) {
+                if op.node.copy_state(state) {
+                    copied += 1;
+                }
+            }
+        }
+
+        copied
+    }
+
+    /// Restores the node states saved by [NodeExecutor::save_node_states] into the
+    /// nodes of the current [NodeProg]. The states are swapped with the ones of the
+    /// nodes, so this does not allocate. Afterwards `states` holds the previous states
+    /// of the restored nodes. Returns the number of restored nodes.
+    pub fn load_node_states(&mut self, states: &mut NodeStates) -> usize {
+        let mut restored = 0;
+
+        for (op_idx, op) in self.prog.prog.iter().enumerate() {
+            if let Some(state) = states.get_mut(&op.node.id()) {
+                if op.node.load_state(state) {
+                    // The state might come from an executor with another sample rate:
+                    let factor = self.prog.oversampling_at(op_idx) as f32;
+                    op.node.set_sample_rate(self.sample_rate * factor);
+                    restored += 1;
+                }
+            }
+        }
+
+        restored
+    }
+
     pub fn get_nodes(&self) -> Vec<Node> {
         self.prog.prog.iter().map(|op| op.node.clone()).collect::<Vec<Node>>()
     }
@@ -406,11 +1239,116 @@ impl NodeExecutor {
         }
     }
 
+    fn push_timed_update(&mut self, upd: TimedUpdate) {
+        if self.timed_updates.len() >= MAX_TIMED_PARAM_UPDATES {
+            // No space left, apply it right away at the next buffer period:
+            match upd.kind {
+                TimedUpdateKind::Param { input_idx, value, .. } => {
+                    self.set_param(input_idx, value);
+                }
+                TimedUpdateKind::Atom { at_idx, value } => self.set_atom(at_idx, value),
+            }
+            return;
+        }
+
+        // Updates for the same frame are applied in the order they arrived:
+        let pos = self.timed_updates.partition_point(|u| u.frame <= upd.frame);
+        self.timed_updates.insert(pos, upd);
+    }
+
+    #[inline]
+    fn set_atom(&mut self, at_idx: usize, value: SAtom) {
+        if at_idx >= self.prog.atoms.len() {
+            return;
+        }
+
+        let garbage = std::mem::replace(&mut self.prog.atoms[at_idx], value);
+
+        // Settings and params hold no heap data and can be dropped right here. This
+        // spares the drop queue a message for each atom update by the MIDI mappings.
+        if !matches!(garbage, SAtom::Setting(_) | SAtom::Param(_)) {
+            let _ = self.shared.graph_drop_prod.push(DropMsg::Atom { atom: garbage });
+        }
+    }
+
+    /// Applies the timed updates that are due in the current buffer period
+    /// of `nframes`. Parameters are written into the input buffers from their
+    /// exact frame on, atoms are set for the whole buffer period.
+    #[inline]
+    fn process_timed_updates(&mut self, nframes: usize) {
+        let due = self.timed_updates.partition_point(|u| u.frame < nframes);
+
+        for i in 0..due {
+            let frame = self.timed_updates[i].frame;
+
+            match &mut self.timed_updates[i].kind {
+                TimedUpdateKind::Atom { at_idx, value } => {
+                    let at_idx = *at_idx;
+                    let value = std::mem::replace(value, SAtom::setting(0));
+                    self.set_atom(at_idx, value);
+                }
+                TimedUpdateKind::Param { input_idx, value, ramp } => {
+                    let prog = &mut self.prog;
+                    let (input_idx, value, ramp) = (*input_idx, *value, *ramp);
+
+                    if input_idx >= prog.params.len() {
+                        continue;
+                    }
+
+                    // The timed update takes over from any smoothing in progress:
+                    for (sm_inp_idx, smoother) in
+                        self.smoothers.iter_mut().filter(|s| !s.1.is_done())
+                    {
+                        if *sm_inp_idx == input_idx {
+                            smoother.stop();
+                        }
+                    }
+
+                    let inp = &mut prog.inp[input_idx];
+                    let current = inp.read(frame.saturating_sub(1));
+
+                    let ramp_smoother = if ramp > 0 {
+                        self.smoothers.iter_mut().find(|s| s.1.is_done())
+                    } else {
+                        None
+                    };
+
+                    let mut last_v = value;
+                    if let Some((sm_inp_idx, smoother)) = ramp_smoother {
+                        *sm_inp_idx = input_idx;
+                        smoother.set_ramp(current, value, ramp);
+
+                        for frame in frame..nframes {
+                            last_v = smoother.next();
+                            inp.write(frame, last_v);
+                        }
+                    } else {
+                        for frame in frame..nframes {
+                            inp.write(frame, value);
+                        }
+                    }
+
+                    prog.params[input_idx] = last_v;
+                    self.target_refresh.push((input_idx, last_v));
+                }
+            }
+        }
+
+        // Drop the applied atom placeholders outside the loop:
+        self.timed_updates.drain(0..due);
+
+        for upd in self.timed_updates.iter_mut() {
+            upd.frame -= nframes;
+        }
+    }
+
     #[inline]
     fn process_smoothers(&mut self, nframes: usize) {
         let prog = &mut self.prog;
 
-        while let Some((idx, v)) = self.target_refresh.pop() {
+        // Refresh in the order the values were pushed, so that the
+        // value of a later timed update wins over a finished smoother:
+        for (idx, v) in self.target_refresh.drain(..) {
             prog.inp[idx].fill(v);
         }
 
@@ -432,8 +1370,6 @@ impl NodeExecutor {
 
     #[inline]
     pub fn process<T: NodeAudioContext>(&mut self, ctx: &mut T) {
-        // let tb = std::time::Instant::now();
-
         if !self.dsp_log_init && crate::log::init_thread_logger("dsp") {
             self.dsp_log_init = true;
             crate::log(|w| {
@@ -441,42 +1377,87 @@ impl NodeExecutor {
             });
         }
 
+        self.finish_midi_feed(ctx.nframes());
         self.process_smoothers(ctx.nframes());
+        self.process_timed_updates(ctx.nframes());
 
-        let ctx_vals = &mut self.shared.node_ctx_values;
-        let prog = &mut self.prog;
+        let nframes = ctx.nframes();
+        let sample_rate = self.sample_rate;
+        let ctx_vals = &self.shared.node_ctx_values[..];
         let exec_ctx = &mut self.exec_ctx;
+        let profiler = &mut self.cpu_profiler;
+        let xfade = &mut self.xfade;
+
+        if xfade.is_active() {
+            {
+                let new_ctx = &mut CaptureContext::new(ctx, &mut xfade.new_out);
+                if let Some(workers) = self.workers.as_mut() {
+                    workers.process(
+                        &mut self.prog,
+                        new_ctx,
+                        exec_ctx,
+                        ctx_vals,
+                        sample_rate,
+                        profiler,
+                    );
+                } else {
+                    process_ops(&mut self.prog, None, new_ctx, exec_ctx, ctx_vals, profiler);
+                }
+            }
 
-        let prog_out_fb = prog.out_feedback.input_buffer();
+            if let Some(old_prog) = xfade.old_prog.as_mut() {
+                process_ops(
+                    old_prog,
+                    Some((&self.prog, &xfade.shared_ops[..])),
+                    &mut CaptureContext::new(ctx, &mut xfade.old_out),
+                    exec_ctx,
+                    ctx_vals,
+                    profiler,
+                );
+            }
 
-        let nframes = ctx.nframes();
+            if let Some(prog) = xfade.mix(ctx) {
+                let _ = self.shared.graph_drop_prod.push(DropMsg::Prog { prog });
+            }
+        } else if let Some(workers) = self.workers.as_mut() {
+            workers.process(&mut self.prog, ctx, exec_ctx, ctx_vals, sample_rate, profiler);
+        } else {
+            process_ops(&mut self.prog, None, ctx, exec_ctx, ctx_vals, profiler);
+        }
 
-        for op in prog.prog.iter() {
-            let out = op.out_idxlen;
-            let inp = op.in_idxlen;
-            let at = op.at_idxlen;
-            let md = op.mod_idxlen;
-            let ctx_idx = op.idx as usize * 2;
+        if profiler.enabled {
+            profiler.end_block(nframes, self.sample_rate, &self.shared.node_cpu_load[..]);
+        }
 
-            for modop in prog.modops[md.0..md.1].iter_mut() {
-                modop.process(nframes);
+        let event_prod = &mut self.shared.graph_event_prod;
+        self.exec_ctx.node_events.drain(|(node_id, ev)| {
+            let _ = event_prod.push(GraphEvent::Node(node_id, ev));
+        });
+
+        let midi_output = &mut self.midi_output;
+        midi_output.clear();
+        self.exec_ctx.midi_out.drain(|ev| {
+            // Insertion sort, to keep the order of the events on the same frame:
+            let idx = midi_output.partition_point(|e| e.timing() <= ev.timing());
+            midi_output.insert(idx, ev);
+        });
+
+        if self.exec_ctx.output_guard.is_some() {
+            for op in self.prog.prog.iter_mut() {
+                if let Some(fault) = op.fault.take() {
+                    let _ = self
+                        .shared
+                        .graph_event_prod
+                        .push(GraphEvent::NodeFault(op.node.id(), fault));
+                }
             }
+        }
 
-            op.node.process(
-                ctx,
-                exec_ctx,
-                &NodeContext {
-                    out_connected: op.out_connected,
-                    in_connected: op.in_connected,
-                    params: &prog.inp[inp.0..inp.1],
-                },
-                &prog.atoms[at.0..at.1],
-                &prog.cur_inp[inp.0..inp.1],
-                &mut prog.out[out.0..out.1],
-                &ctx_vals[ctx_idx..ctx_idx + 2],
-            );
-
-            let last_frame_idx = nframes - 1;
+        let prog = &mut self.prog;
+        let prog_out_fb = prog.out_feedback.input_buffer();
+        let last_frame_idx = nframes - 1;
+        for op in prog.prog.iter() {
+            let out = op.out_idxlen;
             for (pb, out_buf_idx) in prog.out[out.0..out.1].iter().zip(out.0..out.1) {
                 prog_out_fb[out_buf_idx] = pb.read(last_frame_idx);
             }
@@ -484,9 +1465,12 @@ impl NodeExecutor {
 
         prog.out_feedback.publish();
 
-        self.shared.monitor_backend.check_recycle();
+        self.exec_ctx.transport.advance(nframes, self.sample_rate);
+
+        self.audio_clock += nframes as u64;
+        self.shared.audio_clock.store(self.audio_clock, Ordering::Relaxed);
 
-        // let ta = std::time::Instant::now();
+        self.shared.monitor_backend.check_recycle();
 
         for (i, idx) in self.monitor_signal_cur_inp_indices.iter().enumerate() {
             if *idx == UNUSED_MONITOR_IDX {
@@ -503,11 +1487,14 @@ impl NodeExecutor {
                 self.shared.monitor_backend.send_mon_buf(mon);
             }
         }
+    }
 
-        // let ta = std::time::Instant::now().duration_since(ta);
-        // let tb = std::time::Instant::now().duration_since(tb);
-        // println!("ta Elapsed: {:?}", ta);
-        // println!("tb Elapsed: {:?}", tb);
+    /// Returns the MIDI events that the nodes sent in the last call to
+    /// [NodeExecutor::process], sorted by their frame in that buffer period.
+    /// Pass them on to the MIDI output of your audio driver or plugin API.
+    /// See also [NodeExecContext::send_midi].
+    pub fn midi_output(&self) -> &[HxTimedEvent] {
+        &self.midi_output[..]
     }
 
     /// This is a convenience function used for testing
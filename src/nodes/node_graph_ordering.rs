@@ -49,6 +49,8 @@ pub struct NodeGraphOrdering {
     nodes: Vec<Node>,
 
     in_degree: Vec<usize>,
+    /// The dependency level of each node, calculated by [NodeGraphOrdering::calculate_order].
+    levels: Vec<usize>,
 }
 
 impl NodeGraphOrdering {
@@ -58,6 +60,7 @@ impl NodeGraphOrdering {
             node_count: 0,
             nodes: vec![Node::new(); 256],
             in_degree: vec![0; 256],
+            levels: vec![0; 256],
         }
     }
 
@@ -76,6 +79,7 @@ impl NodeGraphOrdering {
             if self.nodes.len() < self.node_count {
                 self.nodes.resize(self.node_count, Node::new());
                 self.in_degree.resize(self.node_count, 0);
+                self.levels.resize(self.node_count, 0);
             }
 
             self.nodes[idx].clear();
@@ -131,17 +135,33 @@ impl NodeGraphOrdering {
         return Some(false);
     }
 
+    /// Returns the dependency level of the node, which is the length of the longest
+    /// path from any node without inputs to it. Nodes with the same level don't depend
+    /// on each other. The levels in the order returned by [NodeGraphOrdering::calculate_order]
+    /// are ascending, so nodes of the same level follow each other.
+    ///
+    /// Only valid after a successful call to [NodeGraphOrdering::calculate_order].
+    pub fn node_level(&self, node_id: &NodeId) -> Option<usize> {
+        let idx = *self.node2idx.get(node_id)?;
+        Some(self.levels[idx])
+    }
+
     /// Run Kahn's Algorithm to find the node order for the directed
     /// graph. `out` will contain the order the nodes should be
     /// executed in. If `false` is returned, the graph contains cycles
     /// and no proper order can be computed. `out` will be cleared
     /// in this case.
+    ///
+    /// This also calculates the dependency levels, see [NodeGraphOrdering::node_level].
     pub fn calculate_order(&mut self, out: &mut Vec<NodeId>) -> bool {
         let mut deq = std::collections::VecDeque::with_capacity(self.node_count);
 
         for indeg in self.in_degree.iter_mut() {
             *indeg = 0;
         }
+        for level in self.levels.iter_mut() {
+            *level = 0;
+        }
 
         // Calculate the number of inputs for each node:
         for node in self.nodes.iter().take(self.node_count) {
@@ -165,11 +185,15 @@ impl NodeGraphOrdering {
             visited_count += 1;
 
             let node = &self.nodes[node_idx];
+            let level = self.levels[node_idx];
 
             out.push(node.node_id);
 
             // Push the nodes we output to, to the end of the dequeue:
             for neigh_node_idx in node.edges.iter().take(node.unused_idx) {
+                // A node is one level above the highest of it's inputs:
+                self.levels[*neigh_node_idx] = self.levels[*neigh_node_idx].max(level + 1);
+
                 // by reducing the input count of the to be visited node:
                 self.in_degree[*neigh_node_idx] -= 1;
 
@@ -330,6 +354,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn check_ngraph_levels() {
+        let mut ng = NodeGraphOrdering::new();
+        ng.add_node(NodeId::Sin(0));
+        ng.add_node(NodeId::Sin(1));
+        ng.add_node(NodeId::Amp(0));
+        ng.add_node(NodeId::Amp(1));
+        ng.add_node(NodeId::Mix3(0));
+        ng.add_node(NodeId::Out(0));
+
+        /*
+            sin0 => amp0 ===========> mix30 => out0
+            sin1 => amp1 ===========> mix30
+            sin1 ===================> out0
+        */
+
+        ng.add_edge(NodeId::Sin(0), NodeId::Amp(0));
+        ng.add_edge(NodeId::Sin(1), NodeId::Amp(1));
+        ng.add_edge(NodeId::Amp(0), NodeId::Mix3(0));
+        ng.add_edge(NodeId::Amp(1), NodeId::Mix3(0));
+        ng.add_edge(NodeId::Mix3(0), NodeId::Out(0));
+        ng.add_edge(NodeId::Sin(1), NodeId::Out(0));
+
+        let mut out = vec![];
+        assert!(ng.calculate_order(&mut out));
+
+        let levels: Vec<usize> = out.iter().map(|n| ng.node_level(n).unwrap()).collect();
+        assert_eq!(levels[..], [0, 0, 1, 1, 2, 3]);
+        assert_eq!(ng.node_level(&NodeId::Sin(1)), Some(0));
+        assert_eq!(ng.node_level(&NodeId::Amp(1)), Some(1));
+        assert_eq!(ng.node_level(&NodeId::Out(0)), Some(3));
+        assert_eq!(ng.node_level(&NodeId::Out(1)), None);
+    }
+
     #[test]
     fn check_ngraph_dfs_cycle_2() {
         let mut ng = NodeGraphOrdering::new();
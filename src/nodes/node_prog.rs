@@ -2,6 +2,7 @@
 // This file is a part of HexoDSP. Released under GPL-3.0-or-later.
 // See README.md and COPYING for details.
 
+use super::{OversamplingGroup, SignalFault};
 use crate::dsp::{Node, ProcBuf, SAtom};
 use std::cell::RefCell;
 use triple_buffer::{Input, Output, TripleBuffer};
@@ -91,9 +92,29 @@ pub struct NodeOp {
     /// A bit mask which indicates which of the output ports are actually
     /// used/connected to some input.
     pub out_connected: u64,
+    /// The dependency level of the node, see [crate::nodes::NodeGraphOrdering::node_level].
+    /// Consecutive ops with the same level don't depend on each other and
+    /// may be executed in parallel. `None` if unknown.
+    pub level: Option<usize>,
+    /// Set by the output guard of the [crate::nodes::NodeExecutor] if it detected a
+    /// fault in the outputs of this node in the current buffer period.
+    pub fault: Option<SignalFault>,
 }
 
 impl NodeOp {
+    /// Returns true if `other` executes the same node with the same buffers and input edges.
+    pub fn has_same_edges(&self, other: &NodeOp) -> bool {
+        self.idx == other.idx
+            && self.out_idxlen == other.out_idxlen
+            && self.in_idxlen == other.in_idxlen
+            && self.at_idxlen == other.at_idxlen
+            && self.mod_idxlen == other.mod_idxlen
+            && self.inputs == other.inputs
+            && self.in_connected == other.in_connected
+            && self.out_connected == other.out_connected
+            && self.level == other.level
+    }
+
     pub fn in_idx_belongs_to_nodeop(&self, idx: usize) -> bool {
         idx >= self.in_idxlen.0 && idx < self.in_idxlen.1
     }
@@ -153,6 +174,55 @@ impl std::fmt::Display for NodeOp {
     }
 }
 
+/// The parts of a [NodeProg] that are needed to process one [NodeOp].
+/// The op only gets mutable access to it's own modulators and output buffers,
+/// see [NodeProg::op_slot] and [ProgPtrs::op_slot].
+pub(crate) struct OpSlot<'a> {
+    pub(crate) op: &'a mut NodeOp,
+    pub(crate) modops: &'a mut [ModOp],
+    /// The smoothed parameter values, see [NodeProg::inp].
+    pub(crate) params: &'a [ProcBuf],
+    /// The inputs, see [NodeProg::cur_inp].
+    pub(crate) inputs: &'a [ProcBuf],
+    pub(crate) atoms: &'a [SAtom],
+    pub(crate) outputs: &'a mut [ProcBuf],
+}
+
+/// Raw pointers to the buffers of a [NodeProg], for handing out the [OpSlot]s
+/// of independent ops to several threads at the same time.
+/// See also [crate::nodes::WorkerPool].
+#[derive(Clone, Copy)]
+pub(crate) struct ProgPtrs {
+    ops: *mut NodeOp,
+    modops: *mut ModOp,
+    inp: *const ProcBuf,
+    cur_inp: *const ProcBuf,
+    atoms: *const SAtom,
+    out: *mut ProcBuf,
+}
+
+impl ProgPtrs {
+    /// Returns the [OpSlot] of the op at `op_idx`.
+    ///
+    /// # Safety
+    /// The [NodeProg] the pointers were taken from must not be moved, resized or
+    /// otherwise accessed while the slot is alive. Two slots that are alive at the
+    /// same time must belong to different ops, so that their outputs don't overlap.
+    pub(crate) unsafe fn op_slot<'a>(&self, op_idx: usize) -> OpSlot<'a> {
+        let op = &mut *self.ops.add(op_idx);
+        let (md, inp, at, out) = (op.mod_idxlen, op.in_idxlen, op.at_idxlen, op.out_idxlen);
+
+        OpSlot {
+            modops: std::slice::from_raw_parts_mut(self.modops.add(md.0), md.1 - md.0),
+            params: std::slice::from_raw_parts(self.inp.add(inp.0), inp.1 - inp.0),
+            inputs: std::slice::from_raw_parts(self.cur_inp.add(inp.0), inp.1 - inp.0),
+            atoms: std::slice::from_raw_parts(self.atoms.add(at.0), at.1 - at.0),
+            outputs: std::slice::from_raw_parts_mut(self.out.add(out.0), out.1 - out.0),
+            op,
+        }
+    }
+}
+
 /// A node graph execution program. It comes with buffers
 /// for the inputs, outputs and node parameters (knob values).
 #[derive(Debug)]
@@ -200,6 +270,10 @@ pub struct NodeProg {
     /// A unique ID assigned to the node prog. Mostly for debugging purposes.
     /// You should only read this field.
     pub unique_id: usize,
+
+    /// The groups of ops that run at a multiple of the sample rate,
+    /// sorted by their first op index.
+    pub ovs_groups: Vec<OversamplingGroup>,
 }
 
 impl Drop for NodeProg {
@@ -239,6 +313,7 @@ impl NodeProg {
             out_fb_cons: Some(output_fb),
             locked_buffers: false,
             unique_id: new_node_prog_id(),
+            ovs_groups: vec![],
         }
     }
 
@@ -274,6 +349,7 @@ impl NodeProg {
             out_fb_cons: Some(output_fb),
             locked_buffers: false,
             unique_id: new_node_prog_id(),
+            ovs_groups: vec![],
         }
     }
 
@@ -293,6 +369,60 @@ impl NodeProg {
         &mut self.modops
     }
 
+    /// Returns the index of the [OversamplingGroup] that contains the op at `op_idx`.
+    #[inline]
+    pub fn ovs_group_of(&self, op_idx: usize) -> Option<usize> {
+        self.ovs_groups.iter().position(|g| g.contains_op(op_idx))
+    }
+
+    /// Returns the oversampling factor of the op at `op_idx`, `1` if it runs
+    /// at the normal sample rate.
+    pub fn oversampling_at(&self, op_idx: usize) -> usize {
+        self.ovs_group_of(op_idx).map(|g| self.ovs_groups[g].factor()).unwrap_or(1)
+    }
+
+    /// Returns the buffer of the output `out_idx` at the normal sample rate.
+    /// For outputs of an [OversamplingGroup] that is the downsampled signal.
+    #[inline]
+    pub(crate) fn host_out_buf(&self, out_idx: usize) -> ProcBuf {
+        self.ovs_groups.iter().find_map(|g| g.host_output(out_idx)).unwrap_or(self.out[out_idx])
+    }
+
+    /// Returns the [OpSlot] of the op at `op_idx`.
+    #[inline]
+    pub(crate) fn op_slot(&mut self, op_idx: usize) -> OpSlot<'_> {
+        let op = &mut self.prog[op_idx];
+        let (md, inp, at, out) = (op.mod_idxlen, op.in_idxlen, op.at_idxlen, op.out_idxlen);
+
+        OpSlot {
+            op,
+            modops: &mut self.modops[md.0..md.1],
+            params: &self.inp[inp.0..inp.1],
+            inputs: &self.cur_inp[inp.0..inp.1],
+            atoms: &self.atoms[at.0..at.1],
+            outputs: &mut self.out[out.0..out.1],
+        }
+    }
+
+    /// Returns the [ProgPtrs] for processing several ops at the same time.
+    pub(crate) fn ptrs(&mut self) -> ProgPtrs {
+        ProgPtrs {
+            ops: self.prog.as_mut_ptr(),
+            modops: self.modops.as_mut_ptr(),
+            inp: self.inp.as_ptr(),
+            cur_inp: self.cur_inp.as_ptr(),
+            atoms: self.atoms.as_ptr(),
+            out: self.out.as_mut_ptr(),
+        }
+    }
+
+    /// Sets the sample rate of the up- and downsampling filters of the [OversamplingGroup]s.
+    pub fn set_ovs_sample_rate(&mut self, srate: f32) {
+        for group in self.ovs_groups.iter_mut() {
+            group.set_sample_rate(srate);
+        }
+    }
+
     pub fn append_op(&mut self, mut node_op: NodeOp) {
         for n_op in self.prog.iter_mut() {
             if n_op.idx == node_op.idx {
@@ -353,6 +483,26 @@ impl NodeProg {
         }
     }
 
+    /// Moves the buffers of `new` into this program, usually because nodes were created.
+    /// The input and output buffers of this program are moved to the front of the new
+    /// buffers beforehand, so the existing nodes keep their (smoothed) input history.
+    /// `new` is left with the old buffers.
+    pub fn swap_buffers(&mut self, new: &mut NodeProg) {
+        new.swap_previous_outputs(self);
+
+        for (old_out_pb, new_out_pb) in self.out.iter_mut().zip(new.out.iter_mut()) {
+            std::mem::swap(old_out_pb, new_out_pb);
+        }
+
+        std::mem::swap(&mut self.inp, &mut new.inp);
+        std::mem::swap(&mut self.cur_inp, &mut new.cur_inp);
+        std::mem::swap(&mut self.out, &mut new.out);
+        std::mem::swap(&mut self.params, &mut new.params);
+        std::mem::swap(&mut self.atoms, &mut new.atoms);
+        std::mem::swap(&mut self.modops, &mut new.modops);
+        std::mem::swap(&mut self.out_feedback, &mut new.out_feedback);
+    }
+
     pub fn unlock_buffers(&mut self) {
         for buf in self.cur_inp.iter_mut() {
             *buf = ProcBuf::null();
@@ -364,7 +514,7 @@ impl NodeProg {
     }
 
     pub fn assign_outputs(&mut self) {
-        for op in self.prog.iter() {
+        for (op_idx, op) in self.prog.iter().enumerate() {
             // First step is copying the ProcBufs to the `cur_inp` current
             // input buffer vector. It holds the data for smoothed paramter
             // inputs or just constant values since the last smoothing.
@@ -390,14 +540,30 @@ impl NodeProg {
 
             // Second step (assign outputs):
             for io in op.inputs.iter() {
-                input_bufs[io.1] = out_bufs[io.0];
+                // Outputs of oversampled nodes are read at the normal
+                // sample rate by the nodes outside of the group:
+                let out_buf = self
+                    .ovs_groups
+                    .iter()
+                    .filter(|g| !g.contains_op(op_idx))
+                    .find_map(|g| g.host_output(io.0))
+                    .unwrap_or(out_bufs[io.0]);
+
+                input_bufs[io.1] = out_buf;
 
                 if let Some(idx) = io.2 {
-                    input_bufs[io.1] = self.modops[idx].lock(self.inp[io.1], out_bufs[io.0]);
+                    input_bufs[io.1] = self.modops[idx].lock(self.inp[io.1], out_buf);
                 }
             }
         }
 
+        // Third step (redirect the inputs of oversampled nodes):
+        let mut groups = std::mem::take(&mut self.ovs_groups);
+        for group in groups.iter_mut() {
+            group.assign_inputs(self);
+        }
+        self.ovs_groups = groups;
+
         self.locked_buffers = true;
     }
 }
@@ -0,0 +1,49 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use crate::dsp::{NodeId, NodeState};
+
+/// Snapshots of the internal state of the nodes of a DSP graph, taken with
+/// [crate::nodes::NodeExecutor::save_node_states]. The states are stored per [NodeId], so
+/// they can be restored into any [crate::nodes::NodeExecutor] that runs the same patch.
+#[derive(Debug, Default)]
+pub struct NodeStates {
+    states: Vec<(NodeId, NodeState)>,
+}
+
+impl NodeStates {
+    pub fn new() -> Self {
+        Self { states: vec![] }
+    }
+
+    /// Stores the `state` of the node, replacing any previous state of it.
+    pub fn insert(&mut self, node_id: NodeId, state: NodeState) {
+        if let Some(entry) = self.states.iter_mut().find(|(id, _)| *id == node_id) {
+            entry.1 = state;
+        } else {
+            self.states.push((node_id, state));
+        }
+    }
+
+    pub fn get(&self, node_id: &NodeId) -> Option<&NodeState> {
+        self.states.iter().find(|(id, _)| id == node_id).map(|(_, state)| state)
+    }
+
+    pub fn get_mut(&mut self, node_id: &NodeId) -> Option<&mut NodeState> {
+        self.states.iter_mut().find(|(id, _)| id == node_id).map(|(_, state)| state)
+    }
+
+    /// Returns the [NodeId]s of all saved nodes.
+    pub fn node_ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.states.iter().map(|(id, _)| *id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+}
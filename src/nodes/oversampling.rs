@@ -0,0 +1,409 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use super::node_exec::{process_op_node, CpuProfiler};
+use super::{uses_audio_driver, uses_host_events, NodeAudioContext, NodeExecContext, NodeProg};
+use crate::dsp::{NodeId, ProcBuf, MAX_BLOCK_SIZE};
+use synfx_dsp::{AtomicFloat, Oversampling};
+
+use std::sync::Arc;
+
+/// The oversampling factors a group of nodes can run at, `1` means no oversampling.
+pub const OVERSAMPLING_FACTORS: [usize; 4] = [1, 2, 4, 8];
+
+/// Up- or downsamples a signal with one of the [OVERSAMPLING_FACTORS].
+#[derive(Debug, Clone, Copy)]
+enum Resampler {
+    X2(Oversampling<2>),
+    X4(Oversampling<4>),
+    X8(Oversampling<8>),
+}
+
+fn upsample_into<const N: usize>(
+    ovs: &mut Oversampling<N>,
+    v: f32,
+    out: &mut ProcBuf,
+    offs: usize,
+) {
+    ovs.upsample(v);
+    for (i, s) in ovs.resample_buffer().iter().enumerate() {
+        out.write(offs + i, *s);
+    }
+}
+
+fn downsample_from<const N: usize>(ovs: &mut Oversampling<N>, inp: &ProcBuf, offs: usize) -> f32 {
+    for (i, s) in ovs.resample_buffer().iter_mut().enumerate() {
+        *s = inp.read(offs + i);
+    }
+    ovs.downsample()
+}
+
+impl Resampler {
+    fn new(factor: usize) -> Self {
+        match factor {
+            2 => Resampler::X2(Oversampling::new()),
+            4 => Resampler::X4(Oversampling::new()),
+            _ => Resampler::X8(Oversampling::new()),
+        }
+    }
+
+    fn set_sample_rate(&mut self, srate: f32) {
+        match self {
+            Resampler::X2(ovs) => ovs.set_sample_rate(srate),
+            Resampler::X4(ovs) => ovs.set_sample_rate(srate),
+            Resampler::X8(ovs) => ovs.set_sample_rate(srate),
+        }
+    }
+
+    /// Upsamples `v` and writes the resulting samples into `out`, starting at `offs`.
+    #[inline]
+    fn upsample(&mut self, v: f32, out: &mut ProcBuf, offs: usize) {
+        match self {
+            Resampler::X2(ovs) => upsample_into(ovs, v, out, offs),
+            Resampler::X4(ovs) => upsample_into(ovs, v, out, offs),
+            Resampler::X8(ovs) => upsample_into(ovs, v, out, offs),
+        }
+    }
+
+    /// Downsamples the samples of `inp` starting at `offs` to one sample.
+    #[inline]
+    fn downsample(&mut self, inp: &ProcBuf, offs: usize) -> f32 {
+        match self {
+            Resampler::X2(ovs) => downsample_from(ovs, inp, offs),
+            Resampler::X4(ovs) => downsample_from(ovs, inp, offs),
+            Resampler::X8(ovs) => downsample_from(ovs, inp, offs),
+        }
+    }
+}
+
+/// An input of a node in an [OversamplingGroup] that is not fed by
+/// another node of the same group.
+#[derive(Debug)]
+struct GroupInput {
+    /// The index of the input in [NodeProg::cur_inp].
+    inp_idx: usize,
+    /// For an input that is modulated by a node of the same group, the index of
+    /// the [crate::nodes::ModOp] and of the modulating output. The modulator is
+    /// then processed at the oversampled rate.
+    ovs_mod: Option<(usize, usize)>,
+    /// The input buffer at the host sample rate, assigned in [NodeProg::assign_outputs].
+    src: ProcBuf,
+    /// The oversampled buffer the nodes of the group read from.
+    buf: ProcBuf,
+    /// The anti-imaging filter for signal inputs. Parameter inputs are just held.
+    up: Option<Resampler>,
+}
+
+/// An output of a node in an [OversamplingGroup] that is used outside of the group.
+#[derive(Debug)]
+struct GroupOutput {
+    /// The index of the oversampled output in [NodeProg::out].
+    out_idx: usize,
+    /// The downsampled output at the host sample rate, read by the nodes outside the group.
+    buf: ProcBuf,
+    /// The anti-aliasing filter.
+    down: Resampler,
+}
+
+/// The input index, the oversampled modulator and whether the input is upsampled,
+/// see [GroupInput].
+type GroupInputShape = (usize, Option<(usize, usize)>, bool);
+
+/// Describes the nodes and edges of an [OversamplingGroup], to find the group of the
+/// running [NodeProg] that a group of a new program can take the filter state from.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct OvsGroupShape {
+    factor: usize,
+    /// The [crate::nodes::NodeOp::idx] of the ops in the group.
+    nodes: Vec<u8>,
+    inputs: Vec<GroupInputShape>,
+    outputs: Vec<usize>,
+    host_mods: Vec<usize>,
+}
+
+/// A group of consecutive [crate::nodes::NodeOp] in a [NodeProg] that runs at
+/// a multiple of the sample rate.
+///
+/// The inputs of the group are upsampled and the outputs that leave the group
+/// are downsampled through a lowpass filter. A buffer period is processed in chunks of
+/// `MAX_BLOCK_SIZE / factor` frames, so that the oversampled signals fit into
+/// the [ProcBuf] of the nodes.
+///
+/// See also [crate::nodes::NodeConfigurator::set_oversampling].
+#[derive(Debug)]
+pub struct OversamplingGroup {
+    factor: usize,
+    /// The range of op indices in [NodeProg::prog].
+    ops: (usize, usize),
+    inputs: Vec<GroupInput>,
+    outputs: Vec<GroupOutput>,
+    /// Modulators of inputs from outside the group, they are processed at the host rate.
+    host_mods: Vec<usize>,
+}
+
+impl Drop for OversamplingGroup {
+    fn drop(&mut self) {
+        for inp in self.inputs.iter() {
+            inp.buf.free();
+        }
+
+        for out in self.outputs.iter() {
+            out.buf.free();
+        }
+    }
+}
+
+impl OversamplingGroup {
+    /// Collects the groups of consecutive ops in `prog` which have the same
+    /// oversampling factor, as returned by `factor_of`. Nodes that access the audio
+    /// driver buffers, like `Out` and `Inp`, are never oversampled.
+    pub(crate) fn collect_groups(
+        prog: &NodeProg,
+        factor_of: &dyn Fn(NodeId) -> usize,
+    ) -> Vec<OversamplingGroup> {
+        let op_factor = |op_idx: usize| {
+            let node_id = prog.prog[op_idx].node.id();
+            if uses_audio_driver(node_id) || uses_host_events(node_id) {
+                1
+            } else {
+                factor_of(node_id)
+            }
+        };
+
+        let mut groups = vec![];
+        let mut i = 0;
+
+        while i < prog.prog.len() {
+            let factor = op_factor(i);
+            let mut j = i + 1;
+            while j < prog.prog.len() && op_factor(j) == factor {
+                j += 1;
+            }
+
+            if factor > 1 {
+                groups.push(OversamplingGroup::new(prog, factor, (i, j)));
+            }
+
+            i = j;
+        }
+
+        groups
+    }
+
+    fn new(prog: &NodeProg, factor: usize, ops: (usize, usize)) -> Self {
+        let in_group = |out_idx: usize| {
+            prog.prog[ops.0..ops.1].iter().any(|op| op.out_idx_belongs_to_nodeop(out_idx))
+        };
+
+        let mut inputs = vec![];
+        let mut outputs = vec![];
+        let mut host_mods = vec![];
+
+        for op in prog.prog[ops.0..ops.1].iter() {
+            for inp_idx in op.in_idxlen.0..op.in_idxlen.1 {
+                let (up, ovs_mod) = match op.inputs.iter().find(|io| io.1 == inp_idx) {
+                    // Unconnected inputs only carry the smoothed parameter:
+                    None => (None, None),
+                    Some((out_idx, _, None)) if in_group(*out_idx) => continue,
+                    Some((out_idx, _, Some(mod_idx))) if in_group(*out_idx) => {
+                        (None, Some((*mod_idx, *out_idx)))
+                    }
+                    Some((_, _, mod_idx)) => {
+                        if let Some(mod_idx) = mod_idx {
+                            host_mods.push(*mod_idx);
+                        }
+                        (Some(Resampler::new(factor)), None)
+                    }
+                };
+
+                inputs.push(GroupInput {
+                    inp_idx,
+                    ovs_mod,
+                    src: ProcBuf::null(),
+                    buf: ProcBuf::new(),
+                    up,
+                });
+            }
+
+            for out_idx in op.out_idxlen.0..op.out_idxlen.1 {
+                let used_outside = prog.prog.iter().enumerate().any(|(op_idx, other)| {
+                    (op_idx < ops.0 || op_idx >= ops.1)
+                        && other.inputs.iter().any(|io| io.0 == out_idx)
+                });
+
+                if used_outside {
+                    outputs.push(GroupOutput {
+                        out_idx,
+                        buf: ProcBuf::new(),
+                        down: Resampler::new(factor),
+                    });
+                }
+            }
+        }
+
+        Self { factor, ops, inputs, outputs, host_mods }
+    }
+
+    /// The oversampling factor of the group.
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /// The range of the op indices in [NodeProg::prog] of this group.
+    pub fn ops(&self) -> (usize, usize) {
+        self.ops
+    }
+
+    pub(crate) fn shape(&self, prog: &NodeProg) -> OvsGroupShape {
+        OvsGroupShape {
+            factor: self.factor,
+            nodes: prog.prog[self.ops.0..self.ops.1].iter().map(|op| op.idx).collect(),
+            inputs: self.inputs.iter().map(|i| (i.inp_idx, i.ovs_mod, i.up.is_some())).collect(),
+            outputs: self.outputs.iter().map(|o| o.out_idx).collect(),
+            host_mods: self.host_mods.clone(),
+        }
+    }
+
+    /// Takes over the buffers and filter states of `other`, which has the same [OvsGroupShape].
+    /// `other` is left with the fresh buffers and filters of this group.
+    pub(crate) fn take_state(&mut self, other: &mut OversamplingGroup) {
+        for (inp, other_inp) in self.inputs.iter_mut().zip(other.inputs.iter_mut()) {
+            std::mem::swap(&mut inp.buf, &mut other_inp.buf);
+            std::mem::swap(&mut inp.up, &mut other_inp.up);
+        }
+
+        for (out, other_out) in self.outputs.iter_mut().zip(other.outputs.iter_mut()) {
+            std::mem::swap(&mut out.buf, &mut other_out.buf);
+            std::mem::swap(&mut out.down, &mut other_out.down);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn contains_op(&self, op_idx: usize) -> bool {
+        op_idx >= self.ops.0 && op_idx < self.ops.1
+    }
+
+    /// Returns the downsampled buffer for the output `out_idx`, if it
+    /// is an output of this group.
+    pub(crate) fn host_output(&self, out_idx: usize) -> Option<ProcBuf> {
+        self.outputs.iter().find(|o| o.out_idx == out_idx).map(|o| o.buf)
+    }
+
+    pub(crate) fn set_sample_rate(&mut self, srate: f32) {
+        for inp in self.inputs.iter_mut() {
+            if let Some(up) = inp.up.as_mut() {
+                up.set_sample_rate(srate);
+            }
+        }
+
+        for out in self.outputs.iter_mut() {
+            out.down.set_sample_rate(srate);
+        }
+    }
+
+    /// Redirects the inputs of the group nodes to the oversampled buffers.
+    /// Called by [NodeProg::assign_outputs] after the outputs were assigned.
+    pub(crate) fn assign_inputs(&mut self, prog: &mut NodeProg) {
+        for inp in self.inputs.iter_mut() {
+            if let Some((mod_idx, out_idx)) = inp.ovs_mod {
+                inp.src = prog.inp[inp.inp_idx];
+                prog.cur_inp[inp.inp_idx] = prog.modops[mod_idx].lock(inp.buf, prog.out[out_idx]);
+            } else {
+                inp.src = prog.cur_inp[inp.inp_idx];
+                prog.cur_inp[inp.inp_idx] = inp.buf;
+            }
+        }
+    }
+}
+
+/// The [NodeAudioContext] for the nodes inside an [OversamplingGroup].
+struct OversampledContext {
+    nframes: usize,
+}
+
+impl NodeAudioContext for OversampledContext {
+    #[inline]
+    fn nframes(&self) -> usize {
+        self.nframes
+    }
+
+    #[inline]
+    fn output(&mut self, _channel: usize, _frame: usize, _v: f32) {}
+
+    #[inline]
+    fn input(&mut self, _channel: usize, _frame: usize) -> f32 {
+        0.0
+    }
+}
+
+/// Processes the [OversamplingGroup] at `group_idx` of the `prog` for one buffer period.
+pub(crate) fn process_group(
+    prog: &mut NodeProg,
+    group_idx: usize,
+    ctx: &mut dyn NodeAudioContext,
+    exec_ctx: &mut NodeExecContext,
+    ctx_vals: &[Arc<AtomicFloat>],
+    profiler: &mut CpuProfiler,
+) {
+    let nframes = ctx.nframes();
+
+    // Moving the vector out does not allocate:
+    let mut groups = std::mem::take(&mut prog.ovs_groups);
+    let group = &mut groups[group_idx];
+    let factor = group.factor;
+
+    for mod_idx in group.host_mods.iter() {
+        prog.modops[*mod_idx].process(nframes);
+    }
+
+    let chunk = MAX_BLOCK_SIZE / factor;
+    let mut offs = 0;
+
+    while offs < nframes {
+        let n = chunk.min(nframes - offs);
+
+        for inp in group.inputs.iter_mut() {
+            if inp.src.is_null() {
+                inp.buf.fill(0.0);
+                continue;
+            }
+
+            if let Some(up) = inp.up.as_mut() {
+                for i in 0..n {
+                    up.upsample(inp.src.read(offs + i), &mut inp.buf, i * factor);
+                }
+            } else {
+                for i in 0..n {
+                    let v = inp.src.read(offs + i);
+                    for j in 0..factor {
+                        inp.buf.write(i * factor + j, v);
+                    }
+                }
+            }
+        }
+
+        for inp in group.inputs.iter() {
+            if let Some((mod_idx, _)) = inp.ovs_mod {
+                prog.modops[mod_idx].process(n * factor);
+            }
+        }
+
+        let ovs_ctx = &mut OversampledContext { nframes: n * factor };
+        for op_idx in group.ops.0..group.ops.1 {
+            process_op_node(prog.op_slot(op_idx), ovs_ctx, exec_ctx, ctx_vals, profiler);
+        }
+
+        for out in group.outputs.iter_mut() {
+            let ovs_out = prog.out[out.out_idx];
+            for i in 0..n {
+                let v = out.down.downsample(&ovs_out, i * factor);
+                out.buf.write(offs + i, v);
+            }
+        }
+
+        offs += n;
+    }
+
+    prog.ovs_groups = groups;
+}
@@ -0,0 +1,187 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use super::MAX_VOICES;
+use crate::build::{mix3, output_port, ConstructorNode, ConstructorNodeBuilder};
+use crate::build::{ConstructorNodeOutputPort, ConstructorOp};
+use crate::dsp::NodeId;
+use crate::synth_constructor::SynthError;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Replicates a voice sub-graph of [crate::build] nodes for polyphonic synthesis.
+///
+/// The voice sub-graph is everything that is reachable from the inputs of the voice
+/// output node, except the nodes that were marked as shared with [PolyVoices::shared].
+/// Each voice gets a copy of these nodes with the instance index offset by
+/// `voice * stride`. The `MidiP` nodes in the copies get their `voice` setting
+/// assigned, so that they receive the notes allocated to their voice by the
+/// [crate::nodes::VoiceAllocator].
+///
+/// The template sub-graph itself is not modified, so make sure the instance indices
+/// of the copies don't collide with other nodes in your graph.
+///
+/// Don't forget to configure the voice allocation with
+/// [crate::SynthConstructor::set_voice_allocation] or
+/// [crate::NodeConfigurator::set_voice_allocation].
+///
+///```
+/// use hexodsp::nodes::PolyVoices;
+/// use hexodsp::build::*;
+///
+/// let midip = midip(0);
+/// let osc = sin(0).input().freq(&midip.output().freq());
+/// let voice = amp(0).input().inp(&osc.output().sig());
+///
+/// // Build 4 voices, the copies use the instances Sin(0), Sin(16), Sin(32) and Sin(48):
+/// let mix = PolyVoices::new(4).mix(&voice.output().sig(), 0).unwrap();
+///
+/// let graph = out(0).input().ch1(&mix);
+///```
+#[derive(Debug, Clone)]
+pub struct PolyVoices {
+    voices: usize,
+    stride: usize,
+    shared: HashSet<NodeId>,
+}
+
+impl PolyVoices {
+    /// Creates a replicator for `voices` voices, which is clamped to `1..=MAX_VOICES`.
+    pub fn new(voices: usize) -> Self {
+        Self { voices: voices.clamp(1, MAX_VOICES), stride: MAX_VOICES, shared: HashSet::new() }
+    }
+
+    /// Sets the instance index offset between two voices. Default is [MAX_VOICES].
+    pub fn stride(mut self, stride: usize) -> Self {
+        self.stride = stride.max(1);
+        self
+    }
+
+    /// Marks a node as shared between all voices. The node and the nodes
+    /// it reads from are not replicated.
+    pub fn shared(mut self, node: &dyn ConstructorNodeBuilder) -> Self {
+        self.shared.insert(node.id());
+        self
+    }
+
+    /// Returns the number of voices.
+    pub fn voices(&self) -> usize {
+        self.voices
+    }
+
+    fn copy_node(
+        &self,
+        node: &ConstructorNode,
+        voice: usize,
+        copies: &mut HashMap<NodeId, ConstructorNode>,
+        visiting: &mut HashSet<NodeId>,
+    ) -> Result<ConstructorNode, SynthError> {
+        if self.shared.contains(&node.node_id) {
+            return Ok(node.clone());
+        }
+
+        if let Some(copy) = copies.get(&node.node_id) {
+            return Ok(copy.clone());
+        }
+
+        if !visiting.insert(node.node_id) {
+            return Err(SynthError::CycleDetected);
+        }
+
+        let instance = node.node_id.instance() + voice * self.stride;
+        if instance > (u8::MAX as usize) {
+            return Err(SynthError::VoiceInstanceOverflow(node.node_id));
+        }
+
+        let mut ops = vec![];
+        for op in node.ops.borrow().iter() {
+            ops.push(match op {
+                ConstructorOp::Input(param, input_node, output) => ConstructorOp::Input(
+                    param.to_string(),
+                    self.copy_node(input_node, voice, copies, visiting)?,
+                    output.to_string(),
+                ),
+                op => op.clone(),
+            });
+        }
+
+        if let NodeId::MidiP(_) = node.node_id {
+            ops.push(ConstructorOp::SetSetting("voice".to_string(), (voice + 1) as i64));
+        }
+
+        visiting.remove(&node.node_id);
+
+        let copy = ConstructorNode {
+            node_id: node.node_id.to_instance(instance),
+            ops: Rc::new(RefCell::new(ops)),
+        };
+        copies.insert(node.node_id, copy.clone());
+
+        Ok(copy)
+    }
+
+    /// Replicates the voice sub-graph that ends in the given output port.
+    /// Returns the output node and output port name for each voice.
+    pub fn replicate(
+        &self,
+        voice_out: &dyn ConstructorNodeOutputPort,
+    ) -> Result<Vec<(ConstructorNode, String)>, SynthError> {
+        let (node, port) = voice_out.port();
+
+        let mut outputs = vec![];
+        for voice in 0..self.voices {
+            let mut copies = HashMap::new();
+            let mut visiting = HashSet::new();
+            let copy = self.copy_node(&node, voice, &mut copies, &mut visiting)?;
+            outputs.push((copy, port.to_string()));
+        }
+
+        Ok(outputs)
+    }
+
+    /// Replicates the voice sub-graph that ends in the given output port and
+    /// sums up the outputs of all voices with a tree of `Mix3` nodes.
+    /// The `Mix3` nodes use consecutive instance indices starting at `mix3_instance`.
+    /// Returns the output port of the summed voices.
+    pub fn mix(
+        &self,
+        voice_out: &dyn ConstructorNodeOutputPort,
+        mix3_instance: u8,
+    ) -> Result<output_port::Mix3, SynthError> {
+        let mut signals = self.replicate(voice_out)?;
+        let mut instance = mix3_instance as usize;
+
+        loop {
+            let mut mixed = vec![];
+
+            for chunk in signals.chunks(3) {
+                if instance > (u8::MAX as usize) {
+                    return Err(SynthError::VoiceInstanceOverflow(NodeId::Mix3(mix3_instance)));
+                }
+
+                let mix = mix3(instance as u8);
+                instance += 1;
+
+                let node = mix.build();
+                for ((input_node, output), param) in chunk.iter().zip(["ch1", "ch2", "ch3"]) {
+                    node.ops.borrow_mut().push(ConstructorOp::Input(
+                        param.to_string(),
+                        input_node.clone(),
+                        output.to_string(),
+                    ));
+                }
+
+                mixed.push((node, mix.output().sig()));
+            }
+
+            if mixed.len() == 1 {
+                return Ok(mixed.pop().unwrap().1);
+            }
+
+            signals = mixed.into_iter().map(|(node, _)| (node, "sig".to_string())).collect();
+        }
+    }
+}
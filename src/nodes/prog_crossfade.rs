@@ -0,0 +1,191 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use super::{NodeAudioContext, NodeOp, NodeProg, MAX_AUDIO_CHANNELS};
+use crate::dsp::MAX_BLOCK_SIZE;
+
+/// Maps the `old_ops` of the previous program to the index of the op in `new_prog`
+/// that processes the same node. This is computed by the [crate::nodes::NodeConfigurator]
+/// and sent along with the new program, see [ProgCrossfade::start].
+pub(crate) fn shared_ops(old_ops: &[NodeOp], new_prog: &NodeProg) -> Vec<Option<usize>> {
+    old_ops
+        .iter()
+        .map(|op| {
+            new_prog
+                .prog
+                .iter()
+                .position(|new_op| std::sync::Arc::ptr_eq(&op.node.0, &new_op.node.0))
+        })
+        .collect()
+}
+
+/// Holds the audio output of one [NodeProg] for one buffer period.
+pub(crate) struct CaptureBuf {
+    data: Vec<[f32; MAX_BLOCK_SIZE]>,
+    /// Bit mask of the channels that were written to.
+    written: u32,
+}
+
+impl CaptureBuf {
+    fn new() -> Self {
+        Self { data: vec![[0.0; MAX_BLOCK_SIZE]; MAX_AUDIO_CHANNELS], written: 0 }
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.written = 0;
+    }
+
+    #[inline]
+    fn get(&self, channel: usize, frame: usize) -> f32 {
+        if self.written & (1 << channel) != 0 {
+            self.data[channel][frame]
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A [NodeAudioContext] that redirects the output of the `Out` nodes into a [CaptureBuf],
+/// while the inputs are read from the audio driver context.
+pub(crate) struct CaptureContext<'a> {
+    ctx: &'a mut dyn NodeAudioContext,
+    buf: &'a mut CaptureBuf,
+}
+
+impl<'a> CaptureContext<'a> {
+    pub(crate) fn new(ctx: &'a mut dyn NodeAudioContext, buf: &'a mut CaptureBuf) -> Self {
+        buf.clear();
+        Self { ctx, buf }
+    }
+}
+
+impl<'a> NodeAudioContext for CaptureContext<'a> {
+    #[inline]
+    fn nframes(&self) -> usize {
+        self.ctx.nframes()
+    }
+
+    #[inline]
+    fn output(&mut self, channel: usize, frame: usize, v: f32) {
+        if channel < MAX_AUDIO_CHANNELS && frame < MAX_BLOCK_SIZE {
+            self.buf.data[channel][frame] = v;
+            self.buf.written |= 1 << channel;
+        }
+    }
+
+    #[inline]
+    fn input(&mut self, channel: usize, frame: usize) -> f32 {
+        self.ctx.input(channel, frame)
+    }
+}
+
+/// Fades from the previous [NodeProg] to the new one after a program upload.
+///
+/// While the crossfade is active, the [crate::nodes::NodeExecutor] runs both programs.
+/// Nodes that exist in both programs are only processed by the new program, the old
+/// program gets a copy of their outputs. Only the nodes that were removed from the graph
+/// and the `Out` nodes are processed by the old program. The audio output of both
+/// programs is captured and blended linearly.
+pub(crate) struct ProgCrossfade {
+    /// The crossfade time in milliseconds, `0.0` disables the crossfade.
+    time_ms: f32,
+    /// The program that is being faded out.
+    pub(crate) old_prog: Option<NodeProg>,
+    /// Maps the ops of the `old_prog` to the index of the op in the new program
+    /// that processes the same node.
+    pub(crate) shared_ops: Vec<Option<usize>>,
+    pub(crate) old_out: CaptureBuf,
+    pub(crate) new_out: CaptureBuf,
+    frames_left: usize,
+    frames_total: usize,
+}
+
+impl ProgCrossfade {
+    pub(crate) fn new() -> Self {
+        Self {
+            time_ms: 0.0,
+            old_prog: None,
+            shared_ops: Vec::new(),
+            old_out: CaptureBuf::new(),
+            new_out: CaptureBuf::new(),
+            frames_left: 0,
+            frames_total: 0,
+        }
+    }
+
+    pub(crate) fn set_time_ms(&mut self, time_ms: f32) {
+        self.time_ms = time_ms.max(0.0);
+    }
+
+    #[inline]
+    pub(crate) fn is_active(&self) -> bool {
+        self.old_prog.is_some()
+    }
+
+    /// Starts fading out `old_prog`. Returns the program that has to be dropped,
+    /// which is either `old_prog` itself if the crossfade is disabled, or
+    /// the program of a crossfade that was still in progress.
+    ///
+    /// The `shared_ops` mapping, see [shared_ops], is swapped in. The vector
+    /// that is left in `shared_ops` has to be dropped outside the audio thread.
+    pub(crate) fn start(
+        &mut self,
+        mut old_prog: NodeProg,
+        shared_ops: &mut Vec<Option<usize>>,
+        sample_rate: f32,
+    ) -> Option<NodeProg> {
+        let frames = ((self.time_ms * sample_rate) / 1000.0) as usize;
+        if frames == 0 {
+            return Some(old_prog);
+        }
+
+        let prev_old_prog = self.old_prog.take();
+
+        std::mem::swap(&mut self.shared_ops, shared_ops);
+
+        // The buffers might have been unlocked by copying the old outputs:
+        old_prog.assign_outputs();
+
+        self.old_prog = Some(old_prog);
+        self.frames_total = frames;
+        self.frames_left = frames;
+
+        prev_old_prog
+    }
+
+    /// Stops any running crossfade and returns the program that has to be dropped.
+    pub(crate) fn stop(&mut self) -> Option<NodeProg> {
+        self.frames_left = 0;
+        self.old_prog.take()
+    }
+
+    /// Blends the captured outputs of the old and new program into the `ctx`.
+    /// Returns the old program once the crossfade is finished, so that it can be dropped.
+    pub(crate) fn mix(&mut self, ctx: &mut dyn NodeAudioContext) -> Option<NodeProg> {
+        let nframes = ctx.nframes();
+        let written = self.old_out.written | self.new_out.written;
+
+        for channel in 0..MAX_AUDIO_CHANNELS {
+            if written & (1 << channel) == 0 {
+                continue;
+            }
+
+            for frame in 0..nframes {
+                let old_gain =
+                    self.frames_left.saturating_sub(frame) as f32 / self.frames_total as f32;
+                let old = self.old_out.get(channel, frame);
+                let new = self.new_out.get(channel, frame);
+                ctx.output(channel, frame, old * old_gain + new * (1.0 - old_gain));
+            }
+        }
+
+        self.frames_left = self.frames_left.saturating_sub(nframes);
+        if self.frames_left == 0 {
+            self.old_prog.take()
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use super::oversampling::OvsGroupShape;
+use super::{NodeOp, NodeProg, OversamplingGroup};
+
+/// The changes between the [NodeProg] that runs in the [crate::nodes::NodeExecutor] and
+/// a newly built one. Computed by [crate::nodes::NodeConfigurator::upload_prog] and
+/// applied to the running program in place, so that the ops, buffers and oversampling
+/// filters of the unchanged parts of the graph keep their state.
+#[derive(Debug)]
+pub struct NodeProgDiff {
+    /// The new list of ops. The ops of nodes whose edges did not change are
+    /// equal to the ops of the running program.
+    pub ops: Vec<NodeOp>,
+    /// The number of ops that were added or whose input edges changed.
+    pub changed_ops: usize,
+    /// The number of ops that were removed.
+    pub removed_ops: usize,
+    /// The new buffers, if nodes were created or modulation amounts were assigned
+    /// since the last upload. The buffers of the running program are moved into
+    /// them. If `None`, the buffers of the running program are kept as they are.
+    pub buffers: Option<NodeProg>,
+    /// The oversampling groups of the new program.
+    pub ovs_groups: Vec<OversamplingGroup>,
+    /// For each of the `ovs_groups` the index of the running group with the same
+    /// nodes and edges, which passes on the state of it's filters.
+    pub ovs_keep: Vec<Option<usize>>,
+}
+
+/// The buffer layout and the ops of the [NodeProg] that was last uploaded to the
+/// [crate::nodes::NodeExecutor]. Kept by the [crate::nodes::NodeConfigurator] to compute
+/// the [NodeProgDiff] for the next upload.
+pub(crate) struct ProgShape {
+    out_len: usize,
+    in_len: usize,
+    at_len: usize,
+    /// The input index of each [crate::nodes::ModOp].
+    mod_inputs: Vec<usize>,
+    ops: Vec<NodeOp>,
+    ovs_groups: Vec<OvsGroupShape>,
+}
+
+impl ProgShape {
+    pub(crate) fn new(prog: &NodeProg, mod_inputs: Vec<usize>) -> Self {
+        Self {
+            out_len: prog.out.len(),
+            in_len: prog.inp.len(),
+            at_len: prog.atoms.len(),
+            mod_inputs,
+            ops: prog.prog.clone(),
+            ovs_groups: prog.ovs_groups.iter().map(|g| g.shape(prog)).collect(),
+        }
+    }
+
+    /// The ops of the program.
+    pub(crate) fn ops(&self) -> &[NodeOp] {
+        &self.ops
+    }
+
+    fn same_buffers(&self, other: &ProgShape) -> bool {
+        self.out_len == other.out_len
+            && self.in_len == other.in_len
+            && self.at_len == other.at_len
+            && self.mod_inputs == other.mod_inputs
+    }
+
+    /// Computes the changes from the program of `self` to `prog`, whose shape is `new`.
+    /// Returns `None` if the program did not change at all.
+    pub(crate) fn diff(&self, new: &ProgShape, mut prog: NodeProg) -> Option<NodeProgDiff> {
+        let changed_ops =
+            new.ops.iter().filter(|op| !self.ops.iter().any(|old| old.has_same_edges(op))).count();
+        let removed_ops =
+            self.ops.iter().filter(|old| !new.ops.iter().any(|op| op.idx == old.idx)).count();
+
+        let same_buffers = self.same_buffers(new);
+        let same_order = self.ops.len() == new.ops.len()
+            && self.ops.iter().zip(new.ops.iter()).all(|(old, op)| old.idx == op.idx);
+
+        if changed_ops == 0
+            && removed_ops == 0
+            && same_buffers
+            && same_order
+            && self.ovs_groups == new.ovs_groups
+        {
+            return None;
+        }
+
+        let ovs_keep = new
+            .ovs_groups
+            .iter()
+            .map(|group| self.ovs_groups.iter().position(|old| old == group))
+            .collect();
+
+        Some(NodeProgDiff {
+            ops: std::mem::take(&mut prog.prog),
+            changed_ops,
+            removed_ops,
+            ovs_groups: std::mem::take(&mut prog.ovs_groups),
+            ovs_keep,
+            buffers: if same_buffers { None } else { Some(prog) },
+        })
+    }
+}
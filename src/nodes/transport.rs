@@ -0,0 +1,158 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+/// Note lengths that host synchronized nodes can select, with their
+/// length in beats (quarter notes).
+pub const HOST_NOTE_DIVS: [(&str, f64); 16] = [
+    ("4/1", 16.0),
+    ("2/1", 8.0),
+    ("1/1", 4.0),
+    ("1/2", 2.0),
+    ("1/2.", 3.0),
+    ("1/2T", 4.0 / 3.0),
+    ("1/4", 1.0),
+    ("1/4.", 1.5),
+    ("1/4T", 2.0 / 3.0),
+    ("1/8", 0.5),
+    ("1/8.", 0.75),
+    ("1/8T", 1.0 / 3.0),
+    ("1/16", 0.25),
+    ("1/16.", 0.375),
+    ("1/16T", 1.0 / 6.0),
+    ("1/32", 0.125),
+];
+
+/// Returns the length in beats of the note length at `idx` in [HOST_NOTE_DIVS].
+/// The index is clamped to the valid range.
+pub fn host_note_div_beats(idx: i64) -> f64 {
+    HOST_NOTE_DIVS[idx.clamp(0, HOST_NOTE_DIVS.len() as i64 - 1) as usize].1
+}
+
+/// The transport and tempo information of the host (DAW or plugin API).
+///
+/// Fill this in with [crate::nodes::NodeExecutor::set_transport] before each call to
+/// [crate::nodes::NodeExecutor::process]. After processing a buffer period, the
+/// [crate::nodes::NodeExecutor] advances the positions by the number of processed frames
+/// if the transport is playing. So if the host does not provide any position information,
+/// it's enough to set the tempo and the playing state once.
+///
+/// Nodes can read this via [crate::nodes::NodeExecContext::transport].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HostTransport {
+    /// The tempo in beats (quarter notes) per minute.
+    pub tempo: f64,
+    /// The numerator of the time signature, eg. the `3` in 3/4.
+    pub time_sig_num: u32,
+    /// The denominator of the time signature, eg. the `4` in 3/4.
+    pub time_sig_den: u32,
+    /// The song position in beats (quarter notes) at the first frame of the buffer period.
+    pub song_pos_beats: f64,
+    /// Whether the host transport is playing.
+    pub playing: bool,
+    /// The song position in samples at the first frame of the buffer period.
+    pub sample_pos: u64,
+}
+
+impl HostTransport {
+    pub fn new() -> Self {
+        Self {
+            tempo: 120.0,
+            time_sig_num: 4,
+            time_sig_den: 4,
+            song_pos_beats: 0.0,
+            playing: false,
+            sample_pos: 0,
+        }
+    }
+
+    /// Returns the length of a bar in beats (quarter notes) according to the time signature.
+    pub fn beats_per_bar(&self) -> f64 {
+        (self.time_sig_num.max(1) as f64 * 4.0) / self.time_sig_den.max(1) as f64
+    }
+
+    /// Returns the number of samples of one beat (quarter note) at the current tempo.
+    pub fn samples_per_beat(&self, srate: f32) -> f64 {
+        (srate as f64 * 60.0) / self.tempo.max(1.0)
+    }
+
+    /// Returns the length of `beats` in milliseconds at the current tempo.
+    pub fn beats_to_ms(&self, beats: f64) -> f64 {
+        (beats * 60000.0) / self.tempo.max(1.0)
+    }
+
+    /// Returns the song position in beats at the given `frame` of the current buffer period.
+    #[inline]
+    pub fn beat_at(&self, frame: usize, srate: f32) -> f64 {
+        if self.playing {
+            self.song_pos_beats + frame as f64 / self.samples_per_beat(srate)
+        } else {
+            self.song_pos_beats
+        }
+    }
+
+    /// Returns true if a multiple of `beats` starts at the given `frame` of the current
+    /// buffer period. Always false if the transport is not playing.
+    ///
+    /// For instance `transport.starts_at(frame, srate, transport.beats_per_bar())` tells
+    /// you if a new bar starts at that frame.
+    #[inline]
+    pub fn starts_at(&self, frame: usize, srate: f32, beats: f64) -> bool {
+        if !self.playing || beats <= 0.0 {
+            return false;
+        }
+
+        let cur = self.beat_at(frame, srate);
+        let prev = cur - 1.0 / self.samples_per_beat(srate);
+        (prev / beats).floor() != (cur / beats).floor()
+    }
+
+    /// Advances the song position by `nframes`, if the transport is playing.
+    pub fn advance(&mut self, nframes: usize, srate: f32) {
+        if self.playing {
+            self.song_pos_beats += nframes as f64 / self.samples_per_beat(srate);
+            self.sample_pos += nframes as u64;
+        }
+    }
+}
+
+impl Default for HostTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_transport_positions() {
+        let mut tr = HostTransport::new();
+        tr.tempo = 60.0;
+        tr.time_sig_num = 3;
+        tr.time_sig_den = 4;
+
+        assert_eq!(tr.beats_per_bar(), 3.0);
+        assert_eq!(tr.samples_per_beat(100.0), 100.0);
+        assert_eq!(tr.beats_to_ms(0.5), 500.0);
+
+        // Stopped transport does not move:
+        assert_eq!(tr.beat_at(50, 100.0), 0.0);
+        assert!(!tr.starts_at(0, 100.0, 1.0));
+        tr.advance(50, 100.0);
+        assert_eq!(tr.song_pos_beats, 0.0);
+
+        tr.playing = true;
+        assert_eq!(tr.beat_at(50, 100.0), 0.5);
+        assert!(tr.starts_at(0, 100.0, tr.beats_per_bar()));
+        assert!(!tr.starts_at(1, 100.0, tr.beats_per_bar()));
+
+        tr.advance(250, 100.0);
+        assert_eq!(tr.song_pos_beats, 2.5);
+        assert_eq!(tr.sample_pos, 250);
+        assert!(tr.starts_at(50, 100.0, tr.beats_per_bar()));
+        assert!(!tr.starts_at(49, 100.0, tr.beats_per_bar()));
+        assert!(!tr.starts_at(51, 100.0, tr.beats_per_bar()));
+    }
+}
@@ -0,0 +1,332 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use super::{HxMidiEvent, HxTimedEvent};
+
+/// The maximum number of voices the [VoiceAllocator] can distribute notes to.
+pub const MAX_VOICES: usize = 16;
+
+/// Determines which voice is taken away from a playing note if
+/// all voices are busy and a new note comes in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceStealing {
+    /// Steal the voice that was started the longest time ago.
+    Oldest,
+    /// Steal the voice that plays the lowest note.
+    Lowest,
+    /// Steal the voice that plays the highest note.
+    Highest,
+    /// Don't steal any voice, the new note is dropped.
+    NoSteal,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct VoiceSlot {
+    channel: u8,
+    note: u8,
+    active: bool,
+    /// Counter value of the last note on or note off of this voice.
+    age: u64,
+}
+
+impl VoiceSlot {
+    fn new() -> Self {
+        Self { channel: 0, note: 0, active: false, age: 0 }
+    }
+}
+
+/// Allocates incoming MIDI notes to a fixed number of voices.
+///
+/// This is used by the [crate::nodes::NodeExecutor] to distribute the MIDI note events
+/// of a buffer period into [crate::nodes::NodeExecContext::voice_notes], from where the
+/// `MidiP` nodes with a `voice` setting pick them up.
+///
+/// Free voices are reused in the order they were released, so that release phases of
+/// envelopes are not cut off too early. If no voice is free, a voice is stolen
+/// according to the [VoiceStealing] policy. The stolen voice receives a note off event for
+/// it's old note right before the note on of the new note.
+#[derive(Debug, Clone)]
+pub struct VoiceAllocator {
+    slots: [VoiceSlot; MAX_VOICES],
+    voices: usize,
+    stealing: VoiceStealing,
+    counter: u64,
+}
+
+impl VoiceAllocator {
+    pub fn new() -> Self {
+        Self {
+            slots: [VoiceSlot::new(); MAX_VOICES],
+            voices: 1,
+            stealing: VoiceStealing::Oldest,
+            counter: 0,
+        }
+    }
+
+    /// Sets the number of voices and the stealing policy.
+    /// The number of voices is clamped to `1..=MAX_VOICES`.
+    /// This also resets the allocation state.
+    pub fn configure(&mut self, voices: usize, stealing: VoiceStealing) {
+        self.voices = voices.clamp(1, MAX_VOICES);
+        self.stealing = stealing;
+        self.reset();
+    }
+
+    /// Forgets about all currently playing notes.
+    pub fn reset(&mut self) {
+        self.slots = [VoiceSlot::new(); MAX_VOICES];
+        self.counter = 0;
+    }
+
+    /// Returns the number of voices notes are distributed to.
+    pub fn voices(&self) -> usize {
+        self.voices
+    }
+
+    /// Returns the current voice stealing policy.
+    pub fn stealing(&self) -> VoiceStealing {
+        self.stealing
+    }
+
+    /// Returns the note that is currently playing on the given voice, if any.
+    pub fn voice_note(&self, voice: usize) -> Option<(u8, u8)> {
+        let slot = self.slots.get(voice)?;
+        if voice < self.voices && slot.active {
+            Some((slot.channel, slot.note))
+        } else {
+            None
+        }
+    }
+
+    fn find_voice(&self, channel: u8, note: u8) -> Option<usize> {
+        self.slots[0..self.voices]
+            .iter()
+            .position(|s| s.active && s.channel == channel && s.note == note)
+    }
+
+    fn find_free_voice(&self) -> Option<usize> {
+        self.slots[0..self.voices]
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| !s.active)
+            .min_by_key(|(_, s)| s.age)
+            .map(|(i, _)| i)
+    }
+
+    fn find_steal_voice(&self) -> Option<usize> {
+        let active = self.slots[0..self.voices].iter().enumerate();
+
+        match self.stealing {
+            VoiceStealing::Oldest => active.min_by_key(|(_, s)| s.age).map(|(i, _)| i),
+            VoiceStealing::Lowest => active.min_by_key(|(_, s)| (s.note, s.age)).map(|(i, _)| i),
+            VoiceStealing::Highest => {
+                active.max_by_key(|(_, s)| (s.note, u64::MAX - s.age)).map(|(i, _)| i)
+            }
+            VoiceStealing::NoSteal => None,
+        }
+    }
+
+    /// Allocates a voice for a note on event. Returns the voice index and
+    /// the `(channel, note)` of a stolen note if a voice had to be stolen.
+    /// Returns `None` if no voice could be allocated.
+    pub fn note_on(&mut self, channel: u8, note: u8) -> Option<(usize, Option<(u8, u8)>)> {
+        self.counter += 1;
+
+        // Retrigger notes that are already playing on the same voice:
+        let (voice, stolen) = if let Some(voice) = self.find_voice(channel, note) {
+            (voice, None)
+        } else if let Some(voice) = self.find_free_voice() {
+            (voice, None)
+        } else {
+            let voice = self.find_steal_voice()?;
+            let slot = &self.slots[voice];
+            (voice, Some((slot.channel, slot.note)))
+        };
+
+        self.slots[voice] = VoiceSlot { channel, note, active: true, age: self.counter };
+
+        Some((voice, stolen))
+    }
+
+    /// Releases the voice that plays the given note. Returns the voice index
+    /// if the note was playing.
+    pub fn note_off(&mut self, channel: u8, note: u8) -> Option<usize> {
+        self.counter += 1;
+
+        let voice = self.find_voice(channel, note)?;
+        self.slots[voice].active = false;
+        self.slots[voice].age = self.counter;
+
+        Some(voice)
+    }
+
+    /// Distributes the note events in `events` to the per voice event lists in `voice_events`.
+    /// Polyphonic key pressure goes to the voice of it's note, pitch bend and channel
+    /// pressure go to all voices. The lists are cleared first. Events are not pushed beyond the capacity of the
+    /// lists, so that this does not allocate on the audio thread.
+    pub fn distribute(&mut self, events: &[HxTimedEvent], voice_events: &mut [Vec<HxTimedEvent>]) {
+        for evs in voice_events.iter_mut() {
+            evs.clear();
+        }
+
+        let push = |evs: &mut Vec<HxTimedEvent>, ev: HxTimedEvent| {
+            if evs.len() < evs.capacity() {
+                evs.push(ev);
+            }
+        };
+
+        for ev in events.iter() {
+            match ev.kind() {
+                HxMidiEvent::NoteOn { channel, note, .. } => {
+                    if let Some((voice, stolen)) = self.note_on(channel, note) {
+                        if let Some(evs) = voice_events.get_mut(voice) {
+                            if let Some((s_chan, s_note)) = stolen {
+                                push(evs, HxTimedEvent::note_off(ev.timing(), s_chan, s_note));
+                            }
+                            push(evs, *ev);
+                        }
+                    }
+                }
+                HxMidiEvent::NoteOff { channel, note } => {
+                    if let Some(voice) = self.note_off(channel, note) {
+                        if let Some(evs) = voice_events.get_mut(voice) {
+                            push(evs, *ev);
+                        }
+                    }
+                }
+                HxMidiEvent::PolyPressure { channel, note, .. } => {
+                    if let Some(voice) = self.find_voice(channel, note) {
+                        if let Some(evs) = voice_events.get_mut(voice) {
+                            push(evs, *ev);
+                        }
+                    }
+                }
+                HxMidiEvent::PitchBend { .. } | HxMidiEvent::ChannelPressure { .. } => {
+                    // Channel wide events go to all voices, also to the released ones:
+                    for evs in voice_events.iter_mut().take(self.voices) {
+                        push(evs, *ev);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Default for VoiceAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_voice_alloc_free_voices() {
+        let mut va = VoiceAllocator::new();
+        va.configure(3, VoiceStealing::Oldest);
+
+        assert_eq!(va.note_on(0, 60), Some((0, None)));
+        assert_eq!(va.note_on(0, 62), Some((1, None)));
+        assert_eq!(va.note_on(0, 64), Some((2, None)));
+
+        // The voice that was released the longest time ago is reused first:
+        assert_eq!(va.note_off(0, 62), Some(1));
+        assert_eq!(va.note_off(0, 60), Some(0));
+        assert_eq!(va.note_on(0, 65), Some((1, None)));
+        assert_eq!(va.note_on(0, 67), Some((0, None)));
+
+        // Unknown notes don't release anything:
+        assert_eq!(va.note_off(0, 10), None);
+        assert_eq!(va.note_off(1, 64), None);
+
+        // Retrigger of an already playing note:
+        assert_eq!(va.note_on(0, 64), Some((2, None)));
+        assert_eq!(va.voice_note(2), Some((0, 64)));
+    }
+
+    #[test]
+    fn check_voice_alloc_stealing() {
+        let mut va = VoiceAllocator::new();
+
+        va.configure(3, VoiceStealing::Oldest);
+        va.note_on(0, 64);
+        va.note_on(0, 60);
+        va.note_on(0, 67);
+        assert_eq!(va.note_on(0, 70), Some((0, Some((0, 64)))));
+        assert_eq!(va.note_on(0, 72), Some((1, Some((0, 60)))));
+
+        va.configure(3, VoiceStealing::Lowest);
+        va.note_on(0, 64);
+        va.note_on(0, 60);
+        va.note_on(0, 67);
+        assert_eq!(va.note_on(0, 70), Some((1, Some((0, 60)))));
+        assert_eq!(va.note_on(0, 72), Some((0, Some((0, 64)))));
+
+        va.configure(3, VoiceStealing::Highest);
+        va.note_on(0, 64);
+        va.note_on(0, 60);
+        va.note_on(0, 67);
+        assert_eq!(va.note_on(0, 50), Some((2, Some((0, 67)))));
+        assert_eq!(va.note_on(0, 52), Some((0, Some((0, 64)))));
+
+        va.configure(2, VoiceStealing::NoSteal);
+        va.note_on(0, 64);
+        va.note_on(0, 60);
+        assert_eq!(va.note_on(0, 70), None);
+        assert_eq!(va.note_off(0, 70), None);
+        assert_eq!(va.note_off(0, 60), Some(1));
+        assert_eq!(va.note_on(0, 70), Some((1, None)));
+    }
+
+    #[test]
+    fn check_voice_alloc_distribute() {
+        let mut va = VoiceAllocator::new();
+        va.configure(2, VoiceStealing::Oldest);
+
+        let mut voice_events = vec![];
+        voice_events.resize_with(MAX_VOICES, || Vec::with_capacity(16));
+
+        va.distribute(
+            &[
+                HxTimedEvent::note_on(1, 0, 60, 1.0),
+                HxTimedEvent::note_on(2, 0, 64, 0.5),
+                HxTimedEvent::note_on(3, 0, 67, 0.7),
+                HxTimedEvent::note_off(4, 0, 64),
+            ],
+            &mut voice_events[..],
+        );
+
+        let dump = |evs: &Vec<HxTimedEvent>| {
+            evs.iter()
+                .map(|ev| match ev.kind() {
+                    HxMidiEvent::NoteOn { note, .. } => (ev.timing(), note, true),
+                    HxMidiEvent::NoteOff { note, .. } => (ev.timing(), note, false),
+                    _ => (ev.timing(), 0, false),
+                })
+                .collect::<Vec<(usize, u8, bool)>>()
+        };
+
+        assert_eq!(dump(&voice_events[0]), vec![(1, 60, true), (3, 60, false), (3, 67, true)]);
+        assert_eq!(dump(&voice_events[1]), vec![(2, 64, true), (4, 64, false)]);
+        assert!(voice_events[2].is_empty());
+
+        va.distribute(
+            &[
+                HxTimedEvent::pitch_bend(1, 0, 0x3FFF),
+                HxTimedEvent::poly_pressure(2, 0, 67, 0.5),
+                HxTimedEvent::poly_pressure(3, 0, 64, 0.5),
+            ],
+            &mut voice_events[..],
+        );
+
+        let kinds =
+            |evs: &Vec<HxTimedEvent>| evs.iter().map(|ev| ev.timing()).collect::<Vec<usize>>();
+        assert_eq!(kinds(&voice_events[0]), vec![1, 2]);
+        assert_eq!(kinds(&voice_events[1]), vec![1]);
+        assert!(voice_events[2].is_empty());
+    }
+}
@@ -0,0 +1,531 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+use super::node_exec::{process_op, process_step, CpuProfiler};
+use super::{
+    uses_audio_driver, uses_exec_context, NodeAudioContext, NodeExecContext, NodeProg, ProgPtrs,
+};
+use synfx_dsp::AtomicFloat;
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// The maximum number of worker threads for the parallel execution of a [NodeProg].
+pub const MAX_WORKER_THREADS: usize = 16;
+
+/// The maximum number of [crate::nodes::NodeOp] in a [NodeProg], limited by the `u8` index.
+const MAX_SCHEDULE_OPS: usize = 256;
+
+/// How often an idle worker polls for a new level before it parks itself.
+const WORKER_SPIN_ROUNDS: usize = 20000;
+
+/// The part of the buffer period, after which the audio thread stops waiting for
+/// the workers and processes the remaining levels of the period on it's own.
+const WORKER_DEADLINE: f64 = 0.5;
+
+/// The [NodeAudioContext] for the worker threads. The nodes that access the audio
+/// driver buffers are never processed by the workers, so it only knows the
+/// number of frames.
+struct WorkerContext {
+    nframes: usize,
+}
+
+impl NodeAudioContext for WorkerContext {
+    #[inline]
+    fn nframes(&self) -> usize {
+        self.nframes
+    }
+
+    #[inline]
+    fn output(&mut self, _channel: usize, _frame: usize, _v: f32) {}
+
+    #[inline]
+    fn input(&mut self, _channel: usize, _frame: usize) -> f32 {
+        0.0
+    }
+}
+
+/// One level of the [NodeProg] that is processed by the audio thread
+/// and the workers together.
+#[derive(Clone, Copy)]
+struct LevelJob {
+    /// Each op of the level is claimed by exactly one thread, which gets
+    /// the [crate::nodes::OpSlot] of the op from these pointers.
+    prog: Option<ProgPtrs>,
+    ctx_vals: *const [Arc<AtomicFloat>],
+    /// Points to [WorkerPool::ops].
+    ops: *const usize,
+    nframes: usize,
+    /// Whether the CPU time of the nodes is measured.
+    profile: bool,
+}
+
+impl LevelJob {
+    fn empty() -> Self {
+        Self {
+            prog: None,
+            ctx_vals: std::ptr::slice_from_raw_parts(std::ptr::null(), 0),
+            ops: std::ptr::null(),
+            nframes: 0,
+            profile: false,
+        }
+    }
+
+    /// Processes the op at index `i` of [WorkerPool::ops].
+    ///
+    /// # Safety
+    /// The op must have been claimed with [PoolShared::claim_op] for the level
+    /// of this job, and the audio thread must still wait for it.
+    unsafe fn process(
+        &self,
+        i: usize,
+        ctx: &mut dyn NodeAudioContext,
+        exec_ctx: &mut NodeExecContext,
+        profiler: &mut CpuProfiler,
+    ) {
+        if let Some(prog) = self.prog {
+            // The ops of a level are independent of each other, each only writes
+            // it's own output buffers, modulators and node state. Because each op
+            // is claimed once, no other thread accesses the slot at the same time.
+            process_op(prog.op_slot(*self.ops.add(i)), ctx, exec_ctx, &*self.ctx_vals, profiler);
+        }
+    }
+}
+
+/// Packs the level generation, the next free op and the end of the level
+/// into the value of [PoolShared::claim].
+fn pack_claim(level_gen: u64, next: usize, end: usize) -> u64 {
+    (level_gen << 32) | ((end as u64) << 16) | next as u64
+}
+
+/// The state shared between the audio thread and the worker threads.
+struct PoolShared {
+    /// The current level. Only written by the audio thread when all ops of the previous
+    /// level are claimed, a worker only uses it after it claimed an op of the level.
+    job: UnsafeCell<LevelJob>,
+    /// The generation of the current level, the index of the next op in [WorkerPool::ops]
+    /// that is free to be claimed and the end of the level, see [pack_claim].
+    /// An op can only be claimed for the current generation, so a worker that
+    /// wakes up late never processes an op of the next level.
+    claim: AtomicU64,
+    /// The number of ops the workers finished in the current level.
+    finished: AtomicUsize,
+    quit: AtomicBool,
+    /// Whether each worker is parked, or about to park itself.
+    parked: Box<[AtomicBool]>,
+    /// The scheduling policy and priority of the audio thread, which the workers take
+    /// over, see [current_thread_priority]. 0 if it's not known yet.
+    priority: AtomicU64,
+    /// The CPU time measured by each worker, only accessed by the worker itself
+    /// while it processes a claimed op, and by the audio thread after the last barrier.
+    profilers: Box<[UnsafeCell<CpuProfiler>]>,
+    /// The [NodeExecContext] of each worker, with the same access rules as `profilers`.
+    exec_ctxs: Box<[UnsafeCell<NodeExecContext>]>,
+}
+
+// The raw pointers in `job` are only dereferenced by a thread that claimed an op,
+// and the audio thread waits for all claimed ops at the end of each level.
+unsafe impl Send for PoolShared {}
+unsafe impl Sync for PoolShared {}
+
+impl PoolShared {
+    /// Claims the next free op of the level `level_gen`. Returns it's index
+    /// in [WorkerPool::ops], or `None` if all ops of the level are claimed.
+    #[inline]
+    fn claim_op(&self, level_gen: u64) -> Option<usize> {
+        let mut claim = self.claim.load(Ordering::Acquire);
+
+        loop {
+            let next = (claim & 0xFFFF) as usize;
+            let end = ((claim >> 16) & 0xFFFF) as usize;
+            if claim >> 32 != level_gen || next >= end {
+                return None;
+            }
+
+            match self.claim.compare_exchange_weak(
+                claim,
+                claim + 1,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(next),
+                Err(cur) => claim = cur,
+            }
+        }
+    }
+
+    /// Claims and processes the ops of the level `level_gen` on the worker
+    /// `worker_idx`, until none are left.
+    ///
+    /// # Safety
+    /// Must only be called by the worker thread `worker_idx`, after it read the
+    /// generation `level_gen` from [PoolShared::claim].
+    unsafe fn work(&self, level_gen: u64, worker_idx: usize) {
+        // The audio thread might already write the next level while we read the job.
+        // But then all ops of this level are claimed, so the job is never used:
+        let job = std::ptr::read_volatile(self.job.get());
+
+        while let Some(i) = self.claim_op(level_gen) {
+            let profiler = &mut *self.profilers[worker_idx].get();
+            let exec_ctx = &mut *self.exec_ctxs[worker_idx].get();
+            profiler.enabled = job.profile;
+            job.process(i, &mut WorkerContext { nframes: job.nframes }, exec_ctx, profiler);
+
+            self.finished.fetch_add(1, Ordering::Release);
+        }
+    }
+}
+
+fn worker_loop(shared: Arc<PoolShared>, worker_idx: usize) {
+    let mut seen_gen = 0;
+    let mut idle_rounds = 0;
+    let mut priority = 0;
+
+    loop {
+        if shared.quit.load(Ordering::Acquire) {
+            return;
+        }
+
+        let audio_priority = shared.priority.load(Ordering::Relaxed);
+        if audio_priority != priority {
+            priority = audio_priority;
+            set_thread_priority(priority);
+        }
+
+        let level_gen = shared.claim.load(Ordering::Acquire) >> 32;
+        if level_gen != seen_gen {
+            seen_gen = level_gen;
+            idle_rounds = 0;
+
+            unsafe { shared.work(level_gen, worker_idx) };
+        } else if idle_rounds < WORKER_SPIN_ROUNDS {
+            idle_rounds += 1;
+            std::hint::spin_loop();
+        } else {
+            // The audio thread only unparks the workers that announced it,
+            // and checks the flag after it published a new level:
+            shared.parked[worker_idx].store(true, Ordering::SeqCst);
+            if shared.claim.load(Ordering::SeqCst) >> 32 == seen_gen
+                && !shared.quit.load(Ordering::SeqCst)
+            {
+                std::thread::park();
+            }
+            shared.parked[worker_idx].store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Returns the scheduling policy and priority of the current thread,
+/// packed into an `u64`. Returns 0 if they are not known.
+#[cfg(unix)]
+fn current_thread_priority() -> u64 {
+    unsafe {
+        let mut policy = 0;
+        let mut param: libc::sched_param = std::mem::zeroed();
+        if libc::pthread_getschedparam(libc::pthread_self(), &mut policy, &mut param) != 0 {
+            return 0;
+        }
+
+        (1 << 63) | ((policy as u32 as u64) << 32) | param.sched_priority as u32 as u64
+    }
+}
+
+/// Sets the scheduling policy and priority, as returned by [current_thread_priority],
+/// for the current thread. Fails silently if the process lacks the permission.
+#[cfg(unix)]
+fn set_thread_priority(priority: u64) {
+    if priority == 0 {
+        return;
+    }
+
+    unsafe {
+        let mut param: libc::sched_param = std::mem::zeroed();
+        param.sched_priority = priority as u32 as i32;
+        let policy = (priority >> 32) as u32 as i32;
+        libc::pthread_setschedparam(libc::pthread_self(), policy, &param);
+    }
+}
+
+#[cfg(windows)]
+mod win32 {
+    pub const THREAD_PRIORITY_ERROR_RETURN: i32 = 0x7FFFFFFF;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn GetCurrentThread() -> *mut std::ffi::c_void;
+        pub fn GetThreadPriority(thread: *mut std::ffi::c_void) -> i32;
+        pub fn SetThreadPriority(thread: *mut std::ffi::c_void, priority: i32) -> i32;
+    }
+}
+
+#[cfg(windows)]
+fn current_thread_priority() -> u64 {
+    let priority = unsafe { win32::GetThreadPriority(win32::GetCurrentThread()) };
+    if priority == win32::THREAD_PRIORITY_ERROR_RETURN {
+        return 0;
+    }
+
+    (1 << 63) | priority as u32 as u64
+}
+
+#[cfg(windows)]
+fn set_thread_priority(priority: u64) {
+    if priority != 0 {
+        unsafe { win32::SetThreadPriority(win32::GetCurrentThread(), priority as u32 as i32) };
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn current_thread_priority() -> u64 {
+    0
+}
+
+#[cfg(not(any(unix, windows)))]
+fn set_thread_priority(_priority: u64) {}
+
+/// A fixed pool of worker threads, which process independent [crate::nodes::NodeOp]
+/// of a [NodeProg] in parallel.
+///
+/// The program is split into levels, as calculated by
+/// [crate::nodes::NodeGraphOrdering::node_level]. The nodes of one level don't depend
+/// on each other and are distributed over the audio thread and the workers. Each level
+/// ends with a barrier, where the audio thread waits for the ops the workers claimed.
+///
+/// The workers busy wait for a short while after each level and park themselves
+/// if there is no more work, so they don't eat up the CPU while the audio
+/// thread is idle. Nodes that access the audio driver buffers, like `Out` and `Inp`,
+/// and the nodes that need the [NodeExecContext] of the audio thread, like `MidiP`
+/// or `ExtA`, are always processed on the audio thread. Each worker has it's own
+/// [NodeExecContext], the events emitted there are forwarded after each buffer period.
+///
+/// The pool can be used from a realtime audio callback. The workers take over the
+/// scheduling policy and priority of the thread that calls [crate::nodes::NodeExecutor::process]
+/// first. The audio thread does not wait for workers that are still parked or preempted,
+/// it claims the ops of a level itself until none are left, and only waits for the ops
+/// that are already in progress. If the workers miss the deadline of half the buffer
+/// period, the audio thread processes the remaining levels of the period on it's own.
+///
+/// Create it with [crate::nodes::NodeConfigurator::set_worker_threads].
+pub struct WorkerPool {
+    shared: Arc<PoolShared>,
+    threads: Vec<JoinHandle<()>>,
+    /// The indices of the ops in [NodeProg::prog] grouped by level. Inside each
+    /// level, the ops that need the audio thread come first.
+    ops: Vec<usize>,
+    /// The levels as `(start, parallel start, end)` indices into `ops`.
+    levels: Vec<(usize, usize, usize)>,
+    /// The generation of the last published level.
+    level_gen: u64,
+}
+
+impl WorkerPool {
+    /// Spawns `threads` worker threads, at most [MAX_WORKER_THREADS].
+    /// The `node_count` is the number of node instances the CPU time is measured for.
+    /// This is not realtime safe and must not be called on the audio thread.
+    pub(crate) fn new(threads: usize, node_count: usize) -> Self {
+        let threads = threads.min(MAX_WORKER_THREADS);
+        let shared = Arc::new(PoolShared {
+            job: UnsafeCell::new(LevelJob::empty()),
+            claim: AtomicU64::new(0),
+            finished: AtomicUsize::new(0),
+            quit: AtomicBool::new(false),
+            parked: (0..threads).map(|_| AtomicBool::new(false)).collect(),
+            priority: AtomicU64::new(0),
+            profilers: (0..threads)
+                .map(|_| UnsafeCell::new(CpuProfiler::new(node_count)))
+                .collect(),
+            exec_ctxs: (0..threads).map(|_| UnsafeCell::new(NodeExecContext::new())).collect(),
+        });
+
+        let threads = (0..threads)
+            .filter_map(|i| {
+                let shared = shared.clone();
+                std::thread::Builder::new()
+                    .name(format!("hexodsp-worker-{}", i))
+                    .spawn(move || worker_loop(shared, i))
+                    .ok()
+            })
+            .collect();
+
+        Self {
+            shared,
+            threads,
+            ops: Vec::with_capacity(MAX_SCHEDULE_OPS),
+            levels: Vec::with_capacity(MAX_SCHEDULE_OPS),
+            level_gen: 0,
+        }
+    }
+
+    /// Returns the number of worker threads, not counting the audio thread.
+    pub fn thread_count(&self) -> usize {
+        self.threads.len()
+    }
+
+    /// Splits the `prog` into levels. Must be called whenever the
+    /// [NodeProg] of the [crate::nodes::NodeExecutor] changes.
+    /// Does not allocate, so it's safe to call on the audio thread.
+    pub(crate) fn update_schedule(&mut self, prog: &NodeProg) {
+        self.ops.clear();
+        self.levels.clear();
+
+        let op_count = prog.prog.len().min(MAX_SCHEDULE_OPS);
+        let mut i = 0;
+
+        while i < op_count {
+            // Oversampling groups are processed as one step on the audio thread:
+            if let Some(g_idx) = prog.ovs_group_of(i) {
+                let start = self.ops.len();
+                self.ops.push(i);
+                self.levels.push((start, start + 1, start + 1));
+                i = prog.ovs_groups[g_idx].ops().1;
+                continue;
+            }
+
+            // Ops without a level are executed on their own:
+            let level = prog.prog[i].level;
+            let mut j = i + 1;
+            if level.is_some() {
+                while j < op_count && prog.prog[j].level == level && prog.ovs_group_of(j).is_none()
+                {
+                    j += 1;
+                }
+            }
+
+            let start = self.ops.len();
+            for k in i..j {
+                if needs_audio_thread(prog.prog[k].node.id()) {
+                    self.ops.push(k);
+                }
+            }
+
+            let par_start = self.ops.len();
+            for k in i..j {
+                if !needs_audio_thread(prog.prog[k].node.id()) {
+                    self.ops.push(k);
+                }
+            }
+
+            self.levels.push((start, par_start, self.ops.len()));
+            i = j;
+        }
+    }
+
+    /// Processes the `prog` level by level, like `process_ops()` of
+    /// the [crate::nodes::NodeExecutor] does sequentially.
+    pub(crate) fn process(
+        &mut self,
+        prog: &mut NodeProg,
+        ctx: &mut dyn NodeAudioContext,
+        exec_ctx: &mut NodeExecContext,
+        ctx_vals: &[Arc<AtomicFloat>],
+        sample_rate: f32,
+        profiler: &mut CpuProfiler,
+    ) {
+        let nframes = ctx.nframes();
+        let shared = &*self.shared;
+
+        if shared.priority.load(Ordering::Relaxed) == 0 {
+            shared.priority.store(current_thread_priority(), Ordering::Relaxed);
+        }
+
+        let period = Duration::from_secs_f64(nframes as f64 / sample_rate as f64);
+        let deadline = Instant::now() + period.mul_f64(WORKER_DEADLINE);
+        let mut missed_deadline = false;
+
+        // The workers only access their context while they process a claimed op:
+        for worker_ctx in shared.exec_ctxs.iter() {
+            unsafe { (*worker_ctx.get()).mirror_host_state(exec_ctx) };
+        }
+
+        for (start, par_start, end) in self.levels.iter().copied() {
+            // Waking up the workers is not worth it for a single op:
+            if self.threads.is_empty() || end - par_start < 2 || missed_deadline {
+                for i in start..end {
+                    process_step(prog, self.ops[i], ctx, exec_ctx, ctx_vals, profiler);
+                }
+                continue;
+            }
+
+            // The `prog` is only accessed through the pointers until the barrier:
+            let prog_ptrs = prog.ptrs();
+            let job = LevelJob {
+                prog: Some(prog_ptrs),
+                ctx_vals: ctx_vals as *const [Arc<AtomicFloat>],
+                ops: self.ops.as_ptr(),
+                nframes,
+                profile: profiler.enabled,
+            };
+
+            // All ops of the previous level are claimed, so no worker uses the job anymore:
+            unsafe { std::ptr::write_volatile(shared.job.get(), job) };
+            shared.finished.store(0, Ordering::Relaxed);
+            self.level_gen += 1;
+            shared.claim.store(pack_claim(self.level_gen, par_start, end), Ordering::SeqCst);
+
+            for (th, parked) in self.threads.iter().zip(shared.parked.iter()) {
+                if parked.swap(false, Ordering::SeqCst) {
+                    th.thread().unpark();
+                }
+            }
+
+            let mut processed = 0;
+            unsafe {
+                for i in start..par_start {
+                    process_op(prog_ptrs.op_slot(self.ops[i]), ctx, exec_ctx, ctx_vals, profiler);
+                }
+
+                // Process the ops the workers did not claim (yet):
+                while let Some(i) = shared.claim_op(self.level_gen) {
+                    job.process(i, ctx, exec_ctx, profiler);
+                    processed += 1;
+                }
+            }
+
+            // The barrier at the end of each level only waits for the ops the workers claimed:
+            let claimed = end - par_start - processed;
+            while shared.finished.load(Ordering::Acquire) < claimed {
+                if !missed_deadline && Instant::now() > deadline {
+                    missed_deadline = true;
+                }
+                std::hint::spin_loop();
+            }
+        }
+
+        // All claimed ops are done, so the contexts and profilers are not accessed anymore:
+        for worker_ctx in shared.exec_ctxs.iter() {
+            unsafe { (*worker_ctx.get()).forward_node_events(exec_ctx) };
+        }
+
+        if profiler.enabled {
+            for worker_profiler in shared.profilers.iter() {
+                profiler.merge_from(unsafe { &mut *worker_profiler.get() });
+            }
+        }
+    }
+}
+
+/// Returns true if the node must be processed on the audio thread.
+fn needs_audio_thread(node_id: crate::dsp::NodeId) -> bool {
+    uses_audio_driver(node_id) || uses_exec_context(node_id)
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.shared.quit.store(true, Ordering::Release);
+
+        for th in self.threads.drain(..) {
+            th.thread().unpark();
+            let _ = th.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for WorkerPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WorkerPool(threads={})", self.threads.len())
+    }
+}
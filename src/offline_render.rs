@@ -0,0 +1,402 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+/*! Faster than realtime rendering of DSP graphs to audio buffers and WAV files.
+
+The [OfflineRenderer] drives a [NodeExecutor] without any audio driver. It
+feeds a scripted list of MIDI and parameter events into the graph and
+writes the output to a stereo WAV file. This is useful for bouncing presets
+or rendering regression audio in CI.
+
+```
+use hexodsp::*;
+use hexodsp::offline_render::*;
+
+let (node_conf, node_exec) = new_node_engine();
+let mut matrix = Matrix::new(node_conf, 3, 3);
+
+let mut chain = MatrixCellChain::new(CellDir::B);
+chain.node_out("sin", "sig").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+matrix.sync().unwrap();
+
+let mut renderer = OfflineRenderer::from_matrix(matrix, node_exec);
+renderer.set_sample_rate(48000.0);
+renderer.set_length(RenderLength::Seconds(0.5));
+renderer.add_param_event(
+    24000,
+    NodeId::Sin(0).inp_param("freq").unwrap(),
+    SAtom::param(NodeId::Sin(0).inp_param("freq").unwrap().norm(880.0)));
+
+let (left, _right) = renderer.render();
+assert_eq!(left.len(), 24000);
+```
+*/
+
+use crate::dsp::{ParamId, SAtom, MAX_BLOCK_SIZE};
+use crate::matrix_repr::{MatrixDeserError, MatrixRepr};
+use crate::nodes::{new_node_engine, EventWindowing, HxMidiEvent, HxTimedEvent};
+use crate::{Matrix, NodeConfigurator, NodeExecutor};
+
+/// Determines how long the [OfflineRenderer] renders.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderLength {
+    /// Render exactly this number of seconds.
+    Seconds(f32),
+    /// Render exactly this number of sample frames.
+    Frames(usize),
+    /// Render until the output has been silent for `hold_ms` milliseconds after the last
+    /// scripted event. Silence means the absolute sample value of both channels is
+    /// below `threshold`. Rendering stops at `max_seconds` in any case.
+    UntilSilence { threshold: f32, hold_ms: f32, max_seconds: f32 },
+}
+
+/// The sample format of the WAV files written by [OfflineRenderer::render_to_wav].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavFormat {
+    /// 32 bit floating point samples.
+    Float32,
+    /// 16 bit integer samples, the output is clipped to -1.0 to 1.0.
+    Int16,
+}
+
+/// Returned by [OfflineRenderer::render_to_wav] if the WAV file could not be written.
+#[derive(Debug)]
+pub enum OfflineRenderError {
+    WavError(hound::Error),
+}
+
+impl From<hound::Error> for OfflineRenderError {
+    fn from(err: hound::Error) -> Self {
+        OfflineRenderError::WavError(err)
+    }
+}
+
+/// A scripted event for the [OfflineRenderer].
+#[derive(Debug, Clone)]
+pub enum RenderEvent {
+    /// Send a MIDI event into the DSP graph.
+    Midi(HxMidiEvent),
+    /// Set a parameter or setting, like [NodeConfigurator::set_param].
+    Param(ParamId, SAtom),
+    /// Set a parameter without smoothing, or with a linear ramp over the given
+    /// number of frames if it's not `0`, like [NodeConfigurator::set_param_at].
+    ParamRamp(ParamId, SAtom, usize),
+    /// Set a modulation amount, like [NodeConfigurator::set_param_modamt].
+    ModAmt(ParamId, Option<f32>),
+}
+
+enum RenderFrontend {
+    Config(Box<NodeConfigurator>),
+    Matrix(Box<Matrix>),
+}
+
+impl RenderFrontend {
+    fn set_param(&mut self, param: ParamId, at: SAtom) {
+        match self {
+            RenderFrontend::Config(config) => config.set_param(param, at),
+            RenderFrontend::Matrix(matrix) => matrix.set_param(param, at),
+        }
+    }
+
+    /// Applies the change at the start of the next buffer period.
+    fn set_param_now(&mut self, param: ParamId, at: SAtom, ramp: usize) {
+        match self {
+            RenderFrontend::Config(config) => {
+                let frame = config.audio_clock();
+                config.set_param_at(param, at, frame, ramp)
+            }
+            RenderFrontend::Matrix(matrix) => {
+                let frame = matrix.audio_clock();
+                matrix.set_param_at(param, at, frame, ramp)
+            }
+        }
+    }
+
+    fn set_param_modamt(&mut self, param: ParamId, modamt: Option<f32>) {
+        match self {
+            RenderFrontend::Config(config) => {
+                config.set_param_modamt(param, modamt);
+            }
+            RenderFrontend::Matrix(matrix) => {
+                let _ = matrix.set_param_modamt(param, modamt);
+            }
+        }
+    }
+}
+
+/// Renders a DSP graph faster than realtime into buffers or WAV files.
+///
+/// See also the [crate::offline_render] module documentation for an example.
+pub struct OfflineRenderer {
+    frontend: RenderFrontend,
+    exec: NodeExecutor,
+    sample_rate: f32,
+    length: RenderLength,
+    events: Vec<(usize, RenderEvent)>,
+    wav_format: WavFormat,
+}
+
+impl OfflineRenderer {
+    /// Creates a renderer for a [NodeConfigurator] and [NodeExecutor] pair,
+    /// as returned by [crate::new_node_engine]. The DSP graph should already be
+    /// uploaded via the [NodeConfigurator].
+    pub fn new(config: NodeConfigurator, exec: NodeExecutor) -> Self {
+        Self::new_with_frontend(RenderFrontend::Config(Box::new(config)), exec)
+    }
+
+    /// Creates a renderer for a [Matrix] and it's [NodeExecutor].
+    pub fn from_matrix(matrix: Matrix, exec: NodeExecutor) -> Self {
+        Self::new_with_frontend(RenderFrontend::Matrix(Box::new(matrix)), exec)
+    }
+
+    /// Creates a renderer for a patch, for instance loaded with [MatrixRepr::read_from_file].
+    pub fn from_repr(repr: &MatrixRepr) -> Result<Self, MatrixDeserError> {
+        let (config, exec) = new_node_engine();
+
+        let w = repr.cells.iter().map(|c| c.x + 1).max().unwrap_or(1);
+        let h = repr.cells.iter().map(|c| c.y + 1).max().unwrap_or(1);
+
+        let mut matrix = Matrix::new(config, w, h);
+        matrix.from_repr(repr)?;
+
+        Ok(Self::from_matrix(matrix, exec))
+    }
+
+    fn new_with_frontend(frontend: RenderFrontend, mut exec: NodeExecutor) -> Self {
+        exec.no_logging();
+
+        Self {
+            frontend,
+            exec,
+            sample_rate: 44100.0,
+            length: RenderLength::Seconds(1.0),
+            events: vec![],
+            wav_format: WavFormat::Float32,
+        }
+    }
+
+    /// Sets the sample rate to render at. Default is 44100 Hz.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Returns the sample rate the renderer renders at.
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Sets the length to render. Default is 1 second.
+    pub fn set_length(&mut self, length: RenderLength) {
+        self.length = length;
+    }
+
+    /// Sets the sample format of the WAV files. Default is [WavFormat::Float32].
+    pub fn set_wav_format(&mut self, wav_format: WavFormat) {
+        self.wav_format = wav_format;
+    }
+
+    /// Converts milliseconds to sample frames at the current sample rate.
+    /// Useful for scripting events.
+    pub fn ms_to_frames(&self, ms: f32) -> usize {
+        ((ms * self.sample_rate) / 1000.0).round() as usize
+    }
+
+    /// Adds an event at sample frame `frame`.
+    pub fn add_event(&mut self, frame: usize, event: RenderEvent) {
+        self.events.push((frame, event));
+    }
+
+    /// Adds a MIDI event at sample frame `frame`.
+    pub fn add_midi_event(&mut self, frame: usize, event: HxMidiEvent) {
+        self.add_event(frame, RenderEvent::Midi(event));
+    }
+
+    /// Adds a parameter or setting change at sample frame `frame`.
+    pub fn add_param_event(&mut self, frame: usize, param: ParamId, at: SAtom) {
+        self.add_event(frame, RenderEvent::Param(param, at));
+    }
+
+    /// Removes all scripted events.
+    pub fn clear_events(&mut self) {
+        self.events.clear();
+    }
+
+    /// Gives access to the [NodeExecutor], for instance to set external parameters.
+    pub fn executor(&mut self) -> &mut NodeExecutor {
+        &mut self.exec
+    }
+
+    /// Gives access to the [Matrix], if the renderer was created from a [Matrix]
+    /// or a [MatrixRepr].
+    pub fn matrix(&mut self) -> Option<&mut Matrix> {
+        if let RenderFrontend::Matrix(matrix) = &mut self.frontend {
+            Some(matrix)
+        } else {
+            None
+        }
+    }
+
+    fn max_frames(&self) -> usize {
+        match self.length {
+            RenderLength::Seconds(s) => (s * self.sample_rate).round() as usize,
+            RenderLength::Frames(frames) => frames,
+            RenderLength::UntilSilence { max_seconds, .. } => {
+                (max_seconds * self.sample_rate).round() as usize
+            }
+        }
+    }
+
+    /// Renders the DSP graph and returns the left and right output channels.
+    ///
+    /// The scripted events are processed in order of their sample frame.
+    /// Parameter changes are applied exactly at their sample frame, but are
+    /// smoothed like parameter changes in realtime operation. Use
+    /// [RenderEvent::ParamRamp] for unsmoothed changes or ramps of a specific length.
+    pub fn render(&mut self) -> (Vec<f32>, Vec<f32>) {
+        let max_frames = self.max_frames();
+
+        self.events.sort_by_key(|(frame, _)| *frame);
+
+        let mut midi_events = vec![];
+        for (frame, ev) in self.events.iter() {
+            if let RenderEvent::Midi(ev) = ev {
+                midi_events.push(HxTimedEvent::new_timed(*frame, *ev));
+            }
+        }
+        let last_event_frame = self.events.last().map(|(frame, _)| *frame).unwrap_or(0);
+
+        let (silence_thres, silence_hold) = match self.length {
+            RenderLength::UntilSilence { threshold, hold_ms, .. } => {
+                (Some(threshold), self.ms_to_frames(hold_ms).max(1))
+            }
+            _ => (None, 0),
+        };
+
+        // Receive the current DSP graph first, so that all nodes get the sample rate:
+        self.exec.process_graph_updates();
+        self.exec.set_sample_rate(self.sample_rate);
+
+        let input = vec![0.0; MAX_BLOCK_SIZE];
+        let mut output_l = Vec::with_capacity(max_frames);
+        let mut output_r = Vec::with_capacity(max_frames);
+        let mut block_l = vec![0.0; MAX_BLOCK_SIZE];
+        let mut block_r = vec![0.0; MAX_BLOCK_SIZE];
+
+        let mut ev_win = EventWindowing::new();
+        let mut midi_idx = 0;
+        let mut ev_idx = 0;
+        let mut silent_frames = 0;
+        let mut offs = 0;
+
+        while offs < max_frames {
+            while ev_idx < self.events.len() && self.events[ev_idx].0 <= offs {
+                match &self.events[ev_idx].1 {
+                    RenderEvent::Param(param, at) => {
+                        self.frontend.set_param(*param, at.clone());
+                    }
+                    RenderEvent::ParamRamp(param, at, ramp) => {
+                        // The block starts at the event frame:
+                        self.frontend.set_param_now(*param, at.clone(), *ramp);
+                    }
+                    RenderEvent::ModAmt(param, modamt) => {
+                        self.frontend.set_param_modamt(*param, *modamt);
+                    }
+                    RenderEvent::Midi(_) => (),
+                }
+                ev_idx += 1;
+            }
+
+            self.exec.process_graph_updates();
+
+            // Cut the block at the next event, so that parameter changes start
+            // exactly at their frame:
+            let mut cur_nframes = (max_frames - offs).min(MAX_BLOCK_SIZE);
+            if let Some((next_frame, _)) = self.events.get(ev_idx) {
+                cur_nframes = cur_nframes.min(*next_frame - offs);
+            }
+
+            self.exec.feed_midi_events_from(|| {
+                if ev_win.feed_me() {
+                    if midi_idx >= midi_events.len() {
+                        return None;
+                    }
+
+                    ev_win.feed(midi_events[midi_idx]);
+                    midi_idx += 1;
+                }
+
+                ev_win.next_event_in_range(offs, cur_nframes)
+            });
+
+            block_l[..cur_nframes].fill(0.0);
+            block_r[..cur_nframes].fill(0.0);
+
+            let mut context = crate::Context {
+                nframes: cur_nframes,
+                output: &mut [&mut block_l[0..cur_nframes], &mut block_r[0..cur_nframes]],
+                input: &[&input[0..cur_nframes], &input[0..cur_nframes]],
+            };
+
+            self.exec.process(&mut context);
+
+            output_l.extend_from_slice(&block_l[0..cur_nframes]);
+            output_r.extend_from_slice(&block_r[0..cur_nframes]);
+
+            offs += cur_nframes;
+
+            if let Some(thres) = silence_thres {
+                for (l, r) in block_l[0..cur_nframes].iter().zip(block_r[0..cur_nframes].iter()) {
+                    if l.abs() < thres && r.abs() < thres {
+                        silent_frames += 1;
+                    } else {
+                        silent_frames = 0;
+                    }
+                }
+
+                if offs > last_event_frame && silent_frames >= silence_hold {
+                    break;
+                }
+            }
+        }
+
+        (output_l, output_r)
+    }
+
+    /// Renders the DSP graph like [OfflineRenderer::render] and writes the
+    /// output to a stereo WAV file at `path`. Returns the number of rendered frames.
+    pub fn render_to_wav(&mut self, path: &str) -> Result<usize, OfflineRenderError> {
+        let (output_l, output_r) = self.render();
+
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: self.sample_rate as u32,
+            bits_per_sample: match self.wav_format {
+                WavFormat::Float32 => 32,
+                WavFormat::Int16 => 16,
+            },
+            sample_format: match self.wav_format {
+                WavFormat::Float32 => hound::SampleFormat::Float,
+                WavFormat::Int16 => hound::SampleFormat::Int,
+            },
+        };
+
+        let mut writer = hound::WavWriter::create(path, spec)?;
+        for (l, r) in output_l.iter().zip(output_r.iter()) {
+            match self.wav_format {
+                WavFormat::Float32 => {
+                    writer.write_sample(*l)?;
+                    writer.write_sample(*r)?;
+                }
+                WavFormat::Int16 => {
+                    let amp = i16::MAX as f32;
+                    writer.write_sample((amp * l.clamp(-1.0, 1.0)) as i16)?;
+                    writer.write_sample((amp * r.clamp(-1.0, 1.0)) as i16)?;
+                }
+            }
+        }
+        writer.finalize()?;
+
+        Ok(output_l.len())
+    }
+}
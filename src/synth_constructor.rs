@@ -37,7 +37,9 @@ See also [SynthConstructor::new] and [crate::build].
 */
 
 use crate::build::*;
-use crate::nodes::{new_node_engine, NodeGraphOrdering};
+use crate::nodes::{
+    new_node_engine, GraphEvent, MidiMapping, MpeZone, NodeGraphOrdering, NodeStates, VoiceStealing,
+};
 use crate::{NodeConfigurator, NodeExecutor, NodeId, SAtom};
 use std::collections::HashMap;
 
@@ -54,6 +56,9 @@ pub enum SynthError {
     /// Unknown parameter name was passed into SynthConstructor somehow. Should
     /// not be possible to happen with [crate::build].
     UnknownParam(NodeId, String),
+    /// The instance index of a replicated voice node does not fit into a [NodeId].
+    /// See also [crate::nodes::PolyVoices].
+    VoiceInstanceOverflow(NodeId),
 }
 
 #[derive(Debug, Clone)]
@@ -286,6 +291,86 @@ impl SynthConstructor {
         }
     }
 
+    /// Configures the voice allocation of the incoming MIDI notes.
+    /// Use this together with [crate::nodes::PolyVoices] to build a polyphonic synthesizer.
+    ///
+    ///```
+    /// use hexodsp::SynthConstructor;
+    /// use hexodsp::nodes::{PolyVoices, VoiceStealing};
+    /// use hexodsp::build::*;
+    ///
+    /// let mut sc = SynthConstructor::new();
+    ///
+    /// let midip = midip(0);
+    /// let osc = bosc(0).input().freq(&midip.output().freq());
+    /// let env = adsr(0).input().gate(&midip.output().gate());
+    /// let voice = amp(0).input().inp(&osc.output().sig()).input().att(&env.output().sig());
+    ///
+    /// let voices = PolyVoices::new(4);
+    /// let mix = voices.mix(&voice.output().sig(), 0).unwrap();
+    ///
+    /// sc.set_voice_allocation(voices.voices(), VoiceStealing::Oldest);
+    /// sc.upload(&out(0).input().ch1(&mix)).unwrap();
+    ///```
+    pub fn set_voice_allocation(&mut self, voices: usize, stealing: VoiceStealing) {
+        self.config.set_voice_allocation(voices, stealing);
+    }
+
+    /// Sets the number of worker threads that execute independent nodes in parallel.
+    /// See also [NodeConfigurator::set_worker_threads].
+    pub fn set_worker_threads(&mut self, threads: usize) {
+        self.config.set_worker_threads(threads);
+    }
+
+    /// Configures the MPE zone of the incoming MIDI.
+    /// See also [NodeConfigurator::set_mpe_zone].
+    pub fn set_mpe_zone(&mut self, zone: Option<MpeZone>) {
+        self.config.set_mpe_zone(zone);
+    }
+
+    /// Sets the global random seed and restarts the random number generators
+    /// of all nodes. See also [NodeConfigurator::set_random_seed].
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.config.set_random_seed(seed);
+    }
+
+    /// Maps a MIDI CC to a parameter or setting. Returns false if the parameter
+    /// can't be mapped. See also [NodeConfigurator::set_midi_mapping].
+    pub fn set_midi_mapping(&mut self, mapping: MidiMapping) -> bool {
+        self.config.set_midi_mapping(mapping)
+    }
+
+    /// Removes all MIDI CC mappings.
+    pub fn clear_midi_mappings(&mut self) {
+        self.config.clear_midi_mappings();
+    }
+
+    /// Requests a snapshot of the internal state of the running nodes, which is
+    /// returned as [GraphEvent::NodeStates] by [SynthConstructor::next_event].
+    /// See also [NodeConfigurator::request_node_states].
+    pub fn request_node_states(&mut self) {
+        self.config.request_node_states();
+    }
+
+    /// Restores a snapshot of the node states in the running nodes.
+    /// See also [NodeConfigurator::load_node_states].
+    pub fn load_node_states(&mut self, states: NodeStates) {
+        self.config.load_node_states(states);
+    }
+
+    /// Enables the output guard against NaN and runaway signals, with a hard
+    /// limit for the `Out` nodes. See also [NodeConfigurator::set_output_guard].
+    pub fn set_output_guard(&mut self, limit: Option<f32>) {
+        self.config.set_output_guard(limit);
+    }
+
+    /// Sets the oversampling factor of a node, it takes effect with the next
+    /// [SynthConstructor::upload]. Returns false if the factor is not supported.
+    /// See also [NodeConfigurator::set_oversampling].
+    pub fn set_oversampling(&mut self, node_id: NodeId, factor: usize) -> bool {
+        self.config.set_oversampling(node_id, factor)
+    }
+
     /// Returns the atomic phase value for [NodeId].
     pub fn phase_value(&self, ni: &NodeId) -> f32 {
         self.config.phase_value_for(ni)
@@ -325,8 +410,11 @@ impl SynthConstructor {
 
         let mut prog = self.config.rebuild_node_ports();
 
+        // The edges point from the inputs to the outputs here, so the levels are
+        // descending. Nodes with the same level are independent nevertheless:
         for node_id in ordered_nodes.iter().rev() {
-            self.config.add_prog_node(&mut prog, node_id);
+            let level = self.graph_ordering.node_level(node_id).unwrap_or(0);
+            self.config.add_prog_node_with_level(&mut prog, node_id, level);
         }
 
         for node_id in ordered_nodes.iter().rev() {
@@ -363,6 +451,29 @@ impl SynthConstructor {
         self.config.update_filters();
     }
 
+    /// Returns the next event from the DSP graph, such as incoming MIDI events or
+    /// a [crate::nodes::NodeEvent] emitted by a node. Call this regularily, otherwise
+    /// the ring buffer runs full and new events are dropped.
+    ///
+    ///```
+    /// use hexodsp::nodes::{GraphEvent, NodeEvent};
+    /// use hexodsp::SynthConstructor;
+    ///
+    /// let mut sc = SynthConstructor::new();
+    ///
+    /// // Setup stuff with sc here..
+    ///
+    /// // In a loop call eg.:
+    /// while let Some(ev) = sc.next_event() {
+    ///     if let GraphEvent::Node(node_id, NodeEvent::TSeqRow { row }) = ev {
+    ///         println!("{:?} is at row {}", node_id, row);
+    ///     }
+    /// }
+    ///```
+    pub fn next_event(&mut self) -> Option<GraphEvent> {
+        self.config.next_event()
+    }
+
     /// Retrieves the output port feedback for a specific output of the given [NodeId].
     ///
     /// Make sure to call [SynthConstructor::poll] regularily to update the feedback values.
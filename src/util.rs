@@ -42,6 +42,17 @@ impl Smoother {
         self.done = false;
     }
 
+    /// Like [Smoother::set], but reaches the `target` after `samples`
+    /// instead of the fixed smoothing time.
+    #[inline]
+    pub fn set_ramp(&mut self, current: f32, target: f32, samples: usize) {
+        self.value = current;
+        self.count = samples.max(1);
+        self.inc = (target - current) / (self.count as f32);
+        self.target = target;
+        self.done = false;
+    }
+
     #[allow(dead_code)]
     #[inline]
     pub fn current(&self) -> f32 {
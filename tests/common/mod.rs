@@ -618,6 +618,44 @@ pub fn run_for_ms(node_exec: &mut hexodsp::nodes::NodeExecutor, ms: f32) -> (Vec
     run_realtime_no_input(node_exec, ms / 1000.0, false)
 }
 
+/// Runs the executor with as many input and output channels as given in `inputs` and
+/// `out_channels`. Each input buffer needs to be `nframes` long.
+#[allow(dead_code)]
+pub fn run_multichannel(
+    node_exec: &mut hexodsp::nodes::NodeExecutor,
+    inputs: &[Vec<f32>],
+    out_channels: usize,
+    nframes: usize,
+) -> Vec<Vec<f32>> {
+    node_exec.set_channel_count(inputs.len(), out_channels);
+    node_exec.set_sample_rate(SAMPLE_RATE);
+    node_exec.process_graph_updates();
+
+    let mut outputs = vec![vec![0.0; nframes]; out_channels];
+
+    let mut offs = 0;
+    while offs < nframes {
+        let cur_nframes = (nframes - offs).min(hexodsp::dsp::MAX_BLOCK_SIZE);
+
+        let mut out_slices: Vec<&mut [f32]> =
+            outputs.iter_mut().map(|o| &mut o[offs..(offs + cur_nframes)]).collect();
+        let in_slices: Vec<&[f32]> =
+            inputs.iter().map(|i| &i[offs..(offs + cur_nframes)]).collect();
+
+        let mut context = hexodsp::Context {
+            nframes: cur_nframes,
+            output: &mut out_slices[..],
+            input: &in_slices[..],
+        };
+
+        node_exec.process(&mut context);
+
+        offs += cur_nframes;
+    }
+
+    outputs
+}
+
 pub fn run_realtime_no_input(
     node_exec: &mut hexodsp::nodes::NodeExecutor,
     seconds: f32,
@@ -1143,3 +1181,56 @@ pub fn fftr(buf: &mut [f32], size: FFT) -> Vec<(u16, u32)> {
 
     res
 }
+
+/// Builds a Standard MIDI File with the given `tracks`, which contain the
+/// events without the track header.
+#[allow(dead_code)]
+pub fn smf(format: u16, tpq: u16, tracks: &[&[u8]]) -> Vec<u8> {
+    let mut data = b"MThd".to_vec();
+    data.extend_from_slice(&6u32.to_be_bytes());
+    data.extend_from_slice(&format.to_be_bytes());
+    data.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+    data.extend_from_slice(&tpq.to_be_bytes());
+
+    for track in tracks.iter() {
+        data.extend_from_slice(b"MTrk");
+        data.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        data.extend_from_slice(track);
+    }
+
+    data
+}
+
+/// Places a `MidiP` node, whose gate goes to the first channel of the `Out` node.
+#[allow(dead_code)]
+pub fn setup_midip(matrix: &mut Matrix) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midip", "gate").node_inp("out", "ch1").place(matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+}
+
+/// Returns the maximum absolute sample value in `buf`.
+#[allow(dead_code)]
+pub fn max_abs(buf: &[f32]) -> f32 {
+    buf.iter().fold(0.0_f32, |a, s| a.max(s.abs()))
+}
+
+/// Counts the upward zero crossings in `buf`.
+#[allow(dead_code)]
+pub fn count_zero_crossings(buf: &[f32]) -> usize {
+    buf.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count()
+}
+
+/// Returns the indices of the samples in `buf` where a gate signal rises above 0.5.
+#[allow(dead_code)]
+pub fn rising_edges(buf: &[f32]) -> Vec<usize> {
+    let mut last = 0.0;
+    let mut edges = vec![];
+    for (i, s) in buf.iter().enumerate() {
+        if last < 0.5 && *s >= 0.5 {
+            edges.push(i);
+        }
+        last = *s;
+    }
+    edges
+}
@@ -0,0 +1,46 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+#[test]
+fn check_cpu_profiling_node_load() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .node_io("pverb", "in_l", "sig_l")
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    // Profiling is disabled by default:
+    node_exec.test_run(0.3, false, &[]);
+    assert_float_eq!(matrix.node_cpu_load(&NodeId::PVerb(0)), 0.0);
+
+    matrix.set_cpu_profiling(true);
+    node_exec.test_run(0.3, false, &[]);
+
+    // The load depends on the machine and whatever else runs on it,
+    // so only check that the profiled nodes report some load:
+    let sin_load = matrix.node_cpu_load(&NodeId::Sin(0));
+    let pverb_load = matrix.node_cpu_load(&NodeId::PVerb(0));
+    let out_load = matrix.node_cpu_load(&NodeId::Out(0));
+    assert!(sin_load > 0.0 && sin_load.is_finite(), "sin_load={}", sin_load);
+    assert!(pverb_load > 0.0 && pverb_load.is_finite(), "pverb_load={}", pverb_load);
+    // The trivial Out node might be below the timer resolution:
+    assert!(out_load >= 0.0 && out_load.is_finite(), "out_load={}", out_load);
+
+    // Nodes that are not in the graph don't have any load:
+    assert_float_eq!(matrix.node_cpu_load(&NodeId::Sin(1)), 0.0);
+
+    matrix.set_cpu_profiling(false);
+    node_exec.test_run(0.3, false, &[]);
+    assert_float_eq!(matrix.node_cpu_load(&NodeId::Sin(0)), 0.0);
+    assert_float_eq!(matrix.node_cpu_load(&NodeId::PVerb(0)), 0.0);
+}
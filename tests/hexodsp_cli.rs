@@ -0,0 +1,147 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::save_patch_to_file;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::Arc;
+
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hexodsp_cli_{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run_cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_hexodsp")).args(args).output().unwrap()
+}
+
+fn save_sine_patch(path: &str, sample: Option<&str>) {
+    let (node_conf, _node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .set_denorm("freq", 220.0)
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+
+    if let Some(sample) = sample {
+        matrix.place(2, 2, Cell::empty(NodeId::Sampl(0)));
+        let mut data = vec![0.0; 10];
+        data[0] = 44100.0;
+        matrix.set_param(
+            NodeId::Sampl(0).inp_param("sample").unwrap(),
+            SAtom::audio(sample, Arc::new(data)),
+        );
+    }
+    matrix.sync().unwrap();
+
+    save_patch_to_file(&mut matrix, path).unwrap();
+}
+
+#[test]
+fn check_cli_info() {
+    let dir = test_dir("info");
+    let patch = dir.join("sine.hxy");
+    let patch = patch.to_str().unwrap();
+    save_sine_patch(patch, None);
+
+    let out = run_cli(&["info", patch]);
+    assert!(out.status.success());
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("nodes: 2\n"), "{}", stdout);
+    assert!(stdout.contains("    Sin 0 at (0, 0)\n"), "{}", stdout);
+    assert!(stdout.contains("        freq = 220.0Hz"), "{}", stdout);
+    assert!(stdout.contains("edges: 1\n    Sin 0:sig -> Out 0:ch1\n"), "{}", stdout);
+}
+
+#[test]
+fn check_cli_info_samples() {
+    let dir = test_dir("samples");
+    std::fs::create_dir_all(dir.join("samples")).unwrap();
+    save_wav(dir.join("samples").join("click.wav").to_str().unwrap(), &[1.0, 0.5, 0.0]);
+
+    // The sample path is relative to the patch file, not to the working directory:
+    let found = dir.join("found.hxy");
+    let found = found.to_str().unwrap();
+    save_sine_patch(found, Some("samples/click.wav"));
+
+    let out = run_cli(&["info", found]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stdout));
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("samples/click.wav'"), "{}", stdout);
+
+    let missing = dir.join("missing.hxy");
+    let missing = missing.to_str().unwrap();
+    save_sine_patch(missing, Some("samples/gone.wav"));
+
+    let out = run_cli(&["info", found, missing]);
+    assert_eq!(out.status.code(), Some(1));
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(!stdout.contains("error"), "{}", stdout);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    let expected = format!("{}: error: Sample not found: 'samples/gone.wav'", missing);
+    assert!(stderr.contains(&expected), "{}", stderr);
+}
+
+#[test]
+fn check_cli_render() {
+    let dir = test_dir("render");
+    let patch = dir.join("sine.hxy");
+    let patch = patch.to_str().unwrap();
+    save_sine_patch(patch, None);
+
+    let wav = dir.join("sine.wav");
+    let wav = wav.to_str().unwrap();
+    let out = run_cli(&["render", "--length", "0.5", "--srate", "48000", patch, wav]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let rd = hound::WavReader::open(wav).unwrap();
+    assert_eq!(rd.spec().sample_rate, 48000);
+    assert_eq!(rd.spec().channels, 2);
+    assert_eq!(rd.duration(), 24000);
+}
+
+#[test]
+fn check_cli_render_midi() {
+    let dir = test_dir("midi");
+
+    let (node_conf, _node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midip", "gate").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+
+    let patch = dir.join("gate.hxy");
+    let patch = patch.to_str().unwrap();
+    save_patch_to_file(&mut matrix, patch).unwrap();
+
+    // A quarter note at 120 BPM, starting at one quarter note:
+    let mut smf = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x00\x60MTrk\x00\x00\x00\x0C".to_vec();
+    smf.extend_from_slice(&[0x60, 0x90, 60, 127, 0x60, 0x80, 60, 0, 0x00, 0xFF, 0x2F, 0x00]);
+    let midi = dir.join("note.mid");
+    std::fs::write(&midi, smf).unwrap();
+
+    let wav = dir.join("gate.wav");
+    let wav = wav.to_str().unwrap();
+    let out = run_cli(&["render", "--midi", midi.to_str().unwrap(), patch, wav]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let mut rd = hound::WavReader::open(wav).unwrap();
+    let left: Vec<f32> = rd.samples::<f32>().step_by(2).map(|s| s.unwrap()).collect();
+
+    // Rendering stops at the end of the block with 500ms of silence after the note:
+    assert!(left.len() >= 44100 + 22050, "len={}", left.len());
+    assert!(left.len() < 44100 + 22050 + 128, "len={}", left.len());
+    // The MidiP gate ends one frame after the note off:
+    assert_eq!(collect_gates(&left), vec![(22050, 22051)]);
+}
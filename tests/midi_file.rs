@@ -0,0 +1,175 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::midi_file::*;
+
+#[test]
+fn check_midi_file_running_status() {
+    let data = smf(
+        0,
+        480,
+        &[&[
+            0x00, 0x90, 60, 100, // Note on
+            0x00, 64, 50, // Running status note on
+            0x83, 0x60, 60, 0, // Note on with velocity 0 after 480 ticks
+            0x00, 0xB1, 74, 127, // CC on channel 1
+            0x00, 0xF0, 0x02, 0x01, 0xF7, // SysEx is skipped
+            0x00, 0xFF, 0x2F, 0x00,
+        ]],
+    );
+
+    let smf = MidiFile::parse(&data).unwrap();
+    assert_eq!(smf.format, 0);
+    assert_eq!(smf.ticks_per_quarter, 480);
+    assert_eq!(smf.tracks.len(), 1);
+    assert_eq!(smf.tracks[0].len(), 4);
+    assert_eq!(smf.tracks[0][2].tick, 480);
+
+    let events = smf.timed_events();
+    assert!(matches!(events[0].1, HxMidiEvent::NoteOn { channel: 0, note: 60, .. }));
+    assert!(matches!(events[1].1, HxMidiEvent::NoteOn { channel: 0, note: 64, .. }));
+    assert!(matches!(events[2].1, HxMidiEvent::NoteOff { channel: 0, note: 60 }));
+    assert!(matches!(events[3].1, HxMidiEvent::CC { channel: 1, cc: 74, .. }));
+
+    if let HxMidiEvent::NoteOn { vel, .. } = events[1].1 {
+        assert_float_eq!(vel, 50.0 / 127.0);
+    }
+    if let HxMidiEvent::CC { value, .. } = events[3].1 {
+        assert_float_eq!(value, 1.0);
+    }
+}
+
+#[test]
+fn check_midi_file_bend_pressure_program() {
+    let data = smf(
+        0,
+        480,
+        &[&[
+            0x00, 0xE0, 0x7F, 0x7F, // Pitch bend up
+            0x00, 0x00, 0x40, // Running status pitch bend center
+            0x00, 0xD2, 64, // Channel pressure
+            0x00, 0xA0, 60, 127, // Poly pressure
+            0x00, 0xC3, 5, // Program change
+            0x00, 0xFF, 0x2F, 0x00,
+        ]],
+    );
+
+    let events = MidiFile::parse(&data).unwrap().timed_events();
+    assert_eq!(events.len(), 5);
+    assert!(matches!(events[2].1, HxMidiEvent::ChannelPressure { channel: 2, .. }));
+    assert!(matches!(events[3].1, HxMidiEvent::PolyPressure { channel: 0, note: 60, .. }));
+    assert!(matches!(events[4].1, HxMidiEvent::ProgramChange { channel: 3, program: 5 }));
+
+    if let HxMidiEvent::PitchBend { value, .. } = events[0].1 {
+        assert_float_eq!(value, 1.0);
+    }
+    if let HxMidiEvent::PitchBend { value, .. } = events[1].1 {
+        assert_float_eq!(value, 0.0);
+    }
+    if let HxMidiEvent::ChannelPressure { value, .. } = events[2].1 {
+        assert_float_eq!(value, 64.0 / 127.0);
+    }
+}
+
+#[test]
+fn check_midi_file_tempo_map() {
+    // Format 1: The tempo track is separate from the note track.
+    let tempo_track: &[u8] = &[
+        0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // 120 BPM
+        0x60, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40, // 60 BPM after one quarter
+        0x00, 0xFF, 0x2F, 0x00,
+    ];
+    let note_track: &[u8] = &[
+        0x00, 0x90, 60, 100, // 0.0s
+        0x60, 0x80, 60, 0, // 0.5s
+        0x60, 0x90, 62, 100, // 1.5s
+        0x30, 0x80, 62, 0, // 2.0s
+        0x00, 0xFF, 0x2F, 0x00,
+    ];
+
+    let smf = MidiFile::parse(&smf(1, 96, &[tempo_track, note_track])).unwrap();
+    assert_eq!(smf.tracks.len(), 2);
+
+    let times: Vec<f64> = smf.timed_events().iter().map(|(secs, _)| *secs).collect();
+    assert_eq!(times, vec![0.0, 0.5, 1.5, 2.0]);
+    assert_eq!(smf.duration_secs(), 2.0);
+}
+
+#[test]
+fn check_midi_file_errors() {
+    assert_eq!(MidiFile::parse(b"RIFF").unwrap_err(), MidiFileError::InvalidHeader);
+    assert_eq!(MidiFile::parse(&smf(2, 96, &[])).unwrap_err(), MidiFileError::UnsupportedFormat(2));
+    assert_eq!(MidiFile::parse(&smf(0, 0xE250, &[])).unwrap_err(), MidiFileError::SmpteTiming);
+
+    // Data byte without a running status:
+    assert_eq!(
+        MidiFile::parse(&smf(0, 96, &[&[0x00, 60, 100]])).unwrap_err(),
+        MidiFileError::InvalidTrack(0)
+    );
+    // Truncated note on:
+    assert_eq!(
+        MidiFile::parse(&smf(0, 96, &[&[0x00, 0x90, 60]])).unwrap_err(),
+        MidiFileError::UnexpectedEnd
+    );
+}
+
+#[test]
+fn check_midi_file_write() {
+    let tempo_track: &[u8] = &[
+        0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // 120 BPM
+        0x00, 0xFF, 0x2F, 0x00,
+    ];
+    let note_track: &[u8] = &[
+        0x00, 0x90, 60, 100, // Note on
+        0x00, 0xE1, 0x00, 0x00, // Pitch bend down
+        0x00, 0xD2, 64, // Channel pressure
+        0x00, 0xC3, 5, // Program change
+        0x83, 0x60, 0x80, 60, 0, // Note off after 480 ticks
+        0x81, 0x80, 0x00, 0xB0, 7, 127, // CC after 16384 ticks
+        0x00, 0xFF, 0x2F, 0x00,
+    ];
+    let data = smf(1, 480, &[tempo_track, note_track]);
+
+    let smf = MidiFile::parse(&data).unwrap();
+    // The events are written without running status:
+    assert_eq!(smf.to_bytes(), data);
+
+    let smf2 = MidiFile::parse(&smf.to_bytes()).unwrap();
+    assert_eq!(smf2.format, 1);
+    assert_eq!(smf2.ticks_per_quarter, 480);
+    assert_eq!(smf2.tracks[1][5].tick, 480 + 16384);
+    assert_eq!(format!("{:?}", smf2.timed_events()), format!("{:?}", smf.timed_events()));
+}
+
+#[test]
+fn check_midi_file_write_hires_cc() {
+    let smf = MidiFile {
+        format: 0,
+        ticks_per_quarter: 96,
+        tracks: vec![vec![
+            SmfTrackEvent {
+                tick: 0,
+                event: SmfEvent::Midi(HxTimedEvent::cc14(0, 1, 7, 1.0).kind()),
+            },
+            SmfTrackEvent {
+                tick: 10,
+                event: SmfEvent::Midi(HxTimedEvent::nrpn(0, 1, 130, 0.5).kind()),
+            },
+        ]],
+    };
+
+    // The high resolution controllers are written as their CC sequences:
+    let data = smf.to_bytes();
+    assert_eq!(
+        &data[22..(data.len() - 4)],
+        &[
+            0x00, 0xB1, 7, 127, 0x00, 0xB1, 39, 127, // 14-bit CC
+            0x0A, 0xB1, 99, 1, 0x00, 0xB1, 98, 2, // NRPN 130
+            0x00, 0xB1, 6, 64, 0x00, 0xB1, 38, 0, // Data entry 8192
+        ]
+    );
+}
@@ -0,0 +1,166 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::matrix_repr::MatrixRepr;
+use hexodsp::nodes::{MidiMapCurve, MidiMapping};
+use std::sync::Mutex;
+
+struct ParamRecorder {
+    params: Mutex<Vec<ParamId>>,
+}
+
+impl MatrixObserver for ParamRecorder {
+    fn update_prop(&self, _key: &str) {}
+    fn update_monitor(&self, _cell: &Cell) {}
+    fn update_param(&self, param_id: &ParamId) {
+        self.params.lock().expect("recorder lock ok").push(*param_id);
+    }
+    fn update_matrix(&self) {}
+    fn update_all(&self) {}
+    fn midi_event(&self, _midi_ev: HxMidiEvent) {}
+}
+
+fn setup_out(matrix: &mut Matrix) {
+    matrix.place(0, 0, Cell::empty(NodeId::Out(0)));
+    matrix.sync().unwrap();
+}
+
+fn out_param(name: &str) -> ParamId {
+    NodeId::Out(0).inp_param(name).expect("param exists")
+}
+
+fn last(buf: &[f32]) -> f32 {
+    buf[buf.len() - 1]
+}
+
+#[test]
+fn check_midi_map_param() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_out(&mut matrix);
+
+    assert!(matrix.set_midi_mapping(MidiMapping::new(1, 7, out_param("ch1"))));
+
+    let (ch1, _) = node_exec.test_run(0.1, false, &[HxTimedEvent::cc(0, 1, 7, 1.0)]);
+    assert_float_eq!(last(&ch1[..]), 1.0);
+
+    // Other channels and CCs don't change the parameter:
+    let events = [HxTimedEvent::cc(0, 2, 7, 0.0), HxTimedEvent::cc(10, 1, 8, 0.0)];
+    let (ch1, _) = node_exec.test_run(0.1, false, &events[..]);
+    assert_float_eq!(last(&ch1[..]), 1.0);
+
+    let (ch1, _) = node_exec.test_run(0.1, false, &[HxTimedEvent::cc(0, 1, 7, 0.25)]);
+    assert_float_eq!(last(&ch1[..]), -0.5);
+
+    matrix.remove_midi_mapping(&out_param("ch1"));
+    assert!(matrix.midi_mappings().is_empty());
+    let (ch1, _) = node_exec.test_run(0.1, false, &[HxTimedEvent::cc(0, 1, 7, 1.0)]);
+    assert_float_eq!(last(&ch1[..]), -0.5);
+}
+
+#[test]
+fn check_midi_map_range_curve() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_out(&mut matrix);
+
+    matrix.set_midi_mapping(MidiMapping::new(0, 1, out_param("ch1")).range(0.0, 0.8));
+    matrix.set_midi_mapping(
+        MidiMapping::new(0, 2, out_param("ch2")).range(0.0, 0.8).curve(MidiMapCurve::Exp),
+    );
+
+    let events = [HxTimedEvent::cc(0, 0, 1, 0.5), HxTimedEvent::cc(0, 0, 2, 0.5)];
+    let (ch1, ch2) = node_exec.test_run(0.1, false, &events[..]);
+    assert_float_eq!(last(&ch1[..]), 0.4);
+    assert_float_eq!(last(&ch2[..]), 0.2);
+
+    // Mapping the same parameter again replaces the mapping:
+    matrix.set_midi_mapping(MidiMapping::new(0, 3, out_param("ch2")).range(0.8, 0.0));
+    assert_eq!(matrix.midi_mappings().len(), 2);
+    let (_, ch2) = node_exec.test_run(0.1, false, &[HxTimedEvent::cc(0, 0, 3, 0.25)]);
+    assert_float_eq!(last(&ch2[..]), 0.6);
+}
+
+#[test]
+fn check_midi_map_setting() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_out(&mut matrix);
+    matrix.set_param(out_param("ch1"), SAtom::param(0.5));
+
+    assert!(matrix.set_midi_mapping(MidiMapping::new(0, 64, out_param("mono"))));
+
+    let (_, ch2) = node_exec.test_run(0.1, false, &[HxTimedEvent::cc(0, 0, 64, 0.4)]);
+    assert_float_eq!(last(&ch2[..]), 0.0);
+
+    let (_, ch2) = node_exec.test_run(0.1, false, &[HxTimedEvent::cc(0, 0, 64, 0.6)]);
+    assert_float_eq!(last(&ch2[..]), 0.5);
+
+    // Samples can't be mapped:
+    let sample = NodeId::Sampl(0).inp_param("sample").expect("param exists");
+    assert!(!matrix.set_midi_mapping(MidiMapping::new(0, 1, sample)));
+}
+
+#[test]
+fn check_midi_map_learn() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_out(&mut matrix);
+    let recorder = std::sync::Arc::new(ParamRecorder { params: Mutex::new(vec![]) });
+    matrix.set_observer(recorder.clone());
+
+    matrix.set_midi_learn(Some(out_param("ch1")));
+    assert_eq!(matrix.midi_learn(), Some(out_param("ch1")));
+
+    node_exec.test_run(0.01, false, &[HxTimedEvent::cc(0, 3, 21, 0.75)]);
+    matrix.handle_graph_events();
+
+    assert_eq!(matrix.midi_learn(), None);
+    assert_eq!(matrix.midi_mappings(), &[MidiMapping::new(3, 21, out_param("ch1"))]);
+    assert_eq!(
+        recorder.params.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        vec![out_param("ch1")]
+    );
+
+    // The learned value is sent to the audio thread:
+    let (ch1, _) = node_exec.test_run(0.1, false, &[]);
+    assert_float_eq!(last(&ch1[..]), 0.5);
+
+    // The mapped values are synced back into the frontend:
+    let (ch1, _) = node_exec.test_run(0.1, false, &[HxTimedEvent::cc(0, 3, 21, 0.25)]);
+    assert_float_eq!(last(&ch1[..]), -0.5);
+    matrix.handle_graph_events();
+    assert_float_eq!(matrix.get_param(&out_param("ch1")).unwrap().f(), -0.5);
+    assert_eq!(recorder.params.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn check_midi_map_repr() {
+    let (node_conf, mut _node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_out(&mut matrix);
+    assert!(matrix.to_repr().serialize().find("midi_map").is_none());
+
+    let mappings = vec![
+        MidiMapping::new(0, 1, out_param("ch1")).range(-0.5, 0.5).curve(MidiMapCurve::Log),
+        MidiMapping::new(15, 127, out_param("mono")),
+    ];
+    for m in mappings.iter() {
+        matrix.set_midi_mapping(*m);
+    }
+
+    let s = matrix.to_repr().serialize();
+    assert!(s.find(r#""midi_map":[[0,1,"out",0,"ch1",-0.5,0.5,"log"]"#).is_some());
+
+    let (node_conf, mut _node_exec) = new_node_engine();
+    let mut matrix2 = Matrix::new(node_conf, 3, 3);
+    matrix2.from_repr(&MatrixRepr::deserialize(&s).unwrap()).unwrap();
+    assert_eq!(matrix2.midi_mappings(), &mappings[..]);
+
+    matrix2.clear();
+    assert!(matrix2.midi_mappings().is_empty());
+}
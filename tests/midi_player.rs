@@ -0,0 +1,136 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::midi_file::MidiFile;
+use hexodsp::midi_player::MidiPlayer;
+
+/// Updates the player every 25ms, like a GUI would, and returns the output.
+fn run_player(
+    node_exec: &mut NodeExecutor,
+    matrix: &mut Matrix,
+    player: &mut MidiPlayer,
+    ms: usize,
+) -> Vec<f32> {
+    let mut out = vec![];
+    for _ in 0..(ms / 25) {
+        player.update(matrix);
+        let (ch1, _) = node_exec.test_run(0.025, false, &[]);
+        out.extend_from_slice(&ch1[..]);
+    }
+    out
+}
+
+#[test]
+fn check_midi_player_tempo_map() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midip(&mut matrix);
+    node_exec.test_run(0.01, false, &[]);
+    assert_eq!(matrix.audio_clock(), 441);
+
+    // Format 1: The tempo track is separate from the note track.
+    let tempo_track: &[u8] = &[
+        0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // 120 BPM
+        0x60, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40, // 60 BPM after one quarter
+        0x00, 0xFF, 0x2F, 0x00,
+    ];
+    let note_track: &[u8] = &[
+        0x00, 0x90, 60, 100, // 0.0s
+        0x60, 0x80, 60, 0, // 0.5s
+        0x60, 0x90, 62, 100, // 1.5s
+        0x30, 0x80, 62, 0, // 2.0s
+        0x00, 0xFF, 0x2F, 0x00,
+    ];
+    let data = smf(1, 96, &[tempo_track, note_track]);
+    let smf = MidiFile::parse(&data).unwrap();
+    assert_eq!(smf.ticks_to_secs(144), 1.0);
+    assert_eq!(smf.secs_to_ticks(1.5), 192);
+
+    let mut player = MidiPlayer::new(&smf);
+    player.play();
+    let out = run_player(&mut node_exec, &mut matrix, &mut player, 2100);
+
+    // The notes start exactly at their frame, relative to the start of the playback:
+    assert_eq!(collect_gates(&out[..]), vec![(0, 22050 + 1), (66150, 22050 + 1)]);
+    assert!(!player.is_playing());
+    assert_eq!(player.position(&matrix), 2.0);
+}
+
+#[test]
+fn check_midi_player_loop() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midip(&mut matrix);
+
+    let data = smf(
+        0,
+        96,
+        &[&[
+            0x00, 0x90, 60, 100, // 0.0s
+            0x60, 0x80, 60, 0, // 0.5s
+            0x00, 0xFF, 0x2F, 0x00,
+        ]],
+    );
+    let smf = MidiFile::parse(&data).unwrap();
+
+    let mut player = MidiPlayer::new(&smf);
+    player.set_loop(Some((0.0, 0.25)));
+    player.play();
+    let out = run_player(&mut node_exec, &mut matrix, &mut player, 1000);
+
+    // The held note is released at the end of the loop and played again:
+    assert_eq!(rising_edges(&out[..]), vec![0, 11025 + 1, 22050 + 1, 33075 + 1]);
+    assert!(player.is_playing());
+    assert!(player.position(&matrix) < 0.25);
+
+    // Without the loop the song plays to the end. The loop start at frame 44100 was
+    // already scheduled, the previous run ended at frame 40 * 1102:
+    player.set_loop(None);
+    let out = run_player(&mut node_exec, &mut matrix, &mut player, 1000);
+    assert_eq!(rising_edges(&out[..]), vec![0, 20 + 1]);
+    assert_eq!(collect_gates(&out[..])[1], (20 + 1, 22050));
+    assert!(!player.is_playing());
+}
+
+#[test]
+fn check_midi_player_stop_seek() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midip(&mut matrix);
+
+    let data = smf(
+        0,
+        96,
+        &[&[
+            0x00, 0x90, 60, 100, // 0.0s
+            0x60, 0x80, 60, 0, // 0.5s
+            0x00, 0x90, 62, 100, // 0.5s
+            0x60, 0x80, 62, 0, // 1.0s
+            0x00, 0xFF, 0x2F, 0x00,
+        ]],
+    );
+    let smf = MidiFile::parse(&data).unwrap();
+
+    let mut player = MidiPlayer::new(&smf);
+    player.play();
+    let out = run_player(&mut node_exec, &mut matrix, &mut player, 200);
+    assert!(out[out.len() - 1] > 0.5);
+
+    // Stopping releases the held note and keeps the position:
+    player.stop(&mut matrix);
+    assert_float_eq!(player.position(&matrix), 0.2);
+    let out = run_player(&mut node_exec, &mut matrix, &mut player, 200);
+    assert_float_eq!(out[0], 1.0);
+    assert!(out[1..].iter().all(|s| *s == 0.0));
+
+    // The second note starts after 0.25s when playing from 1/8th note before it:
+    player.seek_ticks(72, &mut matrix);
+    assert_float_eq!(player.position(&matrix), 0.375);
+    player.play();
+    let out = run_player(&mut node_exec, &mut matrix, &mut player, 300);
+    assert_eq!(rising_edges(&out[..]), vec![5512 + 1]);
+}
@@ -0,0 +1,141 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::midi_file::{MidiFile, SmfEvent};
+use hexodsp::midi_player::MidiPlayer;
+use hexodsp::midi_recorder::MidiRecorder;
+use hexodsp::nodes::GraphEvent;
+
+fn midi_ticks(smf: &MidiFile) -> Vec<(u64, String)> {
+    smf.tracks[0]
+        .iter()
+        .map(|ev| match ev.event {
+            SmfEvent::Midi(HxMidiEvent::NoteOn { note, .. }) => (ev.tick, format!("on{}", note)),
+            SmfEvent::Midi(HxMidiEvent::NoteOff { note, .. }) => (ev.tick, format!("off{}", note)),
+            SmfEvent::Midi(HxMidiEvent::CC { cc, .. }) => (ev.tick, format!("cc{}", cc)),
+            SmfEvent::Midi(ev) => (0, format!("{:?}", ev)),
+            SmfEvent::Tempo(t) => (ev.tick, format!("tempo{}", t)),
+        })
+        .collect()
+}
+
+#[test]
+fn check_midi_recorder_timing() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    node_exec.test_run(0.1, false, &[]);
+
+    // 120 BPM with 441 ticks per quarter are 882 ticks per second, so 50 frames per tick:
+    matrix.start_midi_recording(MidiRecorder::new(120.0, 441));
+    assert!(matrix.midi_recorder().unwrap().is_recording());
+
+    node_exec.test_run(
+        1.0,
+        false,
+        &[
+            HxTimedEvent::note_on(100, 0, 60, 1.0),
+            HxTimedEvent::clock(200),
+            HxTimedEvent::cc(5000, 0, 7, 0.5),
+            HxTimedEvent::note_off(22050, 0, 60),
+            HxTimedEvent::note_on(22050, 1, 64, 0.5),
+        ],
+    );
+    matrix.handle_graph_events();
+    matrix.inject_midi_event(HxMidiEvent::Start);
+    node_exec.test_run(0.5, false, &[]);
+
+    let recorder = matrix.stop_midi_recording().unwrap();
+    assert!(!recorder.is_recording());
+    // The clock and start events are not recorded:
+    assert_eq!(recorder.event_count(), 4);
+
+    // The held note is released at the end of the recording:
+    assert_eq!(
+        midi_ticks(&recorder.midi_file()),
+        vec![
+            (0, "tempo500000".to_string()),
+            (2, "on60".to_string()),
+            (100, "cc7".to_string()),
+            (441, "off60".to_string()),
+            (441, "on64".to_string()),
+            (1323, "off64".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn check_midi_recorder_replay() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midip(&mut matrix);
+
+    matrix.start_midi_recording(MidiRecorder::new(120.0, 960));
+    let (ch1, _) = node_exec.test_run(
+        1.0,
+        false,
+        &[
+            HxTimedEvent::note_on(441, 0, 60, 1.0),
+            HxTimedEvent::note_off(11025, 0, 60),
+            HxTimedEvent::note_on(22050, 0, 62, 1.0),
+            HxTimedEvent::note_off(33075, 0, 62),
+        ],
+    );
+    let played = collect_gates(&ch1[..]);
+    assert_eq!(played.len(), 2);
+
+    let recorder = matrix.stop_midi_recording().unwrap();
+
+    // Write and read the recording as MIDI file, and play it back through the patch:
+    let smf = MidiFile::parse(&recorder.midi_file().to_bytes()).unwrap();
+    let mut player = MidiPlayer::new(&smf);
+    player.play();
+
+    let mut out = vec![];
+    for _ in 0..40 {
+        player.update(&mut matrix);
+        let (ch1, _) = node_exec.test_run(0.025, false, &[]);
+        out.extend_from_slice(&ch1[..]);
+    }
+
+    // The ticks are 23 frames long, so the notes are off by less than half a tick:
+    let replayed = collect_gates(&out[..]);
+    assert_eq!(replayed.len(), 2);
+    for (a, b) in played.iter().zip(replayed.iter()) {
+        assert!((a.0 as i64 - b.0 as i64).abs() <= 12, "{:?} vs {:?}", played, replayed);
+        assert!((a.1 as i64 - b.1 as i64).abs() <= 24, "{:?} vs {:?}", played, replayed);
+    }
+}
+
+#[test]
+fn check_midi_recorder_node_conf() {
+    let (mut node_conf, mut node_exec) = new_node_engine();
+
+    let mut recorder = MidiRecorder::new(60.0, 100);
+    recorder.start(&mut node_conf);
+    node_conf.inject_midi_event(HxMidiEvent::NoteOn { channel: 0, note: 60, vel: 1.0 });
+    node_exec.test_run(0.5, false, &[HxTimedEvent::note_off(22050 - 441, 0, 60)]);
+
+    while let Some(ev) = node_conf.next_event() {
+        recorder.handle_event(&ev);
+    }
+    recorder.stop(&mut node_conf);
+    assert_eq!(recorder.event_count(), 2);
+    assert_eq!(recorder.frame_to_tick(22050 - 441), 49);
+
+    let smf = recorder.midi_file();
+    assert_eq!(smf.timed_events()[1].0, 0.49);
+
+    // Without recording, only the plain MIDI events are sent:
+    node_conf.inject_midi_event(HxMidiEvent::NoteOn { channel: 0, note: 60, vel: 1.0 });
+    node_exec.test_run(0.1, false, &[]);
+    let mut events = vec![];
+    while let Some(ev) = node_conf.next_event() {
+        events.push(ev);
+    }
+    assert_eq!(events.len(), 1);
+    assert!(matches!(events[0], GraphEvent::MIDI(HxMidiEvent::NoteOn { .. })));
+}
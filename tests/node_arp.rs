@@ -0,0 +1,166 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+/// 24 clocks of 441 samples make a quarter note at 250 BPM.
+const CLOCK_LEN: usize = 441;
+/// The `MidiClk` node sends a 1/16 pulse every 6th clock.
+const STEP_LEN: usize = 6 * CLOCK_LEN;
+
+/// Starts the MIDI clock at frame 0, so that the `Arp` gets `steps` clock pulses.
+fn clock_steps(steps: usize) -> Vec<HxTimedEvent> {
+    let mut events = vec![HxTimedEvent::start(0)];
+    events.extend((0..(steps * 6)).map(|i| HxTimedEvent::clock(i * CLOCK_LEN)));
+    events
+}
+
+fn setup_arp(matrix: &mut Matrix, mode: i64, oct: i64) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("midiclk", "clk")
+        .node_io("arp", "clock", "freq")
+        .set_atom("mode", SAtom::setting(mode))
+        .set_atom("oct", SAtom::setting(oct))
+        .node_inp("out", "ch1")
+        .place(matrix, 0, 0)
+        .unwrap();
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("arp", "gate").node_inp("out", "ch2").place(matrix, 1, 0).unwrap();
+    matrix.sync().unwrap();
+}
+
+/// Returns the MIDI note numbers played at the first `steps` steps.
+fn played_notes(freq: &[f32], steps: usize) -> Vec<i64> {
+    (0..steps).map(|i| (freq[i * STEP_LEN + 10] * 120.0 + 69.0).round() as i64).collect()
+}
+
+fn chord(notes: &[u8]) -> Vec<HxTimedEvent> {
+    notes.iter().map(|n| HxTimedEvent::note_on(0, 0, *n, 1.0)).collect()
+}
+
+fn run_arp(mode: i64, oct: i64, notes: &[u8], steps: usize) -> Vec<i64> {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_arp(&mut matrix, mode, oct);
+
+    let mut events = chord(notes);
+    events.extend(clock_steps(steps));
+
+    let (ch1, _) = node_exec.test_run((steps * STEP_LEN) as f32 / 44100.0, false, &events[..]);
+    played_notes(&ch1[..], steps)
+}
+
+#[test]
+fn check_node_arp_modes() {
+    assert_eq!(run_arp(0, 0, &[64, 60, 67], 7), vec![60, 64, 67, 60, 64, 67, 60]);
+    assert_eq!(run_arp(1, 0, &[64, 60, 67], 7), vec![67, 64, 60, 67, 64, 60, 67]);
+    assert_eq!(run_arp(2, 0, &[64, 60, 67], 7), vec![60, 64, 67, 64, 60, 64, 67]);
+    assert_eq!(run_arp(4, 0, &[64, 60, 67], 7), vec![64, 60, 67, 64, 60, 67, 64]);
+
+    // A single note does not get stuck in the UpDown mode:
+    assert_eq!(run_arp(2, 0, &[60], 3), vec![60, 60, 60]);
+
+    let notes = run_arp(3, 0, &[64, 60, 67], 16);
+    assert!(notes.iter().all(|n| [60, 64, 67].contains(n)));
+    assert!(notes.iter().any(|n| *n != notes[0]));
+}
+
+#[test]
+fn check_node_arp_octaves() {
+    assert_eq!(run_arp(0, 1, &[60, 64], 5), vec![60, 64, 72, 76, 60]);
+    assert_eq!(run_arp(1, 2, &[60, 64], 7), vec![88, 84, 76, 72, 64, 60, 88]);
+}
+
+#[test]
+fn check_node_arp_gate_len() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_arp(&mut matrix, 0, 0);
+    node_pset_d(&mut matrix, "arp", 0, "glen", 0.5);
+
+    let mut events = chord(&[60, 64]);
+    events.extend(clock_steps(3));
+    let (_, ch2) = node_exec.test_run((3 * STEP_LEN) as f32 / 44100.0, false, &events[..]);
+
+    // Until the clock interval is known, the gate stays open until the next step,
+    // then it's half of the interval:
+    let changes = collect_signal_changes_both_edges(&ch2[..], 1);
+    assert_eq!(
+        changes,
+        vec![
+            (0, 100),
+            (STEP_LEN, -100),
+            (STEP_LEN + 1, 100),
+            (STEP_LEN + STEP_LEN / 2, -100),
+            (2 * STEP_LEN, 100),
+            (2 * STEP_LEN + STEP_LEN / 2, -100),
+        ]
+    );
+}
+
+#[test]
+fn check_node_arp_release_and_latch() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_arp(&mut matrix, 0, 0);
+    node_pset_d(&mut matrix, "arp", 0, "glen", 0.5);
+
+    // Without latch, the arpeggio stops when the keys are released:
+    let mut events = chord(&[60, 64]);
+    events.extend(clock_steps(4));
+    events.push(HxTimedEvent::note_off(STEP_LEN + 100, 0, 60));
+    events.push(HxTimedEvent::note_off(STEP_LEN + 100, 0, 64));
+    events.sort_by_key(|ev| ev.timing());
+    let (ch1, ch2) = node_exec.test_run((4 * STEP_LEN) as f32 / 44100.0, false, &events[..]);
+
+    assert_eq!(played_notes(&ch1[..], 2), vec![60, 64]);
+    let changes = collect_signal_changes_both_edges(&ch2[..], 1);
+    assert_eq!(
+        changes,
+        vec![(0, 100), (STEP_LEN, -100), (STEP_LEN + 1, 100), (STEP_LEN + 100, -100)]
+    );
+
+    // With latch, the notes keep playing until new notes are played:
+    node_pset_s(&mut matrix, "arp", 0, "latch", 1);
+    let mut events = chord(&[60, 64]);
+    events.extend(clock_steps(6));
+    events.push(HxTimedEvent::note_off(100, 0, 60));
+    events.push(HxTimedEvent::note_off(100, 0, 64));
+    events.push(HxTimedEvent::note_on(3 * STEP_LEN + 100, 0, 50, 1.0));
+    events.push(HxTimedEvent::note_on(3 * STEP_LEN + 100, 0, 53, 1.0));
+    events.push(HxTimedEvent::note_off(4 * STEP_LEN + 100, 0, 50));
+    events.push(HxTimedEvent::note_off(4 * STEP_LEN + 100, 0, 53));
+    events.sort_by_key(|ev| ev.timing());
+    let (ch1, _) = node_exec.test_run((6 * STEP_LEN) as f32 / 44100.0, false, &events[..]);
+
+    assert_eq!(played_notes(&ch1[..], 6), vec![60, 64, 60, 64, 50, 53]);
+
+    // Switching the latch off drops the released notes:
+    node_pset_s(&mut matrix, "arp", 0, "latch", 0);
+    let (_, ch2) = node_exec.test_run((2 * STEP_LEN) as f32 / 44100.0, false, &clock_steps(2));
+    assert_eq!(collect_signal_changes_both_edges(&ch2[..], 1), vec![]);
+}
+
+#[test]
+fn check_node_arp_channel_and_reset() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_arp(&mut matrix, 0, 0);
+    node_pset_s(&mut matrix, "arp", 0, "chan", 1);
+
+    let mut events = chord(&[60, 64, 67]);
+    events.push(HxTimedEvent::note_on(0, 1, 48, 1.0));
+    events.push(HxTimedEvent::note_on(0, 1, 50, 1.0));
+    events.extend(clock_steps(3));
+    let (ch1, _) = node_exec.test_run((3 * STEP_LEN) as f32 / 44100.0, false, &events[..]);
+    assert_eq!(played_notes(&ch1[..], 3), vec![48, 50, 48]);
+
+    // The reset trigger restarts the arpeggio:
+    pset_n(&mut matrix, NodeId::Arp(0), "trig", 1.0);
+    run_for_ms(&mut node_exec, 10.0); // wait for trigger...
+    let (ch1, _) = node_exec.test_run((3 * STEP_LEN) as f32 / 44100.0, false, &clock_steps(3));
+    assert_eq!(played_notes(&ch1[..], 3), vec![48, 50, 48]);
+}
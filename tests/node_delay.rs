@@ -5,6 +5,8 @@
 mod common;
 use common::*;
 
+use hexodsp::nodes::HostTransport;
+
 #[test]
 fn check_node_delay_1() {
     let (node_conf, mut node_exec) = new_node_engine();
@@ -364,3 +366,83 @@ fn check_node_delay_fb_pos() {
         ]
     );
 }
+
+#[test]
+fn check_node_delay_host_sync() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 4, 4);
+
+    let test = NodeId::Test(0);
+    let dly = NodeId::Delay(0);
+    let out = NodeId::Out(0);
+    matrix.place(1, 1, Cell::empty(test).out(None, None, test.out("sig")));
+    matrix.place(
+        1,
+        2,
+        Cell::empty(dly).input(dly.inp("inp"), None, None).out(None, None, dly.out("sig")),
+    );
+    matrix.place(1, 3, Cell::empty(out).input(out.inp("ch1"), None, None).out(None, None, None));
+    matrix.sync().unwrap();
+
+    pset_n(&mut matrix, dly, "mix", 1.0);
+    pset_s(&mut matrix, dly, "mode", 2);
+    // 1/8 note:
+    pset_s(&mut matrix, dly, "note", 9);
+
+    let mut transport = HostTransport::new();
+    transport.tempo = 120.0;
+    node_exec.set_transport(transport);
+
+    // Let the mix parameter settle:
+    run_for_ms(&mut node_exec, 50.0);
+
+    pset_n(&mut matrix, test, "p", 1.0);
+    let mut res = run_for_ms(&mut node_exec, 10.0).0;
+    pset_n(&mut matrix, test, "p", 0.0);
+    res.extend_from_slice(&run_for_ms(&mut node_exec, 300.0).0[..]);
+
+    let idx_first_non_zero = res.iter().position(|s| *s > 0.0).unwrap();
+
+    // An 1/8 note at 120 BPM is 250ms:
+    assert_eq!(idx_first_non_zero, (44100 * 250) / 1000 + 1);
+}
+
+#[test]
+fn check_node_delay_host_sync_max_time() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 4, 4);
+
+    let test = NodeId::Test(0);
+    let dly = NodeId::Delay(0);
+    let out = NodeId::Out(0);
+    matrix.place(1, 1, Cell::empty(test).out(None, None, test.out("sig")));
+    matrix.place(
+        1,
+        2,
+        Cell::empty(dly).input(dly.inp("inp"), None, None).out(None, None, dly.out("sig")),
+    );
+    matrix.place(1, 3, Cell::empty(out).input(out.inp("ch1"), None, None).out(None, None, None));
+    matrix.sync().unwrap();
+
+    pset_n(&mut matrix, dly, "mix", 1.0);
+    pset_s(&mut matrix, dly, "mode", 2);
+    // 4/1 note:
+    pset_s(&mut matrix, dly, "note", 0);
+
+    let mut transport = HostTransport::new();
+    transport.tempo = 10.0;
+    node_exec.set_transport(transport);
+
+    // Let the mix parameter settle:
+    run_for_ms(&mut node_exec, 50.0);
+
+    pset_n(&mut matrix, test, "p", 1.0);
+    let mut res = run_for_ms(&mut node_exec, 10.0).0;
+    pset_n(&mut matrix, test, "p", 0.0);
+    res.extend_from_slice(&run_for_ms(&mut node_exec, 5100.0).0[..]);
+
+    let idx_first_non_zero = res.iter().position(|s| *s > 0.0).unwrap();
+
+    // An 4/1 note at 10 BPM would be 96 seconds, which is limited to 5 seconds:
+    assert_eq!(idx_first_non_zero, 44100 * 5 + 1);
+}
@@ -0,0 +1,147 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::build::*;
+use hexodsp::nodes::{GraphEvent, NodeEvent};
+use hexodsp::synth_constructor::SynthConstructor;
+use std::sync::{Arc, Mutex};
+
+struct EventRecorder {
+    events: Mutex<Vec<(NodeId, NodeEvent)>>,
+}
+
+impl MatrixObserver for EventRecorder {
+    fn update_prop(&self, _key: &str) {}
+    fn update_monitor(&self, _cell: &Cell) {}
+    fn update_param(&self, _param_id: &ParamId) {}
+    fn update_matrix(&self) {}
+    fn update_all(&self) {}
+    fn midi_event(&self, _midi_ev: HxMidiEvent) {}
+    fn node_event(&self, node_id: NodeId, ev: NodeEvent) {
+        self.events.lock().expect("recorder lock ok").push((node_id, ev));
+    }
+}
+
+fn node_events_of(sc: &mut SynthConstructor, node_id: NodeId) -> Vec<NodeEvent> {
+    let mut events = vec![];
+    while let Some(ev) = sc.next_event() {
+        if let GraphEvent::Node(id, ev) = ev {
+            if id == node_id {
+                events.push(ev);
+            }
+        }
+    }
+    events
+}
+
+#[test]
+fn check_node_event_tseq_row() {
+    use hexodsp::dsp::tracker::UIPatternModel;
+
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    let recorder = Arc::new(EventRecorder { events: Mutex::new(vec![]) });
+    matrix.set_observer(recorder.clone());
+
+    let tsq = NodeId::TSeq(0);
+    let out = NodeId::Out(0);
+    matrix.place(0, 0, Cell::empty(tsq).out(None, None, tsq.out("trk1")));
+    matrix.place(0, 1, Cell::empty(out).input(out.inp("ch1"), None, None));
+    matrix.sync().unwrap();
+
+    // The clock input is used directly as phase:
+    pset_s(&mut matrix, tsq, "cmode", 2);
+    pset_d(&mut matrix, tsq, "clock", 0.5);
+
+    let pat = matrix.get_pattern_data(0).unwrap();
+    pat.lock().unwrap().set_rows(16);
+    for _ in 0..10 {
+        matrix.check_pattern_data(0);
+    }
+
+    run_for_ms(&mut node_exec, 50.0);
+    matrix.handle_graph_events();
+
+    let events = recorder.events.lock().unwrap().clone();
+    // The row is only reported when it changes:
+    assert_eq!(events.last(), Some(&(tsq, NodeEvent::TSeqRow { row: 8 })));
+    assert_eq!(events.iter().filter(|e| e.1 == NodeEvent::TSeqRow { row: 8 }).count(), 1);
+}
+
+#[test]
+fn check_node_event_adsr_end() {
+    // A 10Hz gate, with a short release:
+    let gate = sin(0).set().freq(10.0);
+    let env =
+        adsr(0).set().atk(1.0).set().dcy(1.0).set().rel(5.0).input().gate(&gate.output().sig());
+
+    let mut sc = SynthConstructor::new();
+    sc.upload(&out(0).input().ch1(&env.output().sig())).unwrap();
+
+    let mut exec = sc.executor().unwrap();
+    exec.process_graph_updates();
+    exec.test_run(0.3, false, &[]);
+
+    let events = node_events_of(&mut sc, NodeId::Adsr(0));
+    assert_eq!(events, vec![NodeEvent::EnvEnd; 3]);
+}
+
+#[test]
+fn check_node_event_sampl_end() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    let recorder = Arc::new(EventRecorder { events: Mutex::new(vec![]) });
+    matrix.set_observer(recorder.clone());
+
+    let smpl = NodeId::Sampl(0);
+    let out = NodeId::Out(0);
+    matrix.place(0, 0, Cell::empty(smpl).out(None, None, smpl.out("sig")));
+    matrix.place(0, 1, Cell::empty(out).input(out.inp("ch1"), None, None));
+    matrix.sync().unwrap();
+
+    // A 10ms sample, which loops 10 times in 100ms:
+    let mut sample = vec![0.5; 442];
+    sample[0] = SAMPLE_RATE;
+    matrix.set_param(
+        smpl.inp_param("sample").unwrap(),
+        SAtom::audio("10ms_const.wav", Arc::new(sample)),
+    );
+
+    run_for_ms(&mut node_exec, 100.0);
+    matrix.handle_graph_events();
+
+    let events = recorder.events.lock().unwrap().clone();
+    assert!(events.iter().all(|e| *e == (smpl, NodeEvent::SampleEnd)));
+    assert!(events.len() >= 9 && events.len() <= 10, "events={}", events.len());
+}
+
+#[test]
+fn check_node_event_scope_trigger() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    let recorder = Arc::new(EventRecorder { events: Mutex::new(vec![]) });
+    matrix.set_observer(recorder.clone());
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .set_denorm("freq", 100.0)
+        .node_inp("scope", "in1")
+        .set_denorm("time", 5.0)
+        .set_atom("tsrc", SAtom::setting(1))
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    run_for_ms(&mut node_exec, 100.0);
+    matrix.handle_graph_events();
+
+    // The 5ms capture is retriggered by each period of the 100Hz sine:
+    let events = recorder.events.lock().unwrap().clone();
+    assert!(events.iter().all(|e| *e == (NodeId::Scope(0), NodeEvent::ScopeTrigger)));
+    assert!(events.len() >= 9 && events.len() <= 10, "events={}", events.len());
+}
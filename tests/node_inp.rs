@@ -52,3 +52,30 @@ fn check_node_inp_vol() {
     assert!(out_r.len() == 512);
     assert_decimated_feq!(out_r, 1, vec![0.22; 512]);
 }
+
+#[test]
+fn check_node_inp_bank() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("inp", "sig1")
+        .set_atom("bank", SAtom::setting(1))
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("inp", "sig2").node_inp("out", "ch2").place(&mut matrix, 1, 0).unwrap();
+    matrix.sync().unwrap();
+
+    let inputs = vec![vec![0.1; 512], vec![0.2; 512], vec![0.3; 512], vec![0.4; 512]];
+    let outputs = run_multichannel(&mut node_exec, &inputs[..], 2, 512);
+    assert_decimated_feq!(outputs[0], 1, vec![0.3; 512]);
+    assert_decimated_feq!(outputs[1], 1, vec![0.4; 512]);
+
+    // Channels that are not provided by the audio driver are silent:
+    let outputs = run_multichannel(&mut node_exec, &inputs[0..3], 2, 512);
+    assert_decimated_feq!(outputs[0], 1, vec![0.3; 512]);
+    assert_decimated_feq!(outputs[1], 1, vec![0.0; 512]);
+}
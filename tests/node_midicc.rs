@@ -137,3 +137,113 @@ fn check_node_midicc_test_slew2() {
         .collect();
     assert_eq!(changes, &[(5, 11), (25, 238), (45, 465), (111, 414), (133, 191),]);
 }
+
+/// Collects the changes of the signal as 14-bit values.
+fn collect_14bit_changes(buf: &[f32]) -> Vec<(usize, i64)> {
+    let mut last = 0.0;
+    let mut changes = vec![];
+    for (i, s) in buf.iter().enumerate() {
+        if *s != last {
+            changes.push((i, (*s * 16383.0).round() as i64));
+            last = *s;
+        }
+    }
+    changes
+}
+
+fn setup_midicc_mode(matrix: &mut Matrix, mode: i64, pmsb: i64, cc1: i64) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("midicc", "sig1")
+        .set_atom("chan", SAtom::setting(1))
+        .set_atom("cc1", SAtom::setting(cc1))
+        .set_atom("mode", SAtom::setting(mode))
+        .set_atom("pmsb", SAtom::setting(pmsb))
+        .node_inp("out", "ch1")
+        .place(matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+}
+
+#[test]
+fn check_node_midicc_14bit() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midicc_mode(&mut matrix, 1, 0, 10);
+
+    let (ch1, _) = node_exec.test_run(
+        0.005,
+        false,
+        &[
+            HxTimedEvent::cc(5, 1, 10, 64.0 / 127.0),
+            HxTimedEvent::cc(5, 1, 42, 3.0 / 127.0),
+            HxTimedEvent::cc(20, 1, 42, 5.0 / 127.0),
+            // The 7-bit CC and other channels are ignored:
+            HxTimedEvent::cc(30, 1, 74, 1.0),
+            HxTimedEvent::cc(40, 2, 42, 1.0),
+            // The MSB alone resets the LSB:
+            HxTimedEvent::cc(50, 1, 10, 1.0),
+            HxTimedEvent::cc(60, 1, 42, 1.0),
+        ],
+    );
+
+    assert_eq!(
+        collect_14bit_changes(&ch1[..]),
+        vec![(5, 8195), (20, 8197), (50, 16256), (60, 16383)]
+    );
+}
+
+#[test]
+fn check_node_midicc_nrpn() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midicc_mode(&mut matrix, 2, 3, 20);
+
+    let (ch1, _) = node_exec.test_run(
+        0.005,
+        false,
+        &[
+            // NRPN 3 * 128 + 20:
+            HxTimedEvent::cc(5, 1, 99, 3.0 / 127.0),
+            HxTimedEvent::cc(5, 1, 98, 20.0 / 127.0),
+            HxTimedEvent::cc(10, 1, 6, 100.0 / 127.0),
+            HxTimedEvent::cc(20, 1, 38, 1.0 / 127.0),
+            // Another NRPN is ignored:
+            HxTimedEvent::cc(30, 1, 98, 21.0 / 127.0),
+            HxTimedEvent::cc(30, 1, 6, 1.0),
+            // The RPN with the same number is ignored:
+            HxTimedEvent::cc(40, 1, 101, 3.0 / 127.0),
+            HxTimedEvent::cc(40, 1, 100, 20.0 / 127.0),
+            HxTimedEvent::cc(40, 1, 6, 1.0),
+            HxTimedEvent::cc(50, 1, 99, 3.0 / 127.0),
+            HxTimedEvent::cc(50, 1, 98, 20.0 / 127.0),
+            HxTimedEvent::cc(60, 1, 6, 2.0 / 127.0),
+        ],
+    );
+
+    assert_eq!(collect_14bit_changes(&ch1[..]), vec![(10, 12800), (20, 12801), (60, 256)]);
+}
+
+#[test]
+fn check_node_midicc_rpn() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midicc_mode(&mut matrix, 3, 0, 0);
+
+    // Pitch bend sensitivity of 12 semitones:
+    let (ch1, _) = node_exec.test_run(
+        0.005,
+        false,
+        &[
+            HxTimedEvent::cc(5, 1, 101, 0.0),
+            HxTimedEvent::cc(5, 1, 100, 0.0),
+            HxTimedEvent::cc(5, 1, 6, 12.0 / 127.0),
+            HxTimedEvent::cc(5, 1, 38, 0.0),
+            HxTimedEvent::cc(10, 1, 101, 1.0),
+            HxTimedEvent::cc(10, 1, 100, 1.0),
+            HxTimedEvent::cc(20, 1, 6, 1.0),
+        ],
+    );
+
+    assert_eq!(collect_14bit_changes(&ch1[..]), vec![(5, 1536)]);
+}
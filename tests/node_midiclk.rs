@@ -0,0 +1,95 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+/// 24 clocks of 441 samples make a quarter note at 250 BPM.
+const CLOCK_LEN: usize = 441;
+
+fn clocks(offs: usize, count: usize) -> Vec<HxTimedEvent> {
+    (0..count).map(|i| HxTimedEvent::clock(offs + i * CLOCK_LEN)).collect()
+}
+
+fn setup_midiclk(matrix: &mut Matrix, out1: &str, out2: &str) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midiclk", out1).node_inp("out", "ch1").place(matrix, 0, 0).unwrap();
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midiclk", out2).node_inp("out", "ch2").place(matrix, 1, 0).unwrap();
+    matrix.sync().unwrap();
+}
+
+#[test]
+fn check_node_midiclk_pulses() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midiclk(&mut matrix, "clk", "run");
+
+    // Clocks before the start only measure the tempo:
+    let mut events = clocks(0, 4);
+    events.push(HxTimedEvent::start(4 * CLOCK_LEN));
+    events.extend(clocks(5 * CLOCK_LEN, 14));
+    events.push(HxTimedEvent::stop(18 * CLOCK_LEN + 10));
+    events.extend(clocks(19 * CLOCK_LEN, 2));
+    events.push(HxTimedEvent::cont(21 * CLOCK_LEN - 10));
+    events.extend(clocks(21 * CLOCK_LEN, 10));
+
+    let (ch1, ch2) = node_exec.test_run(0.8, false, &events[..]);
+
+    // The default division is 1/16, which is every 6th clock:
+    assert_eq!(
+        rising_edges(&ch1[..]),
+        vec![5 * CLOCK_LEN, 11 * CLOCK_LEN, 17 * CLOCK_LEN, 25 * CLOCK_LEN]
+    );
+    assert_eq!(rising_edges(&ch2[..]), vec![4 * CLOCK_LEN, 21 * CLOCK_LEN - 10]);
+    assert_float_eq!(ch2[18 * CLOCK_LEN + 9], 1.0);
+    assert_float_eq!(ch2[18 * CLOCK_LEN + 10], 0.0);
+    assert_float_eq!(ch2[ch2.len() - 1], 1.0);
+}
+
+#[test]
+fn check_node_midiclk_reset_and_song_position() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midiclk(&mut matrix, "clk", "rst");
+    node_pset_s(&mut matrix, "midiclk", 0, "note", 6);
+
+    let mut events = vec![HxTimedEvent::start(10)];
+    events.extend(clocks(CLOCK_LEN, 4));
+    events.push(HxTimedEvent::stop(5 * CLOCK_LEN));
+    // Jump to the second sixteenth note and continue:
+    events.push(HxTimedEvent::song_position(5 * CLOCK_LEN + 10, 2));
+    events.push(HxTimedEvent::cont(5 * CLOCK_LEN + 20));
+    events.extend(clocks(6 * CLOCK_LEN, 20));
+
+    let (ch1, ch2) = node_exec.test_run(0.7, false, &events[..]);
+
+    // The song position is at clock 12, the next quarter note is 12 clocks later:
+    assert_eq!(rising_edges(&ch1[..]), vec![CLOCK_LEN, 18 * CLOCK_LEN]);
+    assert_eq!(rising_edges(&ch2[..]), vec![10]);
+}
+
+#[test]
+fn check_node_midiclk_bpm() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midiclk(&mut matrix, "bpm", "run");
+
+    let (ch1, _) = node_exec.test_run(0.5, false, &clocks(100, 40)[..]);
+
+    // Nothing measured before the second clock:
+    assert_float_eq!(ch1[100 + CLOCK_LEN - 1], 0.0);
+    assert_float_eq!(ch1[100 + CLOCK_LEN + 1], 0.25);
+    assert_float_eq!(ch1[ch1.len() - 1], 0.25);
+
+    // After a pause the measurement starts over:
+    node_exec.test_run(1.0, false, &[]);
+    let (ch1, _) = node_exec.test_run(0.5, false, &clocks(100, 40)[..]);
+    assert_float_eq!(ch1[100 + CLOCK_LEN + 1], 0.25);
+
+    let events: Vec<HxTimedEvent> =
+        (0..40).map(|i| HxTimedEvent::clock(100 + i * CLOCK_LEN * 2)).collect();
+    let (ch1, _) = node_exec.test_run(1.0, false, &events[..]);
+    assert_float_eq!(ch1[ch1.len() - 1], 0.125);
+}
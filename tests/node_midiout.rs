@@ -0,0 +1,218 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::dsp::MAX_BLOCK_SIZE;
+use hexodsp::nodes::MAX_MIDI_OUT_PER_BLOCK;
+
+/// Runs the executor for about `ms` milliseconds and returns the sent MIDI
+/// events with their frame offset from the start of the run.
+/// The `input` events are passed in the first buffer period.
+fn run_midi_thru(
+    node_exec: &mut NodeExecutor,
+    ms: f32,
+    input_events: &[HxTimedEvent],
+) -> Vec<(usize, HxMidiEvent)> {
+    let blocks = ((ms * 44.1) as usize) / MAX_BLOCK_SIZE;
+    let input = [0.0; MAX_BLOCK_SIZE];
+    let mut events = vec![];
+
+    for block in 0..blocks {
+        let input_events = if block == 0 { input_events } else { &[] };
+        node_exec.test_run_input(&input[..], false, input_events);
+        for ev in node_exec.midi_output() {
+            events.push((block * MAX_BLOCK_SIZE + ev.timing(), ev.kind()));
+        }
+    }
+
+    events
+}
+
+fn run_midi_out(node_exec: &mut NodeExecutor, ms: f32) -> Vec<(usize, HxMidiEvent)> {
+    run_midi_thru(node_exec, ms, &[])
+}
+
+fn kinds(events: &[(usize, HxMidiEvent)]) -> Vec<HxMidiEvent> {
+    events.iter().map(|(_, ev)| *ev).collect()
+}
+
+#[test]
+fn check_node_midiout_notes() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    matrix.place(0, 0, Cell::empty(NodeId::MidiOut(0)));
+    matrix.sync().unwrap();
+
+    node_pset_d(&mut matrix, "midiout", 0, "freq", 440.0);
+    node_pset_d(&mut matrix, "midiout", 0, "vel", 0.5);
+    assert!(run_midi_out(&mut node_exec, 50.0).is_empty());
+
+    node_pset_d(&mut matrix, "midiout", 0, "gate", 1.0);
+    let events = run_midi_out(&mut node_exec, 50.0);
+    assert_eq!(kinds(&events), vec![HxMidiEvent::NoteOn { channel: 0, note: 69, vel: 0.5 }]);
+
+    // Changing the pitch during the gate does not start a new note:
+    node_pset_d(&mut matrix, "midiout", 0, "freq", 880.0);
+    node_pset_s(&mut matrix, "midiout", 0, "chan", 3);
+    assert!(run_midi_out(&mut node_exec, 50.0).is_empty());
+
+    // The note off goes to the channel of the note on:
+    node_pset_d(&mut matrix, "midiout", 0, "gate", 0.0);
+    let events = run_midi_out(&mut node_exec, 50.0);
+    assert_eq!(kinds(&events), vec![HxMidiEvent::NoteOff { channel: 0, note: 69 }]);
+
+    node_pset_d(&mut matrix, "midiout", 0, "gate", 1.0);
+    let events = run_midi_out(&mut node_exec, 50.0);
+    assert_eq!(kinds(&events), vec![HxMidiEvent::NoteOn { channel: 3, note: 81, vel: 0.5 }]);
+}
+
+#[test]
+fn check_node_midiout_tseq() {
+    use hexodsp::dsp::tracker::UIPatternModel;
+
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .set_denorm("freq", 8.0)
+        .node_io("tseq", "clock", "trk1")
+        .set_atom("cmode", SAtom::setting(0))
+        .node_inp("midiout", "gate")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    let pat = matrix.get_pattern_data(0).unwrap();
+    {
+        let mut pr = pat.lock().unwrap();
+        pr.set_rows(4);
+        pr.set_col_gate_type(0);
+        for row in 0..4 {
+            pr.set_cell_value(row, 0, 0x7FF);
+        }
+    }
+    for _ in 0..10 {
+        matrix.check_pattern_data(0);
+    }
+
+    // The TSeq needs a few clock cycles to measure the row length:
+    run_midi_out(&mut node_exec, 200.0);
+
+    // The gates are 125ms long with 125ms pause:
+    let events = run_midi_out(&mut node_exec, 510.0);
+    let note_ons = events.iter().filter(|(_, ev)| matches!(ev, HxMidiEvent::NoteOn { .. }));
+    assert_eq!(note_ons.count(), 2);
+    assert!(events.len() >= 4);
+
+    // Note on and off alternate, at increasing frames:
+    for e in events.windows(2) {
+        assert!(e[0].0 < e[1].0);
+
+        match (e[0].1, e[1].1) {
+            (HxMidiEvent::NoteOn { .. }, HxMidiEvent::NoteOff { channel: 0, note: 69 }) => (),
+            (HxMidiEvent::NoteOff { .. }, HxMidiEvent::NoteOn { channel: 0, note: 69, vel }) => {
+                assert_float_eq!(vel, 0.8);
+            }
+            _ => panic!("Unexpected events: {:?}", e),
+        }
+    }
+}
+
+#[test]
+fn check_node_midiout_full_output() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 5, 2);
+
+    // Each node sends a note on and off every 4 frames, more than fits into the MIDI output:
+    for i in 0..5 {
+        let sin = NodeId::Sin(i as u8);
+        let midiout = NodeId::MidiOut(i as u8);
+        matrix.place(i, 0, Cell::empty(sin).out(None, None, sin.out("sig")));
+        matrix.place(i, 1, Cell::empty(midiout).input(midiout.inp("gate"), None, None));
+    }
+    matrix.sync().unwrap();
+    for i in 0..5 {
+        node_pset_d(&mut matrix, "sin", i, "freq", 11025.0);
+        node_pset_s(&mut matrix, "midiout", i, "chan", i as i64);
+    }
+    let mut events = run_midi_out(&mut node_exec, 20.0);
+    let last_block = events.iter().filter(|(frame, _)| *frame >= 5 * MAX_BLOCK_SIZE).count();
+    assert_eq!(last_block, MAX_MIDI_OUT_PER_BLOCK);
+
+    for i in 0..5 {
+        matrix.place(i, 0, Cell::empty(NodeId::Nop));
+    }
+    matrix.sync().unwrap();
+    events.append(&mut run_midi_out(&mut node_exec, 20.0));
+
+    // Every note on that was sent is followed by exactly one note off:
+    for chan in 0..5 {
+        let mut playing = false;
+        for (_, ev) in events.iter() {
+            match ev {
+                HxMidiEvent::NoteOn { channel, .. } if *channel == chan => {
+                    assert!(!playing);
+                    playing = true;
+                }
+                HxMidiEvent::NoteOff { channel, .. } if *channel == chan => {
+                    assert!(playing);
+                    playing = false;
+                }
+                _ => (),
+            }
+        }
+        assert!(!playing);
+    }
+}
+
+#[test]
+fn check_node_midiccout() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    matrix.place(0, 0, Cell::empty(NodeId::MidiCCOut(0)));
+    matrix.sync().unwrap();
+    node_pset_s(&mut matrix, "midiccout", 0, "chan", 2);
+    node_pset_s(&mut matrix, "midiccout", 0, "cc", 74);
+
+    // The initial value is sent once:
+    let events = run_midi_out(&mut node_exec, 50.0);
+    assert_eq!(kinds(&events), vec![HxMidiEvent::CC { channel: 2, cc: 74, value: 0.0 }]);
+    assert!(run_midi_out(&mut node_exec, 50.0).is_empty());
+
+    // The smoothed parameter ramp is sent at most every millisecond:
+    node_pset_d(&mut matrix, "midiccout", 0, "inp", 0.5);
+    let events = run_midi_out(&mut node_exec, 50.0);
+    assert!(events.len() > 2);
+    assert!(events.windows(2).all(|e| e[1].0 - e[0].0 >= 44));
+    assert_eq!(
+        events[events.len() - 1].1,
+        HxMidiEvent::CC { channel: 2, cc: 74, value: 64.0 / 127.0 }
+    );
+    assert!(run_midi_out(&mut node_exec, 50.0).is_empty());
+}
+
+#[test]
+fn check_node_midiccout_thru() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("midicc", "sig1")
+        .set_atom("cc1", SAtom::setting(1))
+        .node_inp("midiccout", "inp")
+        .set_atom("cc", SAtom::setting(11))
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+    run_midi_out(&mut node_exec, 10.0);
+
+    // Incoming MIDI is not passed through by itself, only the remapped CC is sent:
+    let input = [HxTimedEvent::cc(10, 0, 1, 1.0), HxTimedEvent::note_on(20, 0, 60, 1.0)];
+    let events = run_midi_thru(&mut node_exec, 10.0, &input[..]);
+    assert_eq!(events, vec![(10, HxMidiEvent::CC { channel: 0, cc: 11, value: 1.0 })]);
+}
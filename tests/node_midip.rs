@@ -40,6 +40,36 @@ fn check_node_midip_gate_inserts() {
     );
 }
 
+#[test]
+fn check_node_midip_without_feed() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midip", "gate").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+
+    let input = [0.0; MAX_BLOCK_SIZE];
+    let (mut ch1, _) =
+        node_exec.test_run_input(&input[..], false, &[HxTimedEvent::note_on(5, 0, 69, 1.0)]);
+
+    // The following buffer periods are processed without feeding any MIDI events:
+    for _ in 0..10 {
+        let mut out_l = [0.0; MAX_BLOCK_SIZE];
+        let mut out_r = [0.0; MAX_BLOCK_SIZE];
+        let mut context = hexodsp::Context {
+            nframes: MAX_BLOCK_SIZE,
+            output: &mut [&mut out_l[..], &mut out_r[..]],
+            input: &[&input[..], &input[..]],
+        };
+        node_exec.process(&mut context);
+        ch1.extend_from_slice(&out_l[..]);
+    }
+
+    // The note triggers the gate only once:
+    assert_eq!(collect_signal_changes(&ch1[..], 0), vec![(5, 100)]);
+}
+
 #[test]
 fn check_node_midip_pitch_track() {
     let (node_conf, mut node_exec) = new_node_engine();
@@ -242,3 +272,222 @@ fn check_node_midip_gate_test() {
     //    // As expected, now end of note at 106:
     //    assert_eq!(changes, vec![(100, 100), (106, -100)]);
 }
+
+#[test]
+fn check_node_midip_pitch_bend() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midip", "freq").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midip", "bend").node_inp("out", "ch2").place(&mut matrix, 1, 0).unwrap();
+    matrix.sync().unwrap();
+
+    let (ch1, ch2) = node_exec.test_run(
+        0.005,
+        false,
+        &[
+            HxTimedEvent::note_on(5, 0, 69, 1.0),
+            HxTimedEvent::pitch_bend(10, 0, 0x3FFF),
+            // Other channels are ignored:
+            HxTimedEvent::pitch_bend(20, 1, 0x0),
+            HxTimedEvent::pitch_bend(30, 0, 0x0),
+            HxTimedEvent::pitch_bend(40, 0, 0x2000),
+        ],
+    );
+
+    // The default bend range is 2 semitones:
+    let changes = collect_signal_changes_flt(&ch1[..], 0.001);
+    assert_eq!(changes, vec![(0, -0.575), (5, 0.0), (10, 0.017), (30, -0.017), (40, 0.0)]);
+
+    let changes = collect_signal_changes_flt(&ch2[..], 0.01);
+    assert_eq!(changes, vec![(10, 1.0), (30, -1.0), (40, 0.0)]);
+
+    // An octave bend range:
+    node_pset_s(&mut matrix, "midip", 0, "brange", 12);
+    let (ch1, _) = node_exec.test_run(0.005, false, &[HxTimedEvent::pitch_bend(10, 0, 0x3FFF)]);
+    let changes = collect_signal_changes_flt(&ch1[..], 0.001);
+    assert_eq!(changes, vec![(10, 0.1)]);
+}
+
+#[test]
+fn check_node_midip_pressure() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midip", "press").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+
+    let (ch1, _) = node_exec.test_run(
+        0.005,
+        false,
+        &[
+            HxTimedEvent::note_on(5, 0, 69, 1.0),
+            HxTimedEvent::channel_pressure(10, 0, 0.5),
+            HxTimedEvent::poly_pressure(20, 0, 69, 0.75),
+            // Pressure of other notes is ignored:
+            HxTimedEvent::poly_pressure(30, 0, 60, 0.1),
+            HxTimedEvent::channel_pressure(40, 0, 0.25),
+        ],
+    );
+
+    let changes = collect_signal_changes_flt(&ch1[..], 0.01);
+    assert_eq!(changes, vec![(10, 0.5), (20, 0.75), (40, 0.25)]);
+}
+
+fn setup_midip_freq_gate(matrix: &mut Matrix, prio: i64, legato: i64) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("midip", "freq")
+        .set_atom("prio", SAtom::setting(prio))
+        .set_atom("legato", SAtom::setting(legato))
+        .node_inp("out", "ch1")
+        .place(matrix, 0, 0)
+        .unwrap();
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midip", "gate").node_inp("out", "ch2").place(matrix, 1, 0).unwrap();
+    matrix.sync().unwrap();
+}
+
+#[test]
+fn check_node_midip_note_priority_last() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midip_freq_gate(&mut matrix, 0, 0);
+
+    let (ch1, ch2) = node_exec.test_run(
+        0.005,
+        false,
+        &[
+            HxTimedEvent::note_on(5, 0, 69, 1.0),
+            HxTimedEvent::note_on(10, 0, 72, 1.0),
+            // Returns to the still held note:
+            HxTimedEvent::note_off(20, 0, 72),
+            HxTimedEvent::note_off(30, 0, 69),
+        ],
+    );
+
+    let changes = collect_signal_changes_flt(&ch1[..], 0.01);
+    assert_eq!(changes, vec![(0, -0.575), (5, 0.0), (10, 0.025), (20, 0.0)]);
+
+    let changes = collect_signal_changes_both_edges(&ch2[..], 1);
+    assert_eq!(changes, vec![(5, 100), (10, -100), (11, 100), (20, -100), (21, 100), (31, -100)]);
+}
+
+#[test]
+fn check_node_midip_note_priority_low_high() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midip_freq_gate(&mut matrix, 1, 0);
+
+    let events = [
+        HxTimedEvent::note_on(5, 0, 69, 1.0),
+        HxTimedEvent::note_on(10, 0, 72, 1.0),
+        HxTimedEvent::note_on(15, 0, 60, 1.0),
+        HxTimedEvent::note_off(20, 0, 60),
+        HxTimedEvent::note_off(25, 0, 72),
+        HxTimedEvent::note_off(30, 0, 69),
+    ];
+
+    let (ch1, ch2) = node_exec.test_run(0.005, false, &events);
+
+    // The higher note 72 is only remembered, and not played:
+    let changes = collect_signal_changes_flt(&ch1[..], 0.01);
+    assert_eq!(changes, vec![(0, -0.575), (5, 0.0), (15, -0.075), (20, 0.0)]);
+
+    let changes = collect_signal_changes_both_edges(&ch2[..], 1);
+    assert_eq!(changes, vec![(5, 100), (15, -100), (16, 100), (20, -100), (21, 100), (31, -100)]);
+
+    node_pset_s(&mut matrix, "midip", 0, "prio", 2);
+    let (ch1, ch2) = node_exec.test_run(0.005, false, &events);
+
+    let changes = collect_signal_changes_flt(&ch1[..], 0.01);
+    assert_eq!(changes, vec![(10, 0.025), (25, 0.0)]);
+
+    let changes = collect_signal_changes_both_edges(&ch2[..], 1);
+    assert_eq!(changes, vec![(5, 100), (10, -100), (11, 100), (25, -100), (26, 100), (31, -100)]);
+}
+
+#[test]
+fn check_node_midip_legato() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_midip_freq_gate(&mut matrix, 0, 1);
+
+    let (ch1, ch2) = node_exec.test_run(
+        0.005,
+        false,
+        &[
+            HxTimedEvent::note_on(5, 0, 69, 1.0),
+            HxTimedEvent::note_on(10, 0, 72, 0.5),
+            HxTimedEvent::note_off(20, 0, 72),
+            HxTimedEvent::note_off(30, 0, 69),
+            // A new note after all notes were released retriggers:
+            HxTimedEvent::note_on(40, 0, 60, 1.0),
+        ],
+    );
+
+    let changes = collect_signal_changes_flt(&ch1[..], 0.01);
+    assert_eq!(changes, vec![(0, -0.575), (5, 0.0), (10, 0.025), (20, 0.0), (40, -0.075)]);
+
+    // The gate stays open while the notes overlap:
+    let changes = collect_signal_changes_both_edges(&ch2[..], 1);
+    assert_eq!(changes, vec![(5, 100), (31, -100), (40, 100)]);
+}
+
+#[test]
+fn check_node_midip_glide() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("midip", "freq")
+        .set_denorm("glide", 1.0)
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    let (ch1, _) = node_exec.test_run(
+        0.01,
+        false,
+        &[
+            // The first note does not glide:
+            HxTimedEvent::note_on(0, 0, 69, 1.0),
+            HxTimedEvent::note_on(100, 0, 81, 1.0),
+            HxTimedEvent::note_off(200, 0, 69),
+            HxTimedEvent::note_off(200, 0, 81),
+            HxTimedEvent::note_on(300, 0, 69, 1.0),
+        ],
+    );
+
+    // One octave up in 1ms (44.1 samples):
+    let step = 0.1 / 44.1;
+    assert_float_eq!(ch1[99], 0.0);
+    assert_float_eq!(ch1[121], 22.0 * step);
+    assert_float_eq!(ch1[144], 0.1);
+    // In retrigger mode the glide also applies after releasing all notes:
+    assert_float_eq!(ch1[299], 0.1);
+    assert_float_eq!(ch1[321], 0.1 - 22.0 * step);
+    assert_float_eq!(ch1[344], 0.0);
+
+    // In legato mode, only the overlapping notes glide:
+    node_pset_s(&mut matrix, "midip", 0, "legato", 1);
+    let (ch1, _) = node_exec.test_run(
+        0.01,
+        false,
+        &[
+            HxTimedEvent::note_on(100, 0, 81, 1.0),
+            HxTimedEvent::note_off(200, 0, 69),
+            HxTimedEvent::note_off(200, 0, 81),
+            HxTimedEvent::note_on(300, 0, 69, 1.0),
+        ],
+    );
+
+    assert_float_eq!(ch1[121], 22.0 * step);
+    assert_float_eq!(ch1[299], 0.1);
+    assert_float_eq!(ch1[300], 0.0);
+}
@@ -0,0 +1,118 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::nodes::MpeZone;
+
+/// Places two `MpeP` nodes, the first follows slot 0 and the second `slot2`.
+fn setup_mpep(matrix: &mut Matrix, out1: &str, out2: &str, slot2: i64) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("mpep", out1).node_inp("out", "ch1").place(matrix, 0, 0).unwrap();
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("mpep", out2).node_inp("out", "ch2").place(matrix, 1, 0).unwrap();
+
+    let mut cell = *matrix.get(1, 0).unwrap();
+    cell.set_node_id_keep_ios(NodeId::MpeP(1));
+    matrix.place(1, 0, cell);
+    matrix.sync().unwrap();
+    node_pset_s(matrix, "mpep", 1, "slot", slot2);
+}
+
+fn pitch(note: f32) -> f32 {
+    (note - 69.0) / 120.0
+}
+
+#[test]
+fn check_node_mpep_slots() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_mpep(&mut matrix, "gate", "freq", 1);
+
+    let events = vec![
+        HxTimedEvent::note_on(100, 1, 60, 1.0),
+        HxTimedEvent::note_on(200, 2, 64, 0.5),
+        HxTimedEvent::note_off(300, 1, 60),
+        HxTimedEvent::note_on(400, 3, 67, 1.0),
+        HxTimedEvent::note_off(500, 2, 64),
+        HxTimedEvent::note_off(600, 3, 67),
+    ];
+
+    // Without an MPE zone the nodes get no notes:
+    let (ch1, ch2) = node_exec.test_run(0.02, false, &events[..]);
+    assert!(ch1.iter().all(|s| *s == 0.0));
+    assert_float_eq!(ch2[250], pitch(0.0));
+
+    matrix.set_mpe_zone(Some(MpeZone::lower(15)));
+    let (ch1, ch2) = node_exec.test_run(0.02, false, &events[..]);
+
+    // The third note takes the first slot again:
+    assert_eq!(collect_gates(&ch1[..]), vec![(100, 201), (400, 201)]);
+    assert_float_eq!(ch2[199], pitch(0.0));
+    assert_float_eq!(ch2[250], pitch(64.0));
+    assert_float_eq!(ch2[550], pitch(64.0));
+}
+
+#[test]
+fn check_node_mpep_bend_slide() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_mpep(&mut matrix, "freq", "slide", 0);
+    matrix.set_mpe_zone(Some(MpeZone::lower(15)));
+
+    // The initial bend and slide are sent before the note on:
+    let events = vec![
+        HxTimedEvent::pitch_bend(10, 1, 0x3000),
+        HxTimedEvent::cc(10, 1, 74, 0.25),
+        HxTimedEvent::note_on(10, 1, 60, 1.0),
+        // Other channels don't change the note:
+        HxTimedEvent::pitch_bend(50, 2, 0x0000),
+        HxTimedEvent::cc(50, 2, 74, 1.0),
+        // The zone wide pitch bend on the master channel:
+        HxTimedEvent::pitch_bend(100, 0, 0x3000),
+        HxTimedEvent::cc(150, 1, 74, 0.5),
+        HxTimedEvent::pitch_bend(200, 1, 0x2000),
+    ];
+
+    let (ch1, ch2) = node_exec.test_run(0.01, false, &events[..]);
+
+    // The per note bend range is +/-48 semitones and the master bend range +/-2:
+    assert_float_eq!(ch1[20], pitch(60.0 + 24.0));
+    assert_float_eq!(ch1[60], pitch(60.0 + 24.0));
+    assert_float_eq!(ch1[120], pitch(60.0 + 24.0 + 1.0));
+    assert_float_eq!(ch1[220], pitch(60.0 + 1.0));
+    assert_float_eq!(ch2[20], 0.25);
+    assert_float_eq!(ch2[60], 0.25);
+    assert_float_eq!(ch2[160], 0.5);
+
+    node_pset_s(&mut matrix, "mpep", 0, "brange", 2);
+    node_pset_s(&mut matrix, "mpep", 0, "mrange", 0);
+    let (ch1, _) = node_exec.test_run(0.01, false, &[HxTimedEvent::pitch_bend(10, 1, 0x3000)]);
+    assert_float_eq!(ch1[20], pitch(61.0));
+}
+
+#[test]
+fn check_node_mpep_pressure() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_mpep(&mut matrix, "press", "vel", 1);
+    matrix.set_mpe_zone(Some(MpeZone::upper(4)));
+
+    let events = vec![
+        HxTimedEvent::note_on(10, 14, 60, 0.75),
+        HxTimedEvent::note_on(20, 13, 62, 0.5),
+        // Channel 0 is not part of the upper zone:
+        HxTimedEvent::note_on(30, 0, 64, 1.0),
+        HxTimedEvent::channel_pressure(40, 14, 0.3),
+        HxTimedEvent::poly_pressure(50, 14, 60, 0.6),
+        HxTimedEvent::channel_pressure(60, 13, 0.9),
+    ];
+
+    let (ch1, ch2) = node_exec.test_run(0.01, false, &events[..]);
+    assert_float_eq!(ch1[45], 0.3);
+    assert_float_eq!(ch1[70], 0.6);
+    assert_float_eq!(ch2[25], 0.5);
+    assert_float_eq!(ch2[70], 0.5);
+}
@@ -0,0 +1,47 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+#[test]
+fn check_node_out_bank() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 4, 4);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("inp", "sig1").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("inp", "sig2").node_inp("out", "ch1").place(&mut matrix, 2, 0).unwrap();
+    matrix.sync().unwrap();
+
+    // The second chain spawned a new Out node:
+    assert_eq!(matrix.get(2, 1).unwrap().node_id(), NodeId::Out(1));
+
+    pset_s(&mut matrix, NodeId::Out(1), "bank", 2);
+    pset_s(&mut matrix, NodeId::Out(1), "mono", 1);
+
+    let inputs = vec![vec![0.1; 512], vec![0.2; 512]];
+    let outputs = run_multichannel(&mut node_exec, &inputs[..], 6, 512);
+    assert_eq!(node_exec.input_channels(), 2);
+    assert_eq!(node_exec.output_channels(), 6);
+
+    assert_decimated_feq!(outputs[0], 1, vec![0.1; 512]);
+    assert_decimated_feq!(outputs[1], 1, vec![0.0; 512]);
+    assert_decimated_feq!(outputs[2], 1, vec![0.0; 512]);
+    assert_decimated_feq!(outputs[3], 1, vec![0.0; 512]);
+    // Out(1) in mono mode, sends it's ch1 input to both channels of bank 2:
+    assert_decimated_feq!(outputs[4], 1, vec![0.2; 512]);
+    assert_decimated_feq!(outputs[5], 1, vec![0.2; 512]);
+
+    // Banks beyond the channel count are not written:
+    pset_s(&mut matrix, NodeId::Out(1), "bank", 3);
+    let outputs = run_multichannel(&mut node_exec, &inputs[..], 6, 512);
+    assert_decimated_feq!(outputs[0], 1, vec![0.1; 512]);
+    for ch in 1..6 {
+        assert_decimated_feq!(outputs[ch], 1, vec![0.0; 512]);
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::dsp::NodeState;
+use hexodsp::nodes::NodeStates;
+use std::sync::Mutex;
+
+struct StateRecorder {
+    states: Mutex<Option<NodeStates>>,
+}
+
+impl MatrixObserver for StateRecorder {
+    fn update_prop(&self, _key: &str) {}
+    fn update_monitor(&self, _cell: &Cell) {}
+    fn update_param(&self, _param_id: &ParamId) {}
+    fn update_matrix(&self) {}
+    fn update_all(&self) {}
+    fn midi_event(&self, _midi_ev: HxMidiEvent) {}
+    fn node_states(&self, states: NodeStates) {
+        *self.states.lock().expect("recorder lock ok") = Some(states);
+    }
+}
+
+/// Places `Noise -> Delay -> Out` and a `TsLFO -> Ad -> Out` chain, which all
+/// have some internal state.
+fn setup_stateful(matrix: &mut Matrix) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("noise", "sig")
+        .node_io("delay", "inp", "sig")
+        .set_denorm("time", 30.0)
+        .set_denorm("fb", 0.5)
+        .node_inp("out", "ch1")
+        .place(matrix, 0, 0)
+        .unwrap();
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("tslfo", "sig")
+        .set_denorm("time", 13.0)
+        .node_io("ad", "trig", "sig")
+        .node_inp("out", "ch2")
+        .place(matrix, 1, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+}
+
+fn new_stateful_engine() -> (Matrix, NodeExecutor) {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    setup_stateful(&mut matrix);
+    node_exec.process_graph_updates();
+    (matrix, node_exec)
+}
+
+#[test]
+fn check_node_state_freeze_restore() {
+    let (_matrix, mut node_exec) = new_stateful_engine();
+
+    run_for_ms(&mut node_exec, 50.0);
+    let mut states = node_exec.save_node_states();
+    assert!(states.get(&NodeId::Delay(0)).is_some());
+    assert!(states.get(&NodeId::Ad(0)).is_some());
+
+    let after_save = run_for_ms(&mut node_exec, 50.0);
+    assert!(after_save.0.iter().any(|s| s.abs() > 0.1));
+    assert!(after_save.1.iter().any(|s| s.abs() > 0.1));
+
+    // Without restoring, the noise and LFO continue:
+    assert_ne!(after_save, run_for_ms(&mut node_exec, 50.0));
+
+    assert_eq!(node_exec.load_node_states(&mut states), states.len());
+    assert_eq!(after_save, run_for_ms(&mut node_exec, 50.0));
+}
+
+#[test]
+fn check_node_state_into_other_executor() {
+    let (matrix, mut node_exec) = new_stateful_engine();
+    run_for_ms(&mut node_exec, 50.0);
+    let mut states = node_exec.save_node_states();
+
+    // A second engine running the same patch continues from the saved states:
+    let (node_conf, mut node_exec2) = new_node_engine();
+    let mut matrix2 = Matrix::new(node_conf, 3, 3);
+    matrix2.from_repr(&matrix.to_repr()).unwrap();
+    node_exec2.process_graph_updates();
+    node_exec2.load_node_states(&mut states);
+
+    assert_eq!(run_for_ms(&mut node_exec, 50.0), run_for_ms(&mut node_exec2, 50.0));
+}
+
+#[test]
+fn check_node_state_load_swaps() {
+    let (_matrix, mut node_exec) = new_stateful_engine();
+    let (_matrix2, mut node_exec2) = new_stateful_engine();
+
+    let mut states = node_exec.save_node_states();
+    let first = run_for_ms(&mut node_exec, 50.0);
+    run_for_ms(&mut node_exec, 50.0);
+
+    assert_eq!(first, run_for_ms(&mut node_exec2, 50.0));
+    run_for_ms(&mut node_exec2, 50.0);
+
+    // Loading swaps the states, the replaced ones continue where the nodes left off:
+    node_exec.load_node_states(&mut states);
+    assert_eq!(first, run_for_ms(&mut node_exec, 50.0));
+    node_exec.load_node_states(&mut states);
+    assert_eq!(run_for_ms(&mut node_exec2, 50.0), run_for_ms(&mut node_exec, 50.0));
+}
+
+#[test]
+fn check_node_state_matrix_load() {
+    let (mut matrix, mut node_exec) = new_stateful_engine();
+
+    run_for_ms(&mut node_exec, 50.0);
+    let states = node_exec.save_node_states();
+    assert!(!states.is_empty());
+
+    let after_save = run_for_ms(&mut node_exec, 50.0);
+
+    matrix.load_node_states(states);
+    node_exec.process_graph_updates();
+    assert_eq!(after_save, run_for_ms(&mut node_exec, 50.0));
+}
+
+#[test]
+fn check_node_state_matrix_request() {
+    let (mut matrix, mut node_exec) = new_stateful_engine();
+    let (_matrix2, mut node_exec2) = new_stateful_engine();
+    let recorder = std::sync::Arc::new(StateRecorder { states: Mutex::new(None) });
+    matrix.set_observer(recorder.clone());
+
+    run_for_ms(&mut node_exec, 50.0);
+    run_for_ms(&mut node_exec2, 50.0);
+    matrix.request_node_states();
+    node_exec.process_graph_updates();
+    matrix.handle_graph_events();
+
+    let states = recorder.states.lock().unwrap().take().expect("node states were sent");
+    assert!(states.get(&NodeId::TsLFO(0)).is_some());
+    assert!(states.get(&NodeId::Ad(0)).is_some());
+    // The delay line is too big to be copied on the audio thread:
+    assert!(states.get(&NodeId::Delay(0)).is_none());
+
+    // Copying the states does not disturb the running nodes:
+    let after_save = run_for_ms(&mut node_exec, 50.0);
+    assert_eq!(after_save, run_for_ms(&mut node_exec2, 50.0));
+
+    matrix.load_node_states(states);
+    node_exec.process_graph_updates();
+    assert_eq!(after_save.1, run_for_ms(&mut node_exec, 50.0).1);
+}
+
+#[test]
+fn check_node_state_tseq() {
+    use hexodsp::dsp::tracker::UIPatternModel;
+
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .set_denorm("freq", 100.0)
+        .node_io("tseq", "clock", "trk1")
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    let pat = matrix.get_pattern_data(0).unwrap();
+    {
+        let mut pr = pat.lock().unwrap();
+        pr.set_rows(16);
+        pr.set_col_gate_type(0);
+        for row in 0..16 {
+            pr.set_cell_value(row, 0, 0x7FF);
+        }
+    }
+    for _ in 0..10 {
+        matrix.check_pattern_data(0);
+    }
+
+    run_for_ms(&mut node_exec, 100.0);
+    let mut states = node_exec.save_node_states();
+    assert!(states.get(&NodeId::TSeq(0)).is_some());
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 300.0);
+    let gates = collect_gates(&ch1[..]);
+    assert!(!gates.is_empty());
+
+    node_exec.load_node_states(&mut states);
+    let (ch1, _) = run_for_ms(&mut node_exec, 300.0);
+    assert_eq!(gates, collect_gates(&ch1[..]));
+}
+
+/// Processes `nframes` at the current sample rate of the `node_exec` and
+/// returns the first output channel.
+fn process_frames(node_exec: &mut NodeExecutor, nframes: usize) -> Vec<f32> {
+    let input = [0.0; MAX_BLOCK_SIZE];
+    let mut ch1 = vec![];
+    while ch1.len() < nframes {
+        let mut out_l = [0.0; MAX_BLOCK_SIZE];
+        let mut out_r = [0.0; MAX_BLOCK_SIZE];
+        let mut context = hexodsp::Context {
+            nframes: MAX_BLOCK_SIZE,
+            output: &mut [&mut out_l[..], &mut out_r[..]],
+            input: &[&input[..], &input[..]],
+        };
+        node_exec.process(&mut context);
+        ch1.extend_from_slice(&out_l[..]);
+    }
+    ch1.truncate(nframes);
+    ch1
+}
+
+#[test]
+fn check_node_state_other_sample_rate() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .set_denorm("freq", 440.0)
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    run_for_ms(&mut node_exec, 10.0);
+    let mut states = node_exec.save_node_states();
+
+    // The sine saved at 44.1kHz keeps it's frequency at 96kHz:
+    let (node_conf, mut node_exec2) = new_node_engine();
+    let mut matrix2 = Matrix::new(node_conf, 3, 3);
+    matrix2.from_repr(&matrix.to_repr()).unwrap();
+    node_exec2.set_sample_rate(96000.0);
+    node_exec2.process_graph_updates();
+    assert_eq!(node_exec2.load_node_states(&mut states), 1);
+
+    let ch1 = process_frames(&mut node_exec2, 9600);
+    assert_eq!(count_zero_crossings(&ch1[..]), 44);
+}
+
+#[test]
+fn check_node_state_mismatch() {
+    let (_matrix, mut node_exec) = new_stateful_engine();
+
+    // States of other node types or other patches are ignored:
+    let mut states = NodeStates::new();
+    states.insert(NodeId::Delay(0), NodeState::new(0.5_f32));
+    states.insert(NodeId::Sin(3), NodeState::new(0.5_f32));
+    assert_eq!(node_exec.load_node_states(&mut states), 0);
+}
@@ -5,6 +5,8 @@
 mod common;
 use common::*;
 
+use hexodsp::nodes::HostTransport;
+
 #[test]
 fn check_node_tslfo_1() {
     let (node_conf, mut node_exec) = new_node_engine();
@@ -120,3 +122,42 @@ fn check_node_tslfo_trig_slopes() {
         ]
     );
 }
+
+#[test]
+fn check_node_tslfo_host_sync() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let tsl = NodeId::TsLFO(0);
+    let out = NodeId::Out(0);
+    matrix.place(0, 0, Cell::empty(tsl).out(None, None, tsl.out("sig")));
+    matrix.place(0, 1, Cell::empty(out).input(out.inp("ch1"), None, None));
+    // Sync to 1/4 notes:
+    pset_s(&mut matrix, tsl, "sync", 7);
+    matrix.sync().unwrap();
+
+    let mut transport = HostTransport::new();
+    transport.tempo = 120.0;
+    transport.playing = true;
+    node_exec.set_transport(transport);
+
+    // One beat at 120 BPM is 500ms, the LFO is a triangle by default:
+    let (out_l, _) = run_for_ms(&mut node_exec, 1000.0);
+    assert_decimated_feq!(out_l, 11025, vec![0.0, 1.0, 0.0, 1.0]);
+    assert_float_eq!(out_l[5512], 0.49995);
+    assert_float_eq!(node_exec.transport().song_pos_beats as f32, 2.0);
+
+    // Jumping in the song moves the LFO phase along:
+    transport.song_pos_beats = 0.5;
+    node_exec.set_transport(transport);
+    let (out_l, _) = run_for_ms(&mut node_exec, 10.0);
+    assert_float_eq!(out_l[0], 1.0);
+
+    // The LFO stands still if the host transport is stopped:
+    transport.playing = false;
+    transport.song_pos_beats = 0.25;
+    node_exec.set_transport(transport);
+    let (out_l, _) = run_for_ms(&mut node_exec, 10.0);
+    assert_float_eq!(out_l[0], 0.5);
+    assert_float_eq!(out_l[440], 0.5);
+}
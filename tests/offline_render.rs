@@ -0,0 +1,147 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::offline_render::*;
+
+fn setup_sine_matrix() -> (Matrix, NodeExecutor) {
+    let (node_conf, node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("sin", "sig").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+
+    (matrix, node_exec)
+}
+
+#[test]
+fn check_offline_render_length_and_params() {
+    let (matrix, node_exec) = setup_sine_matrix();
+
+    let mut renderer = OfflineRenderer::from_matrix(matrix, node_exec);
+    renderer.set_sample_rate(48000.0);
+    renderer.set_length(RenderLength::Seconds(1.0));
+
+    let freq = NodeId::Sin(0).inp_param("freq").unwrap();
+    renderer.add_param_event(24000, freq, SAtom::param(freq.norm(880.0)));
+
+    let (l, r) = renderer.render();
+    assert_eq!(l.len(), 48000);
+    assert_eq!(r.len(), 48000);
+
+    // The first half runs at 440Hz, the second half at 880Hz (minus the smoothing time):
+    assert_eq!(count_zero_crossings(&l[0..24000]), 220);
+    let crossings = count_zero_crossings(&l[24000..48000]);
+    assert!(crossings > 430 && crossings <= 440, "crossings={}", crossings);
+}
+
+#[test]
+fn check_offline_render_midi_frame_accurate() {
+    let (node_conf, node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midip", "gate").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+
+    let mut renderer = OfflineRenderer::from_matrix(matrix, node_exec);
+    renderer.set_length(RenderLength::Frames(1000));
+    renderer.add_midi_event(300, HxMidiEvent::NoteOn { channel: 0, note: 60, vel: 1.0 });
+    renderer.add_midi_event(700, HxMidiEvent::NoteOff { channel: 0, note: 60 });
+    // A parameter event in between splits the block:
+    renderer.add_param_event(650, NodeId::MidiP(0).inp_param("det").unwrap(), SAtom::param(0.0));
+
+    let (l, _r) = renderer.render();
+    assert_eq!(l.len(), 1000);
+    assert_eq!(collect_signal_changes(&l[..], -1), vec![(300, 100), (701, 0)]);
+}
+
+#[test]
+fn check_offline_render_param_ramp() {
+    let (node_conf, node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    matrix.place(0, 0, Cell::empty(NodeId::Out(0)));
+    matrix.sync().unwrap();
+
+    let ch1 = NodeId::Out(0).inp_param("ch1").unwrap();
+
+    let mut renderer = OfflineRenderer::from_matrix(matrix, node_exec);
+    renderer.set_length(RenderLength::Frames(1000));
+    renderer.add_event(300, RenderEvent::ParamRamp(ch1, SAtom::param(1.0), 200));
+    renderer.add_event(800, RenderEvent::ParamRamp(ch1, SAtom::param(0.0), 0));
+
+    let (l, _r) = renderer.render();
+    assert_float_eq!(l[299], 0.0);
+    assert_float_eq!(l[300], 0.005);
+    assert_float_eq!(l[399], 0.5);
+    assert_float_eq!(l[499], 1.0);
+    assert_float_eq!(l[799], 1.0);
+    assert_float_eq!(l[800], 0.0);
+}
+
+#[test]
+fn check_offline_render_until_silence() {
+    let (node_conf, node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("midip", "gate")
+        .node_io("ad", "trig", "sig")
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    let mut renderer = OfflineRenderer::from_matrix(matrix, node_exec);
+    renderer.set_length(RenderLength::UntilSilence {
+        threshold: 0.0001,
+        hold_ms: 10.0,
+        max_seconds: 5.0,
+    });
+    let note_on_frame = renderer.ms_to_frames(100.0);
+    renderer.add_midi_event(note_on_frame, HxMidiEvent::NoteOn { channel: 0, note: 60, vel: 1.0 });
+
+    let (l, _r) = renderer.render();
+
+    // The Ad envelope has 3ms attack and 10ms decay by default:
+    let len_ms = (l.len() as f32 * 1000.0) / 44100.0;
+    assert!(len_ms > 120.0 && len_ms < 130.0, "len_ms={}", len_ms);
+
+    assert!(l[0..note_on_frame].iter().all(|s| *s == 0.0));
+    let max = l[note_on_frame..].iter().fold(0.0_f32, |a, s| a.max(*s));
+    assert!(max > 0.9);
+}
+
+#[test]
+fn check_offline_render_from_repr_to_wav() {
+    let (matrix, _node_exec) = setup_sine_matrix();
+    let repr = matrix.to_repr();
+
+    let mut renderer = OfflineRenderer::from_repr(&repr).unwrap();
+    renderer.set_sample_rate(22050.0);
+    renderer.set_length(RenderLength::Frames(2205));
+    renderer.set_wav_format(WavFormat::Int16);
+
+    let frames = renderer.render_to_wav("check_offline_render.wav").unwrap();
+    assert_eq!(frames, 2205);
+
+    let mut rd = hound::WavReader::open("check_offline_render.wav").unwrap();
+    let spec = rd.spec();
+    assert_eq!(spec.channels, 2);
+    assert_eq!(spec.sample_rate, 22050);
+    assert_eq!(spec.bits_per_sample, 16);
+
+    let samples = rd.samples::<i16>().map(|s| s.unwrap()).collect::<Vec<i16>>();
+    assert_eq!(samples.len(), 2 * 2205);
+
+    let left = samples.iter().step_by(2).map(|s| *s as f32 / 32767.0).collect::<Vec<f32>>();
+    // 440Hz for 100ms, the sine starts at 0.0 so the first upward crossing is missing:
+    assert_eq!(count_zero_crossings(&left[..]), 43);
+    let max = left.iter().fold(0.0_f32, |a, s| a.max(*s));
+    assert!(max > 0.99);
+}
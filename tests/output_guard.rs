@@ -0,0 +1,161 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::nodes::SignalFault;
+use hexodsp::{DynNode1x1Context, DynamicNode1x1};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+struct FaultRecorder {
+    faults: Mutex<Vec<(NodeId, SignalFault)>>,
+}
+
+impl MatrixObserver for FaultRecorder {
+    fn update_prop(&self, _key: &str) {}
+    fn update_monitor(&self, _cell: &Cell) {}
+    fn update_param(&self, _param_id: &ParamId) {}
+    fn update_matrix(&self) {}
+    fn update_all(&self) {}
+    fn midi_event(&self, _midi_ev: HxMidiEvent) {}
+    fn node_fault(&self, node_id: NodeId, fault: SignalFault) {
+        self.faults.lock().expect("recorder lock ok").push((node_id, fault));
+    }
+}
+
+/// Outputs `bad_value` until it is reset, and `0.5` afterwards.
+struct BlowUpNode {
+    bad_value: f32,
+    broken: bool,
+    resets: Arc<AtomicUsize>,
+}
+
+impl DynamicNode1x1 for BlowUpNode {
+    fn reset(&mut self) {
+        self.broken = false;
+        self.resets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn process(&mut self, _input: &[f32], output: &mut [f32], _ctx: &DynNode1x1Context) {
+        let v = if self.broken { self.bad_value } else { 0.5 };
+        output.fill(v);
+    }
+}
+
+fn setup_blow_up(
+    bad_value: f32,
+    guard: Option<f32>,
+) -> (Matrix, NodeExecutor, Arc<FaultRecorder>, Arc<AtomicUsize>) {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    let recorder = Arc::new(FaultRecorder { faults: Mutex::new(vec![]) });
+    matrix.set_observer(recorder.clone());
+    matrix.set_output_guard(guard);
+
+    let resets = Arc::new(AtomicUsize::new(0));
+    matrix.set_dynamic_node1x1(
+        0,
+        Box::new(BlowUpNode { bad_value, broken: true, resets: resets.clone() }),
+    );
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("rust1x1", "sig").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+
+    // Let the dynamic node arrive at the audio thread:
+    node_exec.process_graph_updates();
+    resets.store(0, Ordering::Relaxed);
+
+    (matrix, node_exec, recorder, resets)
+}
+
+#[test]
+fn check_output_guard_nan() {
+    let (mut matrix, mut node_exec, recorder, resets) = setup_blow_up(f32::NAN, Some(1.0));
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+    matrix.handle_graph_events();
+
+    assert!(ch1.iter().all(|s| s.is_finite()));
+    // Silenced in the block of the fault, afterwards the reset node works again:
+    assert_float_eq!(ch1[0], 0.0);
+    assert_float_eq!(ch1[200], 0.5);
+    assert_eq!(resets.load(Ordering::Relaxed), 1);
+
+    let faults = recorder.faults.lock().unwrap().clone();
+    assert_eq!(faults, vec![(NodeId::Rust1x1(0), SignalFault::NotFinite)]);
+}
+
+#[test]
+fn check_output_guard_runaway() {
+    let (mut matrix, mut node_exec, recorder, resets) = setup_blow_up(1e6, Some(1.0));
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+    matrix.handle_graph_events();
+
+    assert!(ch1.iter().all(|s| s.abs() <= 1.0));
+    assert_eq!(resets.load(Ordering::Relaxed), 1);
+
+    let faults = recorder.faults.lock().unwrap().clone();
+    assert_eq!(faults, vec![(NodeId::Rust1x1(0), SignalFault::Runaway)]);
+}
+
+#[test]
+fn check_output_guard_out_limit() {
+    // 4.0 is loud, but not a runaway signal:
+    let (mut matrix, mut node_exec, recorder, resets) = setup_blow_up(4.0, Some(0.8));
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+    matrix.handle_graph_events();
+
+    assert_float_eq!(ch1[0], 0.8);
+    assert_float_eq!(ch1[400], 0.8);
+    assert_eq!(resets.load(Ordering::Relaxed), 0);
+    assert!(recorder.faults.lock().unwrap().is_empty());
+}
+
+#[test]
+fn check_output_guard_disabled() {
+    let (mut matrix, mut node_exec, recorder, resets) = setup_blow_up(4.0, None);
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+    matrix.handle_graph_events();
+
+    assert_float_eq!(ch1[0], 4.0);
+    assert_eq!(resets.load(Ordering::Relaxed), 0);
+    assert!(recorder.faults.lock().unwrap().is_empty());
+}
+
+#[test]
+fn check_output_guard_out_not_finite() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    matrix.set_output_guard(Some(1.0));
+
+    let out = NodeId::Out(0);
+    matrix.place(0, 0, Cell::empty(out));
+    matrix.sync().unwrap();
+
+    // The parameter input of the Out node is not checked by the node guard:
+    matrix.set_param(out.inp_param("ch1").unwrap(), SAtom::param(f32::NAN));
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+
+    assert!(ch1.iter().all(|s| *s == 0.0));
+}
+
+#[test]
+fn check_output_guard_invalid_limit() {
+    let (_matrix, mut node_exec, _recorder, _resets) = setup_blow_up(4.0, Some(-0.8));
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+    assert_float_eq!(ch1[0], 0.8);
+    assert_float_eq!(ch1[400], 0.8);
+
+    // A NaN limit does not limit at all:
+    let (_matrix, mut node_exec, _recorder, _resets) = setup_blow_up(4.0, Some(f32::NAN));
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+    assert_float_eq!(ch1[0], 4.0);
+    assert_float_eq!(ch1[400], 4.0);
+}
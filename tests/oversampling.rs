@@ -0,0 +1,156 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+fn setup_sin_amp_out() -> (Matrix, NodeExecutor) {
+    let (node_conf, node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .node_io("amp", "inp", "sig")
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    (matrix, node_exec)
+}
+
+#[test]
+fn check_oversampling_factors() {
+    let (mut matrix, _node_exec) = setup_sin_amp_out();
+
+    let amp = NodeId::Amp(0);
+    assert_eq!(matrix.oversampling(&amp), 1);
+    matrix.set_oversampling(amp, 4).unwrap();
+    assert_eq!(matrix.oversampling(&amp), 4);
+
+    // Unsupported factors are ignored:
+    matrix.set_oversampling(amp, 3).unwrap();
+    assert_eq!(matrix.oversampling(&amp), 4);
+
+    matrix.set_oversampling(amp, 1).unwrap();
+    assert_eq!(matrix.oversampling(&amp), 1);
+}
+
+#[test]
+fn check_oversampling_group_passes_signal() {
+    let (mut matrix, mut node_exec) = setup_sin_amp_out();
+
+    matrix.set_oversampling(NodeId::Amp(0), 4).unwrap();
+    let (ch1, _) = run_for_ms(&mut node_exec, 100.0);
+
+    assert_eq!(count_zero_crossings(&ch1), 44);
+    let peak = max_abs(&ch1[1000..4000]);
+    assert!(peak > 0.95 && peak < 1.05, "peak={}", peak);
+}
+
+#[test]
+fn check_oversampling_node_sample_rate() {
+    let (mut matrix, mut node_exec) = setup_sin_amp_out();
+
+    // The oscillator inside the group has to run at the multiplied
+    // sample rate to keep it's frequency:
+    matrix.set_oversampling(NodeId::Sin(0), 8).unwrap();
+    matrix.set_oversampling(NodeId::Amp(0), 8).unwrap();
+    let freq = run_and_get_counted_freq(&mut node_exec, 1000.0);
+    assert!((freq - 440.0).abs() < 1.0, "freq={}", freq);
+
+    // And back to the normal rate:
+    matrix.set_oversampling(NodeId::Sin(0), 1).unwrap();
+    matrix.set_oversampling(NodeId::Amp(0), 1).unwrap();
+    let freq = run_and_get_counted_freq(&mut node_exec, 1000.0);
+    assert!((freq - 440.0).abs() < 1.0, "freq={}", freq);
+}
+
+fn run_clipped_sine(factor: usize) -> Vec<f32> {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .set_denorm("freq", 4700.0)
+        .node_io("map", "inp", "sig")
+        .set_denorm("imin", -0.05)
+        .set_denorm("imax", 0.05)
+        .set_atom("clip", SAtom::setting(1))
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+    matrix.set_oversampling(NodeId::Map(0), factor).unwrap();
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 200.0);
+    ch1
+}
+
+#[test]
+fn check_oversampling_reduces_aliasing() {
+    // Hard clipping the sine produces harmonics far above nyquist,
+    // which fold back below the fundamental without oversampling:
+    let alias_energy = |mut buf: Vec<f32>| {
+        let spec = fft(&mut buf[4410..], FFT::F4096, 0);
+        spec.iter().filter(|(f, _)| *f > 100 && *f < 4000).map(|(_, a)| *a as u64).sum::<u64>()
+    };
+
+    let plain = alias_energy(run_clipped_sine(1));
+    let oversampled = alias_energy(run_clipped_sine(8));
+
+    assert!(plain > 4 * oversampled, "plain={} oversampled={}", plain, oversampled);
+}
+
+#[test]
+fn check_oversampling_with_worker_threads() {
+    let run = |threads: usize| {
+        let (mut matrix, mut node_exec) = setup_sin_amp_out();
+        matrix.set_worker_threads(threads);
+        matrix.set_oversampling(NodeId::Amp(0), 2).unwrap();
+        run_for_ms(&mut node_exec, 50.0).0
+    };
+
+    let single = run(0);
+    assert!(max_abs(&single[1000..2000]) > 0.95);
+    assert!(single == run(2), "multi threaded output differs");
+}
+
+#[test]
+fn check_oversampling_prog_crossfade() {
+    let (mut matrix, mut node_exec) = setup_sin_amp_out();
+    matrix.set_prog_crossfade(20.0);
+    matrix.set_oversampling(NodeId::Amp(0), 4).unwrap();
+    run_for_ms(&mut node_exec, 50.0);
+
+    // The group is shared with the new program while the old one fades out:
+    let sin = NodeId::Sin(1);
+    matrix.place(1, 0, Cell::empty(sin).out(None, None, sin.out("sig")));
+    matrix.sync().unwrap();
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 50.0);
+    for chunk in ch1.chunks(441) {
+        let peak = max_abs(chunk);
+        assert!(peak > 0.95 && peak < 1.05, "peak={}", peak);
+    }
+}
+
+#[test]
+fn check_oversampling_midi_node_stays_at_normal_rate() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("midip", "gate").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.set_oversampling(NodeId::MidiP(0), 4).unwrap();
+    matrix.sync().unwrap();
+
+    // The MIDI events are timed at the normal sample rate, so the gate
+    // opens exactly at the frame of the note on event:
+    let events = vec![HxTimedEvent::note_on(100, 0, 69, 1.0)];
+    let (ch1, _) = node_exec.test_run(0.01, false, &events[..]);
+    assert_eq!(collect_signal_changes_both_edges(&ch1[..], 1), vec![(100, 100)]);
+}
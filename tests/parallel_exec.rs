@@ -0,0 +1,146 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::build::*;
+use hexodsp::nodes::{GraphEvent, NodeEvent, PolyVoices, VoiceStealing, MAX_WORKER_THREADS};
+use hexodsp::synth_constructor::SynthConstructor;
+
+fn run_poly_synth(worker_threads: usize) -> (Vec<f32>, Vec<f32>) {
+    let midip = midip(0);
+    let osc = bosc(0).input().freq(&midip.output().freq());
+    let filt = sfilter(0).input().inp(&osc.output().sig()).set().ftype(4);
+    let voice = amp(0).input().inp(&filt.output().sig()).input().att(&midip.output().gate());
+
+    let voices = PolyVoices::new(6);
+    let mix = voices.mix(&voice.output().sig(), 0).unwrap();
+
+    let mut sc = SynthConstructor::new();
+    sc.set_voice_allocation(voices.voices(), VoiceStealing::Oldest);
+    sc.set_worker_threads(worker_threads);
+    sc.upload(&out(0).input().ch1(&mix).input().ch2(&voice.output().sig())).unwrap();
+
+    let mut exec = sc.executor().unwrap();
+    exec.process_graph_updates();
+
+    let mut events = vec![];
+    for (i, note) in [48, 52, 55, 60, 64, 67].iter().enumerate() {
+        events.push(HxTimedEvent::note_on(i * 300 + 10, 0, *note, 1.0));
+    }
+    events.push(HxTimedEvent::note_off(3000, 0, 52));
+
+    exec.test_run(0.1, false, &events[..])
+}
+
+#[test]
+fn check_parallel_exec_same_output() {
+    let (ch1_single, ch2_single) = run_poly_synth(0);
+    let (ch1_par, ch2_par) = run_poly_synth(3);
+
+    assert!(max_abs(&ch1_single[2000..4000]) > 0.1);
+    assert!(ch1_single == ch1_par, "multi threaded output differs");
+    assert!(ch2_single == ch2_par, "multi threaded output differs");
+}
+
+#[test]
+fn check_parallel_exec_late_workers() {
+    // More workers than cores, some of them are late and the
+    // audio thread processes their ops:
+    let (ch1_single, ch2_single) = run_poly_synth(0);
+    let (ch1_par, ch2_par) = run_poly_synth(MAX_WORKER_THREADS);
+
+    assert!(ch1_single == ch1_par, "multi threaded output differs");
+    assert!(ch2_single == ch2_par, "multi threaded output differs");
+}
+
+#[test]
+fn check_parallel_exec_matrix() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .node_io("amp", "inp", "sig")
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    // Independent nodes on the same level as the chain:
+    let (sin, amp) = (NodeId::Sin(1), NodeId::Amp(1));
+    matrix.place(1, 0, Cell::empty(sin).out(None, None, sin.out("sig")));
+    matrix.place(1, 1, Cell::empty(amp).input(amp.inp("inp"), None, None));
+    matrix.set_worker_threads(2);
+    matrix.sync().unwrap();
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 100.0);
+    let crossings = ch1.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+    assert_eq!(crossings, 44);
+    assert!(max_abs(&ch1[0..4000]) > 0.99);
+
+    // Switching back to single threaded execution:
+    matrix.set_worker_threads(0);
+    let (ch1, _) = run_for_ms(&mut node_exec, 100.0);
+    assert!(max_abs(&ch1[0..4000]) > 0.99);
+}
+
+#[test]
+fn check_parallel_exec_cpu_profiling() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .node_io("pverb", "in_l", "sig_l")
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    // An independent signal path:
+    let (sin, pverb) = (NodeId::Sin(1), NodeId::PVerb(1));
+    matrix.place(1, 0, Cell::empty(sin).out(None, None, sin.out("sig")));
+    matrix.place(1, 1, Cell::empty(pverb).input(pverb.inp("in_l"), None, None));
+    matrix.set_worker_threads(2);
+    matrix.set_cpu_profiling(true);
+    matrix.sync().unwrap();
+
+    node_exec.test_run(0.3, false, &[]);
+
+    // The nodes report their load, no matter which thread processed them:
+    for node_id in [NodeId::Sin(0), NodeId::Sin(1), NodeId::PVerb(0), NodeId::PVerb(1)] {
+        let load = matrix.node_cpu_load(&node_id);
+        assert!(load > 0.0 && load.is_finite(), "{:?} load={}", node_id, load);
+    }
+}
+
+#[test]
+fn check_parallel_exec_node_events() {
+    // Two independent envelopes with a 10Hz gate on the same level:
+    let gate = sin(0).set().freq(10.0);
+    let env1 =
+        adsr(0).set().atk(1.0).set().dcy(1.0).set().rel(5.0).input().gate(&gate.output().sig());
+    let env2 =
+        adsr(1).set().atk(1.0).set().dcy(1.0).set().rel(5.0).input().gate(&gate.output().sig());
+
+    let mut sc = SynthConstructor::new();
+    sc.set_worker_threads(2);
+    sc.upload(&out(0).input().ch1(&env1.output().sig()).input().ch2(&env2.output().sig())).unwrap();
+
+    let mut exec = sc.executor().unwrap();
+    exec.process_graph_updates();
+    exec.test_run(0.3, false, &[]);
+
+    // The events emitted on the worker threads arrive at the frontend too:
+    let mut events = vec![];
+    while let Some(ev) = sc.next_event() {
+        if let GraphEvent::Node(node_id, ev) = ev {
+            events.push((node_id, ev));
+        }
+    }
+    for node_id in [NodeId::Adsr(0), NodeId::Adsr(1)] {
+        let count = events.iter().filter(|e| **e == (node_id, NodeEvent::EnvEnd)).count();
+        assert_eq!(count, 3, "{:?}", node_id);
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+fn setup_out() -> (Matrix, NodeExecutor) {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let out = NodeId::Out(0);
+    matrix.place(0, 0, Cell::empty(out));
+    matrix.sync().unwrap();
+
+    run_for_ms(&mut node_exec, 10.0);
+
+    (matrix, node_exec)
+}
+
+#[test]
+fn check_param_at_jump() {
+    let (mut matrix, mut node_exec) = setup_out();
+
+    let out = NodeId::Out(0);
+    let now = matrix.audio_clock();
+    matrix.set_param_at(out.inp_param("ch1").unwrap(), SAtom::param(0.5), now + 100, 0);
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+
+    // No smoothing, the value changes exactly at frame 100:
+    assert_float_eq!(ch1[99], 0.0);
+    assert_float_eq!(ch1[100], 0.5);
+    assert_float_eq!(ch1[400], 0.5);
+    assert_float_eq!(matrix.get_param(&out.inp_param("ch1").unwrap()).unwrap().f(), 0.5);
+}
+
+#[test]
+fn check_param_at_ramp() {
+    let (mut matrix, mut node_exec) = setup_out();
+
+    let out = NodeId::Out(0);
+    let now = matrix.audio_clock();
+    matrix.set_param_at(out.inp_param("ch1").unwrap(), SAtom::param(1.0), now + 100, 100);
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+
+    assert_float_eq!(ch1[99], 0.0);
+    assert_float_eq!(ch1[100], 0.01);
+    // Crosses the block boundary at frame 128:
+    assert_float_eq!(ch1[127], 0.28);
+    assert_float_eq!(ch1[128], 0.29);
+    assert_float_eq!(ch1[149], 0.5);
+    assert_float_eq!(ch1[199], 1.0);
+    assert_float_eq!(ch1[200], 1.0);
+    assert_float_eq!(ch1[400], 1.0);
+}
+
+#[test]
+fn check_param_at_later_blocks_and_order() {
+    let (mut matrix, mut node_exec) = setup_out();
+
+    let out = NodeId::Out(0);
+    let ch1_param = out.inp_param("ch1").unwrap();
+    let now = matrix.audio_clock();
+    // Sent out of order, they are applied sorted by frame:
+    matrix.set_param_at(ch1_param, SAtom::param(-0.5), now + 300, 0);
+    matrix.set_param_at(ch1_param, SAtom::param(0.25), now + 5, 0);
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+
+    assert_float_eq!(ch1[4], 0.0);
+    assert_float_eq!(ch1[5], 0.25);
+    assert_float_eq!(ch1[299], 0.25);
+    assert_float_eq!(ch1[300], -0.5);
+    assert_float_eq!(ch1[440], -0.5);
+}
+
+#[test]
+fn check_param_at_atom_at_block_start() {
+    let (mut matrix, mut node_exec) = setup_out();
+
+    let out = NodeId::Out(0);
+    matrix.set_param(out.inp_param("ch1").unwrap(), SAtom::param(0.5));
+    run_for_ms(&mut node_exec, 10.0);
+
+    let now = matrix.audio_clock();
+    matrix.set_param_at(out.inp_param("mono").unwrap(), SAtom::setting(1), now + 200, 0);
+
+    let (ch1, ch2) = run_for_ms(&mut node_exec, 10.0);
+
+    assert_float_eq!(ch1[0], 0.5);
+    // The atom is set at the start of the block containing frame 200:
+    assert_float_eq!(ch2[127], 0.0);
+    assert_float_eq!(ch2[128], 0.5);
+    assert_float_eq!(ch2[300], 0.5);
+}
+
+#[test]
+fn check_param_at_passed_frame() {
+    let (mut matrix, mut node_exec) = setup_out();
+
+    let out = NodeId::Out(0);
+    let now = matrix.audio_clock();
+    run_for_ms(&mut node_exec, 10.0);
+
+    // The frame already passed, so it's applied at the start of the next buffer period:
+    matrix.set_param_at(out.inp_param("ch1").unwrap(), SAtom::param(0.5), now + 100, 0);
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+
+    assert_float_eq!(ch1[0], 0.5);
+    assert_float_eq!(ch1[100], 0.5);
+}
@@ -0,0 +1,76 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+fn setup_sin_out(crossfade_ms: f32) -> (Matrix, NodeExecutor) {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    matrix.set_prog_crossfade(crossfade_ms);
+
+    let sin = NodeId::Sin(0);
+    let out = NodeId::Out(0);
+    matrix.place(0, 0, Cell::empty(sin).out(None, None, sin.out("sig")));
+    matrix.place(0, 1, Cell::empty(out).input(out.inp("ch1"), None, None));
+    matrix.sync().unwrap();
+
+    run_for_ms(&mut node_exec, 50.0);
+
+    (matrix, node_exec)
+}
+
+#[test]
+fn check_prog_crossfade_disabled() {
+    let (mut matrix, mut node_exec) = setup_sin_out(0.0);
+
+    matrix.place(0, 0, Cell::empty(NodeId::Nop));
+    matrix.sync().unwrap();
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 20.0);
+    assert_float_eq!(max_abs(&ch1[..]), 0.0);
+}
+
+#[test]
+fn check_prog_crossfade_removed_node() {
+    let (mut matrix, mut node_exec) = setup_sin_out(10.0);
+
+    matrix.place(0, 0, Cell::empty(NodeId::Nop));
+    matrix.sync().unwrap();
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 20.0);
+
+    // 10ms are 441 samples at 44.1kHz, the old sine fades out linearly:
+    assert!(max_abs(&ch1[0..50]) > 0.8);
+    assert!(max_abs(&ch1[200..250]) < 0.6);
+    assert!(max_abs(&ch1[200..250]) > 0.3);
+    assert!(max_abs(&ch1[400..441]) < 0.1);
+    assert_float_eq!(max_abs(&ch1[441..]), 0.0);
+}
+
+#[test]
+fn check_prog_crossfade_shared_node_processed_once() {
+    let (mut matrix, mut node_exec) = setup_sin_out(10.0);
+
+    // Route the sine through an Amp with unity gain:
+    let amp = NodeId::Amp(0);
+    matrix.place(
+        0,
+        1,
+        Cell::empty(amp).input(amp.inp("inp"), None, None).out(None, None, amp.out("sig")),
+    );
+    let out = NodeId::Out(0);
+    matrix.place(0, 2, Cell::empty(out).input(out.inp("ch1"), None, None));
+    matrix.sync().unwrap();
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 100.0);
+
+    // The Sin node is part of both programs, but must only be executed once per
+    // block. Otherwise it's phase would advance twice as fast during the crossfade:
+    let crossings = ch1.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+    assert!(crossings == 43 || crossings == 44, "crossings={}", crossings);
+
+    // Both programs output the same signal, so the crossfade does not change the amplitude:
+    assert!(max_abs(&ch1[0..441]) > 0.99);
+}
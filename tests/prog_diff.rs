@@ -0,0 +1,144 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::build::*;
+use hexodsp::synth_constructor::SynthConstructor;
+
+fn place_sin_amp(matrix: &mut Matrix, x: usize) {
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("sin", "sig").node_inp("amp", "inp").place(matrix, x, 0).unwrap();
+}
+
+/// Runs an oversampled `Sin -> Amp -> Out` chain for 20ms and applies the `edit`
+/// to the matrix after 10ms. Returns the output and whether the [NodeProg]
+/// was replaced by the edit.
+fn run_with_edit(
+    setup: &dyn Fn(&mut Matrix),
+    edit: &dyn Fn(&mut Matrix),
+) -> (Vec<f32>, bool, Matrix) {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .node_io("amp", "inp", "sig")
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    setup(&mut matrix);
+    matrix.sync().unwrap();
+    // The filters of the oversampling group have a state that must be preserved:
+    matrix.set_oversampling(NodeId::Amp(0), 4).unwrap();
+
+    let (mut out, _) = run_for_ms(&mut node_exec, 10.0);
+    let prog_id = node_exec.get_prog().unique_id;
+
+    edit(&mut matrix);
+    matrix.sync().unwrap();
+
+    let (out2, _) = run_for_ms(&mut node_exec, 10.0);
+    out.extend_from_slice(&out2[..]);
+
+    (out, prog_id != node_exec.get_prog().unique_id, matrix)
+}
+
+#[test]
+fn check_prog_diff_changed_edge_keeps_state() {
+    let (reference, _, _) = run_with_edit(&|m| place_sin_amp(m, 2), &|_| ());
+
+    // Disconnecting the unrelated Amp 1 only changes it's own op:
+    let (out, replaced, _) = run_with_edit(&|m| place_sin_amp(m, 2), &|m| {
+        m.place(2, 1, Cell::empty(NodeId::Amp(1)));
+    });
+
+    assert!(!replaced);
+    assert_eq!(reference, out);
+}
+
+#[test]
+fn check_prog_diff_new_nodes_keep_state() {
+    let (reference, _, _) = run_with_edit(&|_| (), &|_| ());
+
+    // New nodes require new buffers, but the running nodes keep their state:
+    let (out, replaced, mut matrix) = run_with_edit(&|_| (), &|m| place_sin_amp(m, 2));
+
+    assert!(!replaced);
+    assert_eq!(reference, out);
+
+    // The output feedback of the new nodes is available:
+    matrix.update_output_feedback();
+    assert!(matrix.out_fb_for(&NodeId::Amp(1), 0).is_some());
+}
+
+#[test]
+fn check_prog_diff_reroute() {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("sin", "sig").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+    assert!(ch1.iter().any(|s| s.abs() > 0.5));
+    let prog_id = node_exec.get_prog().unique_id;
+
+    // Syncing an unchanged matrix uploads nothing:
+    matrix.sync().unwrap();
+    node_exec.process_graph_updates();
+    assert_eq!(prog_id, node_exec.get_prog().unique_id);
+
+    matrix.place(0, 1, Cell::empty(NodeId::Out(0)));
+    matrix.sync().unwrap();
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+    assert!(ch1.iter().all(|s| *s == 0.0));
+
+    matrix.place(0, 1, Cell::empty(NodeId::Out(0)).input(Some(0), None, None));
+    matrix.sync().unwrap();
+    let (ch1, _) = run_for_ms(&mut node_exec, 10.0);
+    assert!(ch1.iter().any(|s| s.abs() > 0.5));
+
+    assert_eq!(prog_id, node_exec.get_prog().unique_id);
+}
+
+#[test]
+fn check_prog_diff_crossfade_replaces_prog() {
+    let (_, replaced, _) = run_with_edit(
+        &|m| {
+            place_sin_amp(m, 2);
+            m.set_prog_crossfade(5.0);
+        },
+        &|m| {
+            m.place(2, 1, Cell::empty(NodeId::Amp(1)));
+        },
+    );
+
+    // The crossfade needs to run the old program next to the new one:
+    assert!(replaced);
+}
+
+#[test]
+fn check_prog_diff_constructor_upload() {
+    let mut sc = SynthConstructor::new();
+    let mut exec = sc.executor().unwrap();
+
+    let s = sin(0).set().freq(220.0);
+    sc.upload(&out(0).input().ch1(&s.output().sig())).unwrap();
+    let (ch1, _) = run_for_ms(&mut exec, 10.0);
+    assert!(ch1.iter().any(|s| s.abs() > 0.5));
+    let prog_id = exec.get_prog().unique_id;
+
+    let a = amp(0).input().inp(&s.output().sig());
+    sc.upload(&out(0).input().ch1(&s.output().sig()).input().ch2(&a.output().sig())).unwrap();
+    let (ch1_2, ch2) = run_for_ms(&mut exec, 10.0);
+
+    assert_eq!(prog_id, exec.get_prog().unique_id);
+    assert!(ch2.iter().any(|s| s.abs() > 0.5));
+    // The oscillator continues where it left off:
+    assert!((ch1_2[0] - ch1[ch1.len() - 1]).abs() < 0.1);
+}
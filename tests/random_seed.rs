@@ -0,0 +1,172 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::dsp::RandNodeSelector;
+use hexodsp::matrix_repr::MatrixRepr;
+use hexodsp::DEFAULT_RANDOM_SEED;
+
+fn setup_noise(seed: Option<u64>) -> (Matrix, NodeExecutor) {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    if let Some(seed) = seed {
+        matrix.set_random_seed(seed);
+    }
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("noise", "sig").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+    node_exec.process_graph_updates();
+
+    (matrix, node_exec)
+}
+
+fn tseq_gates(seed: u64) -> Vec<(usize, usize)> {
+    use hexodsp::dsp::tracker::UIPatternModel;
+
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+    matrix.set_random_seed(seed);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("sin", "sig")
+        .set_denorm("freq", 100.0)
+        .node_io("tseq", "clock", "trk1")
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+
+    let pat = matrix.get_pattern_data(0).unwrap();
+    {
+        let mut pr = pat.lock().unwrap();
+        pr.set_rows(16);
+        pr.set_col_gate_type(0);
+        for row in 0..16 {
+            // Each row fires with a probability of 50%:
+            pr.set_cell_value(row, 0, 0x7FF);
+        }
+    }
+    for _ in 0..10 {
+        matrix.check_pattern_data(0);
+    }
+
+    let (ch1, _) = run_for_ms(&mut node_exec, 500.0);
+    collect_gates(&ch1[..])
+}
+
+#[test]
+fn check_random_seed_reproducible() {
+    let (_matrix, mut exec1) = setup_noise(Some(1234));
+    let (_matrix, mut exec2) = setup_noise(Some(1234));
+    let (_matrix, mut exec3) = setup_noise(Some(4321));
+
+    let (a, _) = run_for_ms(&mut exec1, 10.0);
+    let (b, _) = run_for_ms(&mut exec2, 10.0);
+    let (c, _) = run_for_ms(&mut exec3, 10.0);
+
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+
+    let gates = tseq_gates(1234);
+    assert!(!gates.is_empty());
+    assert_eq!(gates, tseq_gates(1234));
+    assert_ne!(gates, tseq_gates(4321));
+}
+
+#[test]
+fn check_random_seed_default() {
+    let (matrix, mut exec1) = setup_noise(None);
+    let (_matrix, mut exec2) = setup_noise(Some(DEFAULT_RANDOM_SEED));
+
+    assert_eq!(matrix.random_seed(), DEFAULT_RANDOM_SEED);
+    assert_eq!(run_for_ms(&mut exec1, 10.0), run_for_ms(&mut exec2, 10.0));
+
+    // The default seed is not stored in the patch:
+    assert_eq!(matrix.to_repr().random_seed, None);
+    assert!(matrix.to_repr().serialize().find("random_seed").is_none());
+}
+
+#[test]
+fn check_random_seed_reseed_at_runtime() {
+    let (mut matrix, mut node_exec) = setup_noise(Some(99));
+
+    let (first, _) = run_for_ms(&mut node_exec, 10.0);
+    let (second, _) = run_for_ms(&mut node_exec, 10.0);
+    assert_ne!(first, second);
+
+    // Reseeding with the same seed restarts the random numbers:
+    matrix.set_random_seed(99);
+    node_exec.process_graph_updates();
+    let (again, _) = run_for_ms(&mut node_exec, 10.0);
+    assert_eq!(first, again);
+
+    matrix.set_random_seed(100);
+    node_exec.process_graph_updates();
+    let (other, _) = run_for_ms(&mut node_exec, 10.0);
+    assert_ne!(first, other);
+}
+
+#[test]
+fn check_random_seed_repr() {
+    let (matrix, mut node_exec) = setup_noise(Some(0xFFFF_FFFF_FFFF_0001));
+    let (orig, _) = run_for_ms(&mut node_exec, 10.0);
+
+    let mut repr = matrix.to_repr();
+    assert_eq!(repr.random_seed, Some(0xFFFF_FFFF_FFFF_0001));
+
+    let repr = MatrixRepr::deserialize(&repr.serialize()).unwrap();
+    assert_eq!(repr.random_seed, Some(0xFFFF_FFFF_FFFF_0001));
+
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix2 = Matrix::new(node_conf, 3, 3);
+    matrix2.from_repr(&repr).unwrap();
+    node_exec.process_graph_updates();
+
+    assert_eq!(matrix2.random_seed(), 0xFFFF_FFFF_FFFF_0001);
+    let (loaded, _) = run_for_ms(&mut node_exec, 10.0);
+    assert_eq!(orig, loaded);
+}
+
+#[test]
+fn check_random_seed_rand_node_ids() {
+    let (node_conf, _node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    matrix.set_random_seed(42);
+    let ids = matrix.rand_node_ids(5, RandNodeSelector::OnlyUseful);
+    assert_eq!(ids.len(), 5);
+    assert!(ids.iter().all(|id| *id != NodeId::Nop && *id != NodeId::Out(0)));
+    assert_ne!(ids, matrix.rand_node_ids(5, RandNodeSelector::OnlyUseful));
+
+    // Reseeding undoes the randomization:
+    matrix.set_random_seed(42);
+    assert_eq!(ids, matrix.rand_node_ids(5, RandNodeSelector::OnlyUseful));
+}
+
+#[test]
+fn check_random_seed_reseed_removed_node() {
+    let (mut matrix, mut node_exec) = setup_noise(Some(99));
+    let (first, _) = run_for_ms(&mut node_exec, 10.0);
+
+    // The noise node is not part of the running program while reseeding:
+    matrix.place(0, 0, Cell::empty(NodeId::Nop));
+    matrix.place(0, 1, Cell::empty(NodeId::Nop));
+    matrix.sync().unwrap();
+    node_exec.process_graph_updates();
+
+    matrix.set_random_seed(99);
+    node_exec.process_graph_updates();
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain.node_out("noise", "sig").node_inp("out", "ch1").place(&mut matrix, 0, 0).unwrap();
+    matrix.sync().unwrap();
+    node_exec.process_graph_updates();
+
+    let (again, _) = run_for_ms(&mut node_exec, 10.0);
+    assert_eq!(first, again);
+}
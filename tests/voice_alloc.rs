@@ -0,0 +1,161 @@
+// Copyright (c) 2022 Weird Constructor <weirdconstructor@gmail.com>
+// This file is a part of HexoDSP. Released under GPL-3.0-or-later.
+// See README.md and COPYING for details.
+
+mod common;
+use common::*;
+
+use hexodsp::build::*;
+use hexodsp::nodes::{PolyVoices, VoiceStealing};
+use hexodsp::synth_constructor::{SynthConstructor, SynthError};
+
+fn run_midip_voice_gate(voice: i64, stealing: VoiceStealing) -> Vec<(usize, i64)> {
+    let (node_conf, mut node_exec) = new_node_engine();
+    let mut matrix = Matrix::new(node_conf, 3, 3);
+
+    let mut chain = MatrixCellChain::new(CellDir::B);
+    chain
+        .node_out("midip", "gate")
+        .set_atom("voice", SAtom::setting(voice))
+        .node_inp("out", "ch1")
+        .place(&mut matrix, 0, 0)
+        .unwrap();
+    matrix.sync().unwrap();
+    matrix.set_voice_allocation(2, stealing);
+
+    let (ch1, _) = node_exec.test_run(
+        0.005,
+        false,
+        &[
+            HxTimedEvent::note_on(5, 0, 60, 1.0),
+            HxTimedEvent::note_on(20, 0, 64, 1.0),
+            HxTimedEvent::note_off(50, 0, 60),
+            HxTimedEvent::note_on(100, 0, 67, 1.0),
+            HxTimedEvent::note_on(150, 0, 69, 1.0),
+            HxTimedEvent::note_off(180, 0, 64),
+        ],
+    );
+
+    collect_signal_changes(&ch1[..], -1)
+}
+
+#[test]
+fn check_voice_alloc_midip_voices() {
+    assert_eq!(
+        run_midip_voice_gate(1, VoiceStealing::Oldest),
+        vec![
+            (5, 100),
+            (51, 0),    // Note 60 released
+            (100, 100), // Note 67 on the free voice 1
+        ]
+    );
+
+    assert_eq!(
+        run_midip_voice_gate(2, VoiceStealing::Oldest),
+        vec![
+            (20, 100),
+            (150, 0), // Note 69 steals voice 2 from the older note 64, short pause
+            (151, 100),
+        ]
+    );
+
+    // Without stealing, note 69 is just dropped:
+    assert_eq!(
+        run_midip_voice_gate(2, VoiceStealing::NoSteal),
+        vec![
+            (20, 100),
+            (181, 0), // Note 64 released
+        ]
+    );
+
+    // Mono mode still gets all notes:
+    assert_eq!(
+        run_midip_voice_gate(0, VoiceStealing::Oldest),
+        vec![(5, 100), (20, 0), (21, 100), (100, 0), (101, 100), (150, 0), (151, 100)]
+    );
+}
+
+#[test]
+fn check_voice_alloc_poly_voices_mix() {
+    let midip = midip(0);
+    let voice = amp(0).input().inp(&midip.output().gate());
+
+    let voices = PolyVoices::new(2);
+    let mix = voices.mix(&voice.output().sig(), 0).unwrap();
+
+    let mut sc = SynthConstructor::new();
+    sc.set_voice_allocation(voices.voices(), VoiceStealing::Oldest);
+    sc.upload(&out(0).input().ch1(&mix)).unwrap();
+
+    let mut exec = sc.executor().unwrap();
+    exec.process_graph_updates();
+    let node_ids = exec.get_nodes().iter().map(|n| n.id()).collect::<Vec<NodeId>>();
+    assert!(node_ids.contains(&NodeId::MidiP(0)));
+    assert!(node_ids.contains(&NodeId::MidiP(16)));
+    assert!(!node_ids.contains(&NodeId::MidiP(32)));
+
+    let (ch1, _) = exec.test_run(
+        0.025,
+        false,
+        &[
+            HxTimedEvent::note_on(10, 0, 60, 1.0),
+            HxTimedEvent::note_on(200, 0, 64, 1.0),
+            HxTimedEvent::note_on(400, 0, 67, 1.0),
+            HxTimedEvent::note_off(600, 0, 64),
+            HxTimedEvent::note_off(800, 0, 67),
+        ],
+    );
+
+    assert_eq!(
+        collect_signal_changes(&ch1[..], -1),
+        vec![
+            (10, 100),
+            (200, 200),
+            (400, 100), // Note 67 steals the voice of note 60
+            (401, 200),
+            (601, 100),
+            (801, 0),
+        ]
+    );
+}
+
+#[test]
+fn check_voice_alloc_poly_voices_shared() {
+    let lfo = tslfo(0);
+    let midip = midip(0);
+    let osc = sin(0).input().freq(&midip.output().freq()).input().det(&lfo.output().sig());
+    let voice = amp(0).input().inp(&osc.output().sig());
+
+    let voices = PolyVoices::new(5).shared(&lfo).stride(10);
+    let outputs = voices.replicate(&voice.output().sig()).unwrap();
+
+    assert_eq!(outputs.len(), 5);
+    for (i, (node, port)) in outputs.iter().enumerate() {
+        assert_eq!(node.node_id, NodeId::Amp(i as u8 * 10));
+        assert_eq!(port, "sig");
+    }
+
+    let mut sc = SynthConstructor::new();
+    sc.upload(&out(0).input().ch1(&voices.mix(&voice.output().sig(), 10).unwrap())).unwrap();
+
+    let mut exec = sc.executor().unwrap();
+    exec.process_graph_updates();
+    let node_ids = exec.get_nodes().iter().map(|n| n.id()).collect::<Vec<NodeId>>();
+
+    for i in 0..5 {
+        assert!(node_ids.contains(&NodeId::Sin(i * 10)));
+        assert!(node_ids.contains(&NodeId::MidiP(i * 10)));
+    }
+    // The shared LFO is only there once:
+    assert!(node_ids.contains(&NodeId::TsLFO(0)));
+    assert!(!node_ids.contains(&NodeId::TsLFO(10)));
+    // 5 voices need 2 Mix3 in the first and 1 Mix3 in the second stage:
+    assert!(node_ids.contains(&NodeId::Mix3(12)));
+    assert!(!node_ids.contains(&NodeId::Mix3(13)));
+
+    let voices = PolyVoices::new(16).stride(20);
+    assert_eq!(
+        voices.replicate(&voice.output().sig()).unwrap_err(),
+        SynthError::VoiceInstanceOverflow(NodeId::Amp(0))
+    );
+}